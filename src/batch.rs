@@ -0,0 +1,125 @@
+use crate::config::{ConfigError, SimConfig};
+use crate::metrics::RunSummary;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// How one run of a batch went, or why the simulation its config describes couldn't be built
+pub type RunResult = Result<RunSummary, ConfigError>;
+
+/// Build and run the simulation each config describes for all its steps, and summarize how
+/// each went, in the order the configs were given. The runs are independent, so with the
+/// parallel feature they share out over several threads. Each run seeds the random numbers
+/// on the thread it runs on, so a seeded config gives the same result either way
+pub fn run_batch(configs: &[SimConfig]) -> Vec<RunResult> {
+    #[cfg(feature = "parallel")]
+    let results = configs.par_iter().map(run).collect();
+    #[cfg(not(feature = "parallel"))]
+    let results = configs.iter().map(run).collect();
+    results
+}
+
+/// Build and run the simulation config describes for all its steps, or from event to event
+/// for as long if it's event driven, and summarize it
+pub fn run(config: &SimConfig) -> RunResult {
+    let mut sim = config.build()?;
+    let mut summary = RunSummary::default();
+    if config.event_driven {
+        let end = config.steps as f32 * config.timestep;
+        while let Some(dt) = sim.step_to_next_event_before(end) {
+            summary.record(dt, sim.people().people(), sim.building().state());
+        }
+        return Ok(summary);
+    }
+    for _ in 0..config.steps {
+        sim.step(config.timestep);
+        summary.record(
+            config.timestep,
+            sim.people().people(),
+            sim.building().state(),
+        );
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::ElevatorSim;
+    use crate::people::PeopleSim;
+    use crate::sim::Simulation;
+
+    #[test]
+    fn simulations_can_move_between_threads() {
+        fn send<T: Send>() {}
+        send::<ElevatorSim>();
+        send::<PeopleSim>();
+        send::<Simulation>();
+    }
+
+    #[test]
+    fn batches_repeat_each_run_in_order() {
+        let configs: Vec<SimConfig> = (0..4)
+            .map(|i| SimConfig {
+                floors: 6,
+                cars: 1 + i % 2,
+                steps: 500,
+                seed: Some(i as u64),
+                ..SimConfig::default()
+            })
+            .collect();
+        let batch = run_batch(&configs);
+        assert_eq!(batch.len(), 4);
+        for (config, result) in configs.iter().zip(&batch) {
+            let alone = run(config).unwrap();
+            let result = result.as_ref().unwrap();
+            assert_eq!(result.delivered(), alone.delivered());
+            assert_eq!(result.average_wait(), alone.average_wait());
+        }
+
+        let bad = SimConfig {
+            traffic: "rush".to_string(),
+            ..SimConfig::default()
+        };
+        assert!(matches!(
+            run_batch(&[bad])[..],
+            [Err(ConfigError::UnknownTraffic(_))]
+        ));
+    }
+
+    #[test]
+    fn event_driven_runs_last_as_long_and_deliver_as_many() {
+        // cars which speed up and slow down, which event steps have to follow as closely as
+        // ticks do
+        let config = SimConfig {
+            steps: 6000,
+            spawn_rate: 0.1,
+            seed: Some(3),
+            ..SimConfig::single_car()
+        };
+        assert!(config.max_acceleration > 0.);
+        let ticked = run(&config).unwrap();
+        let evented = run(&SimConfig {
+            event_driven: true,
+            ..config.clone()
+        })
+        .unwrap();
+        assert!(evented.to_string().starts_with("Simulated time: 600.0 s"));
+        let close = |a: f32, b: f32| (a - b).abs() <= a * 0.2;
+        let (ticked_wait, evented_wait) = (ticked.average_wait(), evented.average_wait());
+        assert!(
+            close(ticked_wait.unwrap(), evented_wait.unwrap()),
+            "{ticked_wait:?} {evented_wait:?}"
+        );
+        let (ticked_ride, evented_ride) = (ticked.average_ride(), evented.average_ride());
+        assert!(
+            close(ticked_ride.unwrap(), evented_ride.unwrap()),
+            "{ticked_ride:?} {evented_ride:?}"
+        );
+        assert!((ticked.utilization() - evented.utilization()).abs() <= 0.05);
+        let (ticked, evented) = (ticked.delivered(), evented.delivered());
+        assert!(
+            ticked.abs_diff(evented) <= ticked / 10,
+            "{ticked} {evented}"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::control::ElevatorController;
+use crate::elevator::{ElevatorCommand, ElevatorConfig, ElevatorSim, ServiceState};
+use crate::metrics::RunSummary;
+use crate::observation::{ObservationError, ObservationFilter};
+use crate::people::{PeopleConfig, PeopleSim, Spawner};
+use crate::rng;
+use crate::sim::Simulation;
+use crate::types::{CarId, Floor, ScriptedArrival};
+use std::fmt;
+
+/// Settings shared by every run in a comparison
+/// floors, cars - the size of the building
+/// duration, timestep - how many seconds each run lasts, and how long each step is
+/// elevator - how the building behaves
+/// seed - the random numbers are reseeded with this before every run, so reaction times
+/// and door obstructions are drawn the same way for each controller
+/// observation - what every controller is allowed to see. By default future arrivals are
+/// hidden, so a controller told who will arrive, like OracleController, isn't run unless
+/// the comparison means to include it
+/// faults - cars which break down in every run, the same way for each controller
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchConfig {
+    pub floors: Floor,
+    pub cars: usize,
+    pub duration: f32,
+    pub timestep: f32,
+    pub elevator: ElevatorConfig,
+    pub seed: u64,
+    pub observation: ObservationFilter,
+    pub faults: Vec<CarFault>,
+}
+
+/// A car which goes out of service start seconds into a run, and is back in service at end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CarFault {
+    pub car_id: CarId,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            floors: 10,
+            cars: 2,
+            duration: 600.,
+            timestep: 0.1,
+            elevator: ElevatorConfig::default(),
+            seed: 0,
+            observation: ObservationFilter {
+                hide_future_arrivals: true,
+                ..ObservationFilter::default()
+            },
+            faults: Vec::new(),
+        }
+    }
+}
+
+/// A controller entered into a comparison, with a name for the table and the way people
+/// behave when using it. Destination dispatch, for example, needs people to use kiosks
+pub struct Contender {
+    pub name: String,
+    pub controller: Box<dyn ElevatorController>,
+    pub people: PeopleConfig,
+}
+
+/// The results of running several controllers on the same traffic, in the order they were
+/// given, and the controllers which weren't run because they know something the comparison
+/// hides. Displaying it prints a side by side table
+#[derive(Clone, Debug, Default)]
+pub struct Comparison {
+    pub results: Vec<(String, RunSummary)>,
+    pub refused: Vec<(String, ObservationError)>,
+}
+
+/// Run a spawner for duration seconds, in steps of dt, and write down everyone it spawns
+/// and when, so the same people can be replayed against different controllers
+pub fn record_arrivals(
+    spawner: &mut dyn Spawner,
+    num_floors: Floor,
+    duration: f32,
+    dt: f32,
+) -> Vec<ScriptedArrival> {
+    let mut arrivals = Vec::new();
+    let steps = (duration / dt).round() as u32;
+    for step in 1..=steps {
+        let time = step as f32 * dt;
+        for (origin, destination) in spawner.spawn(time, dt, num_floors) {
+            arrivals.push(ScriptedArrival {
+                time,
+                origin,
+                destination,
+            });
+        }
+    }
+    arrivals
+}
+
+/// Run every contender on exactly the same arrivals, and summarize how each did. People who
+/// spawn on their own, such as children brought along by chaperoned_children or the rest
+/// of a party, aren't part of the arrivals and may differ between runs
+pub fn compare(
+    config: &BenchConfig,
+    arrivals: &[ScriptedArrival],
+    contenders: Vec<Contender>,
+) -> Comparison {
+    let steps = (config.duration / config.timestep).round() as u32;
+    let mut comparison = Comparison::default();
+
+    for contender in contenders {
+        rng::seed(config.seed);
+        let people = PeopleSim::scripted(config.floors, arrivals.to_vec(), contender.people);
+        let building =
+            ElevatorSim::with_config(config.floors as usize, config.cars, config.elevator.clone());
+        let mut sim = Simulation::new(people, building, contender.controller);
+        if let Err(error) = sim.set_observation(config.observation) {
+            comparison.refused.push((contender.name, error));
+            continue;
+        }
+
+        let mut summary = RunSummary::default();
+        for _ in 0..steps {
+            break_down(&mut sim, &config.faults);
+            sim.step(config.timestep);
+            summary.record(
+                config.timestep,
+                sim.people().people(),
+                sim.building().state(),
+            );
+        }
+        comparison.results.push((contender.name, summary));
+    }
+
+    comparison
+}
+
+/// Take cars out of service while their faults last, and put them back once they're over.
+/// Cars an operator has taken over are left to them
+fn break_down(sim: &mut Simulation, faults: &[CarFault]) {
+    let time = sim.time();
+    for fault in faults {
+        let broken = (fault.start..fault.end).contains(&time);
+        let Some(car) = sim.building().state().cars.get(fault.car_id.0 as usize) else {
+            continue;
+        };
+        let in_service = match car.service {
+            ServiceState::Normal if broken => false,
+            ServiceState::OutOfService if !broken => true,
+            _ => continue,
+        };
+        let _ = sim
+            .building_mut()
+            .apply_command(ElevatorCommand::SetCarService {
+                car_id: fault.car_id,
+                in_service,
+            });
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // missing times, when nobody boarded or arrived, are shown as a dash
+        let seconds = |t: Option<f32>| t.map_or("-".to_string(), |t| format!("{t:.1}"));
+        let ratio = |r: Option<f32>, places| r.map_or("-".to_string(), |r| format!("{r:.places$}"));
+        let width = self
+            .results
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["Controller".len()])
+            .max()
+            .unwrap_or(0);
+
+        let header = format!(
+            "{:<width$} | Delivered | Gave up | Avg wait | Max wait | Wait Gini | Max/mean | Avg ride | \
+             Utilization",
+            "Controller"
+        );
+        write!(f, "{header}\n{}", "-".repeat(header.len()))?;
+        for (name, summary) in &self.results {
+            write!(
+                f,
+                "\n{name:<width$} | {:>9} | {:>7} | {:>8} | {:>8} | {:>9} | {:>8} | {:>8} | {:>10.1}%",
+                summary.delivered(),
+                summary.gave_up(),
+                seconds(summary.average_wait()),
+                seconds(summary.max_wait()),
+                ratio(summary.wait_gini(), 2),
+                ratio(summary.wait_ratio(), 1),
+                seconds(summary.average_ride()),
+                summary.utilization() * 100.
+            )?;
+        }
+        for (name, error) in &self.refused {
+            write!(f, "\n{name} wasn't run: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{BasicController, OracleController};
+    use crate::people::{Interfloor, PatternSpawner};
+
+    #[test]
+    fn every_controller_sees_the_same_people() {
+        let mut spawner = PatternSpawner::new(Box::new(Interfloor { rate: 0.2 }));
+        let arrivals = record_arrivals(&mut spawner, 8, 120., 0.1);
+        // one person every 5 seconds
+        assert!((23..=24).contains(&arrivals.len()));
+
+        let config = BenchConfig {
+            floors: 8,
+            duration: 300.,
+            ..BenchConfig::default()
+        };
+        let building = ElevatorSim::with_config(8, config.cars, config.elevator.clone());
+        let contenders = || {
+            vec![
+                Contender {
+                    name: "basic".to_string(),
+                    controller: Box::new(BasicController),
+                    people: PeopleConfig::default(),
+                },
+                Contender {
+                    name: "oracle".to_string(),
+                    controller: Box::new(OracleController::new(&arrivals, &building)),
+                    people: PeopleConfig::default(),
+                },
+            ]
+        };
+
+        // the oracle only runs when the comparison lets it know the future
+        let fair = compare(&config, &arrivals, contenders());
+        assert_eq!(fair.results.len(), 1);
+        assert_eq!(fair.refused.len(), 1);
+        assert!(
+            fair.to_string().ends_with(
+                "oracle wasn't run: the controller knows who will arrive, which is hidden"
+            )
+        );
+
+        let config = BenchConfig {
+            observation: ObservationFilter::default(),
+            ..config
+        };
+        let comparison = compare(&config, &arrivals, contenders());
+
+        assert_eq!(comparison.results.len(), 2);
+        for (_, summary) in &comparison.results {
+            assert_eq!(summary.delivered(), arrivals.len());
+        }
+        let table = comparison.to_string();
+        assert_eq!(table.lines().count(), 4);
+        assert!(table.lines().nth(3).unwrap().starts_with("oracle"));
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Seconds in a day
+pub const DAY: f64 = 86_400.;
+
+/// The simulated time of day. A run starts at start seconds after midnight and has gone on
+/// for elapsed seconds, so a run starting at 07:00 reads 07:30 half an hour in, and carries
+/// on into the next day after midnight
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SimClock {
+    pub start: f64,
+    pub elapsed: f64,
+}
+
+/// implement functions for SimClock
+/// starting_at - a clock starting at a time of day
+/// parse - a clock starting at a time of day written as HH:MM or HH:MM:SS
+/// advance - move the clock on
+/// at - the time of day some seconds into the run
+/// time_of_day, day, hour - read the clock
+impl SimClock {
+    /// A clock starting seconds after midnight
+    pub fn starting_at(seconds: f64) -> Self {
+        Self {
+            start: seconds.rem_euclid(DAY),
+            elapsed: 0.,
+        }
+    }
+
+    /// A clock starting at a time of day such as "07:00" or "16:45:30", or None if text
+    /// isn't one
+    pub fn parse(text: &str) -> Option<Self> {
+        let parts: Vec<u32> = text
+            .trim()
+            .split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        let (hours, minutes, seconds) = match parts[..] {
+            [hours, minutes] => (hours, minutes, 0),
+            [hours, minutes, seconds] => (hours, minutes, seconds),
+            _ => return None,
+        };
+        if hours >= 24 || minutes >= 60 || seconds >= 60 {
+            return None;
+        }
+        Some(Self::starting_at(
+            (hours * 3600 + minutes * 60 + seconds) as f64,
+        ))
+    }
+
+    /// Move the clock on by dt seconds
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed += dt as f64;
+    }
+
+    /// Seconds after midnight on the first day, time seconds into the run. Unlike
+    /// time_of_day this keeps counting past midnight, so later times are always larger
+    pub fn at(&self, time: f64) -> f64 {
+        self.start + time
+    }
+
+    /// Seconds after midnight now, on whatever day it is
+    pub fn time_of_day(&self) -> f64 {
+        self.at(self.elapsed).rem_euclid(DAY)
+    }
+
+    /// How many midnights have passed since the run started
+    pub fn day(&self) -> u32 {
+        (self.at(self.elapsed) / DAY) as u32
+    }
+
+    /// The hour on the clock now, from 0 to 23
+    pub fn hour(&self) -> u32 {
+        (self.time_of_day() / 3600.) as u32
+    }
+}
+
+impl fmt::Display for SimClock {
+    /// The time as HH:MM:SS, with the day once past the first midnight
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.time_of_day() as u32;
+        if self.day() > 0 {
+            write!(f, "day {} ", self.day() + 1)?;
+        }
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_reads_the_time_of_day_past_midnight() {
+        let mut clock = SimClock::parse("23:30").unwrap();
+        assert_eq!(clock.to_string(), "23:30:00");
+        assert_eq!(clock.hour(), 23);
+        clock.advance(45. * 60. + 5.);
+        assert_eq!(clock.to_string(), "day 2 00:15:05");
+        assert_eq!(clock.hour(), 0);
+        assert_eq!(clock.at(0.), 23.5 * 3600.);
+
+        assert!(SimClock::parse("7:05:09").is_some());
+        assert!(SimClock::parse("24:00").is_none());
+        assert!(SimClock::parse("noon").is_none());
+    }
+}
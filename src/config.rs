@@ -0,0 +1,674 @@
+use crate::clock::SimClock;
+use crate::control::{ElevatorController, controller_named};
+use crate::deliveries::DeliveryConfig;
+use crate::elevator::{CarClass, Dedication, ElevatorConfig, ElevatorSim, HallButtons};
+use crate::faults::FaultConfig;
+use crate::forecast::EmaForecaster;
+use crate::observation::{Observation, ObservationError, ObservationFilter};
+use crate::parking::{DistributeEvenly, ForecastParking, Parked, ReturnToLobby};
+use crate::people::{
+    Attribute, BoardingPolicy, FloorWeights, HallCallMode, Overflow, PeopleConfig, PeopleSim,
+    TrafficPattern, traffic_pattern,
+};
+use crate::rng;
+use crate::sim::Simulation;
+use crate::topology::{Bank, BuildingTopology};
+use crate::types::{CarId, Floor, FloorMismatch};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+/// Which controller runs the building
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControllerChoice {
+    /// BasicController, with people pressing up and down buttons
+    Basic,
+    /// DestinationController, with people entering their floor at a kiosk and boarding the
+    /// car they're assigned
+    Destination,
+    /// LookController, which sweeps a single car up and down, with people pressing up and
+    /// down buttons
+    #[serde(alias = "scan")]
+    Look,
+    /// GroupController, which hands each hall call to the cheapest car, with people pressing
+    /// up and down buttons
+    Group,
+}
+
+impl ControllerChoice {
+    /// Every controller a config can choose
+    pub const ALL: [ControllerChoice; 4] = [
+        ControllerChoice::Basic,
+        ControllerChoice::Destination,
+        ControllerChoice::Look,
+        ControllerChoice::Group,
+    ];
+
+    /// What the controller is called in a config file, and in control::CONTROLLERS
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerChoice::Basic => "basic",
+            ControllerChoice::Destination => "destination",
+            ControllerChoice::Look => "look",
+            ControllerChoice::Group => "group",
+        }
+    }
+
+    /// The controller called name, which can also be look's other name, scan, or None if
+    /// there's no such controller
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "scan" => Some(ControllerChoice::Look),
+            _ => Self::ALL.into_iter().find(|c| c.name() == name),
+        }
+    }
+
+    /// A new controller of this kind
+    pub fn build(self) -> Box<dyn ElevatorController> {
+        controller_named(self.name()).expect("every controller choice is registered")
+    }
+}
+
+/// How many seconds ahead parking cars where calls are expected looks
+const FORECAST_HORIZON: f32 = 60.;
+
+/// Where cars the controller has left with nothing to do wait for their next call
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParkingChoice {
+    /// wherever they finished
+    #[default]
+    Stay,
+    /// back at the lobby, see ReturnToLobby
+    Lobby,
+    /// spread evenly up the building, see DistributeEvenly
+    Even,
+    /// at the floors expected to call most over the next minute, from the arrival rates
+    /// seen so far, see ForecastParking
+    Forecast,
+}
+
+/// Everything needed to set up a simulation, which can be read from a TOML or JSON file.
+/// Any setting missing from the file keeps its default
+/// floors, cars - the size of the building
+/// steps, timestep - how many steps to run, and how many seconds each one lasts
+/// event_driven - run for as long as steps and timestep make, but jump straight from one
+/// event to the next rather than ticking every timestep, see Simulation::step_to_next_event.
+/// Headless and batch runs get through quiet spells much faster. Drawn runs still tick
+/// start_time - the time of day the run starts at, as HH:MM or HH:MM:SS
+/// max_speed, max_acceleration, max_jerk - how cars move, in metres, see ElevatorConfig
+/// floor_heights - how tall each floor is in metres, from the bottom up, see BuildingTopology
+/// floor_labels - what each floor is called, from the bottom up, see BuildingTopology
+/// basements - how many floors are below ground, at the bottom of the building. People come
+/// in on the ground floor above them, see BuildingTopology
+/// banks - groups of cars with hall buttons of their own, and the floors they stop at, see
+/// Bank
+/// sky_lobbies, sky_lobby_walk - the floors people change banks at for trips no one bank
+/// makes, and how many metres they walk between the banks there, see BuildingTopology
+/// hall_capacities - how many people fit in each floor's waiting area, from the bottom up,
+/// see BuildingTopology
+/// overflow, back_area_walk - what people do when they find the waiting area full, see
+/// PeopleConfig
+/// secured_floors, visitors - the floors people need a card to ride to, see
+/// BuildingTopology, and the chance someone heading for one has no card, see PeopleConfig
+/// door_open_time, door_close_time, door_dwell_time - door timings, see ElevatorConfig
+/// accessible_dwell_time - how long doors stay open for an accessible call, see
+/// ElevatorConfig
+/// wheelchair_users - the chance each person who arrives uses a wheelchair, see
+/// Attribute::Wheelchair
+/// max_door_reopens - how often people stepping into a closing door open it again, see
+/// ElevatorConfig
+/// door_time_jitter, acceleration_jitter - how much door times and acceleration vary at
+/// random, see ElevatorConfig
+/// transfer_time - how many seconds each person takes to get on or off, holding the door
+/// open, see ElevatorConfig
+/// overload_sensor, weight_limits - whether cars let people in over capacity and hold
+/// their door until someone gets out, and how many kg each car carries, see ElevatorConfig
+/// served_floors - the floors each car stops at, see ElevatorConfig
+/// car_classes - which cars are passenger, freight or service cars, by car number, see
+/// CarClass
+/// hall_buttons - "pair" for up and down call buttons on each floor, or "single" for one
+/// dedications - times when cars are kept for some floors alone, see Dedication
+/// spawn_rate - how many people arrive per second
+/// traffic - the name of the traffic pattern people arrive in
+/// floor_weights - how busy each floor is compared to the others, from the bottom up, which
+/// makes trips start and end there more or less often. Floors past the end weigh 1, and
+/// floors weighing 0 are never visited. Empty for every floor being as busy, see FloorWeights
+/// controller - which controller runs the building
+/// shadow - a controller to run alongside the one in charge, whose commands are compared
+/// with its own but never given, see Simulation::start_shadow
+/// parking, parking_delay - where cars left idle wait, and how many seconds they're left
+/// idle before they go there, see Parked
+/// observation - how much the controller is shown about each car's riders
+/// hide_loads, hide_arrival_rates, hide_queues - what else to hide from the controller, see
+/// ObservationFilter
+/// stuck_rate, door_jam_rate, button_break_rate, repair_time - how often things break down
+/// and how long they take to fix, see FaultConfig
+/// delivery_rate, delivery_reservation - how often deliveries arrive for the freight cars,
+/// and how long each keeps its car, see DeliveryConfig
+/// seed - if set, the seed for every random number, so runs repeat exactly
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimConfig {
+    pub floors: u32,
+    pub cars: usize,
+    pub steps: u32,
+    pub timestep: f32,
+    pub event_driven: bool,
+    pub start_time: String,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub max_jerk: f32,
+    pub floor_heights: Vec<f32>,
+    pub floor_labels: Vec<String>,
+    pub basements: Floor,
+    pub banks: Vec<Bank>,
+    pub sky_lobbies: Vec<Floor>,
+    pub sky_lobby_walk: f32,
+    pub hall_capacities: Vec<u32>,
+    pub overflow: Overflow,
+    pub back_area_walk: f32,
+    pub secured_floors: Vec<Floor>,
+    pub visitors: f32,
+    pub door_open_time: f32,
+    pub door_close_time: f32,
+    pub door_dwell_time: f32,
+    pub accessible_dwell_time: f32,
+    pub wheelchair_users: f32,
+    pub max_door_reopens: u32,
+    pub door_time_jitter: f32,
+    pub acceleration_jitter: f32,
+    pub transfer_time: f32,
+    pub overload_sensor: bool,
+    pub weight_limits: Vec<f32>,
+    pub served_floors: Vec<Vec<Floor>>,
+    pub car_classes: Vec<CarClass>,
+    pub hall_buttons: HallButtons,
+    pub dedications: Vec<Dedication>,
+    pub spawn_rate: f32,
+    pub traffic: String,
+    pub floor_weights: Vec<f32>,
+    pub controller: ControllerChoice,
+    pub shadow: Option<ControllerChoice>,
+    pub parking: ParkingChoice,
+    pub parking_delay: f32,
+    pub observation: Observation,
+    pub hide_loads: bool,
+    pub hide_arrival_rates: bool,
+    pub hide_queues: bool,
+    pub stuck_rate: f32,
+    pub door_jam_rate: f32,
+    pub button_break_rate: f32,
+    pub repair_time: f32,
+    pub delivery_rate: f32,
+    pub delivery_reservation: f32,
+    pub seed: Option<u64>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        let elevator = ElevatorConfig::default();
+        let faults = FaultConfig::default();
+        let deliveries = DeliveryConfig::default();
+        let people = PeopleConfig::default();
+        Self {
+            floors: 10,
+            cars: 2,
+            steps: 2000,
+            timestep: 0.1,
+            event_driven: false,
+            start_time: "00:00".to_string(),
+            max_speed: elevator.max_speed,
+            max_acceleration: elevator.max_acceleration,
+            max_jerk: elevator.max_jerk,
+            floor_heights: Vec::new(),
+            floor_labels: Vec::new(),
+            basements: 0,
+            banks: Vec::new(),
+            sky_lobbies: Vec::new(),
+            sky_lobby_walk: people.sky_lobby_walk,
+            hall_capacities: Vec::new(),
+            overflow: people.overflow,
+            back_area_walk: people.back_area_walk,
+            secured_floors: Vec::new(),
+            visitors: people.visitors,
+            door_open_time: elevator.door_open_time,
+            door_close_time: elevator.door_close_time,
+            door_dwell_time: elevator.door_dwell_time,
+            accessible_dwell_time: elevator.accessible_dwell_time,
+            wheelchair_users: 0.,
+            max_door_reopens: elevator.max_door_reopens,
+            door_time_jitter: elevator.door_time_jitter,
+            acceleration_jitter: elevator.acceleration_jitter,
+            transfer_time: elevator.transfer_time,
+            overload_sensor: elevator.overload_sensor,
+            weight_limits: elevator.weight_limits,
+            served_floors: elevator.served_floors,
+            car_classes: elevator.classes,
+            hall_buttons: elevator.hall_buttons,
+            dedications: elevator.dedications,
+            spawn_rate: 1. / 3.,
+            traffic: "interfloor".to_string(),
+            floor_weights: Vec::new(),
+            controller: ControllerChoice::Basic,
+            shadow: None,
+            parking: ParkingChoice::Stay,
+            parking_delay: 10.,
+            observation: Observation::Classic,
+            hide_loads: false,
+            hide_arrival_rates: false,
+            hide_queues: false,
+            stuck_rate: faults.stuck_rate,
+            door_jam_rate: faults.door_jam_rate,
+            button_break_rate: faults.button_break_rate,
+            repair_time: faults.repair_time,
+            delivery_rate: deliveries.rate,
+            delivery_reservation: deliveries.reservation,
+            seed: None,
+        }
+    }
+}
+
+/// The reasons a SimConfig can't be loaded or used
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    UnknownTraffic(String),
+    StartTime(String),
+    FloorWeights,
+    Observation(ObservationError),
+    Floors(FloorMismatch),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "couldn't read config: {e}"),
+            ConfigError::Toml(e) => write!(f, "invalid TOML config: {e}"),
+            ConfigError::Json(e) => write!(f, "invalid JSON config: {e}"),
+            ConfigError::UnknownTraffic(name) => write!(
+                f,
+                "unknown traffic pattern {name}: traffic must be one of interfloor, up-peak, \
+                 down-peak, lunch, daily, office-day"
+            ),
+            ConfigError::StartTime(text) => {
+                write!(
+                    f,
+                    "invalid start_time {text}: it must look like 07:00 or 07:00:00"
+                )
+            }
+            ConfigError::FloorWeights => write!(
+                f,
+                "floor_weights can't be negative, and at least two floors must weigh more than 0"
+            ),
+            ConfigError::Observation(e) => write!(f, "can't hide information: {e}"),
+            ConfigError::Floors(e) => write!(f, "invalid floors: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// implement functions for SimConfig
+/// single_car - the settings for a small residential building with one car
+/// supertall - the settings for a very tall office tower with three banks of cars
+/// load - read a config from a TOML or JSON file
+/// from_toml, from_json - read a config from text
+/// elevator_config, people_config, observation_filter, fault_config, delivery_config - the
+/// settings for each part of the simulation
+/// clock - the clock the run starts with
+/// traffic_pattern - the traffic people arrive in, with the floors weighted
+/// build - create the simulation the config describes
+impl SimConfig {
+    /// A small residential building with a single, slower car run by LookController, whose
+    /// doors take longer to open and close, and fewer people coming and going
+    pub fn single_car() -> Self {
+        Self {
+            floors: 6,
+            cars: 1,
+            max_speed: 1.75,
+            max_acceleration: 1.75,
+            door_open_time: 2.,
+            door_close_time: 2.,
+            door_dwell_time: 3.,
+            spawn_rate: 0.05,
+            controller: ControllerChoice::Look,
+            ..Self::default()
+        }
+    }
+
+    /// A 120 floor office tower at the start of the working day, with 24 fast cars in three
+    /// banks of 8. The low bank serves floors 1 to 39, the mid bank 40 to 79 and the high
+    /// bank 80 to 119, and every car also stops at the lobby and the sky lobby on floor 60.
+    /// Anyone whose trip no single bank makes rides to the sky lobby and changes to the next
+    /// bank's cars there. The basic controller can't keep up with the morning rush, so queues
+    /// build up in the lobby, which makes this the building headless runs are expected to
+    /// keep up with, see --perf-check
+    pub fn supertall() -> Self {
+        const SKY_LOBBY: Floor = 60;
+        let zones = [
+            ("low rise", 1..40),
+            ("mid rise", 40..80),
+            ("high rise", 80..120),
+        ];
+        let banks = zones
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, zone))| Bank {
+                name: name.to_string(),
+                cars: (8 * i..8 * (i + 1)).map(|car| CarId(car as u32)).collect(),
+                floors: [0, SKY_LOBBY].into_iter().chain(zone).collect(),
+            })
+            .collect();
+        Self {
+            floors: 120,
+            cars: 24,
+            max_speed: 17.5,
+            max_acceleration: 5.25,
+            door_open_time: 1.5,
+            door_close_time: 1.5,
+            door_dwell_time: 2.,
+            banks,
+            sky_lobbies: vec![SKY_LOBBY],
+            spawn_rate: 2.,
+            traffic: "up-peak".to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Read a config from a file. Files ending in .json are read as JSON, and anything else
+    /// as TOML
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        if path.extension().is_some_and(|e| e == "json") {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    /// Read a config from TOML text
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(ConfigError::Toml)
+    }
+
+    /// Read a config from JSON text
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(text).map_err(ConfigError::Json)
+    }
+
+    /// The shape of the building this config describes
+    pub fn topology(&self) -> BuildingTopology {
+        let entrances = if self.basements < self.floors {
+            vec![self.basements]
+        } else {
+            Vec::new()
+        };
+        BuildingTopology {
+            heights: self.floor_heights.clone(),
+            basements: self.basements,
+            entrances,
+            labels: self.floor_labels.clone(),
+            hall_capacities: self.hall_capacities.clone(),
+            secured_floors: self.secured_floors.clone(),
+            banks: self.banks.clone(),
+            sky_lobbies: self.sky_lobbies.clone(),
+            ..BuildingTopology::new(self.floors)
+        }
+    }
+
+    /// The building settings this config describes
+    pub fn elevator_config(&self) -> ElevatorConfig {
+        ElevatorConfig {
+            max_speed: self.max_speed,
+            max_acceleration: self.max_acceleration,
+            max_jerk: self.max_jerk,
+            door_open_time: self.door_open_time,
+            door_close_time: self.door_close_time,
+            door_dwell_time: self.door_dwell_time,
+            accessible_dwell_time: self.accessible_dwell_time,
+            max_door_reopens: self.max_door_reopens,
+            door_time_jitter: self.door_time_jitter,
+            acceleration_jitter: self.acceleration_jitter,
+            transfer_time: self.transfer_time,
+            overload_sensor: self.overload_sensor,
+            weight_limits: self.weight_limits.clone(),
+            served_floors: self.served_floors.clone(),
+            classes: self.car_classes.clone(),
+            hall_buttons: self.hall_buttons,
+            dedications: self.dedications.clone(),
+            ..ElevatorConfig::default()
+        }
+    }
+
+    /// How people behave, which depends on the controller they're using
+    pub fn people_config(&self) -> PeopleConfig {
+        // nobody is given a wheelchair unless asked, so seeded runs repeat as they did
+        let attributes = if self.wheelchair_users > 0. {
+            vec![(Attribute::Wheelchair, self.wheelchair_users)]
+        } else {
+            Vec::new()
+        };
+        let people = PeopleConfig {
+            overflow: self.overflow,
+            back_area_walk: self.back_area_walk,
+            sky_lobby_walk: self.sky_lobby_walk,
+            visitors: self.visitors,
+            attributes,
+            ..PeopleConfig::default()
+        };
+        match self.controller {
+            ControllerChoice::Basic | ControllerChoice::Look | ControllerChoice::Group => people,
+            ControllerChoice::Destination => PeopleConfig {
+                boarding: BoardingPolicy::Assigned,
+                hall_calls: HallCallMode::Kiosk,
+                ..people
+            },
+        }
+    }
+
+    /// The clock at the start of the run, reading start_time
+    pub fn clock(&self) -> Result<SimClock, ConfigError> {
+        SimClock::parse(&self.start_time)
+            .ok_or_else(|| ConfigError::StartTime(self.start_time.clone()))
+    }
+
+    /// The traffic pattern people arrive in, following the clock, with lobby trips to and from
+    /// the building's lobby above any basements, and its floors weighted if floor_weights is
+    /// set. Fails for an unknown pattern or start time, or weights that would leave nowhere
+    /// to go
+    pub fn traffic_pattern(&self) -> Result<Box<dyn TrafficPattern + Send>, ConfigError> {
+        let mut pattern = traffic_pattern(&self.traffic, self.spawn_rate)
+            .ok_or_else(|| ConfigError::UnknownTraffic(self.traffic.clone()))?;
+        pattern.set_clock(self.clock()?);
+        pattern.set_lobby(self.topology().lobby());
+        if self.floor_weights.is_empty() {
+            return Ok(pattern);
+        }
+        let busy = (0..self.floors as usize)
+            .filter(|&f| self.floor_weights.get(f).is_none_or(|&w| w > 0.))
+            .count();
+        if self.floor_weights.iter().any(|&w| w < 0.) || busy < 2 {
+            return Err(ConfigError::FloorWeights);
+        }
+        Ok(Box::new(FloorWeights::new(
+            pattern,
+            self.floor_weights.clone(),
+        )))
+    }
+
+    /// What the controller is allowed to see. Future arrivals are always hidden, since no
+    /// controller a config can choose knows them
+    pub fn observation_filter(&self) -> ObservationFilter {
+        ObservationFilter {
+            riders: self.observation,
+            hide_loads: self.hide_loads,
+            hide_arrival_rates: self.hide_arrival_rates,
+            hide_queues: self.hide_queues,
+            hide_future_arrivals: true,
+        }
+    }
+
+    /// How often things break down
+    pub fn fault_config(&self) -> FaultConfig {
+        FaultConfig {
+            stuck_rate: self.stuck_rate,
+            door_jam_rate: self.door_jam_rate,
+            button_break_rate: self.button_break_rate,
+            repair_time: self.repair_time,
+        }
+    }
+
+    /// How often deliveries arrive
+    pub fn delivery_config(&self) -> DeliveryConfig {
+        DeliveryConfig {
+            rate: self.delivery_rate,
+            reservation: self.delivery_reservation,
+        }
+    }
+
+    /// Create the simulation this config describes, seeding the random numbers first if it
+    /// has a seed
+    pub fn build(&self) -> Result<Simulation, ConfigError> {
+        if let Some(seed) = self.seed {
+            rng::seed(seed);
+        }
+
+        let pattern = self.traffic_pattern()?;
+        let topology = Arc::new(self.topology());
+        let mut people = PeopleSim::with_pattern(self.floors, pattern, self.people_config());
+        people.set_topology(Arc::clone(&topology));
+        let mut building = ElevatorSim::with_topology(topology, self.cars, self.elevator_config());
+        building.set_clock(self.clock()?);
+        let mut controller = self.controller.build();
+        controller = match self.parking {
+            ParkingChoice::Stay => controller,
+            ParkingChoice::Lobby => Box::new(Parked::new(
+                controller,
+                Box::new(ReturnToLobby),
+                self.parking_delay,
+            )),
+            ParkingChoice::Even => Box::new(Parked::new(
+                controller,
+                Box::new(DistributeEvenly),
+                self.parking_delay,
+            )),
+            ParkingChoice::Forecast => Box::new(Parked::new(
+                controller,
+                Box::new(ForecastParking::new(
+                    Box::new(EmaForecaster),
+                    FORECAST_HORIZON,
+                )),
+                self.parking_delay,
+            )),
+        };
+        let mut sim =
+            Simulation::try_new(people, building, controller).map_err(ConfigError::Floors)?;
+        sim.set_observation(self.observation_filter())
+            .map_err(ConfigError::Observation)?;
+        if let Some(shadow) = self.shadow {
+            sim.start_shadow(shadow.build())
+                .map_err(ConfigError::Observation)?;
+        }
+        sim.set_faults(self.fault_config());
+        sim.set_deliveries(self.delivery_config());
+        sim.set_step_length(self.timestep);
+        Ok(sim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_and_json_fill_in_defaults() {
+        let toml =
+            SimConfig::from_toml("floors = 20\ncars = 4\ncontroller = \"destination\"\nseed = 5\n")
+                .unwrap();
+        assert_eq!(toml.floors, 20);
+        assert_eq!(toml.cars, 4);
+        assert_eq!(toml.controller, ControllerChoice::Destination);
+        assert_eq!(toml.seed, Some(5));
+        assert_eq!(toml.steps, SimConfig::default().steps);
+        assert_eq!(toml.observation, Observation::Classic);
+
+        let json = SimConfig::from_json(
+            r#"{"door_dwell_time": 2.5, "traffic": "up-peak", "observation": "rich",
+                "hall_buttons": "single"}"#,
+        )
+        .unwrap();
+        assert_eq!(json.door_dwell_time, 2.5);
+        assert_eq!(json.observation, Observation::Rich);
+        assert_eq!(json.elevator_config().door_dwell_time, 2.5);
+        assert_eq!(json.elevator_config().hall_buttons, HallButtons::Single);
+        assert!(json.build().is_ok());
+
+        let single = SimConfig::single_car();
+        assert_eq!(
+            (single.cars, single.controller),
+            (1, ControllerChoice::Look)
+        );
+        assert!(single.build().is_ok());
+
+        let tall = SimConfig::supertall().build().unwrap();
+        let state = tall.building().state();
+        assert_eq!((state.floors.len(), state.cars.len()), (120, 24));
+        assert_eq!(state.cars_serving(60).count(), 24);
+        assert_eq!(state.cars_serving(100).count(), 8);
+
+        assert!(SimConfig::from_toml("flors = 3").is_err());
+        let bad = SimConfig {
+            traffic: "rush".to_string(),
+            ..SimConfig::default()
+        };
+        assert!(matches!(bad.build(), Err(ConfigError::UnknownTraffic(_))));
+        let nowhere = SimConfig::from_toml("floors = 3\nfloor_weights = [0, 4, 0]").unwrap();
+        assert!(matches!(nowhere.build(), Err(ConfigError::FloorWeights)));
+
+        let morning = SimConfig::from_toml("start_time = \"07:30\"").unwrap();
+        let state = morning.build().unwrap().building().state().clone();
+        assert_eq!(state.clock.to_string(), "07:30:00");
+        let late = SimConfig::from_toml("start_time = \"25:00\"").unwrap();
+        assert!(matches!(late.build(), Err(ConfigError::StartTime(_))));
+    }
+
+    #[test]
+    fn supertall_runs() {
+        use crate::people::PersonState;
+
+        let mut tall = SimConfig {
+            seed: Some(3),
+            ..SimConfig::supertall()
+        }
+        .build()
+        .unwrap();
+        for _ in 0..300 {
+            tall.step(0.1);
+        }
+        // the morning rush has started, and cars are carrying people up from the lobby
+        let people = tall.people().people();
+        assert!(people.iter().any(|p| p.state != PersonState::Waiting));
+        let state = tall.building().state();
+        assert!(state.cars.iter().any(|car| car.current_floor > 0.));
+    }
+
+    #[test]
+    fn controllers_are_picked_by_name() {
+        for choice in ControllerChoice::ALL {
+            assert_eq!(ControllerChoice::named(choice.name()), Some(choice));
+        }
+        assert_eq!(
+            ControllerChoice::named("scan"),
+            Some(ControllerChoice::Look)
+        );
+        assert_eq!(ControllerChoice::named("elevator operator"), None);
+        let scan = SimConfig::from_toml("controller = \"scan\"").unwrap();
+        assert_eq!(scan.controller, ControllerChoice::Look);
+    }
+}
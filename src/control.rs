@@ -1,9 +1,42 @@
-use crate::elevator::{BuildingState, ElevatorCommand};
-use crate::types::Floor;
+use crate::elevator::{DoorState, ElevatorCommand, ElevatorSim, FloorState};
+use crate::metrics::StatsView;
+use crate::types::{BankId, CarId, Direction, Floor, ScriptedArrival};
+use crate::view::{BuildingView, CarView, HallCall};
+use std::collections::{HashMap, VecDeque};
 
-/// This is a trait which allows you to swap between different methods of elevator control
-pub trait ElevatorController {
-    fn tick(&mut self, state: &BuildingState) -> Vec<ElevatorCommand>;
+/// This is a trait which allows you to swap between different methods of elevator control.
+/// Controllers see a read only view of the building, and the statistics collected about it.
+/// They're Send, so a simulation can be run on another thread
+pub trait ElevatorController: Send {
+    fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand>;
+
+    /// whether the controller was told who will arrive before they do, which an
+    /// ObservationFilter can refuse
+    fn sees_future_arrivals(&self) -> bool {
+        false
+    }
+}
+
+/// Builds a new controller of one kind, so one can be picked by name
+pub type ControllerFactory = fn() -> Box<dyn ElevatorController>;
+
+/// The controllers which can be picked by name, with --controller or in a config file. scan
+/// is another name for look, after the algorithm it follows. The remote controller isn't
+/// here, since it needs the address of the scheduler to connect to
+pub const CONTROLLERS: &[(&str, ControllerFactory)] = &[
+    ("basic", || Box::new(BasicController)),
+    ("destination", || Box::new(DestinationController::default())),
+    ("look", || Box::new(LookController)),
+    ("scan", || Box::new(LookController)),
+    ("group", || Box::new(GroupController::default())),
+];
+
+/// A new controller of the kind called name, or None if no controller has that name
+pub fn controller_named(name: &str) -> Option<Box<dyn ElevatorController>> {
+    CONTROLLERS
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|(_, build)| build())
 }
 
 /// An empty struct on which to implement ElevatorController, to use as an object
@@ -13,80 +46,859 @@ pub struct BasicController;
 impl ElevatorController for BasicController {
     /// Based on the building's state, generate a vector of ElevatorCommands
     /// which tell elevators to go to target floors
-    fn tick(&mut self, state: &BuildingState) -> Vec<ElevatorCommand> {
+    fn tick(&mut self, view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
         let mut commands = Vec::new();
 
-        // for each floor process hall buttons
-        for floor_state in &state.floors {
-            if !floor_state.out_up && !floor_state.out_down {
+        // for each floor process hall buttons, accessible calls first so they get the
+        // nearest free car
+        let mut floors: Vec<&FloorState> = view.floors().iter().collect();
+        floors.sort_by_key(|f| !f.access_call);
+        for floor_state in floors {
+            if !floor_state.called() {
                 continue;
             }
 
-            if state.cars.is_empty() {
+            if view.num_cars() == 0 {
                 break;
             }
 
-            // check if an elevator is already headed to that floor
-            let floor = floor_state.floor;
-            let mut already_served = false;
-            for car in &state.cars {
-                if car.target_floor == Some(floor) {
-                    already_served = true;
-                    break;
+            // in a building with banks each bank's buttons are answered by its own cars,
+            // and buttons pressed for no bank in particular by any car
+            let mut banks: Vec<Option<BankId>> = floor_state.called_banks().map(Some).collect();
+            if banks.is_empty() {
+                banks.push(None);
+            }
+            for bank in banks {
+                let in_bank = |car: &CarView| bank.is_none_or(|bank| car.bank() == Some(bank));
+
+                // check if an elevator is already headed to that floor. Cars in independent
+                // service go wherever their operator takes them, so they don't count
+                let floor = floor_state.floor;
+                let mut already_served = false;
+                for car in view.cars().filter(|car| car.dispatchable() && in_bank(car)) {
+                    if car.target_floor() == Some(floor) {
+                        already_served = true;
+                        break;
+                    }
+
+                    // a zoned car left idle here, with the button pressed anyway, doesn't go
+                    // where whoever pressed it wants to, so another car is needed
+                    let car_floor = car.position().round() as Floor;
+                    let ignored =
+                        car.zoned() && car.door_open() && !car.car_buttons().contains(&true);
+                    if car_floor == floor && car.door_opening_or_open() && !ignored {
+                        already_served = true;
+                        break;
+                    }
+                }
+
+                if already_served {
+                    continue;
+                }
+
+                let mut best_car: Option<CarId> = None;
+                let mut best_distance = f32::MAX;
+
+                // for each car
+                for car in view.cars().filter(|car| in_bank(car)) {
+                    if car.target_floor().is_some() || !car.dispatchable() || !car.serves(floor) {
+                        continue;
+                    } //if the car doesn't have a target floor already, and stops here
+                    if car.position().round() as Floor == floor && car.door_opening_or_open() {
+                        continue;
+                    }
+                    // find the car which is the closest to the target floor
+                    let distance = (car.position() - floor_state.floor as f32).abs();
+                    if distance < best_distance {
+                        best_distance = distance;
+                        best_car = Some(car.id());
+                    }
                 }
 
-                let car_floor = car.current_floor.round() as Floor;
-                if car_floor == floor && car.door_open {
-                    already_served = true;
-                    break;
+                //if we found a viable car that wasn't busy
+                if let Some(car_id) = best_car {
+                    commands.push(ElevatorCommand::MoveCarTo {
+                        car_id,
+                        floor: floor_state.floor,
+                    });
                 }
             }
+        }
 
-            if already_served {
-                continue;
+        // process interior elevator buttons
+        for car in view.cars().filter(|car| car.dispatchable()) {
+            for (floor_index, &pressed) in car.car_buttons().iter().enumerate() {
+                if !pressed {
+                    continue;
+                }
+
+                // issue commands to move the car to every pressed interior button
+                commands.push(ElevatorCommand::MoveCarTo {
+                    car_id: car.id(),
+                    floor: floor_index as Floor,
+                });
             }
+        }
 
-            let mut best_car_index: Option<usize> = None;
-            let mut best_distance = f32::MAX;
+        commands
+    }
+}
+
+/// A controller for a building with a single car, which sweeps it up and down like the LOOK
+/// disk scheduler. The car carries on the way it's going, stopping for car buttons and for
+/// hall calls going its way, as far as the furthest request ahead, then turns around and
+/// does the same on the way back. Every car answers every hall call, so in a building with
+/// several cars they bunch together, and BasicController or DestinationController suit it
+/// better
+pub struct LookController;
+
+impl LookController {
+    /// The floor a car heading direction from floor from should stop at next: the nearest car
+    /// button or hall call going its way, or failing that the furthest hall call the other
+    /// way, where it will turn around. from itself only counts if the car is still moving
+    /// and can stop there
+    fn next_stop(
+        view: &BuildingView,
+        car: CarView,
+        from: Floor,
+        direction: Direction,
+    ) -> Option<Floor> {
+        let moving = car.velocity() != 0.;
+        let ahead = |floor: Floor| match direction {
+            Direction::Up => floor > from || (moving && floor == from),
+            Direction::Down => floor < from || (moving && floor == from),
+        };
+        let floors = || {
+            view.floors()
+                .iter()
+                .filter(|f| car.serves(f.floor) && ahead(f.floor))
+        };
+
+        let on_the_way = floors()
+            .filter(|f| f.hall_call(direction) || car.car_buttons()[f.floor as usize])
+            .map(|f| f.floor)
+            .min_by_key(|f| f.abs_diff(from));
+        on_the_way.or_else(|| {
+            floors()
+                .filter(|f| f.called())
+                .map(|f| f.floor)
+                .max_by_key(|f| f.abs_diff(from))
+        })
+    }
+}
+
+impl ElevatorController for LookController {
+    /// Send every car to the next stop of its sweep, changing a moving car's stop when a
+    /// request appears between it and where it's going
+    fn tick(&mut self, view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
+        let mut commands = Vec::new();
 
-            // for each car
-            for (i, car) in state.cars.iter().enumerate() {
-                if car.target_floor.is_some() {
+        for car in view.cars().filter(|car| car.dispatchable()) {
+            let next = if car.velocity() != 0. {
+                // a moving car carries on the way it's going
+                let direction = if car.velocity() > 0. {
+                    Direction::Up
+                } else {
+                    Direction::Down
+                };
+                Self::next_stop(view, car, car.nearest_floor_ahead(), direction)
+            } else {
+                if car.target_floor().is_some() {
                     continue;
-                } //if the car doesn't have a target floor already
-                // find the car which is the closest to the target floor
-                let distance = (car.current_floor - floor_state.floor as f32).abs();
-                if distance < best_distance {
-                    best_distance = distance;
-                    best_car_index = Some(i);
                 }
+                let here = car.position().round() as Floor;
+                let floor_state = &view.floors()[here as usize];
+                let called_here = floor_state.called() || car.car_buttons()[here as usize];
+                if called_here && car.door() == DoorState::Closed {
+                    // someone wants the car where it's parked, so it opens up for them
+                    Some(here)
+                } else {
+                    // a stopped car keeps its direction if there's anything left that way,
+                    // and one with no direction heads for the nearest request
+                    let up = Self::next_stop(view, car, here, Direction::Up);
+                    let down = Self::next_stop(view, car, here, Direction::Down);
+                    match car.direction() {
+                        Some(Direction::Up) => up.or(down),
+                        Some(Direction::Down) => down.or(up),
+                        None => [up, down]
+                            .into_iter()
+                            .flatten()
+                            .min_by_key(|f| f.abs_diff(here)),
+                    }
+                }
+            };
+
+            if let Some(floor) = next
+                && car.target_floor() != Some(floor)
+            {
+                commands.push(ElevatorCommand::MoveCarTo {
+                    car_id: car.id(),
+                    floor,
+                });
             }
+        }
+
+        commands
+    }
+}
+
+/// A controller for destination dispatch, where people enter their destination at a kiosk
+/// instead of pressing up or down. Each trip request is assigned to the car that would
+/// reach it soonest, with cars already stopping at that floor preferred, so people heading
+/// the same way share cars. The assignment is announced on the hall panel. Ordinary hall
+/// buttons are ignored by this controller
+/// stopping_cost - the cost of giving a trip to a car already stopping at its floor
+/// load_weight - the extra cost for every stop a car already has planned, which spreads
+/// trips across cars instead of piling them onto the nearest one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DestinationController {
+    pub stopping_cost: f32,
+    pub load_weight: f32,
+}
 
-            //if we found a viable car that wasn't busy
-            if let Some(car_id) = best_car_index {
-                let car_id = state.cars[car_id].id;
+impl DestinationController {
+    /// Create a controller from a parameter vector of [stopping_cost, load_weight], for
+    /// tuning. Missing parameters keep their defaults
+    pub fn from_params(params: &[f32]) -> Self {
+        let mut controller = Self::default();
+        if let Some(&stopping_cost) = params.first() {
+            controller.stopping_cost = stopping_cost;
+        }
+        if let Some(&load_weight) = params.get(1) {
+            controller.load_weight = load_weight;
+        }
+        controller
+    }
+
+    /// An estimate of how far a car has to travel before it could pick someone up at floor:
+    /// the rest of its current trip, then the trip to floor. Cars already assigned trips
+    /// from floor are stopping there anyway, so they only cost stopping_cost. Every stop
+    /// the car has planned adds load_weight
+    fn cost(&self, view: &BuildingView, car: CarView, floor: Floor) -> f32 {
+        let planned = view
+            .floors()
+            .iter()
+            .filter(|f| f.assignments.iter().any(|a| a.car_id == car.id()))
+            .count()
+            + car.car_buttons().iter().filter(|&&pressed| pressed).count();
+        let load = self.load_weight * planned as f32;
 
+        let already_stopping = view.floors()[floor as usize]
+            .assignments
+            .iter()
+            .any(|a| a.car_id == car.id());
+        if already_stopping {
+            return self.stopping_cost + load;
+        }
+
+        let end = car.target_floor().map_or(car.position(), |t| t as f32);
+        (end - car.position()).abs() + (end - floor as f32).abs() + load
+    }
+}
+
+impl ElevatorController for DestinationController {
+    /// Assign a car to every trip request, then send idle cars to pick up their assigned
+    /// trips, and to drop off their passengers
+    fn tick(&mut self, view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
+        let mut commands = Vec::new();
+
+        // assign every trip request to the cheapest car which serves both ends of it
+        for floor_state in view.floors() {
+            let floor = floor_state.floor;
+            for &destination in &floor_state.trip_requests {
+                let best = view
+                    .cars_serving(floor)
+                    .filter(|car| car.dispatchable() && car.serves(destination))
+                    .min_by(|a, b| {
+                        self.cost(view, *a, floor)
+                            .total_cmp(&self.cost(view, *b, floor))
+                    });
+
+                if let Some(car) = best {
+                    commands.push(ElevatorCommand::AssignTrip {
+                        floor,
+                        destination,
+                        car_id: car.id(),
+                    });
+                }
+            }
+        }
+
+        // send each idle car to the nearest floor where it has a pickup or a drop off. A full
+        // car can't pick anyone up, so it only drops off
+        for car in view.cars() {
+            if car.target_floor().is_some() || !car.dispatchable() {
+                continue;
+            }
+            let car_floor = car.position().round() as Floor;
+
+            let pickups = view.floors().iter().filter(|f| {
+                !car.is_full()
+                    && f.floor != car_floor
+                    && f.assignments.iter().any(|a| a.car_id == car.id())
+            });
+            let dropoffs = view.floors().iter().filter(|f| {
+                car.car_buttons()
+                    .get(f.floor as usize)
+                    .copied()
+                    .unwrap_or(false)
+            });
+
+            let nearest = pickups
+                .chain(dropoffs)
+                .map(|f| f.floor)
+                .min_by_key(|f| f.abs_diff(car_floor));
+
+            if let Some(floor) = nearest {
                 commands.push(ElevatorCommand::MoveCarTo {
-                    car_id,
-                    floor: floor_state.floor,
+                    car_id: car.id(),
+                    floor,
                 });
             }
         }
 
-        // process interior elevator buttons
-        for car in &state.cars {
-            for (floor_index, &pressed) in car.car_buttons.iter().enumerate() {
-                if !pressed {
-                    continue;
+        commands
+    }
+}
+
+/// A controller for collective control, the usual way a group of cars with up and down
+/// buttons is run. Every tick each hall call is handed to the car it would cost least to
+/// send, and each car sweeps up and down through its car buttons and the calls it was
+/// handed, stopping for calls going its way and turning around at the furthest request.
+/// Calls are handed out afresh every tick, so they move to a better car as things change
+/// stop_cost - the extra cost for every stop a car already has to make, in floors
+/// load_weight - the extra cost of a full car over an empty one, in floors. Full cars aren't
+/// handed calls at all
+/// reversal_cost - the extra cost of a car having to turn around to answer a call
+/// switch_cost - how much cheaper another car has to be to take a call from the car it was
+/// handed to last tick, so calls don't flicker between cars costing about the same
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupController {
+    pub stop_cost: f32,
+    pub load_weight: f32,
+    pub reversal_cost: f32,
+    pub switch_cost: f32,
+    assignments: HashMap<HallCall, CarId>,
+}
+
+impl Default for GroupController {
+    fn default() -> Self {
+        Self {
+            stop_cost: 2.,
+            load_weight: 5.,
+            reversal_cost: 2.,
+            switch_cost: 1.,
+            assignments: HashMap::new(),
+        }
+    }
+}
+
+/// implement functions for GroupController
+/// assignment - the car a hall call was last handed to
+/// cost - how costly it would be for a car to answer a hall call
+/// next_stop - where a car should go next to work through its requests
+impl GroupController {
+    /// The car a hall call was handed to on the last tick, if it's still lit
+    pub fn assignment(&self, floor: Floor, direction: Option<Direction>) -> Option<CarId> {
+        self.assignments.get(&(floor, direction)).copied()
+    }
+
+    /// How costly it would be for car to answer call, when it has already been handed
+    /// handed calls this tick: the floors it travels to get there, finishing its sweep
+    /// first if the call is behind it or going the other way, plus its stops and its load
+    fn cost(&self, car: CarView, call: HallCall, handed: usize) -> f32 {
+        let (floor, call_direction) = call;
+        let here = car.position();
+        let target = floor as f32;
+        let pressed: Vec<f32> = car
+            .car_buttons()
+            .iter()
+            .enumerate()
+            .filter(|(_, pressed)| **pressed)
+            .map(|(floor, _)| floor as f32)
+            .collect();
+
+        let travel = match heading(car) {
+            None => (target - here).abs(),
+            Some(direction) => {
+                let sign = match direction {
+                    Direction::Up => 1.,
+                    Direction::Down => -1.,
+                };
+                let ahead = (target - here) * sign >= 0.;
+                if ahead && call_direction.is_none_or(|d| d == direction) {
+                    (target - here).abs()
+                } else {
+                    // the car goes on to its furthest stop first, then comes back
+                    let furthest = pressed
+                        .iter()
+                        .chain(car.target_floor().map(|f| f as f32).as_ref())
+                        .map(|stop| (stop - here) * sign)
+                        .fold(0., f32::max);
+                    let turn = here + furthest * sign;
+                    (turn - here).abs() + (turn - target).abs() + self.reversal_cost
                 }
+            }
+        };
+        let stops = pressed.len() + handed;
+        let load = car.passenger_count() as f32 / car.capacity().max(1) as f32;
+        travel + self.stop_cost * stops as f32 + self.load_weight * load
+    }
 
-                // issue commands to move the car to every pressed interior button
+    /// The floor car should stop at next, out of its car buttons and the calls it was
+    /// handed: the nearest going its way, or failing that the furthest ahead going the other
+    /// way, where it will turn around. A stopped car with nothing ahead turns around, one
+    /// without a direction heads for the nearest request, and a moving car with nothing
+    /// ahead carries on to its target
+    fn next_stop(car: CarView, calls: &[HallCall]) -> Option<Floor> {
+        let moving = car.velocity() != 0.;
+        let here = car.position().round() as Floor;
+        let from = if moving {
+            car.nearest_floor_ahead()
+        } else {
+            here
+        };
+        let pressed = |floor: Floor| {
+            car.car_buttons()
+                .get(floor as usize)
+                .copied()
+                .unwrap_or(false)
+        };
+
+        // a parked car answers a call where it is by opening its door
+        if !moving && car.door() == DoorState::Closed && calls.iter().any(|&(f, _)| f == here) {
+            return Some(here);
+        }
+
+        let sweep = |direction: Direction| {
+            let ahead = |floor: Floor| match direction {
+                Direction::Up => floor > from || (moving && floor == from),
+                Direction::Down => floor < from || (moving && floor == from),
+            };
+            let buttons = (0..car.car_buttons().len() as Floor).filter(|&f| pressed(f));
+            let our_way = calls
+                .iter()
+                .filter(|(_, d)| d.is_none_or(|d| d == direction))
+                .map(|&(f, _)| f);
+            buttons
+                .chain(our_way)
+                .filter(|&f| ahead(f))
+                .min_by_key(|f| f.abs_diff(from))
+                .or_else(|| {
+                    calls
+                        .iter()
+                        .map(|&(f, _)| f)
+                        .filter(|&f| ahead(f))
+                        .max_by_key(|f| f.abs_diff(from))
+                })
+        };
+        let (up, down) = (sweep(Direction::Up), sweep(Direction::Down));
+        match heading(car) {
+            Some(Direction::Up) if moving => up,
+            Some(Direction::Down) if moving => down,
+            Some(Direction::Up) => up.or(down),
+            Some(Direction::Down) => down.or(up),
+            None => [up, down]
+                .into_iter()
+                .flatten()
+                .min_by_key(|f| f.abs_diff(from)),
+        }
+    }
+}
+
+/// Which way a car is going: the way it's moving, or if it's stopped, the way it's
+/// committed to
+fn heading(car: CarView) -> Option<Direction> {
+    if car.velocity() > 0. {
+        Some(Direction::Up)
+    } else if car.velocity() < 0. {
+        Some(Direction::Down)
+    } else {
+        car.direction()
+    }
+}
+
+impl ElevatorController for GroupController {
+    /// Hand every hall call to the cheapest car which stops there, then send each car to
+    /// the next stop of its sweep
+    fn tick(&mut self, view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
+        // every call a car is handed makes it costlier for the next one, so accessible calls
+        // are handed out first
+        let mut handed = vec![0; view.num_cars()];
+        let mut assignments = HashMap::new();
+        let mut calls: Vec<HallCall> = view.calls().collect();
+        calls.sort_by_key(|&(floor, _)| !view.floor(floor).is_some_and(|f| f.access_call));
+        for call in calls {
+            let previous = self.assignments.get(&call).copied();
+            let best = view
+                .cars()
+                .enumerate()
+                .filter(|(_, car)| car.dispatchable() && !car.is_full() && car.serves(call.0))
+                .map(|(i, car)| {
+                    let cost = self.cost(car, call, handed[i]);
+                    let kept = if previous == Some(car.id()) {
+                        self.switch_cost
+                    } else {
+                        0.
+                    };
+                    (i, car.id(), cost - kept)
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2));
+            if let Some((i, car_id, _)) = best {
+                handed[i] += 1;
+                assignments.insert(call, car_id);
+            }
+        }
+        self.assignments = assignments;
+
+        let mut commands = Vec::new();
+        for car in view.cars().filter(|car| car.dispatchable()) {
+            let calls: Vec<HallCall> = self
+                .assignments
+                .iter()
+                .filter(|&(_, &car_id)| car_id == car.id())
+                .map(|(&call, _)| call)
+                .collect();
+            if let Some(floor) = Self::next_stop(car, &calls)
+                && car.target_floor() != Some(floor)
+            {
                 commands.push(ElevatorCommand::MoveCarTo {
-                    car_id: car.id,
-                    floor: floor_index as Floor,
+                    car_id: car.id(),
+                    floor,
                 });
             }
         }
+        commands
+    }
+}
+
+/// A single planned stop in the oracle's schedule
+#[derive(Clone, Debug, PartialEq)]
+enum PlannedStop {
+    /// wait at floor for a person who appears at time, until they press destination
+    Pickup {
+        floor: Floor,
+        time: f32,
+        destination: Floor,
+    },
+    /// let off at floor a person who appeared at time
+    Dropoff { floor: Floor, time: f32 },
+}
+
+impl PlannedStop {
+    /// The floor the stop is at
+    fn floor(&self) -> Floor {
+        match *self {
+            PlannedStop::Pickup { floor, .. } | PlannedStop::Dropoff { floor, .. } => floor,
+        }
+    }
+}
+
+/// How long the oracle keeps a car waiting for a person who never boards it, in seconds
+const ORACLE_PICKUP_PATIENCE: f32 = 5.;
+
+/// How many of the stops a car hasn't got to yet the oracle tries fitting someone in among,
+/// besides the end of its plan. More finds better plans, but planning takes longer
+const ORACLE_LOOKAHEAD: usize = 12;
+
+/// Where a car has got to partway through the oracle's plan for it
+/// time - when it's done with the stops so far, in seconds into the run
+/// floor - where it is then
+/// opened - when its door finished opening there, or None if it's closed
+/// riders - where each person on board is going
+/// journeys - how many seconds everyone let off so far took from appearing to getting out
+#[derive(Clone, Debug, PartialEq)]
+struct PlanProgress {
+    time: f32,
+    floor: Floor,
+    opened: Option<f32>,
+    riders: Vec<Floor>,
+    journeys: f32,
+}
+
+/// A controller that cheats. It is given every scripted arrival in advance, and plans
+/// a schedule before the simulation starts, so its cars are often waiting with open doors
+/// before people even appear. It is only meaningful for scripted runs, and shows roughly
+/// how much better other controllers could do if they knew who was coming. It isn't
+/// optimal, so it isn't a strict bound.
+///
+/// The schedule is built in arrival order: each person is fitted into whichever car's
+/// plan, and wherever in it, adds least to everyone's journeys, so people going the same
+/// way share a car and its stops. Plans are timed with the building's real door timings,
+/// transfer time, car speeds and acceleration, and respect the cars' capacities and the
+/// floors they serve. The oracle reads the time from the building, so it works whatever the
+/// timestep.
+///
+/// As each person appears the oracle assigns them their car with AssignTrip, so it works
+/// best with people who only board the car they're assigned, see BoardingPolicy::Assigned.
+/// Anyone who gets into another car anyway is let off on its way
+pub struct OracleController {
+    plans: Vec<VecDeque<PlannedStop>>,
+    assignments: VecDeque<(ScriptedArrival, CarId)>,
+}
+
+impl OracleController {
+    /// Plan a schedule for building, before any time has passed in it
+    pub fn new(arrivals: &[ScriptedArrival], building: &ElevatorSim) -> Self {
+        let mut arrivals = arrivals.to_vec();
+        arrivals.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let cars = &building.state().cars;
+        let mut plans: Vec<Vec<PlannedStop>> = vec![Vec::new(); cars.len()];
+        let mut assignments = VecDeque::new();
+        for arrival in &arrivals {
+            // the cheapest place to fit them in, as (extra seconds, car, pickup, dropoff)
+            let mut best: Option<(f32, usize, usize, usize)> = None;
+            for (i, car) in cars.iter().enumerate() {
+                if !car.serves(arrival.origin) || !car.serves(arrival.destination) {
+                    continue;
+                }
+                let plan = &plans[i];
+
+                // the stops a car is done with before the person appears stay as they are
+                let mut settled = oracle_start(building, i);
+                let mut fixed = 0;
+                while let Some(next) = plan
+                    .get(fixed)
+                    .and_then(|stop| oracle_advance(building, i, settled.clone(), stop))
+                    .filter(|next| next.time <= arrival.time)
+                {
+                    settled = next;
+                    fixed += 1;
+                }
+                let Some(before) = oracle_run(building, i, settled.clone(), &plan[fixed..]) else {
+                    continue;
+                };
+
+                let tried =
+                    |at: usize, from: usize| at < from + ORACLE_LOOKAHEAD || at == plan.len();
+                for pickup in (fixed..=plan.len()).filter(|&at| tried(at, fixed)) {
+                    for dropoff in (pickup..=plan.len()).filter(|&at| tried(at, pickup)) {
+                        let tail =
+                            with_trip(&plan[fixed..], arrival, pickup - fixed, dropoff - fixed);
+                        let Some(after) = oracle_run(building, i, settled.clone(), &tail) else {
+                            continue;
+                        };
+                        let extra = after.journeys - before.journeys;
+                        if best.is_none_or(|(cheapest, ..)| extra < cheapest) {
+                            best = Some((extra, i, pickup, dropoff));
+                        }
+                    }
+                }
+            }
+
+            if let Some((_, car, pickup, dropoff)) = best {
+                plans[car] = with_trip(&plans[car], arrival, pickup, dropoff);
+                assignments.push_back((*arrival, cars[car].id));
+            }
+        }
+
+        Self {
+            plans: plans.into_iter().map(VecDeque::from).collect(),
+            assignments,
+        }
+    }
+}
+
+/// A plan with someone's trip added, picked up before the stop at pickup and let off before
+/// the stop at dropoff, which is no earlier
+fn with_trip(
+    plan: &[PlannedStop],
+    arrival: &ScriptedArrival,
+    pickup: usize,
+    dropoff: usize,
+) -> Vec<PlannedStop> {
+    let mut plan = plan.to_vec();
+    plan.insert(
+        dropoff,
+        PlannedStop::Dropoff {
+            floor: arrival.destination,
+            time: arrival.time,
+        },
+    );
+    plan.insert(
+        pickup,
+        PlannedStop::Pickup {
+            floor: arrival.origin,
+            time: arrival.time,
+            destination: arrival.destination,
+        },
+    );
+    plan
+}
+
+/// Where car is before its plan starts: standing where the building starts it, door closed
+fn oracle_start(building: &ElevatorSim, car: usize) -> PlanProgress {
+    PlanProgress {
+        time: 0.,
+        floor: building.state().cars[car].current_floor.round() as Floor,
+        opened: None,
+        riders: Vec::new(),
+        journeys: 0.,
+    }
+}
+
+/// Where car has got to after making stop too, or None if it would be too full, or the
+/// person getting on wouldn't, because the car is taking its riders the other way
+fn oracle_advance(
+    building: &ElevatorSim,
+    car: usize,
+    mut progress: PlanProgress,
+    stop: &PlannedStop,
+) -> Option<PlanProgress> {
+    let config = building.config();
+    let doors = config.door_timing(car);
+    let floor = stop.floor();
+    if floor != progress.floor {
+        // shut the door once it's been open long enough, then travel
+        if let Some(opened) = progress.opened.take() {
+            progress.time = progress.time.max(opened + doors.dwell_time) + doors.close_time;
+        }
+        let topology = &building.state().topology;
+        let metres = topology.elevation(floor as f32) - topology.elevation(progress.floor as f32);
+        progress.time += config.travel_time(car, metres);
+        progress.floor = floor;
+    }
+    if progress.opened.is_none() {
+        progress.time += doors.open_time;
+        progress.opened = Some(progress.time);
+    }
+
+    match *stop {
+        PlannedStop::Pickup {
+            time, destination, ..
+        } => {
+            let up = destination > floor;
+            let same_way = progress
+                .riders
+                .iter()
+                .all(|&to| to == floor || (to > floor) == up);
+            if !same_way || progress.riders.len() as u32 >= building.state().cars[car].capacity {
+                return None;
+            }
+            progress.riders.push(destination);
+            progress.time = progress.time.max(time) + config.transfer_time;
+        }
+        PlannedStop::Dropoff { time, .. } => {
+            if let Some(rider) = progress.riders.iter().position(|&to| to == floor) {
+                progress.riders.swap_remove(rider);
+            }
+            progress.time += config.transfer_time;
+            progress.journeys += progress.time - time;
+        }
+    }
+    Some(progress)
+}
+
+/// Where car has got to after making every one of stops, or None if it can't make them all
+fn oracle_run(
+    building: &ElevatorSim,
+    car: usize,
+    progress: PlanProgress,
+    stops: &[PlannedStop],
+) -> Option<PlanProgress> {
+    stops.iter().try_fold(progress, |progress, stop| {
+        oracle_advance(building, car, progress, stop)
+    })
+}
+
+impl ElevatorController for OracleController {
+    fn sees_future_arrivals(&self) -> bool {
+        true
+    }
+
+    /// Follow the precomputed schedule. Once a car's schedule is empty, it serves any
+    /// car buttons still lit, such as those pressed by people who boarded opportunistically,
+    /// then any hall calls from people the schedule missed
+    fn tick(&mut self, view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
+        let now = view.clock().elapsed as f32;
+        let mut commands = Vec::new();
+
+        // people are told which car is coming for them as they appear
+        while let Some(&(arrival, car_id)) = self.assignments.front() {
+            if arrival.time > now {
+                break;
+            }
+            commands.push(ElevatorCommand::AssignTrip {
+                floor: arrival.origin,
+                destination: arrival.destination,
+                car_id,
+            });
+            self.assignments.pop_front();
+        }
+
+        for (i, car) in view.cars().enumerate() {
+            // cars that are moving, or out of the controller's hands, are left alone
+            if car.target_floor().is_some() || !car.dispatchable() {
+                continue;
+            }
+
+            let Some(plan) = self.plans.get_mut(i) else {
+                continue;
+            };
+            let car_floor = car.position().round() as Floor;
+
+            // stops here are done once the door is open, and everyone getting on has boarded,
+            // which they have once they press their destination button, or been given up on
+            if car.door_opening_or_open() {
+                while let Some(stop) = plan.front().filter(|stop| stop.floor() == car_floor) {
+                    let done = match *stop {
+                        PlannedStop::Pickup {
+                            time, destination, ..
+                        } => {
+                            now >= time && car.pressed(destination)
+                                || now >= time + ORACLE_PICKUP_PATIENCE
+                        }
+                        PlannedStop::Dropoff { .. } => true,
+                    };
+                    if !done {
+                        break;
+                    }
+                    plan.pop_front();
+                }
+            }
+
+            match plan.front() {
+                // still waiting for someone to board
+                Some(stop) if stop.floor() == car_floor && car.door_opening_or_open() => {}
+                Some(stop) => {
+                    // anyone who got on without being planned for is let off on the way
+                    let next = stop.floor();
+                    let (low, high) = (car_floor.min(next), car_floor.max(next));
+                    let unplanned = (low + 1..high)
+                        .filter(|&floor| car.pressed(floor))
+                        .filter(|&floor| plan.iter().all(|stop| stop.floor() != floor))
+                        .min_by_key(|&floor| floor.abs_diff(car_floor));
+                    commands.push(ElevatorCommand::MoveCarTo {
+                        car_id: car.id(),
+                        floor: unplanned.unwrap_or(next),
+                    });
+                }
+                None => {
+                    // anyone the schedule missed, who is still waiting once the car has no
+                    // riders left, is picked up like a hall call
+                    let missed = view
+                        .floors()
+                        .iter()
+                        .filter(|f| f.called())
+                        .map(|f| f.floor)
+                        .filter(|&floor| car_floor != floor || !car.door_opening_or_open())
+                        .min_by_key(|&floor| floor.abs_diff(car_floor));
+                    let next = car
+                        .car_buttons()
+                        .iter()
+                        .position(|&pressed| pressed)
+                        .map(|floor| floor as Floor)
+                        .or(missed);
+                    if let Some(floor) = next {
+                        commands.push(ElevatorCommand::MoveCarTo {
+                            car_id: car.id(),
+                            floor,
+                        });
+                    }
+                }
+            }
+        }
 
         commands
     }
@@ -95,8 +907,17 @@ impl ElevatorController for BasicController {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::elevator::{ElevatorCarState, FloorState};
+    use crate::clock::SimClock;
+    use crate::elevator::{
+        BuildingState, CarCapabilities, CarClass, ElevatorCarState, ElevatorConfig, ElevatorEvent,
+        ElevatorSim, EmergencyMode, FloorState, ServiceState,
+    };
+    use crate::metrics::ArrivalRateEstimator;
+    use crate::people::{BoardingPolicy, HallCallMode, PeopleConfig, PeopleSim, PersonState};
+    use crate::sim::Simulation;
+    use crate::topology::BuildingTopology;
     use crate::types::CarId;
+    use std::sync::Arc;
 
     #[test]
     fn no_commands_when_nothing_pressed() {
@@ -105,26 +926,65 @@ mod tests {
                 floor: 0,
                 out_up: false,
                 out_down: false,
+                out_call: false,
+                trip_requests: Vec::new(),
+                assignments: Vec::new(),
+                buttons_broken: false,
+                banks: Vec::new(),
+                access_call: false,
+                lanterns: Vec::new(),
             },
             FloorState {
                 floor: 1,
                 out_up: false,
                 out_down: false,
+                out_call: false,
+                trip_requests: Vec::new(),
+                assignments: Vec::new(),
+                buttons_broken: false,
+                banks: Vec::new(),
+                access_call: false,
+                lanterns: Vec::new(),
             },
         ];
 
         let cars = vec![ElevatorCarState {
             id: CarId(0),
             current_floor: 0.0,
-            target_floor: None,
-            door_open: false,
+            elevation: 0.,
+            velocity: 0.,
+            stops: VecDeque::new(),
+            door: DoorState::Closed,
             car_buttons: vec![false, false],
+            served_floors: vec![true, true],
+            occupants: Vec::new(),
+            transfer: None,
+            capacity: 10,
+            passenger_count: 0,
+            service: ServiceState::Normal,
+            capabilities: CarCapabilities::default(),
+            direction: None,
+            rider_destinations: Vec::new(),
+            malfunction: None,
+            bank: None,
+            class: CarClass::Passenger,
+            reserved: None,
+            overloaded: false,
+            load: 0.,
+            weight_limit: 0.,
         }];
 
-        let state = BuildingState { floors, cars };
+        let state = BuildingState {
+            topology: Arc::new(BuildingTopology::new(2)),
+            floors,
+            cars,
+            emergency: EmergencyMode::Normal,
+            clock: SimClock::default(),
+        };
         let mut controller = BasicController;
 
-        let commands = controller.tick(&state);
+        let arrivals = ArrivalRateEstimator::new(2, 60.);
+        let commands = controller.tick(&BuildingView::new(&state), &StatsView::new(&arrivals));
         assert!(commands.is_empty());
     }
 
@@ -135,26 +995,252 @@ mod tests {
                 floor: 0,
                 out_up: false,
                 out_down: false,
+                out_call: false,
+                trip_requests: Vec::new(),
+                assignments: Vec::new(),
+                buttons_broken: false,
+                banks: Vec::new(),
+                access_call: false,
+                lanterns: Vec::new(),
             },
             FloorState {
                 floor: 1,
                 out_up: false,
                 out_down: true,
+                out_call: false,
+                trip_requests: Vec::new(),
+                assignments: Vec::new(),
+                buttons_broken: false,
+                banks: Vec::new(),
+                access_call: false,
+                lanterns: Vec::new(),
             },
         ];
 
         let cars = vec![ElevatorCarState {
             id: CarId(0),
             current_floor: 0.0,
-            target_floor: Some(1),
-            door_open: false,
+            elevation: 0.,
+            velocity: 0.,
+            stops: VecDeque::from([1]),
+            door: DoorState::Closed,
             car_buttons: vec![false, false],
+            served_floors: vec![true, true],
+            occupants: Vec::new(),
+            transfer: None,
+            capacity: 10,
+            passenger_count: 0,
+            service: ServiceState::Normal,
+            capabilities: CarCapabilities::default(),
+            direction: None,
+            rider_destinations: Vec::new(),
+            malfunction: None,
+            bank: None,
+            class: CarClass::Passenger,
+            reserved: None,
+            overloaded: false,
+            load: 0.,
+            weight_limit: 0.,
         }];
 
-        let state = BuildingState { floors, cars };
+        let state = BuildingState {
+            topology: Arc::new(BuildingTopology::new(2)),
+            floors,
+            cars,
+            emergency: EmergencyMode::Normal,
+            clock: SimClock::default(),
+        };
         let mut controller = BasicController;
 
-        let commands = controller.tick(&state);
+        let arrivals = ArrivalRateEstimator::new(2, 60.);
+        let commands = controller.tick(&BuildingView::new(&state), &StatsView::new(&arrivals));
         assert!(commands.is_empty());
     }
+
+    #[test]
+    fn oracle_moves_before_arrival() {
+        let sim = ElevatorSim::new(5, 1);
+        let arrivals = vec![ScriptedArrival {
+            time: 10.,
+            origin: 4,
+            destination: 0,
+        }];
+        let mut controller = OracleController::new(&arrivals, &sim);
+
+        // long before the person appears, the car is already on its way
+        let commands = controller.tick(&BuildingView::new(sim.state()), &sim.stats());
+        match commands.as_slice() {
+            [ElevatorCommand::MoveCarTo { car_id, floor }] => {
+                assert_eq!(*car_id, CarId(0));
+                assert_eq!(*floor, 4);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn oracle_shares_trips_and_keeps_time_between_events() {
+        let config = ElevatorConfig {
+            door_open_time: 1.,
+            door_close_time: 1.,
+            door_dwell_time: 2.,
+            max_acceleration: 1.,
+            ..ElevatorConfig::default()
+        };
+        let building = ElevatorSim::with_config(10, 1, config);
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 10.,
+                origin: 2,
+                destination: 8,
+            },
+            ScriptedArrival {
+                time: 11.,
+                origin: 3,
+                destination: 7,
+            },
+        ];
+        let oracle = OracleController::new(&arrivals, &building);
+
+        // both people ride up together, rather than one after the other
+        let floors: Vec<_> = oracle.plans[0].iter().map(PlannedStop::floor).collect();
+        assert_eq!(floors, [2, 3, 7, 8]);
+
+        // the oracle reads the time, so steps of any length keep it on schedule
+        let people = PeopleSim::scripted(10, arrivals, PeopleConfig::default());
+        let mut sim = Simulation::new(people, building, Box::new(oracle));
+        while sim.time() < 60. {
+            sim.step_to_next_event(1.);
+        }
+        // the second person only waits while the car picks up the first on its way
+        let people = sim.people().people();
+        assert!(people.iter().all(|p| matches!(p.state, PersonState::Done)));
+        assert!(people.iter().all(|p| p.waited < 5.), "{people:?}");
+    }
+
+    #[test]
+    fn look_serves_calls_its_way_before_turning_around() {
+        let mut sim = ElevatorSim::new(6, 1);
+        for (floor, direction) in [(3, Direction::Down), (2, Direction::Up), (4, Direction::Up)] {
+            sim.apply_command(ElevatorCommand::PressOutButton {
+                floor,
+                direction,
+                bank: None,
+            })
+            .unwrap();
+        }
+
+        let mut stops = Vec::new();
+        for _ in 0..200 {
+            for command in LookController.tick(&BuildingView::new(sim.state()), &sim.stats()) {
+                sim.apply_command(command).unwrap();
+            }
+            sim.tick(0.1);
+            for event in sim.take_events() {
+                if let ElevatorEvent::CarArrived { floor, .. } = event {
+                    stops.push(floor);
+                }
+            }
+        }
+
+        // the down call at 3 waits until the car comes back down, even though it's nearer
+        assert_eq!(stops, vec![2, 4, 3]);
+    }
+
+    #[test]
+    fn group_hands_calls_to_the_car_on_its_way() {
+        let mut sim = ElevatorSim::new(10, 2);
+        sim.apply_command(ElevatorCommand::PressCarButton {
+            car_id: CarId(0),
+            floor: 8,
+        })
+        .unwrap();
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 8,
+        })
+        .unwrap();
+        for _ in 0..25 {
+            sim.tick(0.1);
+        }
+
+        // car 0 is going up past 2, and car 1 is parked at the bottom
+        for (floor, direction) in [(6, Direction::Up), (1, Direction::Down)] {
+            sim.apply_command(ElevatorCommand::PressOutButton {
+                floor,
+                direction,
+                bank: None,
+            })
+            .unwrap();
+        }
+        let mut group = GroupController::default();
+        let commands = group.tick(&BuildingView::new(sim.state()), &sim.stats());
+        assert_eq!(group.assignment(6, Some(Direction::Up)), Some(CarId(0)));
+        assert_eq!(group.assignment(1, Some(Direction::Down)), Some(CarId(1)));
+        assert_eq!(
+            commands,
+            vec![
+                ElevatorCommand::MoveCarTo {
+                    car_id: CarId(0),
+                    floor: 6,
+                },
+                ElevatorCommand::MoveCarTo {
+                    car_id: CarId(1),
+                    floor: 1,
+                },
+            ]
+        );
+
+        // both calls are answered and the riders' floor is still visited
+        let mut stops = Vec::new();
+        for _ in 0..200 {
+            for command in group.tick(&BuildingView::new(sim.state()), &sim.stats()) {
+                sim.apply_command(command).unwrap();
+            }
+            sim.tick(0.1);
+            for event in sim.take_events() {
+                if let ElevatorEvent::CarArrived { car_id, floor, .. } = event {
+                    stops.push((car_id.0, floor));
+                }
+            }
+        }
+        assert_eq!(stops, vec![(1, 1), (0, 6), (0, 8)]);
+    }
+
+    #[test]
+    fn destination_dispatch_delivers_kiosk_trips() {
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 1.,
+                origin: 3,
+                destination: 0,
+            },
+            ScriptedArrival {
+                time: 2.,
+                origin: 5,
+                destination: 1,
+            },
+        ];
+        let config = PeopleConfig {
+            boarding: BoardingPolicy::Assigned,
+            hall_calls: HallCallMode::Kiosk,
+            ..PeopleConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(6, arrivals, config),
+            ElevatorSim::new(6, 2),
+            Box::new(DestinationController::default()),
+        );
+
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+
+        assert!(
+            sim.people()
+                .people()
+                .iter()
+                .all(|p| matches!(p.state, PersonState::Done))
+        );
+    }
 }
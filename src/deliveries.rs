@@ -0,0 +1,159 @@
+use crate::elevator::{CarClass, ElevatorCommand, ElevatorSim};
+use crate::people::{Attribute, PeopleSim, PersonOptions};
+use crate::rng::{self, Stream};
+use crate::types::{CarId, Floor, PersonId};
+use serde::{Deserialize, Serialize};
+
+/// How often deliveries arrive during a run, on top of the people the traffic brings. Each
+/// is brought in at the building's first entrance, the loading dock, for another floor
+/// picked at random. The nearest empty freight or service car making the trip is reserved
+/// for it and sent to pick it up. The rate is 0 by default, and then no random numbers are
+/// drawn, so seeded runs are unchanged
+/// rate - how many deliveries arrive per second
+/// reservation - how many seconds each keeps its car from everyone else, see ReserveCar
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DeliveryConfig {
+    pub rate: f32,
+    pub reservation: f32,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            rate: 0.,
+            reservation: 120.,
+        }
+    }
+}
+
+impl DeliveryConfig {
+    /// Whether any deliveries arrive at all
+    pub fn enabled(&self) -> bool {
+        self.rate > 0.
+    }
+
+    /// Bring in a delivery at random over the next dt seconds, returning who brought it if
+    /// one arrived
+    pub fn deliver(
+        &self,
+        building: &mut ElevatorSim,
+        people: &mut PeopleSim,
+        dt: f32,
+    ) -> Option<PersonId> {
+        let floors = building.topology().floors;
+        if !self.enabled() || dt <= 0. || floors < 2 {
+            return None;
+        }
+        // the chance of at least one delivery in dt seconds at a steady rate
+        if rng::random::<f32>(Stream::Arrivals) >= 1. - (-self.rate * dt).exp() {
+            return None;
+        }
+
+        let dock = building.topology().lobby();
+        let mut destination = rng::random_range(Stream::Arrivals, 0..floors - 1);
+        if destination >= dock {
+            destination += 1;
+        }
+        let person = people.add_person(
+            dock,
+            destination,
+            PersonOptions {
+                attributes: vec![Attribute::Delivery],
+                ..PersonOptions::default()
+            },
+        );
+        if let Some(car_id) = free_car(building, dock, destination) {
+            let reserve = ElevatorCommand::ReserveCar {
+                car_id,
+                duration: self.reservation,
+            };
+            if building.apply_command(reserve).is_ok() {
+                let _ = building.apply_command(ElevatorCommand::MoveCarTo {
+                    car_id,
+                    floor: dock,
+                });
+            }
+        }
+        Some(person)
+    }
+}
+
+/// The nearest car to origin which takes deliveries from there to destination, and is empty,
+/// not reserved already, and can be sent somewhere
+fn free_car(building: &ElevatorSim, origin: Floor, destination: Floor) -> Option<CarId> {
+    building
+        .state()
+        .cars
+        .iter()
+        .filter(|car| car.class != CarClass::Passenger && car.reserved.is_none())
+        .filter(|car| car.dispatchable() && car.passenger_count == 0)
+        .filter(|car| car.serves(origin) && car.serves(destination))
+        .min_by(|a, b| {
+            let distance = |position: f32| (position - origin as f32).abs();
+            distance(a.current_floor).total_cmp(&distance(b.current_floor))
+        })
+        .map(|car| car.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::elevator::CommandError;
+    use crate::people::PersonState;
+
+    #[test]
+    fn a_reserved_freight_car_only_takes_deliveries() {
+        let config = SimConfig {
+            cars: 2,
+            car_classes: vec![CarClass::Freight],
+            spawn_rate: 0.,
+            seed: Some(3),
+            ..SimConfig::default()
+        };
+        let mut sim = config.build().unwrap();
+        assert_eq!(
+            sim.building_mut()
+                .apply_command(ElevatorCommand::ReserveCar {
+                    car_id: CarId(1),
+                    duration: 10.,
+                }),
+            Err(CommandError::PassengerCar { car_id: CarId(1) })
+        );
+
+        // with only the freight car working, someone arriving on the dock along with a
+        // delivery waits until the car's reservation runs out
+        sim.building_mut()
+            .apply_command(ElevatorCommand::SetCarService {
+                car_id: CarId(1),
+                in_service: false,
+            })
+            .unwrap();
+        let deliveries = DeliveryConfig {
+            rate: 1000.,
+            reservation: 20.,
+        };
+        sim.set_deliveries(deliveries.clone());
+        sim.step(0.1);
+        sim.set_deliveries(DeliveryConfig::default());
+        let reserved = sim.building().state().cars[0].reserved.unwrap();
+        assert!((reserved - 19.9).abs() < 1e-4);
+        let passenger = sim.add_person(0, 3);
+
+        let mut boarded = None;
+        for tick in 0..1000 {
+            sim.step(0.1);
+            let people = sim.people().people();
+            assert!(people.iter().all(|p| p.in_car != Some(CarId(1))));
+            if boarded.is_none() && people[1].in_car.is_some() {
+                boarded = Some(tick as f32 * 0.1);
+            }
+        }
+        let people = sim.people().people();
+        assert_eq!(people[1].id, passenger);
+        assert!(people[0].attributes.contains(&Attribute::Delivery));
+        assert!(people.iter().all(|p| p.state == PersonState::Done));
+        assert!(boarded.unwrap() >= deliveries.reservation - 0.2);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::metrics::{MetricsLog, PersonRecord};
+use crate::replay::{CommandLog, RecordedCommand};
+use crate::types::{Floor, PersonId};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// How far apart two runs' car positions can be and still count as the same, which covers
+/// the rounding of CSV files
+const POSITION_TOLERANCE: f32 = 0.001;
+
+/// How much a wait has to change by to count as better or worse, in seconds
+const WAIT_TOLERANCE: f64 = 0.01;
+
+/// How many of the people whose waits changed most are listed each way
+const SHOWN_CHANGES: usize = 5;
+
+/// How one person's trip went in each of two runs, the first run first
+#[derive(Clone, Debug, PartialEq)]
+pub struct PersonChange {
+    pub person: PersonId,
+    pub origin: Floor,
+    pub destination: Floor,
+    pub wait: [f64; 2],
+    pub ride: [f64; 2],
+}
+
+impl PersonChange {
+    /// How much longer the person waited in the second run, negative if they waited less
+    pub fn wait_change(&self) -> f64 {
+        self.wait[1] - self.wait[0]
+    }
+}
+
+/// How many people set off from a floor and reached theirs in each run, and how long they
+/// waited on average, if anyone did
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorChange {
+    pub floor: Floor,
+    pub people: [usize; 2],
+    pub average_wait: [Option<f64>; 2],
+}
+
+/// The first moment two runs stopped matching
+#[derive(Clone, Debug, PartialEq)]
+pub enum Divergence {
+    /// a different number of people were queued on a floor
+    Queue {
+        time: f64,
+        floor: Floor,
+        queues: [u32; 2],
+    },
+    /// a car was somewhere else
+    Car {
+        time: f64,
+        car: usize,
+        positions: [f32; 2],
+    },
+    /// the runs had different numbers of floors or cars, or one ended sooner
+    Shape { time: f64 },
+}
+
+/// Everything that changed between two recorded runs of the same scenario
+/// people - everyone who reached their floor in both runs, by id
+/// only_in - how many people reached their floor in one run but not the other, for each run
+/// floors - how the waits from each floor changed, from the bottom up
+/// divergence - where the runs first stopped matching, if they recorded their steps and
+/// ever did
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunDiff {
+    pub people: Vec<PersonChange>,
+    pub only_in: [usize; 2],
+    pub floors: Vec<FloorChange>,
+    pub divergence: Option<Divergence>,
+}
+
+/// Compare two runs' metrics, the first run first. People are matched by id, so the runs
+/// should have the same people, as runs of the same seeded scenario do
+pub fn diff(a: &MetricsLog, b: &MetricsLog) -> RunDiff {
+    let in_b: HashMap<PersonId, &PersonRecord> = b.people().iter().map(|p| (p.person, p)).collect();
+    let mut people: Vec<PersonChange> = a
+        .people()
+        .iter()
+        .filter_map(|p| {
+            let q = in_b.get(&p.person)?;
+            Some(PersonChange {
+                person: p.person,
+                origin: p.origin,
+                destination: p.destination,
+                wait: [p.wait, q.wait],
+                ride: [p.ride, q.ride],
+            })
+        })
+        .collect();
+    people.sort_by_key(|change| change.person.0);
+    let only_in = [
+        a.people().len() - people.len(),
+        b.people().len() - people.len(),
+    ];
+
+    // total wait and count of everyone delivered from each floor, in each run
+    let mut totals: BTreeMap<Floor, [(f64, usize); 2]> = BTreeMap::new();
+    for (run, log) in [a, b].into_iter().enumerate() {
+        for p in log.people() {
+            let (total, count) = &mut totals.entry(p.origin).or_default()[run];
+            *total += p.wait;
+            *count += 1;
+        }
+    }
+    let floors = totals
+        .into_iter()
+        .map(|(floor, runs)| FloorChange {
+            floor,
+            people: runs.map(|(_, count)| count),
+            average_wait: runs.map(|(total, count)| (count > 0).then(|| total / count as f64)),
+        })
+        .collect();
+
+    RunDiff {
+        people,
+        only_in,
+        floors,
+        divergence: divergence(a, b),
+    }
+}
+
+/// The first step where the two runs' queues or cars differ
+fn divergence(a: &MetricsLog, b: &MetricsLog) -> Option<Divergence> {
+    for (x, y) in a.ticks().iter().zip(b.ticks()) {
+        if x.queues.len() != y.queues.len() || x.cars.len() != y.cars.len() {
+            return Some(Divergence::Shape { time: x.time });
+        }
+        let queue = x.queues.iter().zip(&y.queues).position(|(p, q)| p != q);
+        if let Some(floor) = queue {
+            return Some(Divergence::Queue {
+                time: x.time,
+                floor: floor as Floor,
+                queues: [x.queues[floor], y.queues[floor]],
+            });
+        }
+        let car = x
+            .cars
+            .iter()
+            .zip(&y.cars)
+            .position(|(p, q)| (p - q).abs() > POSITION_TOLERANCE);
+        if let Some(car) = car {
+            return Some(Divergence::Car {
+                time: x.time,
+                car,
+                positions: [x.cars[car], y.cars[car]],
+            });
+        }
+    }
+    // one run carried on after the other ended
+    let shorter = a.ticks().len().min(b.ticks().len());
+    if a.ticks().len() != b.ticks().len() {
+        let time = [a, b]
+            .iter()
+            .find_map(|log| log.ticks().get(shorter))
+            .map_or(0., |tick| tick.time);
+        return Some(Divergence::Shape { time });
+    }
+    None
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let both = self.people.len();
+        writeln!(
+            f,
+            "Delivered: {} -> {} ({both} in both)",
+            both + self.only_in[0],
+            both + self.only_in[1]
+        )?;
+        if both > 0 {
+            let average =
+                |run: usize| self.people.iter().map(|p| p.wait[run]).sum::<f64>() / both as f64;
+            writeln!(
+                f,
+                "Avg wait of people in both: {:.1} s -> {:.1} s",
+                average(0),
+                average(1)
+            )?;
+        }
+        let better = self
+            .people
+            .iter()
+            .filter(|p| p.wait_change() < -WAIT_TOLERANCE);
+        let worse = self
+            .people
+            .iter()
+            .filter(|p| p.wait_change() > WAIT_TOLERANCE);
+        let (better, worse) = (better.count(), worse.count());
+        writeln!(
+            f,
+            "Waited less: {better}, waited longer: {worse}, unchanged: {}",
+            both - better - worse
+        )?;
+
+        let mut by_change: Vec<&PersonChange> = self.people.iter().collect();
+        by_change.sort_by(|x, y| y.wait_change().total_cmp(&x.wait_change()));
+        let worst = by_change
+            .iter()
+            .take(SHOWN_CHANGES)
+            .filter(|p| p.wait_change() > WAIT_TOLERANCE);
+        let best = by_change
+            .iter()
+            .rev()
+            .take(SHOWN_CHANGES)
+            .filter(|p| p.wait_change() < -WAIT_TOLERANCE);
+        for (title, changes) in [
+            ("Waited longer in B:", worst.collect::<Vec<_>>()),
+            ("Waited less in B:", best.collect()),
+        ] {
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "{title}")?;
+            for p in changes {
+                writeln!(
+                    f,
+                    "  person {}, floor {} to {}: {:.1} s -> {:.1} s ({:+.1} s)",
+                    p.person.0,
+                    p.origin,
+                    p.destination,
+                    p.wait[0],
+                    p.wait[1],
+                    p.wait_change()
+                )?;
+            }
+        }
+
+        writeln!(f, "Floor |    People    |   Avg wait A |   Avg wait B")?;
+        for floor in &self.floors {
+            let wait = |run: usize| {
+                floor.average_wait[run].map_or("-".to_string(), |w| format!("{w:.1} s"))
+            };
+            writeln!(
+                f,
+                "{:>5} | {:>4} -> {:<4} | {:>12} | {:>12}",
+                floor.floor,
+                floor.people[0],
+                floor.people[1],
+                wait(0),
+                wait(1)
+            )?;
+        }
+
+        match &self.divergence {
+            None => write!(f, "The steps recorded never diverged"),
+            Some(Divergence::Queue {
+                time,
+                floor,
+                queues,
+            }) => write!(
+                f,
+                "First diverged at {time:.2} s: {} queued on floor {floor} in A, {} in B",
+                queues[0], queues[1]
+            ),
+            Some(Divergence::Car {
+                time,
+                car,
+                positions,
+            }) => write!(
+                f,
+                "First diverged at {time:.2} s: car {car} at {:.3} in A, {:.3} in B",
+                positions[0], positions[1]
+            ),
+            Some(Divergence::Shape { time }) => write!(
+                f,
+                "First diverged at {time:.2} s: the runs have different floors, cars or lengths"
+            ),
+        }
+    }
+}
+
+/// The first command two recorded runs disagree on: which command it was in each run, with
+/// None for a run whose commands ran out first
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandDifference {
+    pub index: usize,
+    pub commands: [Option<RecordedCommand>; 2],
+}
+
+/// Find the first command where two command logs differ, or None if they gave exactly the
+/// same commands on the same ticks
+pub fn first_command_difference(a: &CommandLog, b: &CommandLog) -> Option<CommandDifference> {
+    let len = a.commands.len().max(b.commands.len());
+    (0..len).find_map(|index| {
+        let commands = [
+            a.commands.get(index).cloned(),
+            b.commands.get(index).cloned(),
+        ];
+        (commands[0] != commands[1]).then_some(CommandDifference { index, commands })
+    })
+}
+
+impl fmt::Display for CommandDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Commands first differ at command {}:", self.index)?;
+        for (name, command) in ["A", "B"].iter().zip(&self.commands) {
+            match command {
+                Some(c) => writeln!(
+                    f,
+                    "  {name}: tick {} ({:.2} s) {:?}",
+                    c.tick, c.time, c.command
+                )?,
+                None => writeln!(f, "  {name}: no more commands")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::TickRecord;
+
+    #[test]
+    fn diff_matches_people_and_finds_the_first_divergence() {
+        let person = |id, wait| PersonRecord {
+            person: PersonId(id),
+            spawn_time: 0.,
+            wait,
+            ride: 5.,
+            origin: id,
+            destination: 0,
+            car: None,
+        };
+        let tick = |time, queues: Vec<u32>, car| TickRecord {
+            time,
+            time_of_day: 0.,
+            queues,
+            cars: vec![car],
+        };
+        let a = MetricsLog::from_rows(
+            vec![person(1, 10.), person(2, 4.), person(3, 1.)],
+            vec![tick(0.1, vec![0, 1], 0.), tick(0.2, vec![0, 1], 0.1)],
+        );
+        let b = MetricsLog::from_rows(
+            vec![person(2, 6.), person(1, 3.)],
+            vec![tick(0.1, vec![0, 1], 0.), tick(0.2, vec![0, 1], 0.2)],
+        );
+
+        let diff = diff(&a, &b);
+        assert_eq!(diff.people.len(), 2);
+        assert_eq!(diff.people[0].wait_change(), -7.);
+        assert_eq!(diff.only_in, [1, 0]);
+        assert_eq!(diff.floors[1].average_wait, [Some(4.), Some(6.)]);
+        assert_eq!(
+            diff.divergence,
+            Some(Divergence::Car {
+                time: 0.2,
+                car: 0,
+                positions: [0.1, 0.2],
+            })
+        );
+        let report = diff.to_string();
+        assert!(report.starts_with("Delivered: 3 -> 2 (2 in both)"));
+        assert!(report.contains("Waited less: 1, waited longer: 1, unchanged: 0"));
+        assert!(report.ends_with("car 0 at 0.100 in A, 0.200 in B"));
+    }
+}
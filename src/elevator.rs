@@ -1,179 +1,3130 @@
-use crate::types::{CarId, Direction, Floor};
+use crate::clock::SimClock;
+use crate::metrics::{ArrivalRateEstimator, StatsView};
+use crate::rng::{self, Stream};
+use crate::snapshot::{self, SnapshotError};
+use crate::topology::BuildingTopology;
+use crate::types::{BankId, CarId, Direction, Floor, FloorId, FloorMismatch, PersonId};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
 
 /// The state of an entire building, which contains a vector of the state of each floor,
-/// along with a vector of the state of each elevator car
-#[derive(Clone, Debug, PartialEq)]
+/// along with a vector of the state of each elevator car, whether there's an emergency, and
+/// the time of day. The building's topology is shared with everything else that needs it,
+/// so cloning the state doesn't copy it
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct BuildingState {
+    pub topology: Arc<BuildingTopology>,
     pub floors: Vec<FloorState>,
     pub cars: Vec<ElevatorCarState>,
+    pub emergency: EmergencyMode,
+    #[serde(default)]
+    pub clock: SimClock,
 }
 
-/// The state of each floor, which contains its floor number, and outer buttons
-#[derive(Clone, Debug, PartialEq)]
+/// Whether the building is running normally, or has been taken over in an emergency
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum EmergencyMode {
+    #[default]
+    Normal,
+    /// phase 1 fire service. Every car returns to floor and waits there with its door open,
+    /// and calls and other commands are refused until the emergency is cleared
+    FireRecall { floor: Floor },
+}
+
+impl EmergencyMode {
+    /// the floor cars are recalled to, if there's a recall
+    pub fn recall_floor(&self) -> Option<Floor> {
+        match *self {
+            EmergencyMode::Normal => None,
+            EmergencyMode::FireRecall { floor } => Some(floor),
+        }
+    }
+}
+
+/// implement functions for BuildingState
+/// cars_serving - the cars which stop at a floor
+impl BuildingState {
+    /// The cars which stop at a floor, so controllers only send cars that can get there
+    pub fn cars_serving(&self, floor: Floor) -> impl Iterator<Item = &ElevatorCarState> {
+        self.cars.iter().filter(move |car| car.serves(floor))
+    }
+}
+
+/// The state of each floor, which contains its floor number, outer buttons, or its single
+/// call button on a building with HallButtons::Single, destinations entered at the kiosk
+/// which haven't been assigned a car yet, the cars assigned to trips starting here, shown on
+/// the hall panel, and whether the outer buttons are broken. In a building with banks, each
+/// bank has buttons of its own, in the order of the topology's banks, and the floor's
+/// buttons are lit while any of them are. access_call is lit alongside them by the
+/// accessible call button, for someone who needs longer to get in, and stays lit until a
+/// car answers the floor. lanterns are the hall lanterns lit over the cars about to arrive
+/// here, which stay lit until each car's door closes again
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct FloorState {
     pub floor: Floor,
     pub out_up: bool,
     pub out_down: bool,
+    pub out_call: bool,
+    pub trip_requests: Vec<Floor>,
+    pub assignments: Vec<TripAssignment>,
+    pub buttons_broken: bool,
+    #[serde(default)]
+    pub banks: Vec<BankButtons>,
+    #[serde(default)]
+    pub access_call: bool,
+    #[serde(default)]
+    pub lanterns: Vec<Lantern>,
+}
+
+/// One bank's hall buttons on a floor, which only its cars answer
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct BankButtons {
+    pub out_up: bool,
+    pub out_down: bool,
+    pub out_call: bool,
+}
+
+/// An announcement that people on a floor heading to destination should take car car_id
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TripAssignment {
+    pub destination: Floor,
+    pub car_id: CarId,
+}
+
+/// A hall lantern lit over car car_id's door as it's about to arrive at a floor, showing
+/// which way it'll go on from there, or neither if it doesn't know yet
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Lantern {
+    pub car_id: CarId,
+    pub direction: Option<Direction>,
 }
 
 /// The state of each elevator car, which contains its id number, current floor/location as a
-/// float, target floor if it exists, whether the door is open, and a vector of car buttons
-#[derive(Clone, Debug, PartialEq)]
+/// float, worked out from how many metres up the shaft its elevation is, its velocity in
+/// metres per second with up being positive, the floors it will stop at in order, what its
+/// door is doing, a vector of car buttons, a vector of which floors the car is allowed to stop
+/// at, when occupants are tracked, the people inside, whether someone is in the middle of
+/// getting on or off, how many people the car can hold, how many are inside, whether it
+/// answers to the controller, what it's fitted for, which way it's committed to travel, if
+/// it's committed at all, where each rider is going, which only controllers given a rich
+/// observation see, and what's wrong with it, if it has broken down, the bank it belongs to,
+/// if it's in one, what class of car it is, how many seconds it's still reserved for
+/// deliveries, if it is, whether its overload sensor has gone off, which keeps its door from
+/// closing, how many kg it's carrying, and how many it carries before the sensor goes off, or
+/// 0 for no limit
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct ElevatorCarState {
     pub id: CarId,
     pub current_floor: f32,
-    pub target_floor: Option<Floor>,
-    pub door_open: bool,
+    #[serde(default)]
+    pub elevation: f32,
+    pub velocity: f32,
+    pub stops: VecDeque<Floor>,
+    pub door: DoorState,
     pub car_buttons: Vec<bool>,
+    pub served_floors: Vec<bool>,
+    pub occupants: Vec<PersonId>,
+    pub transfer: Option<PassengerTransfer>,
+    pub capacity: u32,
+    pub passenger_count: u32,
+    pub service: ServiceState,
+    pub capabilities: CarCapabilities,
+    pub direction: Option<Direction>,
+    pub rider_destinations: Vec<Floor>,
+    pub malfunction: Option<Malfunction>,
+    #[serde(default)]
+    pub bank: Option<BankId>,
+    #[serde(default)]
+    pub class: CarClass,
+    #[serde(default)]
+    pub reserved: Option<f32>,
+    #[serde(default)]
+    pub overloaded: bool,
+    #[serde(default)]
+    pub load: f32,
+    #[serde(default)]
+    pub weight_limit: f32,
+}
+
+/// What a car is for, which decides who gets into it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CarClass {
+    /// an ordinary car, for everyone but deliveries
+    #[default]
+    Passenger,
+    /// a big car for goods, which takes deliveries, and anyone else while it isn't reserved
+    /// for deliveries with ReserveCar, though they'd rather take a passenger car
+    Freight,
+    /// a back of house car, which only takes deliveries
+    Service,
+}
+
+/// What's wrong with a car that has broken down. A broken car doesn't move or close its
+/// door, the controller can't send it anywhere, and nobody gets on until it's repaired
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Malfunction {
+    /// the car has stopped dead where it is, possibly between floors, trapping anyone inside
+    Stuck,
+    /// the door is stuck open, so the car can't leave its floor
+    DoorJammed,
+}
+
+/// What a car is fitted for, which boarding rules can require of the cars some people use
+/// freight - a larger, sturdier car, for bicycles, furniture and the like
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CarCapabilities {
+    pub freight: bool,
+}
+
+impl CarCapabilities {
+    /// whether a car with these capabilities has everything in required
+    pub fn satisfies(&self, required: &CarCapabilities) -> bool {
+        self.freight || !required.freight
+    }
+}
+
+/// Who a car takes its orders from
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ServiceState {
+    /// the controller dispatches the car with MoveCarTo
+    Normal,
+    /// someone inside the car drives it with OperateCar, and the controller can't send it
+    /// anywhere. The door stays open whenever the car has nowhere to go
+    Independent,
+    /// out of service for maintenance or a fault. The car takes the people already inside
+    /// to their floors, then parks with its door closed. The controller can't send it
+    /// anywhere, and nobody gets on
+    OutOfService,
+    /// kept for one important passenger waiting on floor, see VipCall. The car drops off
+    /// the people already inside, passing every hall call, fetches them, and takes them
+    /// straight to the floor they choose, then goes back to the controller
+    Vip { floor: Floor, stage: VipStage },
+}
+
+/// How far a car in VIP service has got
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum VipStage {
+    /// dropping off its riders, then heading for the VIP's floor
+    Fetching,
+    /// waiting there with its door open for the VIP to choose a floor
+    Boarding,
+    /// on its way to that floor, without stopping
+    Delivering,
+}
+
+/// Where a car's door is in its cycle. A door opens, stays open for at least its dwell,
+/// then closes. Opening and Closing count down the seconds left until they finish, and a car
+/// only moves once its door is Closed
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DoorState {
+    Closed,
+    Opening { remaining: f32 },
+    Open { dwell_remaining: f32 },
+    Closing { remaining: f32 },
+}
+
+/// Buttons to light before a run starts, for building a situation such as calls on every
+/// floor or stale calls in empty cars directly, rather than by simulating people to press
+/// them
+/// hall - the hall buttons, as floors and the way the caller is going. On a building with
+/// HallButtons::Single the direction lights the floor's only button
+/// car - the buttons inside cars, as cars and the floors they're for
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct LitButtons {
+    pub hall: Vec<(Floor, Direction)>,
+    pub car: Vec<(CarId, Floor)>,
+}
+
+/// A passenger getting on or off a car, with progress going from 0 to 1. Renderers can
+/// animate this, and controllers can see the car is busy loading
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PassengerTransfer {
+    Boarding { progress: f32 },
+    Alighting { progress: f32 },
+}
+
+impl PassengerTransfer {
+    /// how far through the transfer the passenger is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        match *self {
+            PassengerTransfer::Boarding { progress }
+            | PassengerTransfer::Alighting { progress } => progress,
+        }
+    }
+}
+
+/// implement functions for FloorState
+/// hall_call - whether someone here has called a car going a direction, as far as the
+/// buttons tell
+/// called - whether any hall button here is lit
+/// bank_call, called_banks - whether someone here has called a bank's cars
+impl FloorState {
+    /// Whether the button for direction is lit. A single call button doesn't say which way
+    /// its caller is going, so it counts for both
+    pub fn hall_call(&self, direction: Direction) -> bool {
+        self.out_call
+            || match direction {
+                Direction::Up => self.out_up,
+                Direction::Down => self.out_down,
+            }
+    }
+
+    /// Whether any hall button on the floor is lit
+    pub fn called(&self) -> bool {
+        self.out_up || self.out_down || self.out_call
+    }
+
+    /// Whether bank's button for direction is lit, counting a single call button for both
+    pub fn bank_call(&self, bank: BankId, direction: Direction) -> bool {
+        self.banks.get(bank.0 as usize).is_some_and(|b| {
+            b.out_call
+                || match direction {
+                    Direction::Up => b.out_up,
+                    Direction::Down => b.out_down,
+                }
+        })
+    }
+
+    /// Every bank with a hall button lit here
+    pub fn called_banks(&self) -> impl Iterator<Item = BankId> + '_ {
+        self.banks
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.out_up || b.out_down || b.out_call)
+            .map(|(i, _)| BankId(i as u32))
+    }
+
+    /// Light the hall button for direction, or the floor's only button if it has one, and
+    /// bank's button too if given. Returns whether any of them wasn't lit already
+    fn light(&mut self, buttons: HallButtons, direction: Direction, bank: Option<BankId>) -> bool {
+        let lit = match (buttons, direction) {
+            (HallButtons::Single, _) => BankButtons {
+                out_call: true,
+                ..BankButtons::default()
+            },
+            (HallButtons::Pair, Direction::Up) => BankButtons {
+                out_up: true,
+                ..BankButtons::default()
+            },
+            (HallButtons::Pair, Direction::Down) => BankButtons {
+                out_down: true,
+                ..BankButtons::default()
+            },
+        };
+        let unlit = |b: &BankButtons| {
+            lit.out_up && !b.out_up || lit.out_down && !b.out_down || lit.out_call && !b.out_call
+        };
+        let floor = BankButtons {
+            out_up: self.out_up,
+            out_down: self.out_down,
+            out_call: self.out_call,
+        };
+        let mut new = unlit(&floor);
+        self.out_up |= lit.out_up;
+        self.out_down |= lit.out_down;
+        self.out_call |= lit.out_call;
+        if let Some(b) = bank.and_then(|bank| self.banks.get_mut(bank.0 as usize)) {
+            new |= unlit(b);
+            b.out_up |= lit.out_up;
+            b.out_down |= lit.out_down;
+            b.out_call |= lit.out_call;
+        }
+        new
+    }
+
+    /// Turn off every hall button on the floor
+    fn clear_hall_calls(&mut self) {
+        self.out_up = false;
+        self.out_down = false;
+        self.out_call = false;
+        self.access_call = false;
+        self.banks.fill(BankButtons::default());
+    }
+
+    /// Turn off bank's hall buttons, as one of its cars answers them, leaving the floor's
+    /// buttons lit only while another bank's are. A car in no bank answers every button
+    fn clear_bank_calls(&mut self, bank: Option<BankId>) {
+        let Some(b) = bank.and_then(|bank| self.banks.get_mut(bank.0 as usize)) else {
+            self.clear_hall_calls();
+            return;
+        };
+        *b = BankButtons::default();
+        self.out_up = self.banks.iter().any(|b| b.out_up);
+        self.out_down = self.banks.iter().any(|b| b.out_down);
+        self.out_call = self.banks.iter().any(|b| b.out_call);
+    }
+}
+
+impl ElevatorCarState {
+    /// The nearest floor with its button lit inside the car, if any is
+    fn nearest_button(&self) -> Option<Floor> {
+        let here = self.current_floor;
+        (0..self.car_buttons.len())
+            .filter(|&floor| self.car_buttons[floor])
+            .min_by(|&a, &b| (a as f32 - here).abs().total_cmp(&(b as f32 - here).abs()))
+            .map(|floor| floor as Floor)
+    }
+
+    /// What a car in VIP service does next, once it has run out of stops: the stop it
+    /// sets off for, if any, and the service it's in after. None if it's waiting for
+    /// something, such as its door opening or the VIP choosing a floor
+    fn vip_next(&self) -> Option<(Option<Floor>, ServiceState)> {
+        let ServiceState::Vip { floor, stage } = self.service else {
+            return None;
+        };
+        if !self.stops.is_empty() || self.malfunction.is_some() {
+            return None;
+        }
+        let button = self.nearest_button();
+        match stage {
+            // riders get out before the VIP is fetched
+            VipStage::Fetching if button.is_some() => Some((button, self.service)),
+            VipStage::Fetching => {
+                let here = self.velocity == 0. && (self.current_floor - floor as f32).abs() < 0.01;
+                match self.door {
+                    _ if !here => Some((Some(floor), self.service)),
+                    DoorState::Closed => Some((Some(floor), self.service)),
+                    DoorState::Open { .. } => Some((
+                        None,
+                        ServiceState::Vip {
+                            floor,
+                            stage: VipStage::Boarding,
+                        },
+                    )),
+                    DoorState::Opening { .. } | DoorState::Closing { .. } => None,
+                }
+            }
+            VipStage::Boarding => button.map(|to| {
+                let delivering = ServiceState::Vip {
+                    floor,
+                    stage: VipStage::Delivering,
+                };
+                (Some(to), delivering)
+            }),
+            VipStage::Delivering if self.door_open() => Some((None, ServiceState::Normal)),
+            VipStage::Delivering => None,
+        }
+    }
+
+    /// light the button inside the car for a floor. A car with no direction yet commits to
+    /// the way its stops are, so the first button pressed sets it
+    fn press(&mut self, floor: Floor) {
+        if let Some(slot) = self.car_buttons.get_mut(floor as usize) {
+            *slot = true;
+            if self.direction.is_none() {
+                self.direction = self.direction_after_stop(Some(Direction::Up));
+            }
+        }
+    }
+
+    /// whether the door is fully open, which is the only time people can get on or off
+    pub fn door_open(&self) -> bool {
+        matches!(self.door, DoorState::Open { .. })
+    }
+
+    /// whether the door is open or on its way there, so the car is stopped for people
+    pub fn door_opening_or_open(&self) -> bool {
+        matches!(
+            self.door,
+            DoorState::Opening { .. } | DoorState::Open { .. }
+        )
+    }
+
+    /// whether this car has no room for anyone else to get on
+    pub fn is_full(&self) -> bool {
+        self.passenger_count >= self.capacity
+    }
+
+    /// whether this car carries more than its overload sensor allows: more kg than its
+    /// weight limit, or with counting set, more people than its capacity
+    pub fn over_limit(&self, counting: bool) -> bool {
+        (counting && self.passenger_count > self.capacity)
+            || (self.weight_limit > 0. && self.load > self.weight_limit)
+    }
+
+    /// whether the controller can send this car anywhere
+    pub fn dispatchable(&self) -> bool {
+        self.service == ServiceState::Normal && self.malfunction.is_none()
+    }
+
+    /// whether this car can be sent to a floor, which must be in the building and served
+    pub fn serves(&self, floor: Floor) -> bool {
+        self.served_floors
+            .get(floor as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// whether this car only serves some floors, such as an express car
+    pub fn zoned(&self) -> bool {
+        self.served_floors.contains(&false)
+    }
+
+    /// The floor the car is on its way to, the first of its stops
+    pub fn target_floor(&self) -> Option<Floor> {
+        self.stops.front().copied()
+    }
+
+    /// whether the car has a stop or a car button lit in direction from where it is
+    pub fn has_stops_towards(&self, direction: Direction) -> bool {
+        let here = self.current_floor;
+        let ahead = |floor: Floor| match direction {
+            Direction::Up => floor as f32 > here + 0.01,
+            Direction::Down => (floor as f32) < here - 0.01,
+        };
+        self.stops.iter().any(|&floor| ahead(floor))
+            || self
+                .car_buttons
+                .iter()
+                .enumerate()
+                .any(|(floor, &lit)| lit && ahead(floor as Floor))
+    }
+
+    /// The nearest floor the car serves that it can stop at without turning around
+    pub fn nearest_floor_ahead(&self) -> Floor {
+        let here = self.current_floor;
+        (0..self.served_floors.len() as Floor)
+            .filter(|&floor| self.serves(floor))
+            .filter(|&floor| {
+                let offset = floor as f32 - here;
+                offset.abs() < 0.01 || offset * self.velocity >= 0.
+            })
+            .min_by(|&a, &b| (a as f32 - here).abs().total_cmp(&(b as f32 - here).abs()))
+            .unwrap_or(here.round() as Floor)
+    }
+
+    /// The way the car should be committed to after stopping, having come from heading: on
+    /// the same way while it has stops ahead, turned around if its only stops are behind it,
+    /// and either way if it has none
+    fn direction_after_stop(&self, heading: Option<Direction>) -> Option<Direction> {
+        let heading = heading?;
+        let other = match heading {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        };
+        if self.has_stops_towards(heading) {
+            Some(heading)
+        } else if self.has_stops_towards(other) {
+            Some(other)
+        } else {
+            None
+        }
+    }
+}
+
+/// A list of possible elevator commands. Their floors are the Floors the simulation works
+/// with, unless F says otherwise, such as commands from outside naming floors by FloorId,
+/// see resolve
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ElevatorCommand<F = Floor> {
+    /// send a car straight to a floor, replacing any stops it had
+    MoveCarTo {
+        car_id: CarId,
+        floor: F,
+    },
+    /// add a floor to the end of a car's stops, unless it's already one of them
+    AddStop {
+        car_id: CarId,
+        floor: F,
+    },
+    /// forget every stop a car had. A car between floors still stops at the next floor it
+    /// serves
+    ClearStops {
+        car_id: CarId,
+    },
+    /// replace a car's stops with new ones, visited in order
+    ReplaceStops {
+        car_id: CarId,
+        stops: Vec<F>,
+    },
+    /// press the hall button on a floor for a direction. On a building with
+    /// HallButtons::Single there's only one button, which this presses whatever the direction.
+    /// In a building with banks, bank says which bank's buttons are pressed, and None presses
+    /// the floor's buttons for whichever car comes first
+    PressOutButton {
+        floor: F,
+        direction: Direction,
+        #[serde(default)]
+        bank: Option<BankId>,
+    },
+    /// press the accessible call button on a floor, which calls a car as PressOutButton does
+    /// and lights the floor's access_call too, so controllers can answer it first. The car
+    /// that answers holds its door open for at least ElevatorConfig::accessible_dwell_time
+    PressAccessButton {
+        floor: F,
+        direction: Direction,
+        #[serde(default)]
+        bank: Option<BankId>,
+    },
+    /// press the button inside a car for a floor. A secured floor's button is refused unless
+    /// AuthorizeFloor has let the car go there since it was last pressed
+    PressCarButton {
+        car_id: CarId,
+        floor: F,
+    },
+    /// show a card for a secured floor to a car's reader, so its button can be pressed once
+    AuthorizeFloor {
+        car_id: CarId,
+        floor: F,
+    },
+    AssignTrip {
+        floor: F,
+        destination: F,
+        car_id: CarId,
+    },
+    RequestTrip {
+        floor: F,
+        destination: F,
+    },
+    /// a person stepping into a car, weighing weight kg with whatever they've brought
+    Board {
+        car_id: CarId,
+        person_id: PersonId,
+        #[serde(default)]
+        weight: f32,
+    },
+    /// a person stepping out of a car, weighing weight kg with whatever they've brought
+    Alight {
+        car_id: CarId,
+        person_id: PersonId,
+        #[serde(default)]
+        weight: f32,
+    },
+    /// take a car out of the controller's hands, or give it back
+    SetIndependentService {
+        car_id: CarId,
+        on: bool,
+    },
+    /// take a car out of service, or put it back in service under the controller
+    SetCarService {
+        car_id: CarId,
+        in_service: bool,
+    },
+    /// send a car in independent service to a floor, from inside the car
+    OperateCar {
+        car_id: CarId,
+        floor: F,
+    },
+    /// recall every car to floor for the fire service, cancelling every call, and refuse
+    /// everything but getting on and off until ClearEmergency
+    FireRecall {
+        floor: F,
+    },
+    /// end an emergency, leaving the cars where they are
+    ClearEmergency,
+    /// commit a car to leaving its stop in direction, such as the way the hall call it's
+    /// answering goes, so people heading the other way wait for another car. None lets
+    /// anyone board. Cars commit to the way they travel on their own, so this only lasts
+    /// until the car next moves
+    SetCarDirection {
+        car_id: CarId,
+        direction: Option<Direction>,
+    },
+    /// someone stepping into a closing door, as its sensor sees it. The door opens again and
+    /// dwells afresh, unless it's already reopened ElevatorConfig::max_door_reopens times
+    /// since it last closed, when it nudges closed regardless. Does nothing to a door that
+    /// isn't closing
+    ReopenDoor {
+        car_id: CarId,
+    },
+    /// take a car from the controller to serve one important passenger waiting on floor.
+    /// It lets off whoever is already inside, fetches the VIP without answering any hall
+    /// calls, and takes them nonstop to the first floor pressed inside the car. Nobody
+    /// else gets in, and the car goes back to the controller once it's delivered them
+    VipCall {
+        car_id: CarId,
+        floor: F,
+    },
+    /// keep a freight or service car for deliveries alone for duration seconds, so nobody
+    /// else gets in. The car still goes where the controller sends it. A duration of 0 ends
+    /// a reservation early
+    ReserveCar {
+        car_id: CarId,
+        duration: f32,
+    },
+}
+
+impl ElevatorCommand {
+    /// The car this command is for, if it's for one car
+    pub fn car_id(&self) -> Option<CarId> {
+        match *self {
+            ElevatorCommand::MoveCarTo { car_id, .. }
+            | ElevatorCommand::AddStop { car_id, .. }
+            | ElevatorCommand::ClearStops { car_id }
+            | ElevatorCommand::ReplaceStops { car_id, .. }
+            | ElevatorCommand::PressCarButton { car_id, .. }
+            | ElevatorCommand::AuthorizeFloor { car_id, .. }
+            | ElevatorCommand::AssignTrip { car_id, .. }
+            | ElevatorCommand::Board { car_id, .. }
+            | ElevatorCommand::Alight { car_id, .. }
+            | ElevatorCommand::SetIndependentService { car_id, .. }
+            | ElevatorCommand::SetCarService { car_id, .. }
+            | ElevatorCommand::OperateCar { car_id, .. }
+            | ElevatorCommand::SetCarDirection { car_id, .. }
+            | ElevatorCommand::ReopenDoor { car_id }
+            | ElevatorCommand::VipCall { car_id, .. }
+            | ElevatorCommand::ReserveCar { car_id, .. } => Some(car_id),
+            ElevatorCommand::PressOutButton { .. }
+            | ElevatorCommand::PressAccessButton { .. }
+            | ElevatorCommand::RequestTrip { .. }
+            | ElevatorCommand::FireRecall { .. }
+            | ElevatorCommand::ClearEmergency => None,
+        }
+    }
+}
+
+impl<F> ElevatorCommand<F> {
+    /// The same command with each of its floors turned into another kind of floor, stopping
+    /// at the first one floor can't turn
+    pub fn try_map_floors<G, E>(
+        self,
+        mut floor: impl FnMut(F) -> Result<G, E>,
+    ) -> Result<ElevatorCommand<G>, E> {
+        use ElevatorCommand::*;
+        Ok(match self {
+            MoveCarTo { car_id, floor: f } => MoveCarTo {
+                car_id,
+                floor: floor(f)?,
+            },
+            AddStop { car_id, floor: f } => AddStop {
+                car_id,
+                floor: floor(f)?,
+            },
+            ClearStops { car_id } => ClearStops { car_id },
+            ReplaceStops { car_id, stops } => ReplaceStops {
+                car_id,
+                stops: stops.into_iter().map(floor).collect::<Result<_, _>>()?,
+            },
+            PressOutButton {
+                floor: f,
+                direction,
+                bank,
+            } => PressOutButton {
+                floor: floor(f)?,
+                direction,
+                bank,
+            },
+            PressAccessButton {
+                floor: f,
+                direction,
+                bank,
+            } => PressAccessButton {
+                floor: floor(f)?,
+                direction,
+                bank,
+            },
+            PressCarButton { car_id, floor: f } => PressCarButton {
+                car_id,
+                floor: floor(f)?,
+            },
+            AuthorizeFloor { car_id, floor: f } => AuthorizeFloor {
+                car_id,
+                floor: floor(f)?,
+            },
+            AssignTrip {
+                floor: f,
+                destination,
+                car_id,
+            } => AssignTrip {
+                floor: floor(f)?,
+                destination: floor(destination)?,
+                car_id,
+            },
+            RequestTrip {
+                floor: f,
+                destination,
+            } => RequestTrip {
+                floor: floor(f)?,
+                destination: floor(destination)?,
+            },
+            Board {
+                car_id,
+                person_id,
+                weight,
+            } => Board {
+                car_id,
+                person_id,
+                weight,
+            },
+            Alight {
+                car_id,
+                person_id,
+                weight,
+            } => Alight {
+                car_id,
+                person_id,
+                weight,
+            },
+            SetIndependentService { car_id, on } => SetIndependentService { car_id, on },
+            SetCarService { car_id, in_service } => SetCarService { car_id, in_service },
+            OperateCar { car_id, floor: f } => OperateCar {
+                car_id,
+                floor: floor(f)?,
+            },
+            FireRecall { floor: f } => FireRecall { floor: floor(f)? },
+            ClearEmergency => ClearEmergency,
+            SetCarDirection { car_id, direction } => SetCarDirection { car_id, direction },
+            ReopenDoor { car_id } => ReopenDoor { car_id },
+            VipCall { car_id, floor: f } => VipCall {
+                car_id,
+                floor: floor(f)?,
+            },
+            ReserveCar { car_id, duration } => ReserveCar { car_id, duration },
+        })
+    }
+}
+
+impl ElevatorCommand<FloorId> {
+    /// The command with its floors going by the Floors topology gives their names, or
+    /// UnknownFloor for the first name no floor goes by
+    pub fn resolve(self, topology: &BuildingTopology) -> Result<ElevatorCommand, CommandError> {
+        self.try_map_floors(|id| {
+            topology
+                .floor(&id)
+                .ok_or(CommandError::UnknownFloor { floor: id })
+        })
+    }
+}
+
+/// A command a car was given, time seconds after the building was created, and why it was
+/// refused, if it was
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CommandRecord {
+    pub time: f32,
+    pub command: ElevatorCommand,
+    pub error: Option<CommandError>,
+}
+
+/// The reasons ElevatorSim can refuse to apply an ElevatorCommand
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum CommandError {
+    UnknownCar { car_id: CarId },
+    UnknownBank { bank: BankId },
+    FloorOutOfRange { floor: Floor },
+    UnknownFloor { floor: FloorId },
+    FloorNotServed { car_id: CarId, floor: Floor },
+    DoorClosed { car_id: CarId },
+    NotInCar { car_id: CarId, person_id: PersonId },
+    CarFull { car_id: CarId },
+    IndependentService { car_id: CarId },
+    PassengerCar { car_id: CarId },
+    NotIndependent { car_id: CarId },
+    OutOfService { car_id: CarId },
+    VipService { car_id: CarId },
+    Broken { car_id: CarId },
+    ButtonsBroken { floor: Floor },
+    Unauthorized { car_id: CarId, floor: Floor },
+    Emergency,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownCar { car_id } => write!(f, "car {} does not exist", car_id.0),
+            CommandError::UnknownBank { bank } => write!(f, "bank {} does not exist", bank.0),
+            CommandError::FloorOutOfRange { floor } => {
+                write!(f, "floor {floor} is outside the building")
+            }
+            CommandError::UnknownFloor { floor } => write!(f, "no floor is called {floor}"),
+            CommandError::FloorNotServed { car_id, floor } => {
+                write!(f, "car {} does not serve floor {floor}", car_id.0)
+            }
+            CommandError::DoorClosed { car_id } => {
+                write!(f, "car {} has its door closed", car_id.0)
+            }
+            CommandError::NotInCar { car_id, person_id } => {
+                write!(f, "person {} is not in car {}", person_id.0, car_id.0)
+            }
+            CommandError::CarFull { car_id } => write!(f, "car {} is full", car_id.0),
+            CommandError::IndependentService { car_id } => {
+                write!(f, "car {} is in independent service", car_id.0)
+            }
+            CommandError::PassengerCar { car_id } => {
+                write!(
+                    f,
+                    "car {} is a passenger car, and can't be reserved",
+                    car_id.0
+                )
+            }
+            CommandError::NotIndependent { car_id } => {
+                write!(f, "car {} is not in independent service", car_id.0)
+            }
+            CommandError::OutOfService { car_id } => {
+                write!(f, "car {} is out of service", car_id.0)
+            }
+            CommandError::VipService { car_id } => {
+                write!(f, "car {} is serving a VIP", car_id.0)
+            }
+            CommandError::Broken { car_id } => write!(f, "car {} has broken down", car_id.0),
+            CommandError::ButtonsBroken { floor } => {
+                write!(f, "the call buttons on floor {floor} are broken")
+            }
+            CommandError::Unauthorized { car_id, floor } => {
+                write!(f, "car {} needs a card to go to floor {floor}", car_id.0)
+            }
+            CommandError::Emergency => {
+                write!(f, "the building is in emergency recall")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Things that happened inside ElevatorSim which observers may want to know about
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ElevatorEvent {
+    CommandRejected {
+        error: CommandError,
+    },
+    PositionFault {
+        car_id: CarId,
+        position: f32,
+    },
+    DoorObstructed {
+        car_id: CarId,
+    },
+    /// a car's door has reopened as many times as it's allowed to since it last closed, so
+    /// it closes however many more people step in
+    DoorNudged {
+        car_id: CarId,
+    },
+    /// person_id got into a car with no room or weight left, and its overload sensor went
+    /// off. The door stays open until enough people have got out again
+    CarOverloaded {
+        car_id: CarId,
+        person_id: PersonId,
+    },
+    /// a moving car will reach its target floor within ElevatorConfig::arrival_notice
+    /// seconds, travelling in direction
+    CarArriving {
+        car_id: CarId,
+        floor: Floor,
+        direction: Direction,
+    },
+    /// a car has stopped at a floor and is opening its door. direction is the way it was
+    /// travelling, or None if it was already at the floor
+    CarArrived {
+        car_id: CarId,
+        floor: Floor,
+        direction: Option<Direction>,
+    },
+}
+
+/// The most events ElevatorSim holds on to before the oldest are dropped, so a program which
+/// never reads them doesn't grow forever
+pub(crate) const MAX_EVENTS: usize = 1024;
+
+/// The default top speed of a car, in metres per second, one default floor a second
+const CAR_SPEED: f32 = 3.5;
+
+/// How many people a car holds when ElevatorConfig doesn't give it a capacity
+const DEFAULT_CAR_CAPACITY: u32 = 10;
+
+/// The fewest cars for which the parallel feature moves them on several threads. With
+/// fewer, handing out the work takes longer than the work itself
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_CARS: usize = 16;
+
+/// How many seconds of hall call history the arrival rate estimate mostly reflects
+const ARRIVAL_TIME_CONSTANT: f32 = 60.;
+
+/// The longest time_to_next_event lets a car go while it's speeding up or slowing down, so
+/// a run stepped from event to event follows the same curve as one ticked at 0.1 seconds
+const KINEMATIC_STEP: f32 = 0.1;
+
+/// Which call buttons each floor's hall has
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HallButtons {
+    /// an up and a down button, as most office buildings have
+    #[default]
+    Pair,
+    /// a single button which calls a car without saying which way, as many small
+    /// residential buildings have
+    Single,
+}
+
+/// What a car with nowhere to go does with its door after arriving at a floor
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum IdleDoorPolicy {
+    /// leave the door open until the car is sent somewhere else
+    HoldOpen,
+    /// close the door after it has been open for this many seconds
+    CloseAfterDwell(f32),
+    /// close the door on the tick after it opens, leaving just enough time for people to
+    /// get on and off
+    CloseImmediately,
+}
+
+impl IdleDoorPolicy {
+    /// how long an idle car under this policy waits before closing its door, if it does
+    pub fn idle_timer(self) -> Option<f32> {
+        match self {
+            IdleDoorPolicy::HoldOpen => None,
+            IdleDoorPolicy::CloseAfterDwell(dwell) => Some(dwell),
+            IdleDoorPolicy::CloseImmediately => Some(0.),
+        }
+    }
+}
+
+/// Settings which change how the building behaves, rather than its size
+/// door_obstruction_probability - the chance each attempt to close a door is blocked
+/// door_reopen_time - how long an obstructed door stays open before trying to close again
+/// max_door_reopens - how many times someone stepping into a closing door opens it again
+/// before it nudges closed regardless, see ReopenDoor. 0 for doors that never reopen
+/// door_open_time, door_close_time - how many seconds a door takes to open and to close.
+/// 0 makes the change instant
+/// door_dwell_time - the least time a door stays fully open after opening
+/// accessible_dwell_time - the least time a door stays fully open when its car answers an
+/// accessible call, see PressAccessButton
+/// door_time_jitter - how much longer or shorter each opening and closing of a door can
+/// take than door_open_time and door_close_time, as a share of them, drawn afresh each time.
+/// 0 makes every door take exactly as long
+/// idle_door_policy - what idle cars do with their doors
+/// hall_buttons - whether each floor has up and down call buttons, or a single one
+/// track_occupants - whether cars keep a list of the people inside them, updated by Board
+/// and Alight commands. When this is off those commands only start transfers
+/// overload_sensor - whether cars sense their load rather than refuse people once full.
+/// Someone boarding a full car is let in, and the car won't close its door until they've
+/// got out again
+/// weight_limits - how many kg each car carries, by car number, before its overload sensor
+/// goes off, whether or not overload_sensor is set. Cars past the end of the list, or with
+/// a limit of 0, have no weight limit
+/// transfer_time - how many seconds a person takes to get on or off. While they do, the car
+/// shows a PassengerTransfer and its door won't close. People take turns in the doorway, so
+/// a car loading several is held for each of them. 0 makes transfers instant
+/// max_speed - the fastest a car travels, in metres per second
+/// max_acceleration - how quickly a car can speed up or slow down, in metres per second
+/// squared. 0 means no limit, so cars move at max_speed as soon as they start
+/// max_jerk - how quickly a car's acceleration can change, in metres per second cubed.
+/// 0 means no limit
+/// acceleration_jitter - how far each trip's acceleration can be from max_acceleration, as
+/// a share of it, drawn afresh each time a car sets off. Cars with identical settings then
+/// drift apart rather than moving in lockstep. 0 makes every trip accelerate the same
+/// arrival_notice - how many seconds before reaching its target a moving car announces
+/// itself with a CarArriving event and lights its lantern on that floor
+/// capacities - how many people each car holds, by car number. Cars past the end of the
+/// list hold DEFAULT_CAR_CAPACITY
+/// served_floors - the floors each car stops at, by car number, such as 0 to 5 for a low
+/// rise car and 0 and 10 to 20 for an express car. Cars past the end of the list, or with an
+/// empty list, serve every floor
+/// capabilities - what each car is fitted for, by car number. Cars past the end of the list
+/// have no special capabilities
+/// classes - what class of car each car is, by car number. Cars past the end of the list are
+/// passenger cars
+/// dedications - times when a car is kept for some floors alone, such as an executive car
+/// in the morning, see Dedication
+/// command_history - how many of the latest commands each car remembers, see
+/// ElevatorSim::command_history. 0 remembers none
+/// car_speeds - the fastest each car travels, by car number. Cars past the end of the list
+/// travel at max_speed
+/// door_timings - how long each car's door takes, by car number. Cars past the end of the
+/// list use door_open_time, door_close_time and door_dwell_time
+/// starting_floors - the floor each car starts on, by car number. Cars past the end of the
+/// list start on floor 0
+/// See ElevatorSimBuilder for setting the by car number lists one car at a time
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ElevatorConfig {
+    pub door_obstruction_probability: f32,
+    pub door_reopen_time: f32,
+    #[serde(default)]
+    pub max_door_reopens: u32,
+    pub door_open_time: f32,
+    pub door_close_time: f32,
+    pub door_dwell_time: f32,
+    #[serde(default)]
+    pub accessible_dwell_time: f32,
+    pub door_time_jitter: f32,
+    pub idle_door_policy: IdleDoorPolicy,
+    pub hall_buttons: HallButtons,
+    pub track_occupants: bool,
+    #[serde(default)]
+    pub overload_sensor: bool,
+    #[serde(default)]
+    pub weight_limits: Vec<f32>,
+    pub transfer_time: f32,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub max_jerk: f32,
+    pub acceleration_jitter: f32,
+    pub arrival_notice: f32,
+    pub capacities: Vec<u32>,
+    pub served_floors: Vec<Vec<Floor>>,
+    pub capabilities: Vec<CarCapabilities>,
+    pub dedications: Vec<Dedication>,
+    pub command_history: usize,
+    #[serde(default)]
+    pub car_speeds: Vec<f32>,
+    #[serde(default)]
+    pub door_timings: Vec<DoorTiming>,
+    #[serde(default)]
+    pub starting_floors: Vec<Floor>,
+    #[serde(default)]
+    pub classes: Vec<CarClass>,
+}
+
+impl Default for ElevatorConfig {
+    fn default() -> Self {
+        Self {
+            door_obstruction_probability: 0.,
+            door_reopen_time: 3.,
+            max_door_reopens: 0,
+            door_open_time: 0.,
+            door_close_time: 0.,
+            door_dwell_time: 0.,
+            accessible_dwell_time: 0.,
+            door_time_jitter: 0.,
+            idle_door_policy: IdleDoorPolicy::HoldOpen,
+            hall_buttons: HallButtons::Pair,
+            track_occupants: false,
+            overload_sensor: false,
+            weight_limits: Vec::new(),
+            transfer_time: 0.,
+            max_speed: CAR_SPEED,
+            max_acceleration: 0.,
+            max_jerk: 0.,
+            acceleration_jitter: 0.,
+            arrival_notice: 3.,
+            capacities: Vec::new(),
+            served_floors: Vec::new(),
+            capabilities: Vec::new(),
+            dedications: Vec::new(),
+            command_history: 0,
+            car_speeds: Vec::new(),
+            door_timings: Vec::new(),
+            starting_floors: Vec::new(),
+            classes: Vec::new(),
+        }
+    }
+}
+
+/// implement functions for ElevatorConfig
+/// car_speed, door_timing, shared_door_timing - the cars' top speeds and door timings
+/// travel_time - how long a trip between two floors takes
+impl ElevatorConfig {
+    /// The fastest car can travel, in metres per second
+    pub fn car_speed(&self, car: usize) -> f32 {
+        self.car_speeds.get(car).copied().unwrap_or(self.max_speed)
+    }
+
+    /// How long car's door takes to open, close and dwell
+    pub fn door_timing(&self, car: usize) -> DoorTiming {
+        self.door_timings
+            .get(car)
+            .copied()
+            .unwrap_or_else(|| self.shared_door_timing())
+    }
+
+    /// Roughly how many seconds car takes to travel metres from standing to standing,
+    /// speeding up and slowing down as max_acceleration and max_jerk allow. Ignores
+    /// acceleration_jitter
+    pub fn travel_time(&self, car: usize, metres: f32) -> f32 {
+        let distance = metres.abs();
+        let speed = self.car_speed(car);
+        let acceleration = self.max_acceleration;
+        if distance <= 0. {
+            return 0.;
+        }
+        if acceleration <= 0. {
+            return distance / speed;
+        }
+        // a jerk limit takes acceleration / jerk longer over speeding up and slowing down
+        let ramp = if self.max_jerk > 0. {
+            acceleration / self.max_jerk
+        } else {
+            0.
+        };
+        if distance >= speed * speed / acceleration {
+            // long enough to reach full speed
+            distance / speed + speed / acceleration + ramp
+        } else {
+            2. * (distance / acceleration).sqrt() + ramp
+        }
+    }
+
+    /// The door timings of cars without their own
+    fn shared_door_timing(&self) -> DoorTiming {
+        DoorTiming {
+            open_time: self.door_open_time,
+            close_time: self.door_close_time,
+            dwell_time: self.door_dwell_time,
+        }
+    }
+}
+
+/// How many seconds one car's door takes to open and to close, and the least time it stays
+/// fully open after opening. See ElevatorConfig's door_open_time, door_close_time and
+/// door_dwell_time
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DoorTiming {
+    pub open_time: f32,
+    pub close_time: f32,
+    pub dwell_time: f32,
+}
+
+/// A time when a car only stops at some floors, such as an executive car kept for the top
+/// floors from 8 to 10 every morning. While it lasts the car's served_floors are just these
+/// floors, so the building refuses to send it anywhere else, and controllers can see it
+/// car_id - the car which is kept
+/// floors - the only floors it stops at meanwhile. An empty list keeps it for every floor
+/// start, end - when it begins and ends, in seconds since the building was created
+/// period - how often it comes round again, such as 86400 for every day, in which case start
+/// and end are counted from the beginning of each period. 0 means it only happens once
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Dedication {
+    pub car_id: CarId,
+    pub floors: Vec<Floor>,
+    pub start: f32,
+    pub end: f32,
+    pub period: f32,
+}
+
+/// implement functions for Dedication
+/// active - whether the car is kept at a time
+/// next_change - how long until it next begins or ends
+impl Dedication {
+    /// how far into the current period time is, or time itself if it doesn't repeat
+    fn phase(&self, time: f32) -> f32 {
+        if self.period > 0. {
+            time.rem_euclid(self.period)
+        } else {
+            time
+        }
+    }
+
+    /// Whether the car is kept for its floors at time
+    pub fn active(&self, time: f32) -> bool {
+        let phase = self.phase(time);
+        phase >= self.start && phase < self.end
+    }
+
+    /// How many seconds after time the dedication next begins or ends, or None if it never
+    /// does again
+    pub fn next_change(&self, time: f32) -> Option<f32> {
+        let phase = self.phase(time);
+        let mut changes = vec![self.start - phase, self.end - phase];
+        if self.period > 0. {
+            changes.push(self.start + self.period - phase);
+        }
+        changes.into_iter().filter(|&t| t > 0.).reduce(f32::min)
+    }
+}
+
+/// The floors car i stops at when it isn't dedicated: its own served floors if the config
+/// gives any, otherwise its bank's
+fn zone<'a>(
+    config: &'a ElevatorConfig,
+    topology: &'a BuildingTopology,
+    i: usize,
+) -> Option<&'a Vec<Floor>> {
+    config
+        .served_floors
+        .get(i)
+        .filter(|floors| !floors.is_empty())
+        .or_else(|| Some(&topology.bank_of(CarId(i as u32))?.floors))
 }
 
-/// A list of possible elevator commands
-pub enum ElevatorCommand {
-    MoveCarTo { car_id: CarId, floor: Floor },
-    PressOutButton { floor: Floor, direction: Direction },
-    PressCarButton { car_id: CarId, floor: Floor },
+/// The floors a car serves, out of floor_num, given the floors it's limited to. No floors
+/// means every floor
+fn served_floors(zone: Option<&Vec<Floor>>, floor_num: usize) -> Vec<bool> {
+    match zone {
+        Some(zone) if !zone.is_empty() => (0..floor_num)
+            .map(|f| zone.contains(&(f as Floor)))
+            .collect(),
+        _ => vec![true; floor_num],
+    }
 }
 
 /// an elevatorsim struct contains a building state, and an impl to change that state based on
-/// ElevatorCommands
-#[derive(Debug)]
+/// ElevatorCommands. idle_timers holds how long an idle car's door has left before the idle
+/// door policy closes it. For each car, travel holds the direction it has moved in since it
+/// last stopped, announced holds the floor it has sent a CarArriving event for,
+/// accelerations holds how quickly it is speeding up, with up being positive, and
+/// acceleration_scales holds how much of max_acceleration it has on this trip. car_repairs
+/// and button_repairs hold how long each broken car and floor's call buttons have left
+/// until they're repaired. histories holds the latest commands given to each car, if the
+/// config keeps any, dedicated which of the config's dedications each car is kept for right
+/// now, and time how long the building has been running. queued_transfers holds the people
+/// waiting their turn to get on or off each car, behind whoever is in the doorway, and
+/// door_reopens how many times each car's door has reopened for someone since it last closed,
+/// accessible_dwells which cars are opening their doors for an accessible call, so they
+/// dwell for accessible_dwell_time once they're open, and authorized the secured floors a
+/// card has been shown for in each car, whose buttons haven't been pressed since. During a
+/// fire recall recalled_services holds the service each car was in before it, to go back to
+/// once the emergency is cleared
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ElevatorSim {
     state: BuildingState,
+    config: ElevatorConfig,
+    arrivals: ArrivalRateEstimator,
+    events: VecDeque<ElevatorEvent>,
+    idle_timers: Vec<Option<f32>>,
+    travel: Vec<Option<Direction>>,
+    announced: Vec<Option<Floor>>,
+    accelerations: Vec<f32>,
+    acceleration_scales: Vec<f32>,
+    car_repairs: Vec<Option<f32>>,
+    button_repairs: Vec<Option<f32>>,
+    histories: Vec<VecDeque<CommandRecord>>,
+    dedicated: Vec<Option<usize>>,
+    time: f32,
+    #[serde(default)]
+    queued_transfers: Vec<VecDeque<PassengerTransfer>>,
+    #[serde(default)]
+    door_reopens: Vec<u32>,
+    #[serde(default)]
+    accessible_dwells: Vec<bool>,
+    #[serde(default)]
+    authorized: Vec<Vec<Floor>>,
+    #[serde(default)]
+    recalled_services: Vec<ServiceState>,
 }
 
 /// Implement the required functions to modify the building's state
 /// new - create a new building
+/// with_config - create a new building with non-default behaviour
+/// with_topology - create a new building with a topology shared with the rest of the simulation
+/// topology - the building's floors, banks, shafts and entrances
 /// applycommand - apply an ElevatorCommand to the building state
+/// light_buttons - light buttons directly, before a run starts
+/// validate - check an ElevatorCommand can be applied
 /// car_mut - return a mutable referance to a given elevator car state
 /// tick - move elevators, open doors, dismiss call buttons
+/// set_clock - choose the time of day
+/// stats - return a view of the statistics collected from hall calls
+/// take_events - drain the events which have happened since the last call
+/// break_car, break_buttons - make a car or a floor's call buttons fail until repaired
+/// command_history - the latest commands a car was given
+/// save_snapshot, load_snapshot - write the whole building to a file, and read it back
+/// check_floors - make sure the topology and config only name floors the building has
 impl ElevatorSim {
     /// create a new building, with number of floors and number of cars
     pub fn new(floor_num: usize, cars_num: usize) -> Self {
+        Self::with_config(floor_num, cars_num, ElevatorConfig::default())
+    }
+
+    /// create a new building, with number of floors, number of cars, and a config
+    pub fn with_config(floor_num: usize, cars_num: usize, config: ElevatorConfig) -> Self {
+        let topology = BuildingTopology::new(floor_num as Floor);
+        Self::with_topology(Arc::new(topology), cars_num, config)
+    }
+
+    /// create a new building shaped by topology, with number of cars and a config. The
+    /// topology is shared, so the people and anything else built from it agree on the floors
+    pub fn with_topology(
+        topology: Arc<BuildingTopology>,
+        cars_num: usize,
+        config: ElevatorConfig,
+    ) -> Self {
+        let floor_num = topology.floors as usize;
         let mut floors_vec = Vec::new();
         for i in 0..floor_num {
             let floor_state = FloorState {
                 floor: i as Floor,
                 out_up: false,
                 out_down: false,
+                out_call: false,
+                trip_requests: Vec::new(),
+                assignments: Vec::new(),
+                buttons_broken: false,
+                banks: vec![BankButtons::default(); topology.banks.len()],
+                access_call: false,
+                lanterns: Vec::new(),
             };
             floors_vec.push(floor_state)
         }
         let mut cars_vec = Vec::new();
+        let top = floor_num.saturating_sub(1) as Floor;
         for i in 0..cars_num {
+            let bank = topology.bank_id_of(CarId(i as u32));
+            let served_floors = served_floors(zone(&config, &topology, i), floor_num);
+            let start = config.starting_floors.get(i).map_or(0, |&f| f.min(top));
             let car_state = ElevatorCarState {
                 id: CarId(i as u32),
-                current_floor: 0.,
-                target_floor: None,
-                door_open: false,
+                current_floor: start as f32,
+                elevation: topology.elevation(start as f32),
+                velocity: 0.,
+                stops: VecDeque::new(),
+                door: DoorState::Closed,
                 car_buttons: vec![false; floor_num], //create in each elevator car the correct
                                                      //number of buttons
+                served_floors,
+                occupants: Vec::new(),
+                transfer: None,
+                capacity: config
+                    .capacities
+                    .get(i)
+                    .copied()
+                    .unwrap_or(DEFAULT_CAR_CAPACITY),
+                passenger_count: 0,
+                service: ServiceState::Normal,
+                capabilities: config.capabilities.get(i).copied().unwrap_or_default(),
+                direction: None,
+                rider_destinations: Vec::new(),
+                malfunction: None,
+                bank,
+                class: config.classes.get(i).copied().unwrap_or_default(),
+                reserved: None,
+                overloaded: false,
+                load: 0.,
+                weight_limit: config.weight_limits.get(i).copied().unwrap_or(0.),
             };
             cars_vec.push(car_state)
         }
-        ElevatorSim {
+        let mut sim = ElevatorSim {
             state: BuildingState {
+                topology,
                 floors: floors_vec,
                 cars: cars_vec,
+                emergency: EmergencyMode::Normal,
+                clock: SimClock::default(),
             },
+            config,
+            arrivals: ArrivalRateEstimator::new(floor_num, ARRIVAL_TIME_CONSTANT),
+            events: VecDeque::new(),
+            idle_timers: vec![None; cars_num],
+            travel: vec![None; cars_num],
+            announced: vec![None; cars_num],
+            accelerations: vec![0.; cars_num],
+            acceleration_scales: vec![1.; cars_num],
+            car_repairs: vec![None; cars_num],
+            button_repairs: vec![None; floor_num],
+            histories: vec![VecDeque::new(); cars_num],
+            dedicated: vec![None; cars_num],
+            queued_transfers: vec![VecDeque::new(); cars_num],
+            door_reopens: vec![0; cars_num],
+            accessible_dwells: vec![false; cars_num],
+            authorized: vec![Vec::new(); cars_num],
+            recalled_services: Vec::new(),
+            time: 0.,
+        };
+        sim.update_dedications();
+        sim
+    }
+
+    /// Light buttons as if they'd been pressed, without counting anyone arriving or recording
+    /// any commands. A button on a floor or in a car the building doesn't have is refused
+    /// like the command pressing it would be, and then nothing is lit
+    pub fn light_buttons(&mut self, buttons: &LitButtons) -> Result<(), CommandError> {
+        for &(floor, direction) in &buttons.hall {
+            self.validate(&ElevatorCommand::PressOutButton {
+                floor,
+                direction,
+                bank: None,
+            })?;
         }
+        for &(car_id, floor) in &buttons.car {
+            self.validate(&ElevatorCommand::PressCarButton { car_id, floor })?;
+        }
+        for &(floor, direction) in &buttons.hall {
+            if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                f.light(self.config.hall_buttons, direction, None);
+            }
+        }
+        for &(car_id, floor) in &buttons.car {
+            if let Some(car) = self.car_mut(car_id) {
+                car.press(floor);
+            }
+        }
+        Ok(())
     }
 
-    /// Apply an ElevatorCommand to the BuildingState
-    pub fn apply_command(&mut self, cmd: ElevatorCommand) {
+    /// Apply an ElevatorCommand to the BuildingState. Commands which name a car that doesn't
+    /// exist, or a floor outside the building or not served by the car, are rejected with an
+    /// error, and a CommandRejected event is recorded
+    pub fn apply_command(&mut self, cmd: ElevatorCommand) -> Result<(), CommandError> {
+        let result = self.validate(&cmd);
+        self.remember(&cmd, &result);
+        if let Err(error) = result {
+            self.push_event(ElevatorEvent::CommandRejected {
+                error: error.clone(),
+            });
+            return Err(error);
+        }
+
+        let accessible = matches!(cmd, ElevatorCommand::PressAccessButton { .. });
         match cmd {
-            // pressing the outer button on a specific floor pointing in a direction
-            ElevatorCommand::PressOutButton { floor, direction } => {
+            // pressing the outer button on a specific floor pointing in a direction. A floor
+            // with a single call button lights it whichever way the caller is going. The
+            // accessible call button does the same, and asks for longer to get in
+            ElevatorCommand::PressOutButton {
+                floor,
+                direction,
+                bank,
+            }
+            | ElevatorCommand::PressAccessButton {
+                floor,
+                direction,
+                bank,
+            } => {
                 if let Some(f) = self.state.floors.get_mut(floor as usize) {
-                    match direction {
-                        Direction::Up => f.out_up = true,
-                        Direction::Down => f.out_down = true,
+                    let lit = f.light(self.config.hall_buttons, direction, bank);
+                    let new_call = lit || accessible && !f.access_call;
+                    f.access_call |= accessible;
+                    // only a press lighting a button counts as an arrival, so people pressing
+                    // again while they wait don't make the floor look busier
+                    if new_call {
+                        match self.config.hall_buttons {
+                            HallButtons::Single => self.arrivals.record_undirected(floor),
+                            HallButtons::Pair => self.arrivals.record(floor, direction),
+                        }
                     }
                 }
             }
-            // pressing the button inside an elevator car
+            // pressing the button inside an elevator car. A car with no direction yet commits
+            // to the way its stops are, so the first button pressed sets it
             ElevatorCommand::PressCarButton { car_id, floor } => {
                 if let Some(car) = self.car_mut(car_id) {
-                    if let Some(slot) = car.car_buttons.get_mut(floor as usize) {
-                        *slot = true;
+                    car.press(floor);
+                }
+                // a card lets its floor's button be pressed once
+                if let Some(authorized) = self.authorized.get_mut(car_id.0 as usize) {
+                    authorized.retain(|&f| f != floor);
+                }
+            }
+            // showing a card to a car's reader, so the button for its floor works
+            ElevatorCommand::AuthorizeFloor { car_id, floor } => {
+                let index = car_id.0 as usize;
+                if self.authorized.len() <= index {
+                    self.authorized.resize(index + 1, Vec::new());
+                }
+                if !self.authorized[index].contains(&floor) {
+                    self.authorized[index].push(floor);
+                }
+            }
+            // taking every car out of service and sending it to the recall floor, forgetting
+            // every call and plan. Cars hold their doors open once they get there, and the
+            // service each was in is kept for when the emergency is cleared
+            ElevatorCommand::FireRecall { floor } => {
+                if self.state.emergency == EmergencyMode::Normal {
+                    self.recalled_services =
+                        self.state.cars.iter().map(|car| car.service).collect();
+                }
+                self.state.emergency = EmergencyMode::FireRecall { floor };
+                for f in &mut self.state.floors {
+                    f.clear_hall_calls();
+                    f.trip_requests.clear();
+                    f.assignments.clear();
+                }
+                for i in 0..self.state.cars.len() {
+                    let car = &mut self.state.cars[i];
+                    car.car_buttons.fill(false);
+                    car.direction = None;
+                    car.service = ServiceState::Normal;
+                    self.idle_timers[i] = None;
+                    // a car already open at the recall floor just stays there
+                    let here =
+                        car.velocity == 0. && (car.current_floor - floor as f32).abs() < 0.01;
+                    if here && car.door_opening_or_open() {
+                        car.stops.clear();
+                    } else {
+                        car.stops = VecDeque::from([floor]);
+                        let car_id = car.id;
+                        self.try_close_door(car_id);
                     }
                 }
             }
-            // setting the target floor of an elevator car, which also closes its door
-            ElevatorCommand::MoveCarTo { car_id, floor } => {
+            // going back to the service each car was in before the recall. Idle cars close
+            // their doors as the policy says
+            ElevatorCommand::ClearEmergency => {
+                self.state.emergency = EmergencyMode::Normal;
+                let services = self.recalled_services.drain(..);
+                for (car, service) in self.state.cars.iter_mut().zip(services) {
+                    car.service = service;
+                }
+                for (i, car) in self.state.cars.iter().enumerate() {
+                    if car.door_open() && car.stops.is_empty() {
+                        self.idle_timers[i] = self.config.idle_door_policy.idle_timer();
+                    }
+                }
+            }
+            // committing a car to the way it will leave its stop
+            ElevatorCommand::SetCarDirection { car_id, direction } => {
                 if let Some(car) = self.car_mut(car_id) {
-                    car.target_floor = Some(floor);
-                    car.door_open = false;
+                    car.direction = direction;
                 }
             }
-        }
-    }
-
-    /// get a mutable referance to a particular elevator car, based on its id.
-    /// With more time, I would impl functions on the elevator car to do everything
-    /// necessary here
-    fn car_mut(&mut self, car_id: CarId) -> Option<&mut ElevatorCarState> {
-        self.state.cars.get_mut(car_id.0 as usize)
-    }
-
-    /// move elevator cars, if they are at their target floor, open their doors
-    pub fn tick(&mut self, dt: f32) {
-        for car in &mut self.state.cars {
-            if let Some(target) = car.target_floor {
-                //for each car with a target floor
-                let target_f = target as f32;
-                //get the difference between its target and current location
-                let diff = target_f - car.current_floor;
-                let speed = 1.0;
-                if diff.abs() < 0.01 {
-                    // if the elevator is close to its target floor, say we're there and open the
-                    // door
-                    car.current_floor = target_f;
-                    car.target_floor = None;
-                    car.door_open = true;
-
-                    let floor_index = target as usize;
-
-                    // reset the outer buttons on the floor
-                    if let Some(floor_state) = self.state.floors.get_mut(floor_index) {
-                        floor_state.out_up = false;
-                        floor_state.out_down = false;
-                    }
-
-                    // reset the button inside the elevator for this floor
-                    if let Some(button) = car.car_buttons.get_mut(floor_index) {
-                        *button = false;
+            // someone stepping into a closing door, which opens again from as far as it had
+            // closed, or nudges closed once it has reopened too often
+            ElevatorCommand::ReopenDoor { car_id } => {
+                let index = car_id.0 as usize;
+                let timing = self.config.door_timing(index);
+                let limit = self.config.max_door_reopens;
+                self.door_reopens.resize(self.state.cars.len(), 0);
+                let DoorState::Closing { remaining } = self.state.cars[index].door else {
+                    return Ok(());
+                };
+                let reopens = &mut self.door_reopens[index];
+                if *reopens < limit {
+                    *reopens += 1;
+                    let closed = 1. - remaining / timing.close_time.max(f32::EPSILON);
+                    let car = &mut self.state.cars[index];
+                    car.door = DoorState::Opening {
+                        remaining: timing.open_time * closed.clamp(0., 1.),
+                    };
+                    // an idle car that was closing its door tries again once the dwell is over
+                    if car.target_floor().is_none() {
+                        self.idle_timers[index] = Some(0.);
                     }
-                } else {
-                    // move the elevator car down or up based on the direction it needs to move
-                    let step = speed * dt * (if diff > 0. { 1. } else { -1. });
-                    car.current_floor += step;
+                    self.push_event(ElevatorEvent::DoorObstructed { car_id });
+                } else if limit > 0 && *reopens == limit {
+                    *reopens += 1;
+                    self.push_event(ElevatorEvent::DoorNudged { car_id });
                 }
             }
-        }
-    }
-
-    // return a referance to the entire building state, used in render and PeopleSim
-    pub fn state(&self) -> &BuildingState {
-        &self.state
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::{CarId, Direction};
-
-    #[test]
-    fn press_out_button() {
-        let mut sim = ElevatorSim::new(3, 1);
-
-        sim.apply_command(ElevatorCommand::PressOutButton {
-            floor: 1,
-            direction: Direction::Up,
-        });
+            // keeping a freight car for deliveries, or letting everyone use it again
+            ElevatorCommand::ReserveCar { car_id, duration } => {
+                if let Some(car) = self.car_mut(car_id) {
+                    car.reserved = (duration > 0.).then_some(duration);
+                }
+            }
+            // sending an elevator car straight to a floor, which also tries to close its door.
+            // Operators do the same from inside a car in independent service
+            ElevatorCommand::MoveCarTo { car_id, floor }
+            | ElevatorCommand::OperateCar { car_id, floor } => {
+                if let Some(car) = self.car_mut(car_id) {
+                    car.stops = VecDeque::from([floor]);
+                }
+                self.try_close_door(car_id);
+            }
+            // planning another stop for a car, after the ones it already has
+            ElevatorCommand::AddStop { car_id, floor } => {
+                if let Some(car) = self.car_mut(car_id)
+                    && !car.stops.contains(&floor)
+                {
+                    car.stops.push_back(floor);
+                }
+                self.try_close_door(car_id);
+            }
+            // dropping a car's plan, or replacing it. A moving car can't stop between floors
+            ElevatorCommand::ClearStops { car_id } => {
+                if let Some(car) = self.car_mut(car_id) {
+                    car.stops.clear();
+                    if car.velocity != 0. {
+                        car.stops.push_back(car.nearest_floor_ahead());
+                    }
+                }
+            }
+            ElevatorCommand::ReplaceStops { car_id, stops } => {
+                if stops.is_empty() {
+                    return self.apply_command(ElevatorCommand::ClearStops { car_id });
+                }
+                if let Some(car) = self.car_mut(car_id) {
+                    car.stops = stops.into();
+                }
+                self.try_close_door(car_id);
+            }
+            // announcing which car people on a floor going to destination should take,
+            // replacing any earlier announcement for the same trip
+            ElevatorCommand::AssignTrip {
+                floor,
+                destination,
+                car_id,
+            } => {
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    f.trip_requests.retain(|&d| d != destination);
+                    f.assignments.retain(|a| a.destination != destination);
+                    f.assignments.push(TripAssignment {
+                        destination,
+                        car_id,
+                    });
+                }
+            }
+            // entering a destination at a floor's kiosk, which waits for the controller to
+            // assign it a car
+            ElevatorCommand::RequestTrip { floor, destination } => {
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    // entering a destination already waiting for a car isn't another arrival
+                    if !f.trip_requests.contains(&destination) {
+                        f.trip_requests.push(destination);
+                        let direction = if destination > floor {
+                            Direction::Up
+                        } else {
+                            Direction::Down
+                        };
+                        self.arrivals.record(floor, direction);
+                    }
+                }
+            }
+            // a person stepping into a car
+            ElevatorCommand::Board {
+                car_id,
+                person_id,
+                weight,
+            } => {
+                let track = self.config.track_occupants;
+                let sensor = self.config.overload_sensor;
+                let mut overloaded = false;
+                if let Some(car) = self.car_mut(car_id) {
+                    if !track {
+                        car.passenger_count += 1;
+                        car.load += weight;
+                    } else if !car.occupants.contains(&person_id) {
+                        car.occupants.push(person_id);
+                        car.passenger_count = car.occupants.len() as u32;
+                        car.load += weight;
+                    }
+                    car.overloaded = car.over_limit(sensor);
+                    overloaded = car.overloaded;
+                }
+                // the sensor sounds for everyone who gets in over capacity or weight
+                if overloaded {
+                    self.push_event(ElevatorEvent::CarOverloaded { car_id, person_id });
+                }
+                self.start_transfer(car_id, PassengerTransfer::Boarding { progress: 0. });
+            }
+            // switching a car in or out of independent service. A car taken out of the
+            // controller's hands is no longer assigned to any trips, and holds its door open
+            ElevatorCommand::SetIndependentService { car_id, on } => {
+                if let Some(car) = self.car_mut(car_id) {
+                    car.service = if on {
+                        ServiceState::Independent
+                    } else {
+                        ServiceState::Normal
+                    };
+                }
+                if on {
+                    self.idle_timers[car_id.0 as usize] = None;
+                    for f in &mut self.state.floors {
+                        f.assignments.retain(|a| a.car_id != car_id);
+                    }
+                }
+            }
+            // taking a car from the controller for a VIP. It drops the stops it was sent to,
+            // finishing any it's moving towards, makes its own way from there, and is no
+            // longer assigned to any trips
+            ElevatorCommand::VipCall { car_id, floor } => {
+                if let Some(car) = self.car_mut(car_id) {
+                    car.service = ServiceState::Vip {
+                        floor,
+                        stage: VipStage::Fetching,
+                    };
+                    car.stops.clear();
+                    if car.velocity != 0. {
+                        car.stops.push_back(car.nearest_floor_ahead());
+                    }
+                }
+                self.idle_timers[car_id.0 as usize] = None;
+                for f in &mut self.state.floors {
+                    f.assignments.retain(|a| a.car_id != car_id);
+                }
+            }
+            // taking a car out of service, which keeps the stops it has for the people inside
+            // but is no longer assigned to any trips, or putting it back. An idle car closes
+            // its door as it leaves service, and as the policy says once it's back
+            ElevatorCommand::SetCarService { car_id, in_service } => {
+                let index = car_id.0 as usize;
+                if let Some(car) = self.car_mut(car_id) {
+                    car.service = if in_service {
+                        ServiceState::Normal
+                    } else {
+                        ServiceState::OutOfService
+                    };
+                }
+                let car = &self.state.cars[index];
+                let idle = car.door_open() && car.stops.is_empty();
+                self.idle_timers[index] = match idle {
+                    false => None,
+                    true if !in_service => Some(0.),
+                    true => self.config.idle_door_policy.idle_timer(),
+                };
+                if !in_service {
+                    for f in &mut self.state.floors {
+                        f.assignments.retain(|a| a.car_id != car_id);
+                    }
+                }
+            }
+            // a person stepping out of a car
+            ElevatorCommand::Alight {
+                car_id,
+                person_id,
+                weight,
+            } => {
+                let track = self.config.track_occupants;
+                let sensor = self.config.overload_sensor;
+                if let Some(car) = self.car_mut(car_id) {
+                    if track {
+                        car.occupants.retain(|&p| p != person_id);
+                        car.passenger_count = car.occupants.len() as u32;
+                    } else {
+                        car.passenger_count = car.passenger_count.saturating_sub(1);
+                    }
+                    // an empty car weighs nothing, however the sums have rounded
+                    car.load = if car.passenger_count == 0 {
+                        0.
+                    } else {
+                        (car.load - weight).max(0.)
+                    };
+                    car.overloaded &= car.over_limit(sensor);
+                }
+                self.start_transfer(car_id, PassengerTransfer::Alighting { progress: 0. });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start someone getting on or off a car, if transfers take time, or queue them behind
+    /// whoever is already in the doorway, so everyone adds their transfer_time to how long
+    /// the door stays open
+    fn start_transfer(&mut self, car_id: CarId, transfer: PassengerTransfer) {
+        if self.config.transfer_time <= 0. {
+            return;
+        }
+        let i = car_id.0 as usize;
+        let Some(car) = self.state.cars.get_mut(i) else {
+            return;
+        };
+        if car.transfer.is_none() {
+            car.transfer = Some(transfer);
+        } else if let Some(queue) = self.queued_transfers.get_mut(i) {
+            queue.push_back(transfer);
+        }
+    }
+
+    /// Check that every car and floor named by a command exists, and that cars are only asked
+    /// to go to floors they serve, and that nobody boards a full car. Only cars in independent
+    /// service can be operated, and only cars in normal service dispatched. When occupants are
+    /// tracked, people can only board or alight through an open door, and only alight from the
+    /// car they're in
+    pub fn validate(&self, cmd: &ElevatorCommand) -> Result<(), CommandError> {
+        // during a recall, people can still get out of the cars
+        let allowed_in_emergency = matches!(
+            cmd,
+            ElevatorCommand::Board { .. }
+                | ElevatorCommand::Alight { .. }
+                | ElevatorCommand::FireRecall { .. }
+                | ElevatorCommand::ClearEmergency
+        );
+        if self.state.emergency != EmergencyMode::Normal && !allowed_in_emergency {
+            return Err(CommandError::Emergency);
+        }
+
+        let (car_id, floor) = match *cmd {
+            ElevatorCommand::Board {
+                car_id, person_id, ..
+            }
+            | ElevatorCommand::Alight {
+                car_id, person_id, ..
+            } => {
+                let Some(car) = self.state.cars.get(car_id.0 as usize) else {
+                    return Err(CommandError::UnknownCar { car_id });
+                };
+                let boarding = matches!(cmd, ElevatorCommand::Board { .. });
+                // a car with an overload sensor lets them in, and sounds instead
+                if boarding
+                    && car.is_full()
+                    && !car.occupants.contains(&person_id)
+                    && !self.config.overload_sensor
+                {
+                    return Err(CommandError::CarFull { car_id });
+                }
+                if !self.config.track_occupants {
+                    return Ok(());
+                }
+                if !car.door_open() {
+                    return Err(CommandError::DoorClosed { car_id });
+                }
+                let alighting = matches!(cmd, ElevatorCommand::Alight { .. });
+                if alighting && !car.occupants.contains(&person_id) {
+                    return Err(CommandError::NotInCar { car_id, person_id });
+                }
+                return Ok(());
+            }
+            ElevatorCommand::PressOutButton { floor, bank, .. }
+            | ElevatorCommand::PressAccessButton { floor, bank, .. } => {
+                let Some(f) = self.state.floors.get(floor as usize) else {
+                    return Err(CommandError::FloorOutOfRange { floor });
+                };
+                if let Some(bank) = bank
+                    && self.state.topology.bank(bank).is_none()
+                {
+                    return Err(CommandError::UnknownBank { bank });
+                }
+                if f.buttons_broken {
+                    return Err(CommandError::ButtonsBroken { floor });
+                }
+                return Ok(());
+            }
+            ElevatorCommand::PressCarButton { car_id, floor } => {
+                let authorized = self
+                    .authorized
+                    .get(car_id.0 as usize)
+                    .is_some_and(|floors| floors.contains(&floor));
+                let known = (car_id.0 as usize) < self.state.cars.len();
+                if known && self.state.topology.is_secured(floor) && !authorized {
+                    return Err(CommandError::Unauthorized { car_id, floor });
+                }
+                (car_id, floor)
+            }
+            ElevatorCommand::AuthorizeFloor { car_id, floor } => (car_id, floor),
+            ElevatorCommand::MoveCarTo { car_id, floor }
+            | ElevatorCommand::AddStop { car_id, floor }
+            | ElevatorCommand::VipCall { car_id, floor } => {
+                if let Some(car) = self.state.cars.get(car_id.0 as usize) {
+                    check_dispatchable(car)?;
+                }
+                (car_id, floor)
+            }
+            ElevatorCommand::ClearStops { car_id } => {
+                let Some(car) = self.state.cars.get(car_id.0 as usize) else {
+                    return Err(CommandError::UnknownCar { car_id });
+                };
+                return check_dispatchable(car);
+            }
+            ElevatorCommand::ReplaceStops { car_id, ref stops } => {
+                self.validate(&ElevatorCommand::ClearStops { car_id })?;
+                for &floor in stops {
+                    self.validate(&ElevatorCommand::MoveCarTo { car_id, floor })?;
+                }
+                return Ok(());
+            }
+            ElevatorCommand::OperateCar { car_id, floor } => {
+                if let Some(car) = self.state.cars.get(car_id.0 as usize)
+                    && car.service != ServiceState::Independent
+                {
+                    return Err(CommandError::NotIndependent { car_id });
+                }
+                (car_id, floor)
+            }
+            ElevatorCommand::SetIndependentService { car_id, .. }
+            | ElevatorCommand::SetCarService { car_id, .. }
+            | ElevatorCommand::SetCarDirection { car_id, .. }
+            | ElevatorCommand::ReopenDoor { car_id } => {
+                if car_id.0 as usize >= self.state.cars.len() {
+                    return Err(CommandError::UnknownCar { car_id });
+                }
+                return Ok(());
+            }
+            ElevatorCommand::ReserveCar { car_id, .. } => {
+                let Some(car) = self.state.cars.get(car_id.0 as usize) else {
+                    return Err(CommandError::UnknownCar { car_id });
+                };
+                if car.class == CarClass::Passenger {
+                    return Err(CommandError::PassengerCar { car_id });
+                }
+                return Ok(());
+            }
+            ElevatorCommand::FireRecall { floor } => {
+                if floor as usize >= self.state.floors.len() {
+                    return Err(CommandError::FloorOutOfRange { floor });
+                }
+                // every car has to be able to get there
+                if let Some(car) = self.state.cars.iter().find(|car| !car.serves(floor)) {
+                    return Err(CommandError::FloorNotServed {
+                        car_id: car.id,
+                        floor,
+                    });
+                }
+                return Ok(());
+            }
+            ElevatorCommand::ClearEmergency => return Ok(()),
+            ElevatorCommand::RequestTrip { floor, destination } => {
+                for f in [floor, destination] {
+                    if f as usize >= self.state.floors.len() {
+                        return Err(CommandError::FloorOutOfRange { floor: f });
+                    }
+                }
+                return Ok(());
+            }
+            ElevatorCommand::AssignTrip {
+                floor,
+                destination,
+                car_id,
+            } => {
+                // the assigned car has to be able to stop at both ends of the trip
+                self.validate(&ElevatorCommand::MoveCarTo { car_id, floor })?;
+                (car_id, destination)
+            }
+        };
+
+        let Some(car) = self.state.cars.get(car_id.0 as usize) else {
+            return Err(CommandError::UnknownCar { car_id });
+        };
+        if floor as usize >= self.state.floors.len() {
+            return Err(CommandError::FloorOutOfRange { floor });
+        }
+        if !car.serves(floor) {
+            return Err(CommandError::FloorNotServed { car_id, floor });
+        }
+        Ok(())
+    }
+
+    /// try to start closing a car's door. Only a fully open door whose dwell is over can
+    /// close. If the door is obstructed it dwells for door_reopen_time, and tick tries again
+    /// after. If someone is getting on or off, tick tries again once they're done
+    fn try_close_door(&mut self, car_id: CarId) {
+        let index = car_id.0 as usize;
+        let Some(car) = self.state.cars.get(index) else {
+            return;
+        };
+        if car.door
+            != (DoorState::Open {
+                dwell_remaining: 0.,
+            })
+            || car.transfer.is_some()
+            || car.overloaded
+            || car.malfunction == Some(Malfunction::DoorJammed)
+        {
+            return;
+        }
+
+        let p = self.config.door_obstruction_probability;
+        if p > 0. && rng::random::<f32>(Stream::Behavior) < p {
+            let car = &mut self.state.cars[index];
+            car.door = DoorState::Open {
+                dwell_remaining: self.config.door_reopen_time,
+            };
+            // an idle car that was closing its door tries again once the dwell is over
+            if car.target_floor().is_none() {
+                self.idle_timers[index] = Some(0.);
+            }
+            self.push_event(ElevatorEvent::DoorObstructed { car_id });
+        } else {
+            let close_time = jittered(
+                self.config.door_timing(index).close_time,
+                self.config.door_time_jitter,
+            );
+            let car = &mut self.state.cars[index];
+            car.door = if close_time > 0. {
+                DoorState::Closing {
+                    remaining: close_time,
+                }
+            } else {
+                DoorState::Closed
+            };
+
+            // people assigned to this car here have had their chance to board
+            let floor_index = car.current_floor.round() as usize;
+            if let Some(floor_state) = self.state.floors.get_mut(floor_index) {
+                floor_state.assignments.retain(|a| a.car_id != car_id);
+            }
+        }
+    }
+
+    /// add a command to the history of the car it was for, forgetting the oldest once there
+    /// are more than the config keeps
+    fn remember(&mut self, cmd: &ElevatorCommand, result: &Result<(), CommandError>) {
+        let keep = self.config.command_history;
+        let Some(history) = cmd
+            .car_id()
+            .and_then(|car_id| self.histories.get_mut(car_id.0 as usize))
+            .filter(|_| keep > 0)
+        else {
+            return;
+        };
+        if history.len() >= keep {
+            history.pop_front();
+        }
+        history.push_back(CommandRecord {
+            time: self.time,
+            command: cmd.clone(),
+            error: result.clone().err(),
+        });
+    }
+
+    /// The latest commands a car was given, oldest first, including ones it refused. Only
+    /// the last ElevatorConfig::command_history are kept, so this is empty unless that's set
+    pub fn command_history(&self, car_id: CarId) -> impl Iterator<Item = &CommandRecord> {
+        self.histories.get(car_id.0 as usize).into_iter().flatten()
+    }
+
+    /// Break a car down for repair_time seconds. A stuck car stops dead where it is, and a
+    /// jammed door stays open, so only a car with its door open can have it jam. Either way
+    /// the car is no longer assigned to any trips, and keeps its stops for when it's fixed
+    pub fn break_car(
+        &mut self,
+        car_id: CarId,
+        malfunction: Malfunction,
+        repair_time: f32,
+    ) -> Result<(), CommandError> {
+        let index = car_id.0 as usize;
+        let car = self
+            .car_mut(car_id)
+            .ok_or(CommandError::UnknownCar { car_id })?;
+        match malfunction {
+            Malfunction::Stuck => car.velocity = 0.,
+            Malfunction::DoorJammed => {
+                if car.door == DoorState::Closed {
+                    return Err(CommandError::DoorClosed { car_id });
+                }
+                car.door = DoorState::Open {
+                    dwell_remaining: 0.,
+                };
+            }
+        }
+        car.malfunction = Some(malfunction);
+        self.accelerations[index] = 0.;
+        self.idle_timers[index] = None;
+        self.car_repairs[index] = Some(repair_time.max(0.));
+        for f in &mut self.state.floors {
+            f.assignments.retain(|a| a.car_id != car_id);
+        }
+        Ok(())
+    }
+
+    /// Break the call buttons on a floor for repair_time seconds. Calls made before they
+    /// broke stay lit, but new presses don't register
+    pub fn break_buttons(&mut self, floor: Floor, repair_time: f32) -> Result<(), CommandError> {
+        let floor_state = self
+            .state
+            .floors
+            .get_mut(floor as usize)
+            .ok_or(CommandError::FloorOutOfRange { floor })?;
+        floor_state.buttons_broken = true;
+        self.button_repairs[floor as usize] = Some(repair_time.max(0.));
+        Ok(())
+    }
+
+    /// count down to repairing broken cars and buttons, and fix the ones whose time is up.
+    /// A car whose door was jammed then treats it like any other idle door. Reservations
+    /// for deliveries run out the same way
+    fn tick_repairs(&mut self, dt: f32) {
+        // reservations run out the same way
+        for car in &mut self.state.cars {
+            if let Some(remaining) = &mut car.reserved {
+                *remaining -= dt;
+                if *remaining <= 0. {
+                    car.reserved = None;
+                }
+            }
+        }
+        for i in 0..self.state.cars.len() {
+            let Some(remaining) = &mut self.car_repairs[i] else {
+                continue;
+            };
+            *remaining -= dt;
+            if *remaining > 0. {
+                continue;
+            }
+            self.car_repairs[i] = None;
+            let car = &mut self.state.cars[i];
+            car.malfunction = None;
+            if car.door_open() && car.stops.is_empty() && car.dispatchable() {
+                self.idle_timers[i] = self.config.idle_door_policy.idle_timer();
+            }
+        }
+        for (floor, repair) in self.state.floors.iter_mut().zip(&mut self.button_repairs) {
+            let Some(remaining) = repair else {
+                continue;
+            };
+            *remaining -= dt;
+            if *remaining <= 0. {
+                *repair = None;
+                floor.buttons_broken = false;
+            }
+        }
+    }
+
+    /// get a mutable referance to a particular elevator car, based on its id.
+    /// With more time, I would impl functions on the elevator car to do everything
+    /// necessary here
+    fn car_mut(&mut self, car_id: CarId) -> Option<&mut ElevatorCarState> {
+        self.state.cars.get_mut(car_id.0 as usize)
+    }
+
+    /// move elevator cars, if they are at their target floor, open their doors
+    pub fn tick(&mut self, dt: f32) {
+        self.time += dt;
+        self.state.clock.advance(dt);
+        self.arrivals.tick(dt);
+        self.tick_repairs(dt);
+        self.update_dedications();
+
+        // the controller can't send cars out of service anywhere, so they take the people
+        // inside to the nearest floor with a button lit themselves
+        for car in &mut self.state.cars {
+            if car.service != ServiceState::OutOfService || !car.stops.is_empty() {
+                continue;
+            }
+            if let Some(floor) = car.nearest_button() {
+                car.stops.push_back(floor);
+            }
+        }
+
+        // cars in VIP service make their own way too, and go back to the controller, with
+        // their door left as the policy says, once the VIP's floor opens up
+        for i in 0..self.state.cars.len() {
+            let car = &mut self.state.cars[i];
+            let Some((stop, service)) = car.vip_next() else {
+                continue;
+            };
+            car.stops.extend(stop);
+            car.service = service;
+            if service == ServiceState::Normal {
+                self.idle_timers[i] = self.config.idle_door_policy.idle_timer();
+            }
+        }
+
+        // doors move through their cycle. A car with somewhere to go tries to close its door
+        // as soon as the dwell is over, and one whose door closes partway through the tick
+        // only moves for what's left of it
+        let mut move_times = vec![dt; self.state.cars.len()];
+        for (i, move_time) in move_times.iter_mut().enumerate() {
+            let mut dwell_time = self.config.door_timing(i).dwell_time;
+            let car = &mut self.state.cars[i];
+            match &mut car.door {
+                DoorState::Closed => {}
+                DoorState::Opening { remaining } => {
+                    *remaining -= dt;
+                    if *remaining <= 0. {
+                        if let Some(accessible) = self.accessible_dwells.get_mut(i)
+                            && std::mem::take(accessible)
+                        {
+                            dwell_time = dwell_time.max(self.config.accessible_dwell_time);
+                        }
+                        car.door = DoorState::Open {
+                            dwell_remaining: dwell_time,
+                        };
+                    }
+                }
+                DoorState::Open { dwell_remaining } => {
+                    if *dwell_remaining > 0. {
+                        *dwell_remaining = (*dwell_remaining - dt).max(0.);
+                        if *dwell_remaining == 0. && car.target_floor().is_some() {
+                            let car_id = car.id;
+                            self.try_close_door(car_id);
+                        }
+                    } else if car.target_floor().is_some() && car.transfer.is_none() {
+                        // a car with more stops planned leaves without being told again
+                        let car_id = car.id;
+                        self.try_close_door(car_id);
+                    }
+                }
+                DoorState::Closing { remaining } => {
+                    *remaining -= dt;
+                    if *remaining <= 0. {
+                        *move_time = -*remaining;
+                        car.door = DoorState::Closed;
+                        if let Some(reopens) = self.door_reopens.get_mut(i) {
+                            *reopens = 0;
+                        }
+                    }
+                }
+            }
+        }
+
+        // people getting on or off make progress one at a time, and once the last is done a
+        // car which has somewhere to go tries to close its door
+        let transfer_time = self.config.transfer_time;
+        for i in 0..self.state.cars.len() {
+            let car = &mut self.state.cars[i];
+            let Some(transfer) = &mut car.transfer else {
+                continue;
+            };
+            match transfer {
+                PassengerTransfer::Boarding { progress }
+                | PassengerTransfer::Alighting { progress } => {
+                    *progress = (*progress + dt / transfer_time).min(1.);
+                }
+            }
+            if transfer.progress() >= 1. {
+                car.transfer = self
+                    .queued_transfers
+                    .get_mut(i)
+                    .and_then(VecDeque::pop_front);
+                if car.transfer.is_none() && car.target_floor().is_some() {
+                    let car_id = car.id;
+                    self.try_close_door(car_id);
+                }
+            }
+        }
+
+        // idle cars close their doors once their dwell runs out. This happens before cars
+        // move, so a door which opens this tick stays open for at least one tick
+        for i in 0..self.state.cars.len() {
+            let car = &self.state.cars[i];
+            // the idle dwell waits for anyone getting on or off, and for the door's own dwell
+            let dwelling =
+                matches!(car.door, DoorState::Open { dwell_remaining } if dwell_remaining > 0.);
+            if car.transfer.is_some() || dwelling {
+                continue;
+            }
+            if !car.door_open() || car.target_floor().is_some() {
+                self.idle_timers[i] = None;
+                continue;
+            }
+            if let Some(remaining) = self.idle_timers[i] {
+                if remaining <= 0. {
+                    self.idle_timers[i] = None;
+                    self.try_close_door(car.id);
+                } else {
+                    self.idle_timers[i] = Some(remaining - dt);
+                }
+            }
+        }
+
+        // anything random is drawn first, one car after another, so the cars can then move
+        // independently, at the same time with the parallel feature, and still repeat exactly
+        let mut open_times = vec![None; self.state.cars.len()];
+        for (i, car) in self.state.cars.iter().enumerate() {
+            let Some(target) = car.target_floor() else {
+                continue;
+            };
+            if car.door != DoorState::Closed || car.malfunction.is_some() {
+                continue;
+            }
+            if (target as f32 - car.current_floor).abs() < 0.01 {
+                open_times[i] = Some(jittered(
+                    self.config.door_timing(i).open_time,
+                    self.config.door_time_jitter,
+                ));
+            } else if self.travel[i].is_none() {
+                self.acceleration_scales[i] = jittered(1., self.config.acceleration_jitter);
+            }
+        }
+
+        let moves: Vec<CarMove> = self
+            .state
+            .cars
+            .iter_mut()
+            .zip(&mut self.accelerations)
+            .zip(&mut self.travel)
+            .zip(&mut self.announced)
+            .zip(&mut self.idle_timers)
+            .zip(
+                self.acceleration_scales
+                    .iter()
+                    .zip(open_times)
+                    .zip(move_times),
+            )
+            .map(
+                |(
+                    ((((car, acceleration), travel), announced), idle_timer),
+                    ((&scale, open_time), dt),
+                )| {
+                    CarMove {
+                        car,
+                        acceleration,
+                        travel,
+                        announced,
+                        idle_timer,
+                        scale,
+                        open_time,
+                        dt,
+                    }
+                },
+            )
+            .collect();
+        let context = MoveContext {
+            config: &self.config,
+            topology: &self.state.topology,
+            emergency: self.state.emergency,
+        };
+        #[cfg(feature = "parallel")]
+        let moved: Vec<CarMoved> = if moves.len() >= PARALLEL_MIN_CARS {
+            moves
+                .into_par_iter()
+                .map(|m| move_car(m, &context))
+                .collect()
+        } else {
+            moves.into_iter().map(|m| move_car(m, &context)).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let moved: Vec<CarMoved> = moves.into_iter().map(|m| move_car(m, &context)).collect();
+
+        // then what the cars did is applied to the building in car order
+        for (i, car) in moved.into_iter().enumerate() {
+            let bank = self.state.cars[i].bank;
+            if let Some(floor_state) = car
+                .cleared
+                .and_then(|floor| self.state.floors.get_mut(floor as usize))
+            {
+                // a car answering an accessible call dwells longer, once its door is open
+                let accessible = std::mem::take(&mut floor_state.access_call);
+                floor_state.clear_bank_calls(bank);
+                if accessible {
+                    let accessible_dwell = self.config.accessible_dwell_time;
+                    match &mut self.state.cars[i].door {
+                        DoorState::Open { dwell_remaining } => {
+                            *dwell_remaining = dwell_remaining.max(accessible_dwell);
+                        }
+                        _ => {
+                            if let Some(accessible) = self.accessible_dwells.get_mut(i) {
+                                *accessible = true;
+                            }
+                        }
+                    }
+                }
+            }
+            for event in car.events {
+                if let ElevatorEvent::CarArriving { car_id, floor, .. } = event {
+                    self.light_lantern(car_id, floor);
+                }
+                self.push_event(event);
+            }
+        }
+
+        // a lantern goes out once its car has closed its door at the floor, or gone elsewhere
+        let cars = &self.state.cars;
+        for floor_state in &mut self.state.floors {
+            let floor = floor_state.floor;
+            floor_state.lanterns.retain(|lantern| {
+                cars.get(lantern.car_id.0 as usize).is_some_and(|car| {
+                    car.target_floor() == Some(floor)
+                        || (car.current_floor - floor as f32).abs() < 0.01
+                            && car.door != DoorState::Closed
+                })
+            });
+        }
+    }
+
+    /// Light car_id's lantern at floor as it's about to arrive, pointing the way it goes on
+    /// from there: towards its next stop, the trips it's been assigned from the floor, or the
+    /// one way people on the floor have called for
+    fn light_lantern(&mut self, car_id: CarId, floor: Floor) {
+        let Some(car) = self.state.cars.get(car_id.0 as usize) else {
+            return;
+        };
+        let Some(floor_state) = self.state.floors.get_mut(floor as usize) else {
+            return;
+        };
+        let towards = |to: Floor| match to.cmp(&floor) {
+            std::cmp::Ordering::Greater => Some(Direction::Up),
+            std::cmp::Ordering::Less => Some(Direction::Down),
+            std::cmp::Ordering::Equal => None,
+        };
+        let assigned = floor_state
+            .assignments
+            .iter()
+            .find(|a| a.car_id == car_id)
+            .map(|a| a.destination);
+        let called = match (floor_state.out_up, floor_state.out_down) {
+            (true, false) => Some(Direction::Up),
+            (false, true) => Some(Direction::Down),
+            _ => None,
+        };
+        let direction = car
+            .stops
+            .get(1)
+            .or(assigned.as_ref())
+            .and_then(|&to| towards(to))
+            .or(called);
+        floor_state.lanterns.retain(|l| l.car_id != car_id);
+        floor_state.lanterns.push(Lantern { car_id, direction });
+    }
+
+    /// Give each car the floors it serves now, as dedications begin and end. A car keeps the
+    /// stops it already has, so nobody inside is stranded
+    fn update_dedications(&mut self) {
+        if self.config.dedications.is_empty() {
+            return;
+        }
+        let floor_num = self.state.floors.len();
+        for i in 0..self.state.cars.len() {
+            let active = self
+                .config
+                .dedications
+                .iter()
+                .position(|d| d.car_id.0 as usize == i && d.active(self.time));
+            if active == self.dedicated[i] {
+                continue;
+            }
+            self.dedicated[i] = active;
+            let zone = match active {
+                Some(d) => Some(&self.config.dedications[d].floors),
+                None => zone(&self.config, &self.state.topology, i),
+            };
+            self.state.cars[i].served_floors = served_floors(zone, floor_num);
+        }
+    }
+
+    /// How long until something happens in the building if no commands are given: a car
+    /// announcing itself, arriving at its target floor, or a door timer running out. A car
+    /// speeding up or slowing down changes speed as it goes, so it's only let go for
+    /// KINEMATIC_STEP at a time, and a car at top speed until it has to start braking.
+    /// Returns None if every car is idle with nothing scheduled
+    pub fn time_to_next_event(&self) -> Option<f32> {
+        let mut next: Option<f32> = None;
+        let mut consider = |t: f32| next = Some(next.map_or(t, |n| n.min(t)));
+
+        for dedication in &self.config.dedications {
+            if let Some(change) = dedication.next_change(self.time) {
+                consider(change);
+            }
+        }
+
+        for remaining in self
+            .car_repairs
+            .iter()
+            .chain(&self.button_repairs)
+            .flatten()
+            .chain(
+                self.state
+                    .cars
+                    .iter()
+                    .filter_map(|car| car.reserved.as_ref()),
+            )
+        {
+            consider(remaining.max(0.));
+        }
+
+        for (i, car) in self.state.cars.iter().enumerate() {
+            if car.malfunction.is_some() {
+                continue;
+            }
+            let door_timer = match car.door {
+                DoorState::Closed => 0.,
+                DoorState::Opening { remaining } | DoorState::Closing { remaining } => remaining,
+                DoorState::Open { dwell_remaining } => dwell_remaining,
+            };
+            if let Some(transfer) = car.transfer {
+                consider((1. - transfer.progress()) * self.config.transfer_time);
+            } else if door_timer > 0. {
+                consider(door_timer);
+            } else if car.overloaded {
+                // the car goes nowhere until someone gets out
+            } else if let Some(target) = car.target_floor() {
+                let distance = (target as f32 - car.current_floor).abs();
+                // within arrival tolerance, the car arrives on the next tick
+                if distance < 0.01 {
+                    consider(0.);
+                } else {
+                    // a car can't get there faster than at top speed the whole way
+                    let metres =
+                        (self.state.topology.elevation(target as f32) - car.elevation).abs();
+                    let speed = self.config.car_speed(i);
+                    let arrival = metres / speed;
+                    consider(arrival);
+                    let acceleration = self.config.max_acceleration * self.acceleration_scales[i];
+                    if acceleration > 0. {
+                        let cruising = car.velocity.abs() >= speed * 0.999;
+                        let braking = stopping_distance(speed, acceleration, self.config.max_jerk);
+                        if cruising && metres > braking {
+                            consider((metres - braking) / speed);
+                        } else {
+                            consider(KINEMATIC_STEP);
+                        }
+                    }
+                    if self.announced[i] != Some(target) {
+                        consider((arrival - self.config.arrival_notice).max(0.));
+                    }
+                }
+            } else if car.service == ServiceState::OutOfService && car.car_buttons.contains(&true)
+                || car.vip_next().is_some()
+            {
+                // the car sets off for its riders' floors, or the VIP's, on the next tick
+                consider(0.);
+            } else if let Some(remaining) = self.idle_timers[i] {
+                consider(remaining.max(0.));
+            }
+        }
+
+        next
+    }
+
+    // return a referance to the entire building state, used in render and PeopleSim
+    pub fn state(&self) -> &BuildingState {
+        &self.state
+    }
+
+    /// set the time of day, which starts at midnight unless this is called
+    pub fn set_clock(&mut self, clock: SimClock) {
+        self.state.clock = clock;
+    }
+
+    /// return the config the building was created with
+    pub fn config(&self) -> &ElevatorConfig {
+        &self.config
+    }
+
+    /// remove and return every event recorded since the last call
+    pub fn take_events(&mut self) -> Vec<ElevatorEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Save everything about the building, including its config and any unread events, to a
+    /// JSON file
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        snapshot::save(path, self)
+    }
+
+    /// Load a building saved by save_snapshot, exactly as it was
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let building: Self = snapshot::load(path)?;
+        building.check_floors().map_err(SnapshotError::Floors)?;
+        Ok(building)
+    }
+
+    /// The building's floors, banks, shafts and entrances, shared with anything else built
+    /// from them
+    pub fn topology(&self) -> &Arc<BuildingTopology> {
+        &self.state.topology
+    }
+
+    /// Check every floor the topology and config name, for zones, dedications and starting
+    /// floors, is one the building has
+    pub fn check_floors(&self) -> Result<(), FloorMismatch> {
+        self.state.topology.check_floors()?;
+        let building = self.state.floors.len() as Floor;
+        let settings = [
+            (
+                "served_floors",
+                self.config.served_floors.iter().flatten().max(),
+            ),
+            (
+                "dedications",
+                self.config.dedications.iter().flat_map(|d| &d.floors).max(),
+            ),
+            ("starting_floors", self.config.starting_floors.iter().max()),
+        ];
+        for (setting, floor) in settings {
+            if let Some(&floor) = floor
+                && floor >= building
+            {
+                return Err(FloorMismatch::Setting {
+                    setting,
+                    floor,
+                    building,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// record an event, dropping the oldest if too many are waiting to be read
+    fn push_event(&mut self, event: ElevatorEvent) {
+        if self.events.len() >= MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// return a view of the statistics collected by the building, used by controllers
+    pub fn stats(&self) -> StatsView<'_> {
+        StatsView::new(&self.arrivals)
+    }
+}
+
+/// One car of an ElevatorSimBuilder's fleet. Anything left None takes the ElevatorConfig's
+/// setting, which every other car shares
+/// speed - the fastest the car travels, in metres per second
+/// capacity - how many people it holds
+/// weight_limit - how many kg it carries before its overload sensor goes off
+/// starting_floor - the floor it starts on
+/// doors - how long its door takes to open, close and dwell
+/// served_floors - the floors it stops at. Empty for every floor
+/// capabilities - what it's fitted for
+/// class - what class of car it is
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CarConfig {
+    pub speed: Option<f32>,
+    pub capacity: Option<u32>,
+    pub weight_limit: Option<f32>,
+    pub starting_floor: Floor,
+    pub doors: Option<DoorTiming>,
+    pub served_floors: Vec<Floor>,
+    pub capabilities: Option<CarCapabilities>,
+    pub class: Option<CarClass>,
+}
+
+/// Builds an ElevatorSim one car at a time, for fleets whose cars aren't all the same, such
+/// as a slow freight car working alongside fast express cars
+#[derive(Clone, Debug, Default)]
+pub struct ElevatorSimBuilder {
+    topology: BuildingTopology,
+    config: ElevatorConfig,
+    cars: Vec<CarConfig>,
+}
+
+/// implement functions for ElevatorSimBuilder
+/// new - start a building with some floors and no cars
+/// config - the settings every car shares
+/// topology, floor_heights - the shape of the building, or just how tall each floor is
+/// car, cars - add a car set up on its own, or some cars that take the shared settings
+/// build - create the building
+impl ElevatorSimBuilder {
+    /// Start a building with floors floors, no cars, and the default settings
+    pub fn new(floors: usize) -> Self {
+        Self {
+            topology: BuildingTopology::new(floors as Floor),
+            ..Self::default()
+        }
+    }
+
+    /// Use config for every setting the cars don't set themselves
+    pub fn config(mut self, config: ElevatorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Shape the building by topology, whose floors replace the number given to new
+    pub fn topology(mut self, topology: BuildingTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Set how tall each floor is, see BuildingTopology's heights
+    pub fn floor_heights(mut self, heights: Vec<f32>) -> Self {
+        self.topology.heights = heights;
+        self
+    }
+
+    /// Add a car, which gets the next car number
+    pub fn car(mut self, car: CarConfig) -> Self {
+        self.cars.push(car);
+        self
+    }
+
+    /// Add count cars which take the shared settings
+    pub fn cars(mut self, count: usize) -> Self {
+        self.cars
+            .extend(std::iter::repeat_n(CarConfig::default(), count));
+        self
+    }
+
+    /// Create the building, with each car's settings written into the config's by car
+    /// number lists
+    pub fn build(self) -> ElevatorSim {
+        let mut config = self.config;
+        let speed = config.max_speed;
+        let doors = config.shared_door_timing();
+        for (i, car) in self.cars.iter().enumerate() {
+            set_car(&mut config.car_speeds, i, car.speed, speed);
+            set_car(
+                &mut config.capacities,
+                i,
+                car.capacity,
+                DEFAULT_CAR_CAPACITY,
+            );
+            set_car(&mut config.weight_limits, i, car.weight_limit, 0.);
+            set_car(&mut config.starting_floors, i, Some(car.starting_floor), 0);
+            set_car(&mut config.door_timings, i, car.doors, doors);
+            let zone = (!car.served_floors.is_empty()).then(|| car.served_floors.clone());
+            set_car(&mut config.served_floors, i, zone, Vec::new());
+            set_car(
+                &mut config.capabilities,
+                i,
+                car.capabilities,
+                CarCapabilities::default(),
+            );
+            set_car(&mut config.classes, i, car.class, CarClass::Passenger);
+        }
+        ElevatorSim::with_topology(Arc::new(self.topology), self.cars.len(), config)
+    }
+}
+
+/// Set car's entry in one of ElevatorConfig's by car number lists, if there's a value for
+/// it, filling the list out to the car with fill
+fn set_car<T: Clone>(list: &mut Vec<T>, car: usize, value: Option<T>, fill: T) {
+    if list.len() <= car {
+        list.resize(car + 1, fill);
+    }
+    if let Some(value) = value {
+        list[car] = value;
+    }
+}
+
+/// Check the controller can send a car somewhere, which it can only in normal service
+fn check_dispatchable(car: &ElevatorCarState) -> Result<(), CommandError> {
+    let car_id = car.id;
+    if car.malfunction.is_some() {
+        return Err(CommandError::Broken { car_id });
+    }
+    match car.service {
+        ServiceState::Normal => Ok(()),
+        ServiceState::Independent => Err(CommandError::IndependentService { car_id }),
+        ServiceState::OutOfService => Err(CommandError::OutOfService { car_id }),
+        ServiceState::Vip { .. } => Err(CommandError::VipService { car_id }),
+    }
+}
+
+/// A time or rate, made up to jitter longer or shorter at random, as a share of it. No
+/// random numbers are drawn without jitter, so seeded runs without it are unchanged
+fn jittered(value: f32, jitter: f32) -> f32 {
+    if jitter <= 0. || value <= 0. {
+        return value;
+    }
+    let jitter = jitter.min(1.);
+    value * rng::random_range(Stream::Jitter, 1. - jitter..=1. + jitter)
+}
+
+/// One car's share of ElevatorSim, borrowed so it can move independently of the others.
+/// scale is the acceleration scale for its trip, open_time how long its door takes to open
+/// if it arrives this tick, and dt how long it has to move for, which is less than the tick
+/// if its door only finished closing partway through
+struct CarMove<'a> {
+    car: &'a mut ElevatorCarState,
+    acceleration: &'a mut f32,
+    travel: &'a mut Option<Direction>,
+    announced: &'a mut Option<Floor>,
+    idle_timer: &'a mut Option<f32>,
+    scale: f32,
+    open_time: Option<f32>,
+    dt: f32,
+}
+
+/// What every car moving in a tick needs to know about the building
+struct MoveContext<'a> {
+    config: &'a ElevatorConfig,
+    topology: &'a BuildingTopology,
+    emergency: EmergencyMode,
+}
+
+/// What a car did in a tick that the rest of the building has to hear about: the floor whose
+/// hall calls it answered, if it arrived, and its events
+struct CarMoved {
+    cleared: Option<Floor>,
+    events: Vec<ElevatorEvent>,
+}
+
+/// Move one car for a tick: towards its target if its door is closed, arriving and opening
+/// its door once it's there, and re-levelling it if it ever ends up outside the building
+fn move_car(m: CarMove, context: &MoveContext) -> CarMoved {
+    let CarMove {
+        car,
+        acceleration,
+        travel,
+        announced,
+        idle_timer,
+        scale,
+        open_time,
+        dt,
+    } = m;
+    let config = context.config;
+    let topology = context.topology;
+    let mut moved = CarMoved {
+        cleared: None,
+        events: Vec::new(),
+    };
+
+    // a car can't move until its door has closed, or while it's broken down
+    if car.door != DoorState::Closed && car.target_floor().is_some() || car.malfunction.is_some() {
+        return moved;
+    }
+
+    if let Some(target) = car.target_floor() {
+        //get the difference between its target and current location
+        let target_f = target as f32;
+        let diff = target_f - car.current_floor;
+        if diff.abs() < 0.01 {
+            // if the elevator is close to its target floor, say we're there and open the door
+            car.current_floor = target_f;
+            car.elevation = topology.elevation(target_f);
+            car.velocity = 0.;
+            *acceleration = 0.;
+            car.stops.pop_front();
+            let doors = config.door_timing(car.id.0 as usize);
+            let open_time = open_time.unwrap_or(doors.open_time);
+            car.door = if open_time > 0. {
+                DoorState::Opening {
+                    remaining: open_time,
+                }
+            } else {
+                DoorState::Open {
+                    dwell_remaining: doors.dwell_time,
+                }
+            };
+
+            let heading = travel.take();
+            moved.events.push(ElevatorEvent::CarArrived {
+                car_id: car.id,
+                floor: target,
+                direction: heading,
+            });
+            *announced = None;
+
+            // start counting down to closing the door, if the policy closes it. Cars in
+            // independent service wait for their operator with the door open, and recalled
+            // cars for the fire service. Cars out of service close theirs as soon as their
+            // riders are out
+            *idle_timer = match config.idle_door_policy {
+                _ if car.service == ServiceState::OutOfService => Some(0.),
+                _ if !car.dispatchable() => None,
+                _ if context.emergency != EmergencyMode::Normal => None,
+                policy => policy.idle_timer(),
+            };
+
+            // reset the outer buttons on the floor, unless nobody can get on
+            if !matches!(
+                car.service,
+                ServiceState::OutOfService | ServiceState::Vip { .. }
+            ) {
+                moved.cleared = Some(target);
+            }
+
+            // reset the button inside the elevator for this floor
+            if let Some(button) = car.car_buttons.get_mut(target as usize) {
+                *button = false;
+            }
+
+            // carry on the same way if there's more to do that way
+            car.direction = car.direction_after_stop(heading);
+        } else {
+            // move the elevator car down or up based on the direction it needs to move,
+            // speeding up and slowing down as the config allows
+            let direction = if diff > 0. {
+                Direction::Up
+            } else {
+                Direction::Down
+            };
+            // the car moves in metres, so floors of different heights take different times
+            let metres = topology.elevation(target_f) - car.elevation;
+            let motion = Motion {
+                max_speed: config.car_speed(car.id.0 as usize),
+                max_acceleration: config.max_acceleration * scale,
+                max_jerk: config.max_jerk,
+            };
+            let (velocity, new_acceleration) =
+                accelerate(&motion, car.velocity, *acceleration, metres, dt);
+            car.velocity = velocity;
+            *acceleration = new_acceleration;
+            // never go past the target, which would take a tick to come back from
+            let step = velocity * dt;
+            if step * metres.signum() >= metres.abs() {
+                car.current_floor = target_f;
+                car.elevation = topology.elevation(target_f);
+            } else {
+                car.elevation += step;
+                car.current_floor = topology.floor_at(car.elevation);
+            }
+            *travel = Some(direction);
+            car.direction = Some(direction);
+
+            // let people know the car is nearly there, once per target
+            let remaining = (topology.elevation(target_f) - car.elevation).abs() / motion.max_speed;
+            if remaining <= config.arrival_notice && *announced != Some(target) {
+                *announced = Some(target);
+                moved.events.push(ElevatorEvent::CarArriving {
+                    car_id: car.id,
+                    floor: target,
+                    direction,
+                });
+            }
+        }
+    }
+
+    // a car should never be outside the building, but if numerical error or a bad command
+    // puts it there, clamp it back inside, stop it, and re-level it at the nearest floor
+    // with its door closed
+    let position = car.current_floor;
+    let top = topology.top() as f32;
+    if !position.is_finite() || position < 0. || position > top {
+        let clamped = if position.is_finite() {
+            position.clamp(0., top)
+        } else {
+            0.
+        };
+        car.current_floor = clamped;
+        car.elevation = topology.elevation(clamped);
+        car.velocity = 0.;
+        *acceleration = 0.;
+        car.stops.push_front(clamped.round() as Floor);
+        car.door = DoorState::Closed;
+        moved.events.push(ElevatorEvent::PositionFault {
+            car_id: car.id,
+            position,
+        });
+    }
+    moved
+}
+
+/// How fast a car can go and how quickly it can change speed, in metres
+struct Motion {
+    max_speed: f32,
+    max_acceleration: f32,
+    max_jerk: f32,
+}
+
+/// Work out a moving car's new velocity and acceleration after dt seconds, heading for a
+/// target diff metres away. Without an acceleration limit the car moves at max_speed,
+/// otherwise it speeds up towards max_speed and slows down in time to stop at the target
+fn accelerate(motion: &Motion, velocity: f32, acceleration: f32, diff: f32, dt: f32) -> (f32, f32) {
+    let sign = diff.signum();
+    let max_accel = motion.max_acceleration;
+    if max_accel <= 0. {
+        return (motion.max_speed * sign, 0.);
+    }
+    if dt <= 0. {
+        return (velocity, acceleration);
+    }
+
+    // work along the direction of the target, so speed is negative when heading away
+    let speed = velocity * sign;
+    let desired = motion
+        .max_speed
+        .min(stopping_speed(diff.abs(), max_accel, motion.max_jerk));
+    let mut accel = ((desired - speed) / dt).clamp(-max_accel, max_accel);
+    if motion.max_jerk > 0. {
+        let previous = acceleration * sign;
+        let change = motion.max_jerk * dt;
+        accel = accel.clamp(previous - change, previous + change);
+    }
+    let speed = (speed + accel * dt).min(motion.max_speed);
+    (speed * sign, accel * sign)
+}
+
+/// The fastest a car can be travelling and still stop within distance metres. A jerk limit
+/// means braking has to build up, so the car needs to be going slower
+fn stopping_speed(distance: f32, acceleration: f32, jerk: f32) -> f32 {
+    if jerk > 0. {
+        let ramp = acceleration * acceleration / jerk;
+        (-ramp + (ramp * ramp + 8. * acceleration * distance).sqrt()) / 2.
+    } else {
+        (2. * acceleration * distance).sqrt()
+    }
+}
+
+/// How far a car travelling at speed needs to stop, the distance at which stopping_speed
+/// gives that speed
+fn stopping_distance(speed: f32, acceleration: f32, jerk: f32) -> f32 {
+    if jerk > 0. {
+        let ramp = acceleration * acceleration / jerk;
+        speed * (speed + ramp) / (2. * acceleration)
+    } else {
+        speed * speed / (2. * acceleration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CarId, Direction};
+
+    #[test]
+    fn press_out_button() {
+        let mut sim = ElevatorSim::new(3, 1);
+
+        sim.apply_command(ElevatorCommand::PressOutButton {
+            floor: 1,
+            direction: Direction::Up,
+            bank: None,
+        })
+        .unwrap();
+
+        assert!(sim.state().floors[1].out_up);
+        assert!(!sim.state().floors[1].out_down);
+        assert!(sim.stats().arrival_rate(1, Direction::Up) > 0.);
+    }
+
+    #[test]
+    fn pressing_a_lit_button_again_is_not_another_arrival() {
+        let mut sim = ElevatorSim::new(3, 1);
+        let press = ElevatorCommand::PressOutButton {
+            floor: 1,
+            direction: Direction::Up,
+            bank: None,
+        };
+        sim.apply_command(press.clone()).unwrap();
+        let once = sim.stats().arrival_rate(1, Direction::Up);
+        sim.apply_command(press).unwrap();
+        assert_eq!(sim.stats().arrival_rate(1, Direction::Up), once);
+
+        // the other way's button is a new call
+        sim.apply_command(ElevatorCommand::PressOutButton {
+            floor: 1,
+            direction: Direction::Down,
+            bank: None,
+        })
+        .unwrap();
+        assert!(sim.stats().arrival_rate(1, Direction::Down) > 0.);
+    }
+
+    #[test]
+    fn single_call_button_calls_for_either_way() {
+        let config = ElevatorConfig {
+            hall_buttons: HallButtons::Single,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.apply_command(ElevatorCommand::PressOutButton {
+            floor: 1,
+            direction: Direction::Down,
+            bank: None,
+        })
+        .unwrap();
+
+        // the building can't tell which way the caller is going
+        let floor = &sim.state().floors[1];
+        assert!(floor.out_call && !floor.out_up && !floor.out_down);
+        assert!(floor.hall_call(Direction::Up) && floor.hall_call(Direction::Down));
+        let stats = sim.stats();
+        assert_eq!(
+            stats.arrival_rate(1, Direction::Up),
+            stats.arrival_rate(1, Direction::Down)
+        );
+
+        // a car arriving answers the call
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 1,
+        })
+        .unwrap();
+        for _ in 0..20 {
+            sim.tick(0.1);
+        }
+        assert!(!sim.state().floors[1].called());
+    }
+
+    #[test]
+    fn banks_answer_their_own_hall_buttons() {
+        use crate::config::SimConfig;
+        use crate::people::PersonState;
+        use crate::topology::Bank;
+
+        // car 1 serves the low floors and car 0 the high ones, both from the lobby, and they
+        // share floor 4
+        let config = SimConfig {
+            floors: 10,
+            banks: vec![
+                Bank {
+                    name: "low".to_string(),
+                    cars: vec![CarId(1)],
+                    floors: vec![0, 1, 2, 3, 4],
+                },
+                Bank {
+                    name: "high".to_string(),
+                    cars: vec![CarId(0)],
+                    floors: vec![0, 4, 5, 6, 7, 8, 9],
+                },
+            ],
+            spawn_rate: 0.,
+            ..SimConfig::default()
+        };
+        let mut sim = config.build().unwrap();
+        let state = sim.building().state();
+        assert_eq!(state.cars[0].bank, Some(BankId(1)));
+        assert!(!state.cars[0].serves(3) && state.cars[0].serves(7));
+        assert_eq!(
+            sim.building_mut()
+                .apply_command(ElevatorCommand::PressOutButton {
+                    floor: 0,
+                    direction: Direction::Up,
+                    bank: Some(BankId(2)),
+                }),
+            Err(CommandError::UnknownBank { bank: BankId(2) })
+        );
+
+        // the high bank's button lights the lobby's, but not the low bank's
+        sim.building_mut()
+            .apply_command(ElevatorCommand::PressOutButton {
+                floor: 0,
+                direction: Direction::Up,
+                bank: Some(BankId(1)),
+            })
+            .unwrap();
+        let lobby = &sim.building().state().floors[0];
+        assert!(lobby.hall_call(Direction::Up) && lobby.bank_call(BankId(1), Direction::Up));
+        assert!(!lobby.bank_call(BankId(0), Direction::Up));
 
-        assert!(sim.state().floors[1].out_up);
-        assert!(!sim.state().floors[1].out_down);
+        // someone heading for floor 4 calls the low bank, the first which goes there, and
+        // waits for its car rather than getting into the high bank's, open for its call
+        let person = sim.add_person(0, 4);
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+        let person = sim.people().people().iter().find(|p| p.id == person);
+        assert_eq!(person.map(|p| p.state), Some(PersonState::Done));
+        let cars = &sim.building().state().cars;
+        assert_eq!((cars[0].current_floor, cars[1].current_floor), (0., 4.));
+        assert!(!sim.building().state().floors[0].called());
     }
 
     #[test]
@@ -182,7 +3133,8 @@ mod tests {
         sim.apply_command(ElevatorCommand::PressCarButton {
             car_id: CarId(0),
             floor: 2,
-        });
+        })
+        .unwrap();
 
         assert!(sim.state().cars[0].car_buttons[2])
     }
@@ -193,10 +3145,1000 @@ mod tests {
         sim.apply_command(ElevatorCommand::MoveCarTo {
             car_id: CarId(0),
             floor: 1,
-        });
+        })
+        .unwrap();
         sim.tick(1.0);
         let car = &sim.state().cars[0];
-        assert!(car.target_floor == Some(1));
+        assert!(car.target_floor() == Some(1));
         assert!(car.current_floor != 0.0);
     }
+
+    #[test]
+    fn move_car_out_of_range_rejected() {
+        let mut sim = ElevatorSim::new(3, 1);
+        let result = sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 999,
+        });
+
+        assert_eq!(result, Err(CommandError::FloorOutOfRange { floor: 999 }));
+        assert_eq!(sim.state().cars[0].target_floor(), None);
+        assert_eq!(
+            sim.take_events(),
+            vec![ElevatorEvent::CommandRejected {
+                error: CommandError::FloorOutOfRange { floor: 999 }
+            }]
+        );
+    }
+
+    #[test]
+    fn unserved_floor_rejected() {
+        let mut sim = ElevatorSim::new(3, 2);
+        sim.state.cars[1].served_floors[2] = false;
+
+        let result = sim.apply_command(ElevatorCommand::PressCarButton {
+            car_id: CarId(1),
+            floor: 2,
+        });
+        assert!(matches!(result, Err(CommandError::FloorNotServed { .. })));
+        assert!(!sim.state().cars[1].car_buttons[2]);
+
+        let result = sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(5),
+            floor: 0,
+        });
+        assert_eq!(result, Err(CommandError::UnknownCar { car_id: CarId(5) }));
+    }
+
+    #[test]
+    fn out_of_range_position_recovers() {
+        let mut sim = ElevatorSim::new(3, 1);
+        sim.state.cars[0].current_floor = 7.5;
+        sim.state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        sim.tick(0.1);
+
+        let car = &sim.state().cars[0];
+        assert_eq!(car.current_floor, 2.);
+        assert_eq!(car.target_floor(), Some(2));
+        assert!(!car.door_open());
+        assert!(matches!(
+            sim.take_events().as_slice(),
+            [ElevatorEvent::PositionFault { .. }]
+        ));
+
+        // the next tick re-levels the car and opens its door
+        sim.tick(0.1);
+        assert!(sim.state().cars[0].door_open());
+    }
+
+    #[test]
+    fn obstructed_door_holds_car() {
+        let config = ElevatorConfig {
+            door_obstruction_probability: 1.0,
+            door_reopen_time: 1.0,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+        for _ in 0..20 {
+            sim.tick(0.1);
+        }
+
+        // every close attempt is obstructed, so the car never leaves
+        let car = &sim.state().cars[0];
+        assert!(car.door_open());
+        assert_eq!(car.current_floor, 0.);
+        let obstructions = sim
+            .take_events()
+            .into_iter()
+            .filter(|e| matches!(e, ElevatorEvent::DoorObstructed { .. }))
+            .count();
+        assert!(obstructions >= 2);
+    }
+
+    #[test]
+    fn closing_door_reopens_until_nudged() {
+        let config = ElevatorConfig {
+            max_door_reopens: 1,
+            door_open_time: 1.0,
+            door_close_time: 1.0,
+            door_dwell_time: 1.0,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.state.cars[0].door = DoorState::Closing { remaining: 0.25 };
+
+        // three quarters closed, so the door takes three quarters of its open time to reopen
+        let reopen = ElevatorCommand::ReopenDoor { car_id: CarId(0) };
+        sim.apply_command(reopen.clone()).unwrap();
+        assert_eq!(
+            sim.state().cars[0].door,
+            DoorState::Opening { remaining: 0.75 }
+        );
+        sim.tick(0.8);
+        assert_eq!(
+            sim.state().cars[0].door,
+            DoorState::Open {
+                dwell_remaining: 1.0
+            }
+        );
+
+        // the second time, the door closes anyway
+        sim.state.cars[0].door = DoorState::Closing { remaining: 0.5 };
+        sim.apply_command(reopen.clone()).unwrap();
+        sim.apply_command(reopen.clone()).unwrap();
+        assert_eq!(
+            sim.state().cars[0].door,
+            DoorState::Closing { remaining: 0.5 }
+        );
+        let events = sim.take_events();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| matches!(e, ElevatorEvent::DoorNudged { .. }))
+                .count(),
+            1
+        );
+
+        // once it's closed, it may reopen again
+        sim.tick(0.6);
+        sim.state.cars[0].door = DoorState::Closing { remaining: 0.5 };
+        sim.apply_command(reopen).unwrap();
+        assert!(sim.state().cars[0].door_opening_or_open());
+    }
+
+    #[test]
+    fn overloaded_car_holds_its_door_open() {
+        let config = ElevatorConfig {
+            overload_sensor: true,
+            capacities: vec![1],
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        for person in 0..2 {
+            sim.apply_command(ElevatorCommand::Board {
+                car_id: CarId(0),
+                person_id: PersonId(person),
+                weight: 75.,
+            })
+            .unwrap();
+        }
+        assert!(sim.state().cars[0].overloaded);
+        assert_eq!(
+            sim.take_events(),
+            vec![ElevatorEvent::CarOverloaded {
+                car_id: CarId(0),
+                person_id: PersonId(1),
+            }]
+        );
+
+        // the car won't leave while it's overloaded
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+        for _ in 0..5 {
+            sim.tick(0.1);
+        }
+        assert!(sim.state().cars[0].door_open());
+        assert_eq!(sim.time_to_next_event(), None);
+
+        // until whoever set it off gets out again
+        sim.apply_command(ElevatorCommand::Alight {
+            car_id: CarId(0),
+            person_id: PersonId(1),
+            weight: 75.,
+        })
+        .unwrap();
+        assert!(!sim.state().cars[0].overloaded);
+        sim.tick(0.1);
+        assert!(!sim.state().cars[0].door_open());
+    }
+
+    #[test]
+    fn accessible_calls_hold_the_door_open_longer() {
+        let config = ElevatorConfig {
+            door_open_time: 1.,
+            door_dwell_time: 1.,
+            accessible_dwell_time: 5.,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.apply_command(ElevatorCommand::PressAccessButton {
+            floor: 2,
+            direction: Direction::Down,
+            bank: None,
+        })
+        .unwrap();
+        assert!(sim.state().floors[2].out_down && sim.state().floors[2].access_call);
+
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+        for _ in 0..200 {
+            sim.tick(0.1);
+            if matches!(sim.state().cars[0].door, DoorState::Open { .. }) {
+                break;
+            }
+        }
+        assert!(!sim.state().floors[2].access_call);
+        match sim.state().cars[0].door {
+            DoorState::Open { dwell_remaining } => assert!(dwell_remaining > 4.),
+            door => panic!("door is {door:?}"),
+        }
+    }
+
+    #[test]
+    fn vip_call_drops_off_riders_then_carries_the_vip_nonstop() {
+        let mut sim = ElevatorSim::new(6, 1);
+        sim.apply_command(ElevatorCommand::PressCarButton {
+            car_id: CarId(0),
+            floor: 1,
+        })
+        .unwrap();
+        sim.apply_command(ElevatorCommand::VipCall {
+            car_id: CarId(0),
+            floor: 3,
+        })
+        .unwrap();
+        // the controller can't send it anywhere now
+        let refused = sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 5,
+        });
+        assert_eq!(refused, Err(CommandError::VipService { car_id: CarId(0) }));
+        sim.apply_command(ElevatorCommand::PressOutButton {
+            floor: 2,
+            direction: Direction::Up,
+            bank: None,
+        })
+        .unwrap();
+
+        let mut stops = Vec::new();
+        let run = |sim: &mut ElevatorSim, stops: &mut Vec<Floor>| {
+            for _ in 0..500 {
+                sim.tick(0.1);
+                for event in sim.take_events() {
+                    if let ElevatorEvent::CarArrived { floor, .. } = event {
+                        stops.push(floor);
+                    }
+                }
+            }
+        };
+        run(&mut sim, &mut stops);
+        // the rider is let off first, and the hall call is passed by
+        assert_eq!(stops, vec![1, 3]);
+        assert!(sim.state().floors[2].out_up);
+        assert_eq!(
+            sim.state().cars[0].service,
+            ServiceState::Vip {
+                floor: 3,
+                stage: VipStage::Boarding,
+            }
+        );
+
+        // the VIP goes straight to their floor, and the car goes back to the controller
+        sim.apply_command(ElevatorCommand::PressCarButton {
+            car_id: CarId(0),
+            floor: 0,
+        })
+        .unwrap();
+        run(&mut sim, &mut stops);
+        assert_eq!(stops, vec![1, 3, 0]);
+        assert_eq!(sim.state().cars[0].service, ServiceState::Normal);
+    }
+
+    #[test]
+    fn idle_door_closes_after_dwell() {
+        let config = ElevatorConfig {
+            idle_door_policy: IdleDoorPolicy::CloseAfterDwell(0.5),
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 0,
+        })
+        .unwrap();
+
+        // arrive immediately, since the car is already there
+        sim.tick(0.1);
+        assert!(sim.state().cars[0].door_open());
+
+        for _ in 0..4 {
+            sim.tick(0.1);
+        }
+        assert!(sim.state().cars[0].door_open());
+
+        for _ in 0..3 {
+            sim.tick(0.1);
+        }
+        assert!(!sim.state().cars[0].door_open());
+    }
+
+    #[test]
+    fn door_cycles_through_states_before_car_moves() {
+        let config = ElevatorConfig {
+            door_open_time: 0.5,
+            door_dwell_time: 1.0,
+            door_close_time: 0.5,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 0,
+        })
+        .unwrap();
+        sim.tick(0.1);
+        assert!(matches!(
+            sim.state().cars[0].door,
+            DoorState::Opening { .. }
+        ));
+        assert!(!sim.state().cars[0].door_open());
+
+        for _ in 0..6 {
+            sim.tick(0.1);
+        }
+        assert!(sim.state().cars[0].door_open());
+
+        // the car is sent away, but waits out the dwell and the closing door
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+        for _ in 0..11 {
+            sim.tick(0.1);
+        }
+        assert!(matches!(
+            sim.state().cars[0].door,
+            DoorState::Closing { .. }
+        ));
+        assert_eq!(sim.state().cars[0].current_floor, 0.);
+
+        for _ in 0..8 {
+            sim.tick(0.1);
+        }
+        assert_eq!(sim.state().cars[0].door, DoorState::Closed);
+        assert!(sim.state().cars[0].current_floor > 0.);
+    }
+
+    #[test]
+    fn car_announces_itself_before_arriving() {
+        let mut sim = ElevatorSim::new(10, 1);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 9,
+        })
+        .unwrap();
+
+        let mut announced_at = None;
+        let mut events = Vec::new();
+        for _ in 0..120 {
+            sim.tick(0.1);
+            for event in sim.take_events() {
+                if matches!(event, ElevatorEvent::CarArriving { .. }) {
+                    announced_at = Some(sim.state().cars[0].current_floor);
+                }
+                events.push(event);
+            }
+        }
+
+        // three seconds out at one floor per second
+        assert!((announced_at.unwrap() - 6.).abs() < 0.2);
+        assert_eq!(
+            events,
+            vec![
+                ElevatorEvent::CarArriving {
+                    car_id: CarId(0),
+                    floor: 9,
+                    direction: Direction::Up,
+                },
+                ElevatorEvent::CarArrived {
+                    car_id: CarId(0),
+                    floor: 9,
+                    direction: Some(Direction::Up),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lantern_lights_before_arrival_and_goes_out_once_the_door_closes() {
+        let mut sim = ElevatorSim::new(10, 1);
+        sim.apply_command(ElevatorCommand::PressOutButton {
+            floor: 5,
+            direction: Direction::Down,
+            bank: None,
+        })
+        .unwrap();
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 5,
+        })
+        .unwrap();
+
+        while sim.state().floors[5].lanterns.is_empty() {
+            sim.tick(0.1);
+        }
+        // it lights before the door opens, pointing the way the floor called for
+        assert!(sim.state().cars[0].current_floor < 5.);
+        assert_eq!(
+            sim.state().floors[5].lanterns,
+            vec![Lantern {
+                car_id: CarId(0),
+                direction: Some(Direction::Down),
+            }]
+        );
+
+        // it stays lit while the door's open, and goes out when the car leaves
+        for _ in 0..50 {
+            sim.tick(0.1);
+        }
+        assert!(sim.state().cars[0].door_open());
+        assert_eq!(sim.state().floors[5].lanterns.len(), 1);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 0,
+        })
+        .unwrap();
+        for _ in 0..30 {
+            sim.tick(0.1);
+        }
+        assert_eq!(sim.state().cars[0].door, DoorState::Closed);
+        assert!(sim.state().floors[5].lanterns.is_empty());
+    }
+
+    #[test]
+    fn car_ramps_up_and_slows_into_floor() {
+        let config = ElevatorConfig {
+            max_speed: 2.,
+            max_acceleration: 1.,
+            max_jerk: 2.,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(10, 1, config);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 8,
+        })
+        .unwrap();
+
+        let mut speeds = Vec::new();
+        for _ in 0..200 {
+            sim.tick(0.1);
+            let car = &sim.state().cars[0];
+            assert!(car.current_floor <= 8.);
+            speeds.push(car.velocity);
+        }
+
+        // starts slowly, reaches top speed, and stops at the floor
+        assert!(speeds[0] > 0. && speeds[0] < 0.1);
+        assert!(speeds.iter().any(|&v| (v - 2.).abs() < 0.01));
+        assert!(speeds.iter().all(|&v| v <= 2.));
+        let car = &sim.state().cars[0];
+        assert_eq!(car.current_floor, 8.);
+        assert_eq!(car.velocity, 0.);
+        assert!(car.door_open());
+    }
+
+    #[test]
+    fn assignment_cleared_when_car_leaves() {
+        let mut sim = ElevatorSim::new(3, 2);
+        sim.apply_command(ElevatorCommand::AssignTrip {
+            floor: 0,
+            destination: 2,
+            car_id: CarId(1),
+        })
+        .unwrap();
+        assert_eq!(
+            sim.state().floors[0].assignments,
+            vec![TripAssignment {
+                destination: 2,
+                car_id: CarId(1)
+            }]
+        );
+
+        // the car opens its door at floor 0, then leaves for floor 2
+        sim.state.cars[1].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(1),
+            floor: 2,
+        })
+        .unwrap();
+        assert!(sim.state().floors[0].assignments.is_empty());
+    }
+
+    #[test]
+    fn full_car_rejects_boarding() {
+        let config = ElevatorConfig {
+            capacities: vec![2, 1],
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 3, config);
+        assert_eq!(sim.state().cars[0].capacity, 2);
+        assert_eq!(sim.state().cars[2].capacity, DEFAULT_CAR_CAPACITY);
+
+        let board = |person| ElevatorCommand::Board {
+            car_id: CarId(1),
+            person_id: PersonId(person),
+            weight: 75.,
+        };
+        sim.apply_command(board(1)).unwrap();
+        assert_eq!(sim.state().cars[1].passenger_count, 1);
+        assert_eq!(
+            sim.apply_command(board(2)),
+            Err(CommandError::CarFull { car_id: CarId(1) })
+        );
+
+        sim.apply_command(ElevatorCommand::Alight {
+            car_id: CarId(1),
+            person_id: PersonId(1),
+            weight: 75.,
+        })
+        .unwrap();
+        assert!(sim.apply_command(board(2)).is_ok());
+    }
+
+    #[test]
+    fn tracked_occupants_board_and_alight() {
+        let config = ElevatorConfig {
+            track_occupants: true,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        let board = ElevatorCommand::Board {
+            car_id: CarId(0),
+            person_id: PersonId(7),
+            weight: 75.,
+        };
+
+        // nobody can board through a closed door
+        assert_eq!(
+            sim.apply_command(board.clone()),
+            Err(CommandError::DoorClosed { car_id: CarId(0) })
+        );
+
+        sim.state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        sim.apply_command(board).unwrap();
+        assert_eq!(sim.state().cars[0].occupants, vec![PersonId(7)]);
+
+        sim.apply_command(ElevatorCommand::Alight {
+            car_id: CarId(0),
+            person_id: PersonId(7),
+            weight: 75.,
+        })
+        .unwrap();
+        assert!(sim.state().cars[0].occupants.is_empty());
+        assert!(matches!(
+            sim.apply_command(ElevatorCommand::Alight {
+                car_id: CarId(0),
+                person_id: PersonId(7),
+                weight: 75.,
+            }),
+            Err(CommandError::NotInCar { .. })
+        ));
+    }
+
+    #[test]
+    fn boarding_holds_door_until_done() {
+        let config = ElevatorConfig {
+            transfer_time: 1.0,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+
+        sim.apply_command(ElevatorCommand::Board {
+            car_id: CarId(0),
+            person_id: PersonId(0),
+            weight: 75.,
+        })
+        .unwrap();
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+        sim.tick(0.5);
+
+        let car = &sim.state().cars[0];
+        assert!(car.door_open());
+        assert_eq!(
+            car.transfer,
+            Some(PassengerTransfer::Boarding { progress: 0.5 })
+        );
+
+        sim.tick(0.5);
+        let car = &sim.state().cars[0];
+        assert_eq!(car.transfer, None);
+        assert!(!car.door_open());
+    }
+
+    #[test]
+    fn people_take_turns_in_the_doorway() {
+        let config = ElevatorConfig {
+            transfer_time: 1.0,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(3, 1, config);
+        sim.state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        sim.apply_command(ElevatorCommand::Alight {
+            car_id: CarId(0),
+            person_id: PersonId(0),
+            weight: 75.,
+        })
+        .unwrap();
+        for person in 1..3 {
+            sim.apply_command(ElevatorCommand::Board {
+                car_id: CarId(0),
+                person_id: PersonId(person),
+                weight: 75.,
+            })
+            .unwrap();
+        }
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+
+        // the rider gets off first, then the two waiting get on one after the other
+        sim.tick(1.);
+        assert_eq!(
+            sim.state().cars[0].transfer,
+            Some(PassengerTransfer::Boarding { progress: 0. })
+        );
+        sim.tick(1.);
+        sim.tick(0.5);
+        let car = &sim.state().cars[0];
+        assert!(car.door_open());
+        assert_eq!(
+            car.transfer,
+            Some(PassengerTransfer::Boarding { progress: 0.5 })
+        );
+        sim.tick(0.5);
+        assert!(!sim.state().cars[0].door_open());
+    }
+
+    #[test]
+    fn car_commits_to_the_way_its_stops_are() {
+        let mut sim = ElevatorSim::new(10, 1);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 5,
+        })
+        .unwrap();
+        sim.apply_command(ElevatorCommand::PressCarButton {
+            car_id: CarId(0),
+            floor: 8,
+        })
+        .unwrap();
+        sim.tick(0.1);
+        assert_eq!(sim.state().cars[0].direction, Some(Direction::Up));
+
+        // stopping at 5 with 8 still to go, the car keeps heading up
+        for _ in 0..100 {
+            sim.tick(0.1);
+        }
+        assert_eq!(sim.state().cars[0].current_floor, 5.);
+        assert_eq!(sim.state().cars[0].direction, Some(Direction::Up));
+
+        // the controller can turn it around while it's stopped
+        sim.apply_command(ElevatorCommand::SetCarDirection {
+            car_id: CarId(0),
+            direction: Some(Direction::Down),
+        })
+        .unwrap();
+        assert_eq!(sim.state().cars[0].direction, Some(Direction::Down));
+
+        // once its last stop is done it can go either way
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 8,
+        })
+        .unwrap();
+        for _ in 0..100 {
+            sim.tick(0.1);
+        }
+        assert_eq!(sim.state().cars[0].current_floor, 8.);
+        assert_eq!(sim.state().cars[0].direction, None);
+    }
+
+    #[test]
+    fn car_works_through_its_stops_in_order() {
+        let mut sim = ElevatorSim::new(10, 1);
+        for floor in [3, 6, 3] {
+            sim.apply_command(ElevatorCommand::AddStop {
+                car_id: CarId(0),
+                floor,
+            })
+            .unwrap();
+        }
+        assert_eq!(sim.state().cars[0].stops, VecDeque::from([3, 6]));
+
+        // one plan takes the car to both floors, stopping at each
+        let mut arrivals = Vec::new();
+        for _ in 0..150 {
+            sim.tick(0.1);
+            for event in sim.take_events() {
+                if let ElevatorEvent::CarArrived { floor, .. } = event {
+                    arrivals.push(floor);
+                }
+            }
+        }
+        assert_eq!(arrivals, vec![3, 6]);
+        assert!(sim.state().cars[0].stops.is_empty());
+
+        let replace = |stops| ElevatorCommand::ReplaceStops {
+            car_id: CarId(0),
+            stops,
+        };
+        assert_eq!(
+            sim.apply_command(replace(vec![2, 12])),
+            Err(CommandError::FloorOutOfRange { floor: 12 })
+        );
+        sim.apply_command(replace(vec![2, 0])).unwrap();
+        for _ in 0..15 {
+            sim.tick(0.1);
+        }
+
+        // cleared between floors, the car stops at the next one down
+        sim.apply_command(ElevatorCommand::ClearStops { car_id: CarId(0) })
+            .unwrap();
+        assert_eq!(sim.state().cars[0].stops, VecDeque::from([4]));
+        for _ in 0..30 {
+            sim.tick(0.1);
+        }
+        assert_eq!(sim.state().cars[0].current_floor, 4.);
+        assert!(sim.state().cars[0].stops.is_empty());
+    }
+
+    #[test]
+    fn cars_remember_their_latest_commands() {
+        let config = ElevatorConfig {
+            command_history: 2,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(5, 2, config);
+        let move_to = |car, floor| ElevatorCommand::MoveCarTo {
+            car_id: CarId(car),
+            floor,
+        };
+        sim.apply_command(move_to(0, 3)).unwrap();
+        sim.tick(0.5);
+        sim.apply_command(move_to(1, 2)).unwrap();
+        assert!(sim.apply_command(move_to(0, 9)).is_err());
+        sim.apply_command(move_to(0, 4)).unwrap();
+
+        // only the last two commands to car 0 are kept, refused ones included
+        let history: Vec<_> = sim.command_history(CarId(0)).collect();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].command, move_to(0, 9));
+        assert_eq!(
+            history[0].error,
+            Some(CommandError::FloorOutOfRange { floor: 9 })
+        );
+        assert_eq!((history[1].time, history[1].error.clone()), (0.5, None));
+        assert_eq!(sim.command_history(CarId(1)).count(), 1);
+        assert_eq!(sim.command_history(CarId(7)).count(), 0);
+    }
+
+    #[test]
+    fn dedicated_cars_only_go_to_their_floors_while_kept() {
+        let config = ElevatorConfig {
+            dedications: vec![Dedication {
+                car_id: CarId(1),
+                floors: vec![0, 4],
+                start: 10.,
+                end: 20.,
+                period: 0.,
+            }],
+            ..ElevatorConfig::default()
+        };
+        let mut sim = ElevatorSim::with_config(5, 2, config);
+        let move_to = |car, floor| ElevatorCommand::MoveCarTo {
+            car_id: CarId(car),
+            floor,
+        };
+        assert!(sim.state().cars[1].serves(2));
+        assert_eq!(sim.time_to_next_event(), Some(10.));
+
+        for _ in 0..100 {
+            sim.tick(0.1);
+        }
+        sim.tick(0.05);
+        assert!(!sim.state().cars[1].serves(2));
+        assert_eq!(
+            sim.apply_command(move_to(1, 2)),
+            Err(CommandError::FloorNotServed {
+                car_id: CarId(1),
+                floor: 2,
+            })
+        );
+        sim.apply_command(move_to(1, 4)).unwrap();
+        sim.apply_command(move_to(0, 2)).unwrap();
+
+        // once it's over the car goes back to serving every floor
+        for _ in 0..100 {
+            sim.tick(0.1);
+        }
+        assert!(sim.state().cars[1].serves(2));
+
+        let daily = Dedication {
+            car_id: CarId(0),
+            floors: vec![0],
+            start: 8.,
+            end: 10.,
+            period: 24.,
+        };
+        assert!(daily.active(33.) && !daily.active(35.));
+        assert_eq!(daily.next_change(35.), Some(21.));
+    }
+
+    #[test]
+    fn jitter_breaks_up_identical_cars() {
+        // run two identical cars on the same trip, returning where each is part way there
+        let positions = |jitter: f32| {
+            rng::seed(3);
+            let config = ElevatorConfig {
+                max_acceleration: 1.,
+                door_time_jitter: jitter,
+                acceleration_jitter: jitter,
+                ..ElevatorConfig::default()
+            };
+            let mut sim = ElevatorSim::with_config(10, 2, config);
+            for car in 0..2 {
+                sim.apply_command(ElevatorCommand::MoveCarTo {
+                    car_id: CarId(car),
+                    floor: 8,
+                })
+                .unwrap();
+            }
+            for _ in 0..20 {
+                sim.tick(0.1);
+            }
+            let cars = &sim.state().cars;
+            (cars[0].current_floor, cars[1].current_floor)
+        };
+
+        let (first, second) = positions(0.);
+        assert_eq!(first, second);
+        let (first, second) = positions(0.5);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn many_jittered_cars_repeat_exactly() {
+        // enough cars to be moved on several threads with the parallel feature
+        let run = || {
+            rng::seed(11);
+            let config = ElevatorConfig {
+                max_acceleration: 1.,
+                door_time_jitter: 0.5,
+                acceleration_jitter: 0.5,
+                ..ElevatorConfig::default()
+            };
+            let mut sim = ElevatorSim::with_config(30, 40, config);
+            let mut events = Vec::new();
+            for step in 0..200 {
+                if step % 50 == 0 {
+                    for car in 0..40 {
+                        let _ = sim.apply_command(ElevatorCommand::MoveCarTo {
+                            car_id: CarId(car),
+                            floor: (car * 7 + step) as Floor % 30,
+                        });
+                    }
+                }
+                sim.tick(0.1);
+                events.extend(sim.take_events());
+            }
+            (sim.state().clone(), events)
+        };
+
+        let (state, events) = run();
+        assert!(!events.is_empty());
+        assert_eq!(run(), (state, events));
+    }
+
+    #[test]
+    fn builder_sets_each_car_up_on_its_own() {
+        let doors = DoorTiming {
+            open_time: 1.,
+            close_time: 1.,
+            dwell_time: 0.,
+        };
+        let mut sim = ElevatorSimBuilder::new(6)
+            .floor_heights(vec![7.])
+            .car(CarConfig {
+                speed: Some(7.),
+                doors: Some(doors),
+                ..CarConfig::default()
+            })
+            .car(CarConfig {
+                capacity: Some(4),
+                starting_floor: 3,
+                served_floors: vec![0, 3, 4, 5],
+                ..CarConfig::default()
+            })
+            .cars(1)
+            .build();
+        let cars = &sim.state().cars;
+        assert_eq!(cars.len(), 3);
+        assert_eq!(cars[1].current_floor, 3.);
+        assert_eq!(cars[1].capacity, 4);
+        assert!(!cars[1].serves(1));
+        assert_eq!(cars[2].capacity, DEFAULT_CAR_CAPACITY);
+        assert!(cars[2].serves(1));
+
+        for (car, floor) in [(0, 2), (1, 4)] {
+            sim.apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(car),
+                floor,
+            })
+            .unwrap();
+        }
+        for _ in 0..12 {
+            sim.tick(0.1);
+        }
+        // the fast car is held up by the tall lobby, which the other car never goes through
+        let cars = &sim.state().cars;
+        assert!(cars[0].current_floor > 1. && cars[0].current_floor < 2.);
+        assert!(cars[1].door_open());
+        for _ in 0..8 {
+            sim.tick(0.1);
+        }
+        let car = &sim.state().cars[0];
+        assert_eq!(car.current_floor, 2.);
+        assert!(matches!(car.door, DoorState::Opening { .. }));
+    }
+
+    #[test]
+    fn floors_of_different_heights_convert_to_metres() {
+        let topology = BuildingTopology {
+            heights: vec![5., 2.5],
+            ..BuildingTopology::new(4)
+        };
+
+        // a car covers the tall lobby and the low mezzanine at the same speed in metres
+        let mut sim = ElevatorSim::with_topology(Arc::new(topology), 1, ElevatorConfig::default());
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 2,
+        })
+        .unwrap();
+        let mut ticks = 0;
+        while !sim.state().cars[0].door_open() {
+            sim.tick(0.1);
+            ticks += 1;
+        }
+        let car = &sim.state().cars[0];
+        assert_eq!((car.current_floor, car.elevation), (2., 7.5));
+        assert!((21..=23).contains(&ticks));
+    }
 }
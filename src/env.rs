@@ -0,0 +1,220 @@
+use crate::config::{ConfigError, SimConfig};
+use crate::control::ElevatorController;
+use crate::elevator::ElevatorCommand;
+use crate::features::{encode_state, feature_len};
+use crate::metrics::StatsView;
+use crate::people::PersonState;
+use crate::sim::Simulation;
+use crate::types::{CarId, Floor};
+use crate::view::BuildingView;
+
+/// Settings for a reinforcement learning environment
+/// sim - the building, traffic and run length of every episode. An episode lasts sim.steps
+/// ticks of sim.timestep seconds. If sim.seed is set, episode n is seeded with seed + n, so
+/// training runs repeat exactly while each episode still sees different people
+/// ticks_per_step - how many ticks pass between the agent's decisions
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvConfig {
+    pub sim: SimConfig,
+    pub ticks_per_step: u32,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            sim: SimConfig::default(),
+            ticks_per_step: 1,
+        }
+    }
+}
+
+/// A controller which does nothing, since the agent gives the commands
+struct AgentControlled;
+
+impl ElevatorController for AgentControlled {
+    fn tick(&mut self, _view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
+        Vec::new()
+    }
+}
+
+/// A gym style environment for training dispatchers. Observations are the feature vectors
+/// of features::encode_state, of whatever the config's observation settings let a
+/// controller see. Each action gives every car a floor to go to, or None to leave it be.
+/// The reward for a step is minus the seconds everyone spent waiting for a car during it,
+/// so an episode's rewards add up to minus the total wait. People who give up stop counting
+/// once they've gone
+pub struct ElevatorEnv {
+    config: EnvConfig,
+    sim: Simulation,
+    ticks: u32,
+    episodes: u64,
+}
+
+/// implement functions for ElevatorEnv
+/// new - create an environment, ready for its first episode
+/// reset - start a new episode
+/// step - give the cars their floors and run until the next decision
+/// observation - the current observation
+/// observation_len, action_len - how long observations and actions are
+/// simulation - the simulation behind the current episode
+impl ElevatorEnv {
+    /// Create an environment. Fails if the config doesn't describe a valid simulation
+    pub fn new(config: EnvConfig) -> Result<Self, ConfigError> {
+        let sim = Self::episode(&config, 0)?;
+        Ok(Self {
+            config,
+            sim,
+            ticks: 0,
+            episodes: 0,
+        })
+    }
+
+    /// Build the simulation for episode number episode
+    fn episode(config: &EnvConfig, episode: u64) -> Result<Simulation, ConfigError> {
+        let sim_config = SimConfig {
+            seed: config.sim.seed.map(|seed| seed + episode),
+            ..config.sim.clone()
+        };
+        let mut sim = sim_config.build()?;
+        sim.set_controller(Box::new(AgentControlled));
+        Ok(sim)
+    }
+
+    /// Start a new episode with fresh people, returning its first observation
+    pub fn reset(&mut self) -> Vec<f32> {
+        self.episodes += 1;
+        // the config was valid when the environment was created, so it still is
+        if let Ok(sim) = Self::episode(&self.config, self.episodes) {
+            self.sim = sim;
+        }
+        self.ticks = 0;
+        self.observation()
+    }
+
+    /// Send each car to the floor its action gives, if any, then run for ticks_per_step
+    /// ticks. Returns the next observation, the reward, and whether the episode is over.
+    /// Actions past the number of cars are ignored, and cars without one are left be.
+    /// Floors a car can't go to are refused by the building, as any controller's would be
+    pub fn step(&mut self, actions: &[Option<Floor>]) -> (Vec<f32>, f32, bool) {
+        let building = self.sim.building_mut();
+        let num_cars = building.state().cars.len();
+        for (car, &floor) in actions.iter().enumerate().take(num_cars) {
+            let Some(floor) = floor else {
+                continue;
+            };
+            if building.state().cars[car].target_floor() != Some(floor) {
+                let _ = building.apply_command(ElevatorCommand::MoveCarTo {
+                    car_id: CarId(car as u32),
+                    floor,
+                });
+            }
+        }
+
+        let dt = self.config.sim.timestep;
+        let mut reward = 0.;
+        for _ in 0..self.config.ticks_per_step {
+            if self.done() {
+                break;
+            }
+            self.sim.step(dt);
+            self.ticks += 1;
+            let waiting = self
+                .sim
+                .people()
+                .people()
+                .iter()
+                .filter(|p| matches!(p.state, PersonState::New | PersonState::Waiting))
+                .count();
+            reward -= waiting as f32 * dt;
+        }
+        (self.observation(), reward, self.done())
+    }
+
+    /// Whether the episode has run for all its ticks
+    fn done(&self) -> bool {
+        self.ticks >= self.config.sim.steps
+    }
+
+    /// The building as the agent sees it now
+    pub fn observation(&self) -> Vec<f32> {
+        let filter = self.config.sim.observation_filter();
+        let view = filter.view(self.sim.building().state(), self.sim.people().people());
+        let stats = filter.stats(self.sim.building().stats());
+        encode_state(&view, &stats)
+    }
+
+    /// How many numbers are in every observation
+    pub fn observation_len(&self) -> usize {
+        feature_len(self.config.sim.floors as usize, self.config.sim.cars)
+    }
+
+    /// How many entries an action has, one per car
+    pub fn action_len(&self) -> usize {
+        self.config.sim.cars
+    }
+
+    /// The simulation behind the current episode, to look at or render
+    pub fn simulation(&self) -> &Simulation {
+        &self.sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn episodes_run_to_the_end_and_punish_waiting() {
+        let config = EnvConfig {
+            sim: SimConfig {
+                floors: 6,
+                cars: 2,
+                steps: 300,
+                spawn_rate: 1.,
+                seed: Some(7),
+                ..SimConfig::default()
+            },
+            ticks_per_step: 10,
+        };
+        let mut env = ElevatorEnv::new(config).unwrap();
+        let first = env.reset();
+        assert_eq!(first.len(), env.observation_len());
+        assert_eq!(env.action_len(), 2);
+
+        // an agent which never moves the cars leaves everyone waiting
+        let mut idle = 0.;
+        let mut steps = 0;
+        loop {
+            let (observation, reward, done) = env.step(&[None, None]);
+            assert_eq!(observation.len(), env.observation_len());
+            assert!(reward <= 0.);
+            idle += reward;
+            steps += 1;
+            if done {
+                break;
+            }
+        }
+        assert_eq!(steps, 30);
+        assert!(idle < 0.);
+
+        // the same agent on the same episode, but sending the cars to every lit button
+        let mut env = ElevatorEnv::new(env.config.clone()).unwrap();
+        env.reset();
+        let mut busy = 0.;
+        let mut done = false;
+        while !done {
+            let state = env.simulation().building().state();
+            let called: Vec<Floor> = state
+                .floors
+                .iter()
+                .filter(|f| f.called())
+                .map(|f| f.floor)
+                .collect();
+            let actions: Vec<Option<Floor>> = (0..2).map(|i| called.get(i).copied()).collect();
+            let (_, reward, finished) = env.step(&actions);
+            busy += reward;
+            done = finished;
+        }
+        assert!(busy > idle);
+    }
+}
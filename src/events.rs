@@ -0,0 +1,387 @@
+use crate::elevator::{BuildingState, DoorState, ElevatorCommand, ElevatorEvent};
+use crate::people::{Person, PersonState};
+use crate::types::{CarId, Floor, PersonId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Something that happened in a simulation
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum SimEvent {
+    /// tracing began, with the building as it was then
+    Started { state: BuildingState },
+    /// someone appeared on origin, wanting to go to destination
+    PersonSpawned {
+        person: PersonId,
+        origin: Floor,
+        destination: Floor,
+    },
+    /// someone got into a car
+    PersonBoarded {
+        person: PersonId,
+        car: CarId,
+        floor: Floor,
+    },
+    /// someone got out of a car
+    PersonAlighted {
+        person: PersonId,
+        car: CarId,
+        floor: Floor,
+    },
+    /// someone stopped waiting and took the stairs
+    PersonGaveUp { person: PersonId, floor: Floor },
+    /// a car's door finished opening
+    DoorOpened { car: CarId, floor: Floor },
+    /// a car's door finished closing
+    DoorClosed { car: CarId, floor: Floor },
+    /// a car that was standing still began to move, position floors up the shaft
+    CarStarted { car: CarId, position: f32 },
+    /// a moving car came to a stop, position floors up the shaft
+    CarStopped { car: CarId, position: f32 },
+    /// the building accepted a command from the controller or from someone pressing a button
+    CommandApplied { command: ElevatorCommand },
+    /// anything the building reported itself, such as a car arriving or a command being
+    /// refused
+    Building(ElevatorEvent),
+}
+
+/// An event, time seconds into the run, during the tick-th tick
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TracedEvent {
+    pub time: f32,
+    pub tick: u64,
+    pub event: SimEvent,
+}
+
+/// Somewhere the events of a run go, as they happen. Sinks are Send, so a simulation
+/// tracing its events can be run on another thread
+pub trait EventSink: Send {
+    /// Take in one event
+    fn emit(&mut self, event: &TracedEvent);
+
+    /// Finish writing everything taken in, reporting anything that went wrong on the way.
+    /// By default there's nothing to finish
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Prints every event on its own line, for following a run as it goes
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn emit(&mut self, event: &TracedEvent) {
+        println!("{:>9.2} s  {:?}", event.time, event.event);
+    }
+}
+
+/// Keeps the latest capacity events in memory. Clones share the same buffer, so a clone
+/// kept aside can read what the simulation's copy took in
+#[derive(Clone, Debug)]
+pub struct RingBuffer {
+    capacity: usize,
+    events: Arc<Mutex<VecDeque<TracedEvent>>>,
+}
+
+/// implement functions for RingBuffer
+/// new - create an empty buffer
+/// events - the events held, oldest first
+impl RingBuffer {
+    /// A buffer which holds on to the latest capacity events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// The events held, oldest first
+    pub fn events(&self) -> Vec<TracedEvent> {
+        match self.events.lock() {
+            Ok(events) => events.iter().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+impl EventSink for RingBuffer {
+    fn emit(&mut self, event: &TracedEvent) {
+        let Ok(mut events) = self.events.lock() else {
+            return;
+        };
+        if self.capacity == 0 {
+            return;
+        }
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+    }
+}
+
+/// Writes every event to a file as one line of JSON, for processing after the run. If
+/// writing fails, the rest of the events are dropped and finish reports the error
+pub struct JsonlSink {
+    writer: BufWriter<File>,
+    error: Option<io::Error>,
+}
+
+impl JsonlSink {
+    /// Create or overwrite the file at path
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            error: None,
+        })
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn emit(&mut self, event: &TracedEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let written = serde_json::to_writer(&mut self.writer, event)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"));
+        if let Err(e) = written {
+            self.error = Some(e);
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// Turns what happens in a simulation into events for a sink. People, doors and cars are
+/// compared with how they were last time, so their events are found without PeopleSim or
+/// ElevatorSim having to report them
+pub(crate) struct Tracer<S: EventSink + ?Sized = dyn EventSink> {
+    sink: Box<S>,
+    people: HashMap<PersonId, (PersonState, Option<CarId>)>,
+    doors_open: Vec<Option<bool>>,
+    moving: Vec<Option<bool>>,
+}
+
+impl<S: EventSink + ?Sized> Tracer<S> {
+    /// Start tracing into sink
+    pub(crate) fn new(sink: Box<S>) -> Self {
+        Self {
+            sink,
+            people: HashMap::new(),
+            doors_open: Vec::new(),
+            moving: Vec::new(),
+        }
+    }
+
+    /// Send an event to the sink
+    pub(crate) fn emit(&mut self, time: f32, tick: u64, event: SimEvent) {
+        self.sink.emit(&TracedEvent { time, tick, event });
+    }
+
+    /// Emit an event for every person, door and car that has changed since the last call
+    pub(crate) fn observe(
+        &mut self,
+        time: f32,
+        tick: u64,
+        people: &[Person],
+        state: &BuildingState,
+    ) {
+        for person in people {
+            let before = self.people.insert(person.id, (person.state, person.in_car));
+            let (was, was_in) = match before {
+                Some(before) => before,
+                None => {
+                    self.emit(
+                        time,
+                        tick,
+                        SimEvent::PersonSpawned {
+                            person: person.id,
+                            origin: person.current_floor,
+                            destination: person.destination(),
+                        },
+                    );
+                    (PersonState::New, None)
+                }
+            };
+            if was == person.state {
+                continue;
+            }
+            let floor = person.current_floor;
+            if was == PersonState::Riding
+                && let Some(car) = was_in
+            {
+                self.emit(
+                    time,
+                    tick,
+                    SimEvent::PersonAlighted {
+                        person: person.id,
+                        car,
+                        floor,
+                    },
+                );
+            }
+            match person.state {
+                PersonState::Riding => {
+                    if let Some(car) = person.in_car {
+                        self.emit(
+                            time,
+                            tick,
+                            SimEvent::PersonBoarded {
+                                person: person.id,
+                                car,
+                                floor,
+                            },
+                        );
+                    }
+                }
+                PersonState::GaveUp => self.emit(
+                    time,
+                    tick,
+                    SimEvent::PersonGaveUp {
+                        person: person.id,
+                        floor,
+                    },
+                ),
+                _ => {}
+            }
+        }
+
+        self.doors_open.resize(state.cars.len(), None);
+        for (car, was_open) in state.cars.iter().zip(self.doors_open.iter_mut()) {
+            let open = match car.door {
+                DoorState::Open { .. } => true,
+                DoorState::Closed => false,
+                // a door on the move keeps whatever it last finished doing
+                DoorState::Opening { .. } | DoorState::Closing { .. } => continue,
+            };
+            if was_open.replace(open).is_none_or(|was| was == open) {
+                continue;
+            }
+            let floor = car.current_floor.round() as Floor;
+            let event = if open {
+                SimEvent::DoorOpened { car: car.id, floor }
+            } else {
+                SimEvent::DoorClosed { car: car.id, floor }
+            };
+            self.sink.emit(&TracedEvent { time, tick, event });
+        }
+
+        self.moving.resize(state.cars.len(), None);
+        for (car, was_moving) in state.cars.iter().zip(self.moving.iter_mut()) {
+            let moving = car.velocity != 0.;
+            if was_moving.replace(moving).is_none_or(|was| was == moving) {
+                continue;
+            }
+            let position = car.current_floor;
+            let event = if moving {
+                SimEvent::CarStarted {
+                    car: car.id,
+                    position,
+                }
+            } else {
+                SimEvent::CarStopped {
+                    car: car.id,
+                    position,
+                }
+            };
+            self.sink.emit(&TracedEvent { time, tick, event });
+        }
+    }
+
+    /// The sink being traced into
+    pub(crate) fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Finish the sink
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::BasicController;
+    use crate::elevator::ElevatorSim;
+    use crate::people::{PeopleConfig, PeopleSim};
+    use crate::sim::Simulation;
+    use crate::types::ScriptedArrival;
+
+    #[test]
+    fn trace_follows_a_trip_into_every_sink() {
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 2,
+            destination: 0,
+        }];
+        let people = PeopleSim::scripted(4, arrivals, PeopleConfig::default());
+        let mut sim = Simulation::new(people, ElevatorSim::new(4, 1), Box::new(BasicController));
+        let buffer = RingBuffer::new(1000);
+        sim.start_trace(Box::new(buffer.clone()));
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+        sim.finish_trace().unwrap();
+
+        let events: Vec<SimEvent> = buffer.events().into_iter().map(|e| e.event).collect();
+        let position = |wanted: &dyn Fn(&SimEvent) -> bool| {
+            events
+                .iter()
+                .position(wanted)
+                .unwrap_or_else(|| panic!("missing from {events:#?}"))
+        };
+        let spawned = position(&|e| matches!(e, SimEvent::PersonSpawned { origin: 2, .. }));
+        let called = position(&|e| matches!(e, SimEvent::CommandApplied { .. }));
+        let arrived = position(&|e| {
+            matches!(
+                e,
+                SimEvent::Building(ElevatorEvent::CarArrived { floor: 2, .. })
+            )
+        });
+        let opened = position(&|e| matches!(e, SimEvent::DoorOpened { floor: 2, .. }));
+        let boarded = position(&|e| matches!(e, SimEvent::PersonBoarded { floor: 2, .. }));
+        let closed = position(&|e| matches!(e, SimEvent::DoorClosed { floor: 2, .. }));
+        let alighted = position(&|e| matches!(e, SimEvent::PersonAlighted { floor: 0, .. }));
+        assert!(spawned < called && called < arrived && arrived < opened);
+        assert!(opened < boarded && boarded < closed && closed < alighted);
+
+        // a small buffer only keeps the latest events
+        let mut small = RingBuffer::new(2);
+        for tick in 0..5 {
+            small.emit(&TracedEvent {
+                time: tick as f32,
+                tick,
+                event: SimEvent::PersonGaveUp {
+                    person: PersonId(0),
+                    floor: 0,
+                },
+            });
+        }
+        let ticks: Vec<u64> = small.events().iter().map(|e| e.tick).collect();
+        assert_eq!(ticks, vec![3, 4]);
+
+        // the JSON lines sink writes one event per line, which read back the same
+        let path = std::env::temp_dir().join("elevator_trace_test.jsonl");
+        let mut file = JsonlSink::create(&path).unwrap();
+        for event in buffer.events() {
+            file.emit(&event);
+        }
+        file.finish().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let read: Vec<TracedEvent> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(read, buffer.events());
+    }
+}
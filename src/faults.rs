@@ -0,0 +1,164 @@
+use crate::elevator::{BuildingState, DoorState, ElevatorSim, Malfunction};
+use crate::rng::{self, Stream};
+use crate::types::Floor;
+use serde::{Deserialize, Serialize};
+
+/// How often things break during a run. Rates are chances per second, so a stuck_rate of
+/// 0.001 gets a moving car stuck about once every 1000 seconds it spends moving. Every rate
+/// is 0 by default, and then no random numbers are drawn, so seeded runs are unchanged
+/// stuck_rate - how often a moving car stops dead, possibly between floors
+/// door_jam_rate - how often an open door jams open
+/// button_break_rate - how often a floor's call buttons stop registering presses
+/// repair_time - how many seconds anything broken takes to be fixed
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FaultConfig {
+    pub stuck_rate: f32,
+    pub door_jam_rate: f32,
+    pub button_break_rate: f32,
+    pub repair_time: f32,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            stuck_rate: 0.,
+            door_jam_rate: 0.,
+            button_break_rate: 0.,
+            repair_time: 120.,
+        }
+    }
+}
+
+impl FaultConfig {
+    /// Whether anything can break at all
+    pub fn enabled(&self) -> bool {
+        self.stuck_rate > 0. || self.door_jam_rate > 0. || self.button_break_rate > 0.
+    }
+
+    /// Break things in the building at random over the next dt seconds. Only moving cars
+    /// get stuck, only open doors jam, and nothing already broken breaks again
+    pub fn inject(&self, building: &mut ElevatorSim, dt: f32) {
+        if !self.enabled() || dt <= 0. {
+            return;
+        }
+        // the chance of at least one failure in dt seconds at a steady rate
+        let happens =
+            |rate: f32| rate > 0. && rng::random::<f32>(Stream::Faults) < 1. - (-rate * dt).exp();
+
+        let cars: Vec<_> = building
+            .state()
+            .cars
+            .iter()
+            .filter(|car| car.malfunction.is_none())
+            .map(|car| (car.id, car.velocity != 0., car.door != DoorState::Closed))
+            .collect();
+        for (car_id, moving, open) in cars {
+            if moving && happens(self.stuck_rate) {
+                let _ = building.break_car(car_id, Malfunction::Stuck, self.repair_time);
+            } else if open && happens(self.door_jam_rate) {
+                let _ = building.break_car(car_id, Malfunction::DoorJammed, self.repair_time);
+            }
+        }
+
+        let floors: Vec<Floor> = building
+            .state()
+            .floors
+            .iter()
+            .filter(|f| !f.buttons_broken)
+            .map(|f| f.floor)
+            .collect();
+        for floor in floors {
+            if happens(self.button_break_rate) {
+                let _ = building.break_buttons(floor, self.repair_time);
+            }
+        }
+    }
+}
+
+/// A line listing everything broken in the building, for renderers, or None if nothing is
+pub fn describe(state: &BuildingState) -> Option<String> {
+    let cars = state.cars.iter().filter_map(|car| {
+        let fault = match car.malfunction? {
+            Malfunction::Stuck => "stuck",
+            Malfunction::DoorJammed => "door jammed",
+        };
+        Some(format!("car {} {fault}", car.id.0))
+    });
+    let floors = state
+        .floors
+        .iter()
+        .filter(|f| f.buttons_broken)
+        .map(|f| format!("floor {} buttons broken", f.floor));
+    let broken: Vec<String> = cars.chain(floors).collect();
+    (!broken.is_empty()).then(|| format!("FAULTS: {}", broken.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::{CommandError, ElevatorCommand};
+    use crate::types::{CarId, Direction};
+
+    #[test]
+    fn broken_cars_and_buttons_are_repaired_after_a_while() {
+        let mut building = ElevatorSim::new(6, 2);
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 5,
+            })
+            .unwrap();
+        for _ in 0..10 {
+            building.tick(0.1);
+        }
+        let stuck_at = building.state().cars[0].current_floor;
+        assert!(stuck_at > 0.);
+
+        let faults = FaultConfig {
+            stuck_rate: 1000.,
+            button_break_rate: 1000.,
+            repair_time: 5.,
+            ..FaultConfig::default()
+        };
+        faults.inject(&mut building, 0.1);
+        let state = building.state();
+        assert_eq!(state.cars[0].malfunction, Some(Malfunction::Stuck));
+        // the idle car wasn't moving and its door is closed, so nothing could break
+        assert_eq!(state.cars[1].malfunction, None);
+        assert!(state.floors.iter().all(|f| f.buttons_broken));
+        assert!(!state.cars[0].dispatchable());
+        assert!(describe(state).unwrap().starts_with("FAULTS: car 0 stuck"));
+
+        // the controller can't use the broken car, and nobody can call one
+        assert_eq!(
+            building.apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 1,
+            }),
+            Err(CommandError::Broken { car_id: CarId(0) })
+        );
+        assert_eq!(
+            building.apply_command(ElevatorCommand::PressOutButton {
+                floor: 2,
+                direction: Direction::Up,
+                bank: None,
+            }),
+            Err(CommandError::ButtonsBroken { floor: 2 })
+        );
+
+        // the car stays put until it's repaired, then carries on to its stop
+        for _ in 0..40 {
+            building.tick(0.1);
+        }
+        assert_eq!(building.state().cars[0].current_floor, stuck_at);
+        for _ in 0..200 {
+            building.tick(0.1);
+        }
+        let state = building.state();
+        assert_eq!(state.cars[0].malfunction, None);
+        assert_eq!(state.cars[0].current_floor, 5.);
+        assert!(state.floors.iter().all(|f| !f.buttons_broken));
+        assert_eq!(describe(state), None);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::elevator::ElevatorCommand;
+use crate::metrics::StatsView;
+use crate::types::{Direction, Floor};
+use crate::view::BuildingView;
+use std::io::{self, Write};
+
+/// The number of features describing each floor: up button, down button, up arrival rate,
+/// down arrival rate
+const FLOOR_FEATURES: usize = 4;
+
+/// The number of features describing each car, not counting its car buttons: position,
+/// target floor, door open
+const CAR_FEATURES: usize = 3;
+
+/// The length of the feature vector for a building with num_floors floors and num_cars cars.
+/// Every decision epoch in the same building produces a vector of exactly this length
+pub fn feature_len(num_floors: usize, num_cars: usize) -> usize {
+    num_floors * FLOOR_FEATURES + num_cars * (CAR_FEATURES + num_floors)
+}
+
+/// Flatten the building, as a controller sees it, and statistics into a fixed size vector
+/// of numbers.
+/// Floors are listed first, then cars. Positions and target floors are divided by the top
+/// floor so they fall between 0 and 1, and a car without a target floor gets -1
+pub fn encode_state(view: &BuildingView, stats: &StatsView) -> Vec<f32> {
+    let num_floors = view.floors().len();
+    let mut features = Vec::with_capacity(feature_len(num_floors, view.num_cars()));
+
+    for floor_state in view.floors() {
+        features.push(bool_feature(floor_state.hall_call(Direction::Up)));
+        features.push(bool_feature(floor_state.hall_call(Direction::Down)));
+        features.push(stats.arrival_rate(floor_state.floor, Direction::Up));
+        features.push(stats.arrival_rate(floor_state.floor, Direction::Down));
+    }
+
+    for car in view.cars() {
+        features.push(car.position() / top_floor(num_floors));
+        features.push(
+            car.target_floor()
+                .map_or(-1., |f| normalize_floor(f, num_floors)),
+        );
+        features.push(bool_feature(car.door_open()));
+        for &pressed in car.car_buttons() {
+            features.push(bool_feature(pressed));
+        }
+    }
+
+    features
+}
+
+/// Encode the floor each car was sent to by a controller's commands, one entry per car.
+/// A car is sent somewhere by MoveCarTo, or by AssignTrip to the floor it's to pick the trip
+/// up from, and cars which weren't sent anywhere get -1. If a car was sent to several
+/// floors, the last command wins
+pub fn encode_assignment(num_cars: usize, commands: &[ElevatorCommand]) -> Vec<f32> {
+    let mut assignment = vec![-1.; num_cars];
+    for cmd in commands {
+        let (car_id, floor) = match *cmd {
+            ElevatorCommand::MoveCarTo { car_id, floor } => (car_id, floor),
+            ElevatorCommand::AssignTrip { floor, car_id, .. } => (car_id, floor),
+            _ => continue,
+        };
+        if let Some(slot) = assignment.get_mut(car_id.0 as usize) {
+            *slot = floor as f32;
+        }
+    }
+    assignment
+}
+
+fn bool_feature(value: bool) -> f32 {
+    if value { 1. } else { 0. }
+}
+
+/// Writes one CSV row per controller decision epoch, containing the encoded state followed
+/// by the encoded assignment. The output can be loaded into any training pipeline and
+/// the resulting model exported to ONNX. Simulation::start_feature_export records every
+/// decision a controller makes with one. If writing fails, the rest of the rows are dropped
+/// and finish reports the error
+pub struct FeatureExporter<W: Write> {
+    out: W,
+    wrote_header: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> FeatureExporter<W> {
+    /// Create an exporter which writes into out
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            wrote_header: false,
+            error: None,
+        }
+    }
+
+    /// Write a row for the building the controller saw, and the commands it chose
+    pub fn record(&mut self, view: &BuildingView, stats: &StatsView, commands: &[ElevatorCommand]) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.write_row(view, stats, commands) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Report anything that went wrong while writing, or flush the writer if nothing did
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    fn write_row(
+        &mut self,
+        view: &BuildingView,
+        stats: &StatsView,
+        commands: &[ElevatorCommand],
+    ) -> io::Result<()> {
+        let features = encode_state(view, stats);
+        let assignment = encode_assignment(view.num_cars(), commands);
+
+        // the header names every column so rows from different runs can be checked
+        if !self.wrote_header {
+            let mut names: Vec<String> = (0..features.len()).map(|i| format!("x{i}")).collect();
+            names.extend((0..assignment.len()).map(|i| format!("car{i}_target")));
+            writeln!(self.out, "{}", names.join(","))?;
+            self.wrote_header = true;
+        }
+
+        let row: Vec<String> = features
+            .iter()
+            .chain(assignment.iter())
+            .map(|v| v.to_string())
+            .collect();
+        writeln!(self.out, "{}", row.join(","))
+    }
+
+    /// Consume the exporter, returning the writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Encode a floor number the same way as encode_state does, so a model's output can be
+/// compared against it
+pub fn normalize_floor(floor: Floor, num_floors: usize) -> f32 {
+    floor as f32 / top_floor(num_floors)
+}
+
+/// The highest floor number, kept at 1 or above so it's always safe to divide by
+fn top_floor(num_floors: usize) -> f32 {
+    (num_floors.max(2) - 1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::ElevatorSim;
+    use crate::types::CarId;
+
+    #[test]
+    fn encoded_state_has_fixed_length() {
+        let sim = ElevatorSim::new(5, 2);
+        let features = encode_state(&BuildingView::new(sim.state()), &sim.stats());
+        assert_eq!(features.len(), feature_len(5, 2));
+    }
+
+    #[test]
+    fn exporter_writes_header_and_rows() {
+        let sim = ElevatorSim::new(3, 2);
+        let mut exporter = FeatureExporter::new(Vec::new());
+        let commands = vec![ElevatorCommand::MoveCarTo {
+            car_id: CarId(1),
+            floor: 2,
+        }];
+
+        let view = BuildingView::new(sim.state());
+        exporter.record(&view, &sim.stats(), &commands);
+        exporter.record(&view, &sim.stats(), &[]);
+        exporter.finish().unwrap();
+
+        let text = String::from_utf8(exporter.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with("-1,2"));
+        assert!(lines[2].ends_with("-1,-1"));
+    }
+
+    #[test]
+    fn assigned_trips_are_encoded_as_their_pickup_floor() {
+        use crate::control::{DestinationController, ElevatorController};
+
+        let mut sim = ElevatorSim::new(6, 2);
+        sim.apply_command(ElevatorCommand::RequestTrip {
+            floor: 4,
+            destination: 1,
+        })
+        .unwrap();
+        let view = BuildingView::new(sim.state());
+        let commands = DestinationController::default().tick(&view, &sim.stats());
+        assert!(
+            commands
+                .iter()
+                .any(|c| matches!(c, ElevatorCommand::AssignTrip { floor: 4, .. }))
+        );
+
+        let assignment = encode_assignment(2, &commands);
+        assert_eq!(assignment.iter().filter(|&&f| f == 4.).count(), 1);
+        assert_eq!(assignment.iter().filter(|&&f| f == -1.).count(), 1);
+    }
+
+    #[test]
+    fn simulations_export_every_decision() {
+        use crate::config::SimConfig;
+        use std::fs::{self, File};
+        use std::io::BufWriter;
+
+        let path = std::env::temp_dir().join(format!("features-{}.csv", std::process::id()));
+        let file = BufWriter::new(File::create(&path).unwrap());
+        let mut sim = SimConfig {
+            floors: 4,
+            cars: 2,
+            seed: Some(1),
+            ..SimConfig::default()
+        }
+        .build()
+        .unwrap();
+        sim.start_feature_export(FeatureExporter::new(Box::new(file)));
+        for _ in 0..50 {
+            sim.step(0.1);
+        }
+        sim.finish_feature_export().unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 51);
+        let columns = feature_len(4, 2) + 2;
+        assert!(lines.iter().all(|l| l.split(',').count() == columns));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::metrics::StatsView;
+use crate::types::Floor;
+
+/// This is a trait which allows you to swap between different methods of predicting demand.
+/// Given the statistics collected so far, a forecaster predicts how many hall calls each
+/// floor will receive over the next horizon seconds. Controllers and parking policies
+/// consume forecasts without caring how they were made, such as ForecastParking. They're
+/// Send, so a simulation can be run on another thread
+pub trait DemandForecaster: Send {
+    fn forecast(&mut self, stats: &StatsView, horizon: f32) -> Vec<f32>;
+}
+
+/// A naive forecaster which assumes the current exponential moving average arrival rate
+/// will hold steady for the whole horizon
+pub struct EmaForecaster;
+
+impl DemandForecaster for EmaForecaster {
+    /// Multiply each floor's arrival rate by the horizon
+    fn forecast(&mut self, stats: &StatsView, horizon: f32) -> Vec<f32> {
+        (0..stats.num_floors())
+            .map(|floor| stats.floor_arrival_rate(floor as Floor) * horizon)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::ArrivalRateEstimator;
+    use crate::types::Direction;
+
+    #[test]
+    fn ema_forecast_scales_with_horizon() {
+        let mut arrivals = ArrivalRateEstimator::new(3, 10.);
+        arrivals.record(2, Direction::Down);
+        let stats = StatsView::new(&arrivals);
+        let mut forecaster = EmaForecaster;
+
+        let short = forecaster.forecast(&stats, 10.);
+        let long = forecaster.forecast(&stats, 20.);
+
+        assert_eq!(short.len(), 3);
+        assert_eq!(short[0], 0.);
+        assert!(short[2] > 0.);
+        assert!((long[2] - 2. * short[2]).abs() < 1e-6);
+    }
+}
@@ -1,12 +1,146 @@
 /// Types is a module that allows communication between other modules with shared names
 pub mod types;
 
+/// topology is a module describing the shape of the building, its floors, banks, shafts and
+/// entrances, which every other module shares
+pub mod topology;
+
 /// Elevator is a module that controls elevator movement, and the building state
 pub mod elevator;
 
+/// clock is a module which turns the seconds a run has gone on for into the simulated time
+/// of day
+pub mod clock;
+
+/// rng is a module holding the random number generator the simulation draws from, so
+/// runs can be seeded
+pub mod rng;
+
 /// people is a module that controls people movement, and people state,
 /// along with decision making
 pub mod people;
 
+/// queue is a module which keeps the people waiting on each floor in the order they arrived,
+/// and summarizes them for controllers
+pub mod queue;
+
 /// control is a module which handles decision making for the elevator module
 pub mod control;
+
+/// parking is a module which decides where cars with nothing to do wait for their next
+/// call, such as back at the lobby
+pub mod parking;
+
+/// shadow is a module which runs a second controller alongside the one in charge, writing
+/// down where it would have done something different without letting it
+pub mod shadow;
+
+/// metrics is a module which collects statistics about the simulation, such as
+/// how often people arrive at each floor
+pub mod metrics;
+
+/// forecast is a module which predicts future demand from collected statistics
+pub mod forecast;
+
+/// features is a module which flattens the building state into numeric feature vectors,
+/// so controller decisions can be exported for offline learning
+pub mod features;
+
+/// env is a module containing a gym style environment, for training dispatchers with
+/// reinforcement learning against the simulation
+pub mod env;
+
+/// onnx is a module containing a controller which runs a trained ONNX model
+#[cfg(feature = "onnx")]
+pub mod onnx;
+
+/// tui is a module containing an interactive terminal renderer, which can be paused, stepped
+/// and sped up from the keyboard
+#[cfg(feature = "tui")]
+pub mod tui;
+
+/// remote is a module containing a controller which asks a scheduler in another process
+/// what to do, over TCP or the process's stdin and stdout
+pub mod remote;
+
+/// faults is a module which breaks cars and call buttons at random during a run, so
+/// controllers can be tested on routing around them
+pub mod faults;
+
+/// deliveries is a module which brings deliveries into the building during a run, and
+/// reserves freight cars to carry them
+pub mod deliveries;
+
+/// wasm is a module exposing the simulation to JavaScript, for driving it from a browser
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// observation is a module which decides what controllers are allowed to see of the
+/// building, so experiments about what information is worth can't be cheated
+pub mod observation;
+
+/// view is a module holding the read only views of the building which controllers are given,
+/// which borrow its state rather than copying it
+pub mod view;
+
+/// sim is a module which ties together people, the building, and a controller into one
+/// simulation that can be stepped by a fixed timestep or from event to event
+pub mod sim;
+
+/// validate is a module which checks the building after every tick for things that should
+/// never happen, such as a car leaving with its door open, for --check and fuzzing harnesses
+pub mod validate;
+
+/// events is a module which traces everything that happens in a simulation, such as people
+/// boarding and doors opening, into sinks that print, keep or write the events
+pub mod events;
+
+/// replay is a module which records the commands a controller gives over a run, and replays
+/// them without the controller to check the run repeats exactly, and plays traces back
+/// without simulating anything
+pub mod replay;
+
+/// scenario is a module which reads scripts listing exactly who arrives and what happens to
+/// the cars, and when, and runs them without any random traffic
+pub mod scenario;
+
+/// bench is a module which runs several controllers on exactly the same traffic, and
+/// compares how well each one did
+pub mod bench;
+
+/// diff is a module which compares two recorded runs of the same scenario, showing who
+/// waited more or less and where the runs first went different ways
+pub mod diff;
+
+/// tune is a module which searches for the controller parameters that minimize an objective,
+/// such as the 95th percentile wait, over a batch of runs
+pub mod tune;
+
+/// robust is a module which runs controllers on many perturbed versions of a scenario, to
+/// show how much their results vary when the traffic isn't what they were built for
+pub mod robust;
+
+/// batch is a module which runs many independent simulations, on several threads with the
+/// parallel feature, for experiments such as parameter sweeps
+pub mod batch;
+
+/// montecarlo is a module which runs every controller many times with different seeds, and
+/// estimates each one's mean wait with a confidence interval, since one run is too noisy to
+/// compare them by
+pub mod montecarlo;
+
+/// sweep is a module which runs a simulation over every combination of ranges of its
+/// settings, such as the number of cars, and writes the waits and throughput as a CSV grid
+pub mod sweep;
+
+/// soak is a module which runs a simulation for hours as fast as it goes, watching memory,
+/// the people vector, floating point drift and speed for anything that keeps getting worse
+pub mod soak;
+
+/// snapshot is a module which saves the state of a simulation to a file and loads it back,
+/// so runs can be checkpointed and resumed
+pub mod snapshot;
+
+/// config is a module which reads simulation settings from a TOML or JSON file, and builds
+/// the simulation they describe
+pub mod config;
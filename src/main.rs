@@ -1,92 +1,944 @@
-use elevator_simulation::control::{ElevatorController, BasicController};
-use elevator_simulation::elevator::ElevatorSim;
-use elevator_simulation::elevator::{BuildingState, ElevatorCommand};
-use elevator_simulation::people::{PeopleSim, Person, PersonAction, PersonState};
-use std::{env, thread, time::Duration};
+use elevator_simulation::bench::{self, BenchConfig, Contender};
+use elevator_simulation::config::{ControllerChoice, SimConfig};
+use elevator_simulation::control::{
+    BasicController, CONTROLLERS, DestinationController, ElevatorController, GroupController,
+    LookController, OracleController,
+};
+use elevator_simulation::diff;
+use elevator_simulation::elevator::{BuildingState, ElevatorEvent, ElevatorSim};
+use elevator_simulation::events::{JsonlSink, SimEvent, StdoutSink};
+use elevator_simulation::features::FeatureExporter;
+use elevator_simulation::metrics::{MetricsLog, RunSummary};
+use elevator_simulation::montecarlo;
+use elevator_simulation::observation::ObservationFilter;
+use elevator_simulation::people::{
+    BoardingPolicy, PatternSpawner, PeopleConfig, Person, traffic_pattern,
+};
+use elevator_simulation::remote::RemoteController;
+use elevator_simulation::replay::{CommandLog, ReplayController, Scenario, TracePlayer};
+use elevator_simulation::rng;
+use elevator_simulation::robust::{self, RobustnessConfig};
+use elevator_simulation::scenario;
+use elevator_simulation::soak::{self, SoakConfig};
+use elevator_simulation::sweep::{self, Axis};
+use elevator_simulation::tune::{self, Objective, TuneConfig};
+use elevator_simulation::types::{CarId, Direction, Floor, ScriptedArrival};
+use elevator_simulation::validate::InvariantChecker;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::time::{Duration, Instant};
+
+//the plain text renderer, used unless the tui feature is enabled
+#[cfg(not(feature = "tui"))]
+use elevator_simulation::{faults, people::PersonState};
+#[cfg(not(feature = "tui"))]
+use std::thread;
+
+#[cfg(feature = "tui")]
+use elevator_simulation::tui::Tui;
+
+/// How many differently seeded runs tune scores each set of weights on
+const TUNING_RUNS: u64 = 3;
+
+/// How many differently seeded runs montecarlo gives each controller, unless --runs says
+const MONTE_CARLO_RUNS: u32 = 30;
+
+/// How many steps per second a headless run of the supertall preset should manage, and how
+/// many steps it's timed over, long enough for queues to build up
+const PERF_TARGET: f64 = 2000.;
+const PERF_STEPS: u32 = 10000;
 
 ///ties together PeopleSim, ElevatorSim, and ElevatorController
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut floors: u32 = 10;
-    let mut num_elevators = 2;
-    let mut steps = 2000;
+    let mut args: Vec<String> = env::args().collect();
+
+    //--perf-check times a headless run of the supertall preset, ignoring everything else
+    if args.iter().any(|arg| arg == "--perf-check") {
+        return check_performance();
+    }
+
+    //settings come from --config if given, or a preset with --single-car or --supertall, and
+    //positional arguments override them
+    let mut config = SimConfig::default();
+    if let Some(index) = args.iter().position(|arg| arg == "--single-car") {
+        args.remove(index);
+        config = SimConfig::single_car();
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--supertall") {
+        args.remove(index);
+        config = SimConfig::supertall();
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--config") {
+        let Some(path) = args.get(index + 1) else {
+            eprintln!("Error: --config needs a path to a TOML or JSON file");
+            std::process::exit(1);
+        };
+        match SimConfig::load(path) {
+            Ok(loaded) => config = loaded,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+        args.drain(index..index + 2);
+    }
+
+    //--record saves every command the controller gives, and --replay gives a saved run's
+    //commands again instead of running the controller, checking it ends up the same way.
+    //--replay given a trace from --trace plays it back instead, without simulating anything,
+    //and --verify then runs the simulation again to check it traces the same events
+    let record = take_path(&mut args, "--record");
+    let replay_path = take_path(&mut args, "--replay");
+    let verify = take_flag(&mut args, "--verify");
+
+    //--scenario plays back a session driven by hand, such as one recorded in the browser
+    //with startMacro, adding its people and giving its commands on top of everything else
+    let scenario = take_path(&mut args, "--scenario");
+
+    //--script runs a scenario script listing exactly who arrives and what happens, and when,
+    //instead of random traffic
+    let script = take_path(&mut args, "--script");
+
+    //--remote hands the decisions to a scheduler listening on a TCP address
+    let remote = take_path(&mut args, "--remote");
+
+    //--controller picks the controller by name instead of the config, and the people using
+    //it behave the way it expects. The remote controller also needs --remote's address
+    if let Some(name) = take_path(&mut args, "--controller") {
+        match ControllerChoice::named(&name) {
+            Some(choice) => config.controller = choice,
+            None if name == "remote" && remote.is_some() => {}
+            None if name == "remote" => {
+                eprintln!("Error: the remote controller needs --remote address");
+                std::process::exit(1);
+            }
+            None => {
+                let names: Vec<&str> = CONTROLLERS.iter().map(|&(name, _)| name).collect();
+                eprintln!(
+                    "Error: unknown controller {name}: controller must be one of {}, or remote",
+                    names.join(", ")
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    //--metrics-out writes everyone who reached their floor, and the queues and cars after
+    //every step, to CSV or JSON files for analysis elsewhere
+    let metrics_out = take_path(&mut args, "--metrics-out");
+
+    //--features-out writes the building as the controller saw it and the floors it sent the
+    //cars to, for every decision it makes, to a CSV file for training a model to imitate it
+    let features_out = take_path(&mut args, "--features-out");
+
+    //--trace writes every event, from people spawning to doors closing, to a JSON lines
+    //file, or prints them for -
+    let trace = take_path(&mut args, "--trace");
+
+    //--headless runs as fast as possible without drawing anything, then prints a summary
+    let headless = take_flag(&mut args, "--headless");
+
+    //--event-driven jumps straight from one event to the next instead of ticking every
+    //timestep, so headless runs, and montecarlo's and sweep's, get through quiet spells faster
+    if take_flag(&mut args, "--event-driven") {
+        config.event_driven = true;
+    }
+
+    //--check looks for anything that should never happen after every step, such as a car
+    //leaving with its door open, and fails the run if it finds any
+    let mut checker = take_flag(&mut args, "--check").then(InvariantChecker::new);
+    let mut violations = 0;
+
+    //--hours sets how long soak runs for, in wall clock time
+    let hours = take_path(&mut args, "--hours");
+
+    //--runs sets how many differently seeded runs montecarlo gives each controller
+    let runs = take_path(&mut args, "--runs");
+
+    //compare runs every controller on the same people, then prints a table of how they did,
+    //tune searches for the destination controller weights with the shortest waits, and
+    //robust runs every controller on many perturbed versions of the traffic, and soak runs
+    //for hours, failing if memory, people or drift keep growing. montecarlo runs every
+    //controller with many seeds and estimates their mean waits. sweep runs every combination
+    //of the ranges given, such as cars=1..6, and prints the waits and throughput as CSV.
+    //compare given two recorded runs instead reports what changed between them
+    let mode = match args.get(1).map(String::as_str) {
+        Some("compare" | "tune" | "robust" | "soak" | "montecarlo" | "sweep") => {
+            Some(args.remove(1))
+        }
+        _ => None,
+    };
+
+    //sweep's ranges are the arguments naming a setting, the rest are read as usual
+    let mut axes = Vec::new();
+    if mode.as_deref() == Some("sweep") {
+        for arg in args.extract_if(1.., |arg| arg.contains('=')) {
+            match arg.parse::<Axis>() {
+                Ok(axis) => axes.push(axis),
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if mode.as_deref() == Some("compare")
+        && args.len() == 3
+        && args[1..].iter().any(|arg| arg.parse::<u32>().is_err())
+    {
+        return diff_runs(&args[1], &args[2]);
+    }
     
-    if args.len() > 4 {
+    if args.len() > 5 {
         eprintln!("Too many arguments.
-Usage: cargo run -- [floors] [num_elevators] [steps]");
+Usage: cargo run -- compare run_a run_b
+       cargo run -- [compare | tune | robust | soak [--hours hours] | montecarlo [--runs runs] | sweep setting=from..to ...] [--config path | --single-car | --supertall | --perf-check] [--record path | --replay path [--verify]] [--scenario path] [--script path] [--controller name] [--remote address] [--metrics-out path] [--features-out path] [--trace path] [--headless [--event-driven]] [--check] [floors] [num_elevators] [steps] [traffic]
+traffic is one of interfloor, up-peak, down-peak, lunch, daily, office-day");
         std::process::exit(1);
     }
 
     if args.len() > 1 {
         match args[1].parse() {
-            Ok(floor_num) => floors = floor_num,
+            Ok(floor_num) => config.floors = floor_num,
             Err(e) => eprintln!("Error: {e}: Floors must be a positive integer"),
         };
     }
 
     if args.len() > 2 {
         match args[2].parse() {
-            Ok(elevator_num) => num_elevators = elevator_num,
+            Ok(elevator_num) => config.cars = elevator_num,
             Err(e) => eprintln!("Error: {e}: num_elevators must be a positive integer"),
         };
     }
 
     if args.len() > 3 {
         match args[3].parse() {
-            Ok(steps_num) => steps = steps_num,
+            Ok(steps_num) => config.steps = steps_num,
             Err(e) => eprintln!("Error: {e}: steps must be a positive integer"),
         };
     }
 
-    let mut people = PeopleSim::new(floors, 3.);
-    let mut building = ElevatorSim::new(floors as usize, num_elevators);
-    let mut controller = BasicController;
+    if args.len() > 4 {
+        match traffic_pattern(&args[4], config.spawn_rate) {
+            Some(_) => config.traffic = args[4].clone(),
+            None => eprintln!(
+                "Error: unknown traffic pattern {}: traffic must be one of interfloor, up-peak, \
+down-peak, lunch, daily, office-day",
+                args[4]
+            ),
+        };
+    }
+
+    match mode.as_deref() {
+        Some("compare") => return compare_controllers(&config),
+        Some("tune") => return tune_destination(&config),
+        Some("robust") => return check_robustness(&config),
+        Some("soak") => return soak_test(&config, hours.as_deref()),
+        Some("montecarlo") => return estimate_waits(&config, runs.as_deref()),
+        Some("sweep") => return sweep_settings(&config, &axes),
+        _ => {}
+    }
+
+    //anything --replay can't read as a command log is played back as a trace
+    let replay = match replay_path {
+        Some(path) => match CommandLog::load(&path) {
+            Ok(log) => Some(log),
+            Err(log_error) => match TracePlayer::load(&path) {
+                Ok(player) => return play_trace(player, &config, verify, headless),
+                Err(trace_error) => {
+                    eprintln!(
+                        "Error: {path} is neither a command log ({log_error}) nor a trace \
+({trace_error})"
+                    );
+                    std::process::exit(1);
+                }
+            },
+        },
+        None => None,
+    };
+
+    //a replay has to use the recording's seed, and a recording needs a seed to be replayed
+    if let Some(log) = &replay {
+        config.seed = log.seed;
+    } else if record.is_some() && config.seed.is_none() {
+        config.seed = Some(rng::new_seed());
+    }
+
+    let built = match &script {
+        Some(path) => scenario::load(path, &config.topology())
+            .and_then(|script| scenario::build(&config, &script))
+            .map_err(|e| format!("couldn't run the script {path}: {e}")),
+        None => config.build().map_err(|e| e.to_string()),
+    };
+    let mut sim = match built {
+        Ok(sim) => sim,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(log) = &replay {
+        sim.set_controller(Box::new(ReplayController::new(log)));
+    } else if let Some(address) = &remote {
+        match RemoteController::connect(address) {
+            Ok(controller) => sim.set_controller(Box::new(controller)),
+            Err(e) => {
+                eprintln!("Error: couldn't connect to the scheduler at {address}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if replay.is_none() && record.is_some() {
+        sim.start_recording();
+    }
+    if let Some(path) = &scenario {
+        match Scenario::load(path) {
+            Ok(scenario) => {
+                if let Err(e) = sim.play_scenario(&scenario) {
+                    eprintln!("Error: couldn't play the scenario {path}: {e}");
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: couldn't read the scenario {path}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &features_out {
+        match File::create(path) {
+            Ok(file) => {
+                sim.start_feature_export(FeatureExporter::new(Box::new(BufWriter::new(file))))
+            }
+            Err(e) => {
+                eprintln!("Error: couldn't create the features file {path}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    match trace.as_deref() {
+        Some("-") => sim.start_trace(Box::new(StdoutSink)),
+        Some(path) => match JsonlSink::create(path) {
+            Ok(sink) => sim.start_trace(Box::new(sink)),
+            Err(e) => {
+                eprintln!("Error: couldn't create the trace {path}: {e}");
+                std::process::exit(1);
+            }
+        },
+        None => {}
+    }
 
     //amount to advance the simulation by
-    let timestep = 0.1;
+    let timestep = config.timestep;
+
+    //cars which have announced they're about to arrive, and haven't yet
+    let mut arriving: Vec<(CarId, Floor, Direction)> = Vec::new();
 
-    for _ in 0..steps {
-        // step PeopleSim, and get the vector of PersonActions
-        let person_action = people.tick(timestep, building.state());
-        for act in person_action {
-            //translate those PersonActions into ElevatorCommands
-            if let Some(cmd) = person_action_to_cmd(act) {
-                building.apply_command(cmd);
+    let mut summary = RunSummary::default();
+    let mut metrics = MetricsLog::default();
+
+    //with the tui feature, the building is drawn full screen and can be paused and stepped
+    #[cfg(feature = "tui")]
+    let mut tui = start_tui(headless);
+
+    //a drawn run ticks every timestep, so the cars move smoothly
+    let event_driven = config.event_driven && headless;
+    let end = config.steps as f32 * timestep;
+    let mut steps = 0..config.steps;
+    loop {
+        let dt = if event_driven {
+            let Some(dt) = sim.step_to_next_event_before(end) else {
+                break;
+            };
+            dt
+        } else {
+            if steps.next().is_none() {
+                break;
             }
+            sim.step(timestep);
+            timestep
+        };
+
+        //report any commands the building refused, and keep track of arriving cars
+        for event in sim.take_events() {
+            match event {
+                ElevatorEvent::CommandRejected { error } => {
+                    //printing would scroll the full screen display, so it's shown there instead
+                    #[cfg(feature = "tui")]
+                    if let Some(tui) = &mut tui {
+                        tui.message(format!("Error: {error}"));
+                        continue;
+                    }
+                    eprintln!("Error: {error}")
+                }
+                event => track_arrivals(&mut arriving, &event),
+            }
+        }
+
+        if let Some(checker) = &mut checker {
+            for violation in checker.check(sim.building().state()) {
+                eprintln!("Error: at {:.1} s, {violation}", sim.time());
+                violations += 1;
+            }
+        }
+
+        summary.record(dt, sim.people().people(), sim.building().state());
+        if metrics_out.is_some() {
+            metrics.record(dt, sim.people().people(), sim.building().state());
+        }
+
+        #[cfg(not(feature = "tui"))]
+        if !headless {
+            render(sim.building().state(), sim.people().people(), &arriving);
+
+            thread::sleep(Duration::from_millis(25));
         }
 
-        //get the building state and pass it to the controller to get ElevatorCommands
-        let state = building.state();
-        let control_cmds = controller.tick(state);
-        for cmd in control_cmds {
-            //apply all elevator commands
-            building.apply_command(cmd);
+        #[cfg(feature = "tui")]
+        if !draw_tui(
+            &mut tui,
+            sim.time(),
+            sim.building().state(),
+            sim.people().people(),
+            &arriving,
+        ) {
+            break;
         }
+    }
+
+    #[cfg(feature = "tui")]
+    finish_tui(tui);
+
+    if headless {
+        println!("{summary}");
+    }
+
+    //a shadow controller's commands were only written down, so say where they'd have differed
+    if let Some(report) = sim.finish_shadow() {
+        print!("{report}");
+    }
 
-        building.tick(timestep);
+    if let Some(path) = metrics_out {
+        match metrics.save(&path) {
+            Ok(()) => println!(
+                "Wrote metrics for {} people to {path}",
+                metrics.people().len()
+            ),
+            Err(e) => {
+                eprintln!("Error: couldn't write metrics: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
 
-        render(building.state(), people.people());
+    if let Err(e) = sim.finish_trace() {
+        eprintln!("Error: couldn't write the trace: {e}");
+        std::process::exit(1);
+    }
 
-        thread::sleep(Duration::from_millis(25));
+    if let Err(e) = sim.finish_feature_export() {
+        eprintln!("Error: couldn't write the features: {e}");
+        std::process::exit(1);
+    }
+
+    if let Some(path) = record
+        && let Some(log) = sim.finish_recording(config.seed)
+    {
+        match log.save(&path) {
+            Ok(()) => println!("Recorded {} commands to {path}", log.commands.len()),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(log) = replay {
+        if log.matches(&sim) {
+            println!("Replay matched the recording");
+        } else {
+            eprintln!("Error: replay ended up differently from the recording");
+            std::process::exit(1);
+        }
+    }
+
+    if violations > 0 {
+        eprintln!("Error: the run broke {violations} invariants");
+        std::process::exit(1);
     }
 }
 
-/// Translate PersonActions to ElevatorCommands
-fn person_action_to_cmd(action: PersonAction) -> Option<ElevatorCommand> {
-    match action {
-        //If a person tries to call an elevator, press the outer button
-        PersonAction::CallElevator { floor, direction } => {
-            Some(ElevatorCommand::PressOutButton { floor, direction })
+/// Remove a flag from args, returning whether it was given
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => {
+            args.remove(index);
+            true
         }
-        //If a person tries to press an interior car button, press the interior car button
-        PersonAction::PressCarButton { car_id, floor } => {
-            Some(ElevatorCommand::PressCarButton { car_id, floor })
+        None => false,
+    }
+}
+
+/// Remove a flag and the path after it from args, returning the path if the flag was given
+fn take_path(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    let Some(path) = args.get(index + 1).cloned() else {
+        eprintln!("Error: {flag} needs a path");
+        std::process::exit(1);
+    };
+    args.drain(index..index + 2);
+    Some(path)
+}
+
+/// Keep track of which cars have announced they're about to arrive, and haven't yet
+fn track_arrivals(arriving: &mut Vec<(CarId, Floor, Direction)>, event: &ElevatorEvent) {
+    match *event {
+        ElevatorEvent::CarArriving {
+            car_id,
+            floor,
+            direction,
+        } => {
+            arriving.retain(|&(id, _, _)| id != car_id);
+            arriving.push((car_id, floor, direction));
+        }
+        ElevatorEvent::CarArrived { car_id, .. } => {
+            arriving.retain(|&(id, _, _)| id != car_id);
+        }
+        _ => {}
+    }
+}
+
+/// Start the full screen display, unless running headless
+#[cfg(feature = "tui")]
+fn start_tui(headless: bool) -> Option<Tui> {
+    if headless {
+        return None;
+    }
+    match Tui::new() {
+        Ok(tui) => Some(tui),
+        Err(e) => {
+            eprintln!("Error: couldn't start the terminal display: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Draw a step on the full screen display, if there is one, and wait for the next. Returns
+/// false if the user quit
+#[cfg(feature = "tui")]
+fn draw_tui(
+    tui: &mut Option<Tui>,
+    time: f32,
+    state: &BuildingState,
+    people: &[Person],
+    arriving: &[(CarId, Floor, Direction)],
+) -> bool {
+    let Some(display) = tui else {
+        return true;
+    };
+    match display
+        .draw(time, state, people, arriving)
+        .and_then(|()| display.wait())
+    {
+        Ok(running) => running,
+        Err(e) => {
+            //give the terminal back before printing
+            drop(tui.take());
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Keep the last step on the full screen display until the user quits
+#[cfg(feature = "tui")]
+fn finish_tui(tui: Option<Tui>) {
+    if let Some(mut tui) = tui
+        && let Err(e) = tui.finish()
+    {
+        drop(tui);
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Play back a trace written by --trace, drawing the building as it was every timestep
+/// without simulating anything. With verify, config is then simulated again, and has to
+/// trace exactly the same events
+fn play_trace(mut player: TracePlayer, config: &SimConfig, verify: bool, headless: bool) {
+    let mut arriving: Vec<(CarId, Floor, Direction)> = Vec::new();
+
+    #[cfg(feature = "tui")]
+    let mut tui = start_tui(headless);
+
+    while !player.finished() {
+        for event in player.step(config.timestep) {
+            if let SimEvent::Building(event) = event {
+                track_arrivals(&mut arriving, &event);
+            }
+        }
+
+        #[cfg(not(feature = "tui"))]
+        if !headless {
+            render(player.state(), player.people(), &arriving);
+
+            thread::sleep(Duration::from_millis(25));
+        }
+
+        #[cfg(feature = "tui")]
+        if !draw_tui(
+            &mut tui,
+            player.time(),
+            player.state(),
+            player.people(),
+            &arriving,
+        ) {
+            break;
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    finish_tui(tui);
+
+    if headless {
+        println!(
+            "Played back {:.1} s, with {} people",
+            player.end_time(),
+            player.people().len()
+        );
+    }
+
+    if verify {
+        let mut sim = match config.build() {
+            Ok(sim) => sim,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        };
+        match player.verify(&mut sim, config.timestep) {
+            None => println!("Simulating again traced exactly the same events"),
+            Some(mismatch) => {
+                eprint!("Error: {mismatch}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The settings for replaying recorded people, taken from config
+fn bench_config(config: &SimConfig) -> BenchConfig {
+    BenchConfig {
+        floors: config.floors,
+        cars: config.cars,
+        duration: config.steps as f32 * config.timestep,
+        timestep: config.timestep,
+        elevator: config.elevator_config(),
+        seed: config.seed.unwrap_or(0),
+        observation: config.observation_filter(),
+        faults: Vec::new(),
+    }
+}
+
+/// Record the people config would spawn over a whole run, with the random numbers seeded
+/// with seed
+fn record_people(
+    config: &SimConfig,
+    bench_config: &BenchConfig,
+    seed: u64,
+) -> Vec<ScriptedArrival> {
+    let pattern = config.traffic_pattern().unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    });
+    rng::seed(seed);
+    let mut spawner = PatternSpawner::new(pattern);
+    bench::record_arrivals(
+        &mut spawner,
+        bench_config.floors,
+        bench_config.duration,
+        bench_config.timestep,
+    )
+}
+
+/// How people behave when using a controller, with the rest of the settings from config
+fn people_for(config: &SimConfig, controller: ControllerChoice) -> PeopleConfig {
+    SimConfig {
+        controller,
+        ..config.clone()
+    }
+    .people_config()
+}
+
+/// Record the people config would spawn, then run each controller on them and print a table
+fn compare_controllers(config: &SimConfig) {
+    // the oracle knows who will arrive, and is in the table to show roughly what that is worth
+    let bench_config = BenchConfig {
+        observation: ObservationFilter {
+            hide_future_arrivals: false,
+            ..config.observation_filter()
+        },
+        ..bench_config(config)
+    };
+    let arrivals = record_people(config, &bench_config, bench_config.seed);
+    //the oracle plans for the same building the comparison runs on
+    let building = ElevatorSim::with_config(
+        bench_config.floors as usize,
+        bench_config.cars,
+        bench_config.elevator.clone(),
+    );
+
+    //each controller gets people who behave the way it expects
+    let contenders = vec![
+        Contender {
+            name: "basic".to_string(),
+            controller: Box::new(BasicController),
+            people: people_for(config, ControllerChoice::Basic),
+        },
+        Contender {
+            name: "destination".to_string(),
+            controller: Box::new(DestinationController::default()),
+            people: people_for(config, ControllerChoice::Destination),
+        },
+        Contender {
+            name: "look".to_string(),
+            controller: Box::new(LookController),
+            people: people_for(config, ControllerChoice::Look),
+        },
+        Contender {
+            name: "group".to_string(),
+            controller: Box::new(GroupController::default()),
+            people: people_for(config, ControllerChoice::Group),
+        },
+        Contender {
+            name: "oracle".to_string(),
+            controller: Box::new(OracleController::new(&arrivals, &building)),
+            //people wait for the car the oracle assigns them
+            people: PeopleConfig {
+                boarding: BoardingPolicy::Assigned,
+                ..people_for(config, ControllerChoice::Basic)
+            },
+        },
+    ];
+
+    println!("{}", bench::compare(&bench_config, &arrivals, contenders));
+}
+
+/// Report what changed between two recorded runs of the same scenario, either two command
+/// logs from --record or two metrics files from --metrics-out
+fn diff_runs(a: &str, b: &str) {
+    let fail = |path: &str, e: &dyn std::fmt::Display| -> ! {
+        eprintln!("Error: couldn't read {path}: {e}");
+        std::process::exit(1);
+    };
+    //a command log is compared with another command log, otherwise both are metrics
+    if let Ok(log_a) = CommandLog::load(a) {
+        let log_b = CommandLog::load(b).unwrap_or_else(|e| fail(b, &e));
+        match diff::first_command_difference(&log_a, &log_b) {
+            Some(difference) => print!("{difference}"),
+            None => println!("Both runs gave exactly the same commands"),
         }
+        return;
+    }
+    let log_a = MetricsLog::load(a).unwrap_or_else(|e| fail(a, &e));
+    let log_b = MetricsLog::load(b).unwrap_or_else(|e| fail(b, &e));
+    println!("{}", diff::diff(&log_a, &log_b));
+}
+
+/// Tune the destination controller's weights for the 95th percentile wait, over a batch of
+/// runs with different people, and print how the tuned weights compare to the defaults
+fn tune_destination(config: &SimConfig) {
+    let bench_config = bench_config(config);
+    let scenarios: Vec<Vec<ScriptedArrival>> = (0..TUNING_RUNS)
+        .map(|i| record_people(config, &bench_config, bench_config.seed + i))
+        .collect();
+    let tune_config = TuneConfig {
+        bench: bench_config,
+        bounds: vec![(0., 10.), (0., 10.)],
+        objective: Objective::WaitPercentile(0.95),
+        ..TuneConfig::default()
+    };
+    let people = people_for(config, ControllerChoice::Destination);
+    let build = |params: &[f32]| -> Box<dyn ElevatorController> {
+        Box::new(DestinationController::from_params(params))
+    };
+
+    let default = DestinationController::default();
+    let start = [default.stopping_cost, default.load_weight];
+    let before = tune::evaluate(&tune_config, &scenarios, &people, &start, &build);
+    let tuned = tune::tune(&tune_config, &scenarios, &people, &start, &build);
+    println!("Default weights: 95th percentile wait {before:.1} s");
+    println!(
+        "Tuned weights: stopping_cost {:.2}, load_weight {:.2}, 95th percentile wait {:.1} s \
+after {} evaluations",
+        tuned.params[0], tuned.params[1], tuned.score, tuned.evaluations
+    );
+}
+
+/// Run every controller with runs differently seeded versions of the traffic config
+/// describes, MONTE_CARLO_RUNS if not given, and print each one's mean wait with its 95%
+/// confidence interval
+fn estimate_waits(config: &SimConfig, runs: Option<&str>) {
+    let runs = match runs.map(str::parse) {
+        None => MONTE_CARLO_RUNS,
+        Some(Ok(runs)) if runs > 0 => runs,
+        Some(_) => {
+            eprintln!("Error: --runs must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    match montecarlo::montecarlo(config, &ControllerChoice::ALL, runs) {
+        Ok(results) => println!("{results}"),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run config with every combination of the axes' values, and print each run's settings,
+/// average wait and throughput as CSV
+fn sweep_settings(config: &SimConfig, axes: &[Axis]) {
+    if axes.is_empty() {
+        eprintln!("Error: sweep needs at least one range, such as cars=1..6");
+        std::process::exit(1);
+    }
+    let result = match sweep::sweep(config, axes) {
+        Ok(sweep) => sweep
+            .write_csv(&mut io::stdout().lock())
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Run the basic, destination and group controllers on many perturbed versions of the traffic
+/// config describes, with a car breaking down in each, and print the spread of their results
+fn check_robustness(config: &SimConfig) {
+    if let Err(e) = config.traffic_pattern() {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+    let robustness_config = RobustnessConfig {
+        bench: bench_config(config),
+        ..RobustnessConfig::default()
+    };
+    let pattern = || config.traffic_pattern().unwrap();
+    let contenders = |_: &[ScriptedArrival]| {
+        vec![
+            Contender {
+                name: "basic".to_string(),
+                controller: Box::new(BasicController) as Box<dyn ElevatorController>,
+                people: people_for(config, ControllerChoice::Basic),
+            },
+            Contender {
+                name: "destination".to_string(),
+                controller: Box::new(DestinationController::default()),
+                people: people_for(config, ControllerChoice::Destination),
+            },
+            Contender {
+                name: "group".to_string(),
+                controller: Box::new(GroupController::default()),
+                people: people_for(config, ControllerChoice::Group),
+            },
+        ]
+    };
+
+    println!(
+        "{}",
+        robust::robustness(&robustness_config, &pattern, &contenders)
+    );
+}
+
+/// Run the simulation config describes for hours of wall clock time, one by default,
+/// printing a sample every minute or so. Exits with an error if anything kept growing or
+/// drifted too far
+fn soak_test(config: &SimConfig, hours: Option<&str>) {
+    let hours = match hours.map(str::parse::<f64>) {
+        None => 1.,
+        Some(Ok(hours)) if hours.is_finite() && hours > 0. => hours,
+        Some(_) => {
+            eprintln!("Error: --hours must be a positive number");
+            std::process::exit(1);
+        }
+    };
+    let duration = Duration::from_secs_f64(hours * 3600.);
+    let soak_config = SoakConfig {
+        duration,
+        interval: (duration / 60).min(Duration::from_secs(60)),
+        ..SoakConfig::default()
+    };
+    let mut sim = match config.build() {
+        Ok(sim) => sim,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    println!("Soaking for {hours} h");
+    let report = soak::soak(&mut sim, config.timestep, &soak_config, |sample| {
+        println!("{sample}")
+    });
+    if report.passed() {
+        println!("Nothing kept growing or drifted too far");
+        return;
+    }
+    for failure in &report.failures {
+        eprintln!("Error: {failure}");
+    }
+    std::process::exit(1);
+}
+
+/// Run the supertall preset headless, and report how many steps per second it managed. Exits
+/// with an error if that's short of PERF_TARGET
+fn check_performance() {
+    let config = SimConfig {
+        steps: PERF_STEPS,
+        seed: Some(0),
+        ..SimConfig::supertall()
+    };
+    let mut sim = match config.build() {
+        Ok(sim) => sim,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let mut summary = RunSummary::default();
+
+    let start = Instant::now();
+    for _ in 0..config.steps {
+        sim.step(config.timestep);
+        sim.take_events();
+        summary.record(
+            config.timestep,
+            sim.people().people(),
+            sim.building().state(),
+        );
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let rate = config.steps as f64 / elapsed;
+    println!(
+        "Ran {} steps of the supertall preset in {elapsed:.2} s: {rate:.0} steps per second \
+(target {PERF_TARGET:.0})",
+        config.steps
+    );
+    println!("{} people delivered", summary.delivered());
+    if rate < PERF_TARGET {
+        eprintln!("Error: slower than the target");
+        std::process::exit(1);
     }
 }
 
 /// Render the BuildingState and Person locations
-fn render(state: &BuildingState, people: &[Person]) {
+#[cfg(not(feature = "tui"))]
+fn render(state: &BuildingState, people: &[Person], arriving: &[(CarId, Floor, Direction)]) {
     let num_floors = state.floors.len();
     let num_elevators = state.cars.len();
 
@@ -113,12 +965,32 @@ fn render(state: &BuildingState, people: &[Person]) {
         }
     }
 
+    //show the simulated time of day
+    println!("Time: {}", state.clock);
+
+    //warn that the cars have been recalled, and won't answer calls
+    if let Some(floor) = state.emergency.recall_floor() {
+        let floor = state.topology.floor_id(floor);
+        println!("FIRE RECALL: all cars to floor {floor}");
+    }
+
+    //list anything that has broken down
+    if let Some(faults) = faults::describe(state) {
+        println!("{faults}");
+    }
+
     //for each floor
     for floor_index in (0..num_floors).rev() {
         let floor_state = &state.floors[floor_index];
 
-        //create up and down arrow buttons
-        let up = if floor_state.out_up { '^' } else { '.' };
+        //create up and down arrow buttons, or a star for a single call button
+        let up = if floor_state.out_call {
+            '*'
+        } else if floor_state.out_up {
+            '^'
+        } else {
+            '.'
+        };
         let down = if floor_state.out_down { 'v' } else { '.' };
 
         let waiting = waiting_counts[floor_index];
@@ -143,45 +1015,45 @@ fn render(state: &BuildingState, people: &[Person]) {
 
         let join_cells = elevator_cells.join(" ");
 
-        let floor = floor_state.floor;
-        //print each floor in this format
-        println!("Floor: {floor} [{up}{down}] Waiting: {waiting} | {join_cells}")
-    }
-
-    println!();
-}
+        //show which car each trip from this floor has been assigned to, if any
+        let assigned: Vec<String> = floor_state
+            .assignments
+            .iter()
+            .map(|a| {
+                let destination = state.topology.floor_id(a.destination);
+                format!("{destination}->car {}", a.car_id.0)
+            })
+            .collect();
+        let assigned = if assigned.is_empty() {
+            String::new()
+        } else {
+            format!(" | Assigned: {}", assigned.join(", "))
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use elevator_simulation::types::{CarId, Direction};
+        //show which cars are about to arrive at this floor, and which way they're going
+        let incoming: Vec<String> = arriving
+            .iter()
+            .filter(|&&(_, floor, _)| floor == floor_state.floor)
+            .map(|&(car_id, _, direction)| {
+                let arrow = match direction {
+                    Direction::Up => '^',
+                    Direction::Down => 'v',
+                };
+                format!("car {}{arrow}", car_id.0)
+            })
+            .collect();
+        let incoming = if incoming.is_empty() {
+            String::new()
+        } else {
+            format!(" | Arriving: {}", incoming.join(", "))
+        };
 
-    #[test]
-    fn call_elevator_to_press_out_button() {
-        let cmd = person_action_to_cmd(PersonAction::CallElevator {
-            floor: 3,
-            direction: Direction::Up,
-        });
-        match cmd {
-            Some(ElevatorCommand::PressOutButton { floor, .. }) => {
-                assert_eq!(floor, 3)
-            }
-            _ => panic!(),
-        }
+        let floor = state.topology.floor_id(floor_state.floor);
+        //print each floor in this format
+        println!(
+            "Floor: {floor} [{up}{down}] Waiting: {waiting} | {join_cells}{assigned}{incoming}"
+        )
     }
 
-    #[test]
-    fn press_car_button_to_press_car_button() {
-        let cmd = person_action_to_cmd(PersonAction::PressCarButton {
-            car_id: CarId(0),
-            floor: 3,
-        });
-        match cmd {
-            Some(ElevatorCommand::PressCarButton { car_id, floor }) => {
-                assert_eq!(car_id, CarId(0));
-                assert_eq!(floor, 3)
-            }
-            _ => panic!(),
-        }
-    }
+    println!();
 }
@@ -0,0 +1,1010 @@
+use crate::elevator::BuildingState;
+use crate::events::EventSink;
+use crate::people::{Person, PersonState};
+use crate::queue::{DemandInfo, PeopleSummary};
+use crate::types::{CarId, Direction, Floor, PersonId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// An online estimate of how often hall calls arrive at each floor in each direction.
+/// Every rate is an exponential moving average in calls per second, where time_constant
+/// controls how quickly old calls are forgotten
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ArrivalRateEstimator {
+    time_constant: f32,
+    up: Vec<f32>,
+    down: Vec<f32>,
+}
+
+/// implement functions for ArrivalRateEstimator
+/// new - create an estimator with every rate at zero
+/// record - count one hall call at a floor in a direction
+/// record_undirected - count one hall call at a floor going either way
+/// tick - decay every rate by the time that has passed
+/// rate - get the estimated calls per second for a floor and direction
+impl ArrivalRateEstimator {
+    /// Create a new estimator for a building with num_floors floors
+    pub fn new(num_floors: usize, time_constant: f32) -> Self {
+        Self {
+            time_constant,
+            up: vec![0.; num_floors],
+            down: vec![0.; num_floors],
+        }
+    }
+
+    /// Count a single hall call. Each call adds 1/time_constant, so a steady stream of
+    /// calls settles at its true rate
+    pub fn record(&mut self, floor: Floor, direction: Direction) {
+        let rates = match direction {
+            Direction::Up => &mut self.up,
+            Direction::Down => &mut self.down,
+        };
+        if let Some(rate) = rates.get_mut(floor as usize) {
+            *rate += 1. / self.time_constant;
+        }
+    }
+
+    /// Count a call from a floor with a single call button, which doesn't say which way the
+    /// caller is going, as half a call each way
+    pub fn record_undirected(&mut self, floor: Floor) {
+        for rates in [&mut self.up, &mut self.down] {
+            if let Some(rate) = rates.get_mut(floor as usize) {
+                *rate += 0.5 / self.time_constant;
+            }
+        }
+    }
+
+    /// Decay every rate towards zero by dt seconds
+    pub fn tick(&mut self, dt: f32) {
+        let decay = (-dt / self.time_constant).exp();
+        for rate in self.up.iter_mut().chain(self.down.iter_mut()) {
+            *rate *= decay;
+        }
+    }
+
+    /// Estimated calls per second at a floor in a direction, 0 for floors outside the building
+    pub fn rate(&self, floor: Floor, direction: Direction) -> f32 {
+        let rates = match direction {
+            Direction::Up => &self.up,
+            Direction::Down => &self.down,
+        };
+        rates.get(floor as usize).copied().unwrap_or(0.)
+    }
+
+    /// The number of floors this estimator covers
+    pub fn num_floors(&self) -> usize {
+        self.up.len()
+    }
+}
+
+/// A read-only view of the statistics the simulation collects, which is handed to
+/// controllers alongside the BuildingState. It may carry a summary of the people and the
+/// demand they make too, for controllers which want to know how many are waiting where
+#[derive(Clone, Copy, Debug)]
+pub struct StatsView<'a> {
+    arrivals: &'a ArrivalRateEstimator,
+    hide_arrivals: bool,
+    people: Option<PeopleSummary<'a>>,
+    demand: Option<&'a DemandInfo>,
+}
+
+impl<'a> StatsView<'a> {
+    /// Create a view over an arrival rate estimator
+    pub fn new(arrivals: &'a ArrivalRateEstimator) -> Self {
+        Self {
+            arrivals,
+            hide_arrivals: false,
+            people: None,
+            demand: None,
+        }
+    }
+
+    /// The same view carrying a summary of the people
+    pub fn with_people(self, people: PeopleSummary<'a>) -> Self {
+        Self {
+            people: Some(people),
+            ..self
+        }
+    }
+
+    /// The same view carrying the demand people make
+    pub fn with_demand(self, demand: &'a DemandInfo) -> Self {
+        Self {
+            demand: Some(demand),
+            ..self
+        }
+    }
+
+    /// The same view without a summary of the people or their demand
+    pub fn without_people(self) -> Self {
+        Self {
+            people: None,
+            demand: None,
+            ..self
+        }
+    }
+
+    /// The summary of the people, if the view carries one
+    pub fn people(&self) -> Option<PeopleSummary<'a>> {
+        self.people
+    }
+
+    /// The demand people make, if the view carries it
+    pub fn demand(&self) -> Option<&'a DemandInfo> {
+        self.demand
+    }
+
+    /// The same view with every arrival rate reading 0
+    pub fn without_arrival_rates(self) -> Self {
+        Self {
+            hide_arrivals: true,
+            ..self
+        }
+    }
+
+    /// Estimated hall calls per second at a floor in a direction
+    pub fn arrival_rate(&self, floor: Floor, direction: Direction) -> f32 {
+        if self.hide_arrivals {
+            return 0.;
+        }
+        self.arrivals.rate(floor, direction)
+    }
+
+    /// Estimated hall calls per second at a floor in both directions combined
+    pub fn floor_arrival_rate(&self, floor: Floor) -> f32 {
+        self.arrival_rate(floor, Direction::Up) + self.arrival_rate(floor, Direction::Down)
+    }
+
+    /// The number of floors covered by these statistics
+    pub fn num_floors(&self) -> usize {
+        self.arrivals.num_floors()
+    }
+}
+
+/// Something which measures a run in a way RunSummary doesn't, such as how long nurses wait
+/// for a car in a hospital. It's added to a simulation with Simulation::add_collector, takes
+/// in every event as a sink does, starting with the building as it was when it was added,
+/// and is asked what it measured once the run is over
+pub trait MetricCollector: EventSink {
+    /// What was measured over the run
+    fn report(&mut self) -> Vec<Metric>;
+}
+
+/// One thing a MetricCollector measured, such as "nurse wait" and its average in seconds
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Metric {
+    pub name: String,
+    pub value: f64,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:.2}", self.name, self.value)
+    }
+}
+
+/// A summary of a whole run, built up by recording the people and the building after every
+/// step. A person's wait runs from when they appear until they board a car, their ride from
+/// then until they reach their floor, and a car is busy whenever it's moving, has somewhere
+/// to go, or has anyone aboard. People who give up waiting and take the stairs are counted
+/// separately, so a controller which leaves people waiting doesn't look better for it.
+/// Operators aren't counted as passengers. Time cars spend broken down is counted too, and
+/// so are people who found their floor's waiting area full when they arrived. Someone who
+/// changes banks at a sky lobby waits for their first car, rides from then until they reach
+/// their last floor, and their journey runs from when they appeared until then. The waits of
+/// people in wheelchairs are kept apart too, so it shows whether they wait longer
+#[derive(Clone, Debug, Default)]
+pub struct RunSummary {
+    // times are summed in f64, so long runs don't drift
+    time: f64,
+    waiting_since: HashMap<PersonId, f64>,
+    boarded: HashSet<PersonId>,
+    waits: Vec<f64>,
+    accessible_waits: Vec<f64>,
+    riding_since: HashMap<PersonId, f64>,
+    rides: Vec<f64>,
+    travelling_since: HashMap<PersonId, f64>,
+    journeys: Vec<f64>,
+    transferred: HashSet<PersonId>,
+    abandoned_waits: Vec<f64>,
+    delivered: usize,
+    gave_up: usize,
+    overflowed: usize,
+    car_time: f64,
+    busy_time: f64,
+    down_time: f64,
+    // whether each car was busy and broken down at the last record, which is how it spent
+    // the step after
+    cars: Vec<(bool, bool)>,
+}
+
+/// implement functions for RunSummary
+/// record - take in the people and building after a step of dt seconds
+/// delivered - how many people have reached their floor
+/// gave_up - how many people gave up waiting and took the stairs
+/// overflowed - how many people found the waiting area full
+/// throughput - people delivered per minute
+/// average_wait, max_wait - how long people waited for a car on average, and at most
+/// wait_percentile - how long people waited, at a percentile
+/// wait_gini, wait_ratio - how unevenly waits were shared out
+/// average_accessible_wait, average_other_wait - how long people in wheelchairs, and
+/// everyone else, waited on average
+/// average_ride - how long people spent in a car on average
+/// average_journey, transferred - how long people took to get from where they appeared to
+/// their floor, and how many changed banks on the way
+/// utilization - the share of the time cars were busy
+/// downtime - how long cars spent broken down
+impl RunSummary {
+    /// Take in the people and building after the simulation advanced by dt seconds
+    pub fn record(&mut self, dt: f32, people: &[Person], state: &BuildingState) {
+        let dt = dt as f64;
+        self.time += dt;
+
+        // operators work in the building rather than travelling through it
+        for person in people.iter().filter(|p| p.operates.is_none()) {
+            if !matches!(person.state, PersonState::Done | PersonState::GaveUp) {
+                self.travelling_since.entry(person.id).or_insert(self.time);
+            }
+            match person.state {
+                PersonState::New | PersonState::Waiting => {
+                    self.waiting_since.entry(person.id).or_insert(self.time);
+                }
+                PersonState::Transferring => {
+                    self.transferred.insert(person.id);
+                }
+                PersonState::Riding | PersonState::Done => {
+                    if self.boarded.insert(person.id) {
+                        // someone who boarded within the step they appeared didn't wait
+                        let since = self.waiting_since.remove(&person.id).unwrap_or(self.time);
+                        self.waits.push(self.time - since);
+                        if person.accessible() {
+                            self.accessible_waits.push(self.time - since);
+                        }
+                        self.riding_since.insert(person.id, self.time);
+                    }
+                    if person.state == PersonState::Done
+                        && let Some(since) = self.riding_since.remove(&person.id)
+                    {
+                        self.rides.push(self.time - since);
+                    }
+                    if person.state == PersonState::Done
+                        && let Some(since) = self.travelling_since.remove(&person.id)
+                    {
+                        self.journeys.push(self.time - since);
+                    }
+                }
+                PersonState::GaveUp => {
+                    self.travelling_since.remove(&person.id);
+                    if let Some(since) = self.waiting_since.remove(&person.id) {
+                        self.abandoned_waits.push(self.time - since);
+                    }
+                }
+            }
+        }
+        self.delivered = people
+            .iter()
+            .filter(|p| p.state == PersonState::Done)
+            .count();
+        self.gave_up = people
+            .iter()
+            .filter(|p| p.state == PersonState::GaveUp)
+            .count();
+        self.overflowed = people.iter().filter(|p| p.overflowed).count();
+
+        // a car spends a step as it was at the start of it, which matters when steps run
+        // from one event to the next. Before the first record, cars start as they are now
+        let cars: Vec<_> = state
+            .cars
+            .iter()
+            .map(|car| {
+                let busy =
+                    car.target_floor().is_some() || car.velocity != 0. || car.passenger_count > 0;
+                (busy, car.malfunction.is_some())
+            })
+            .collect();
+        for (i, &now) in cars.iter().enumerate() {
+            let (busy, down) = self.cars.get(i).copied().unwrap_or(now);
+            self.car_time += dt;
+            if busy {
+                self.busy_time += dt;
+            }
+            if down {
+                self.down_time += dt;
+            }
+        }
+        self.cars = cars;
+    }
+
+    /// How many people have reached their target floor
+    pub fn delivered(&self) -> usize {
+        self.delivered
+    }
+
+    /// How many people gave up waiting for a car and took the stairs
+    pub fn gave_up(&self) -> usize {
+        self.gave_up
+    }
+
+    /// How many people arrived to find their floor's waiting area full, and waited in the
+    /// back or left
+    pub fn overflowed(&self) -> usize {
+        self.overflowed
+    }
+
+    /// People delivered per minute, 0 before any time has passed
+    pub fn throughput(&self) -> f32 {
+        if self.time > 0. {
+            (self.delivered as f64 * 60. / self.time) as f32
+        } else {
+            0.
+        }
+    }
+
+    /// The average seconds people waited before boarding, or None if nobody has boarded
+    pub fn average_wait(&self) -> Option<f32> {
+        if self.waits.is_empty() {
+            None
+        } else {
+            Some((self.waits.iter().sum::<f64>() / self.waits.len() as f64) as f32)
+        }
+    }
+
+    /// The average seconds people in wheelchairs waited before boarding, or None if none of
+    /// them has boarded
+    pub fn average_accessible_wait(&self) -> Option<f32> {
+        if self.accessible_waits.is_empty() {
+            None
+        } else {
+            let total: f64 = self.accessible_waits.iter().sum();
+            Some((total / self.accessible_waits.len() as f64) as f32)
+        }
+    }
+
+    /// The average seconds everyone but people in wheelchairs waited before boarding, or None
+    /// if none of them has boarded
+    pub fn average_other_wait(&self) -> Option<f32> {
+        let others = self.waits.len() - self.accessible_waits.len();
+        if others == 0 {
+            None
+        } else {
+            let total = self.waits.iter().sum::<f64>() - self.accessible_waits.iter().sum::<f64>();
+            Some((total / others as f64) as f32)
+        }
+    }
+
+    /// The longest anyone waited before boarding, or None if nobody has boarded
+    pub fn max_wait(&self) -> Option<f32> {
+        self.waits
+            .iter()
+            .copied()
+            .reduce(f64::max)
+            .map(|w| w as f32)
+    }
+
+    /// The wait that a share p of people, from 0 to 1, waited no longer than, so 0.95 gives
+    /// the 95th percentile. Unlike the other waits, this counts people who gave up or are
+    /// still waiting by how long they had waited, so leaving people behind doesn't improve
+    /// it. None if nobody has appeared yet
+    pub fn wait_percentile(&self, p: f32) -> Option<f32> {
+        let waiting = self.waiting_since.values().map(|since| self.time - since);
+        let mut waits: Vec<f64> = self
+            .waits
+            .iter()
+            .chain(&self.abandoned_waits)
+            .copied()
+            .chain(waiting)
+            .collect();
+        if waits.is_empty() {
+            return None;
+        }
+        waits.sort_by(f64::total_cmp);
+        // the nearest rank, so the result is always someone's actual wait
+        let rank = (p.clamp(0., 1.) as f64 * waits.len() as f64).ceil() as usize;
+        Some(waits[rank.saturating_sub(1)] as f32)
+    }
+
+    /// The Gini coefficient of the waits, from 0 when everyone waited the same time, towards
+    /// 1 when a few people did all the waiting. None if nobody has boarded
+    pub fn wait_gini(&self) -> Option<f32> {
+        if self.waits.is_empty() {
+            return None;
+        }
+        let mut waits = self.waits.clone();
+        waits.sort_by(f64::total_cmp);
+        let total: f64 = waits.iter().sum();
+        if total <= 0. {
+            return Some(0.);
+        }
+        // with waits sorted, each is weighted by its rank
+        let n = waits.len() as f64;
+        let ranked: f64 = waits
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (i + 1) as f64 * w)
+            .sum();
+        Some((2. * ranked / (n * total) - (n + 1.) / n) as f32)
+    }
+
+    /// How many times longer the longest wait was than the average, or None if nobody has
+    /// boarded or nobody had to wait
+    pub fn wait_ratio(&self) -> Option<f32> {
+        let average = self.average_wait()?;
+        if average > 0. {
+            Some(self.max_wait()? / average)
+        } else {
+            None
+        }
+    }
+
+    /// The average seconds people spent in a car, or None if nobody has arrived yet
+    pub fn average_ride(&self) -> Option<f32> {
+        if self.rides.is_empty() {
+            None
+        } else {
+            Some((self.rides.iter().sum::<f64>() / self.rides.len() as f64) as f32)
+        }
+    }
+
+    /// The average seconds people took from appearing to reaching their floor, including
+    /// any changes at sky lobbies, or None if nobody has arrived yet
+    pub fn average_journey(&self) -> Option<f32> {
+        if self.journeys.is_empty() {
+            None
+        } else {
+            Some((self.journeys.iter().sum::<f64>() / self.journeys.len() as f64) as f32)
+        }
+    }
+
+    /// How many people have changed banks at a sky lobby
+    pub fn transferred(&self) -> usize {
+        self.transferred.len()
+    }
+
+    /// The share of the time, from 0 to 1, that cars were busy, averaged over every car
+    pub fn utilization(&self) -> f32 {
+        if self.car_time > 0. {
+            (self.busy_time / self.car_time) as f32
+        } else {
+            0.
+        }
+    }
+
+    /// How many seconds cars spent broken down, added up over every car
+    pub fn downtime(&self) -> f32 {
+        self.down_time as f32
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Simulated time: {:.1} s", self.time)?;
+        writeln!(
+            f,
+            "Throughput: {} people delivered ({:.2} per minute)",
+            self.delivered,
+            self.throughput()
+        )?;
+        writeln!(f, "Gave up and took the stairs: {}", self.gave_up)?;
+        if self.overflowed > 0 {
+            writeln!(f, "Found the waiting area full: {}", self.overflowed)?;
+        }
+        match self.average_wait() {
+            Some(wait) => writeln!(f, "Average wait: {wait:.1} s")?,
+            None => writeln!(f, "Average wait: nobody boarded")?,
+        }
+        // waits are only split up when someone in a wheelchair boarded
+        if let Some(accessible) = self.average_accessible_wait() {
+            match self.average_other_wait() {
+                Some(other) => writeln!(
+                    f,
+                    "Average wait in a wheelchair: {accessible:.1} s, everyone else {other:.1} s"
+                )?,
+                None => writeln!(f, "Average wait in a wheelchair: {accessible:.1} s")?,
+            }
+        }
+        if let (Some(gini), Some(ratio)) = (self.wait_gini(), self.wait_ratio()) {
+            writeln!(
+                f,
+                "Wait fairness: Gini {gini:.2}, longest wait {ratio:.1} times the average"
+            )?;
+        }
+        match self.average_ride() {
+            Some(ride) => writeln!(f, "Average ride: {ride:.1} s")?,
+            None => writeln!(f, "Average ride: nobody arrived")?,
+        }
+        // journeys only differ from a wait and a ride for people who changed banks
+        if let Some(journey) = self.average_journey()
+            && !self.transferred.is_empty()
+        {
+            writeln!(
+                f,
+                "Average journey: {journey:.1} s, {} changed banks at a sky lobby",
+                self.transferred.len()
+            )?;
+        }
+        write!(f, "Car utilization: {:.1}%", self.utilization() * 100.)?;
+        if self.down_time > 0. {
+            write!(f, "\nCar downtime: {:.1} s", self.down_time)?;
+        }
+        Ok(())
+    }
+}
+
+/// One person who reached their floor, for exporting
+/// spawn_time - the seconds into the run they appeared
+/// wait, ride - how many seconds they waited for their first car, and spent from then until
+/// they reached their floor, changing cars at any sky lobbies on the way
+/// destination - the floor their whole trip ended on
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PersonRecord {
+    pub person: PersonId,
+    pub spawn_time: f64,
+    pub wait: f64,
+    pub ride: f64,
+    pub origin: Floor,
+    pub destination: Floor,
+    pub car: Option<CarId>,
+}
+
+/// How many people were waiting on each floor, from the bottom up, and where each car was,
+/// time seconds into the run, when the clock read time_of_day seconds after midnight
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TickRecord {
+    pub time: f64,
+    #[serde(default)]
+    pub time_of_day: f64,
+    pub queues: Vec<u32>,
+    pub cars: Vec<f32>,
+}
+
+/// A record of a whole run for analysis outside the simulation, built up like RunSummary by
+/// recording after every step. It keeps a row for every person who reached their floor, in
+/// the order they got there, and a row for every step. Operators aren't counted as people
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MetricsLog {
+    people: Vec<PersonRecord>,
+    ticks: Vec<TickRecord>,
+    #[serde(skip)]
+    time: f64,
+    // the people still on their way, with when they boarded, if they have
+    #[serde(skip)]
+    travelling: HashMap<PersonId, (PersonRecord, Option<f64>)>,
+}
+
+/// implement functions for MetricsLog
+/// record - take in the people and building after a step of dt seconds
+/// from_rows - a log holding rows recorded elsewhere
+/// people, ticks - the rows recorded so far
+/// save, load - write the rows to CSV or JSON files, and read them back
+impl MetricsLog {
+    /// A log of rows already recorded, such as ones read from a file
+    pub fn from_rows(people: Vec<PersonRecord>, ticks: Vec<TickRecord>) -> Self {
+        Self {
+            people,
+            ticks,
+            ..Self::default()
+        }
+    }
+
+    /// Take in the people and building after the simulation advanced by dt seconds
+    pub fn record(&mut self, dt: f32, people: &[Person], state: &BuildingState) {
+        self.time += dt as f64;
+        let mut queues = vec![0; state.floors.len()];
+
+        for person in people.iter().filter(|p| p.operates.is_none()) {
+            match person.state {
+                PersonState::New
+                | PersonState::Waiting
+                | PersonState::Riding
+                | PersonState::Transferring => {
+                    let (record, boarded) = self.travelling.entry(person.id).or_insert_with(|| {
+                        let record = PersonRecord {
+                            person: person.id,
+                            spawn_time: self.time,
+                            wait: 0.,
+                            ride: 0.,
+                            origin: person.current_floor,
+                            destination: person.destination(),
+                            car: None,
+                        };
+                        (record, None)
+                    });
+                    if person.state == PersonState::Riding {
+                        boarded.get_or_insert(self.time);
+                        record.car = record.car.or(person.in_car);
+                    } else if person.state != PersonState::Transferring
+                        && let Some(queue) = queues.get_mut(person.current_floor as usize)
+                    {
+                        *queue += 1;
+                    }
+                }
+                // people only need following until they've finished their trip
+                PersonState::Done => {
+                    if let Some((mut record, boarded)) = self.travelling.remove(&person.id) {
+                        let boarded = boarded.unwrap_or(self.time);
+                        record.wait = boarded - record.spawn_time;
+                        record.ride = self.time - boarded;
+                        self.people.push(record);
+                    }
+                }
+                PersonState::GaveUp => {
+                    self.travelling.remove(&person.id);
+                }
+            }
+        }
+
+        self.ticks.push(TickRecord {
+            time: self.time,
+            time_of_day: state.clock.time_of_day(),
+            queues,
+            cars: state.cars.iter().map(|car| car.current_floor).collect(),
+        });
+    }
+
+    /// Everyone who has reached their floor, in the order they got there
+    pub fn people(&self) -> &[PersonRecord] {
+        &self.people
+    }
+
+    /// The queues and car positions after every step
+    pub fn ticks(&self) -> &[TickRecord] {
+        &self.ticks
+    }
+
+    /// Write the rows to path. A path ending in .json gets both tables as one JSON object.
+    /// Anything else gets the people as CSV, and the steps as CSV next to it, with .ticks
+    /// added before the extension, so out.csv comes with out.ticks.csv
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|e| e == "json") {
+            let file = BufWriter::new(File::create(path)?);
+            return Ok(serde_json::to_writer(file, self)?);
+        }
+
+        let mut people = BufWriter::new(File::create(path)?);
+        writeln!(people, "person,spawn_time,wait,ride,origin,destination,car")?;
+        for p in &self.people {
+            let car = p.car.map_or(String::new(), |car| car.0.to_string());
+            writeln!(
+                people,
+                "{},{:.2},{:.2},{:.2},{},{},{car}",
+                p.person.0, p.spawn_time, p.wait, p.ride, p.origin, p.destination
+            )?;
+        }
+        people.flush()?;
+
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("csv");
+        let mut ticks = BufWriter::new(File::create(
+            path.with_extension(format!("ticks.{extension}")),
+        )?);
+        let floors = self.ticks.first().map_or(0, |t| t.queues.len());
+        let cars = self.ticks.first().map_or(0, |t| t.cars.len());
+        let header: Vec<String> = ["time".to_string(), "time_of_day".to_string()]
+            .into_iter()
+            .chain((0..floors).map(|floor| format!("queue_{floor}")))
+            .chain((0..cars).map(|car| format!("car_{car}")))
+            .collect();
+        writeln!(ticks, "{}", header.join(","))?;
+        for tick in &self.ticks {
+            let row: Vec<String> = [
+                format!("{:.2}", tick.time),
+                format!("{:.2}", tick.time_of_day),
+            ]
+            .into_iter()
+            .chain(tick.queues.iter().map(|q| q.to_string()))
+            .chain(tick.cars.iter().map(|floor| format!("{floor:.3}")))
+            .collect();
+            writeln!(ticks, "{}", row.join(","))?;
+        }
+        ticks.flush()
+    }
+
+    /// Read back rows written by save. The steps are left empty if a CSV file's steps file
+    /// is missing
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|e| e == "json") {
+            return Ok(serde_json::from_str(&text)?);
+        }
+
+        let invalid = |line: usize, what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: line {line}: {what}", path.display()),
+            )
+        };
+        let mut people = Vec::new();
+        for (i, line) in text.lines().enumerate().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            let [person, spawn_time, wait, ride, origin, destination, car] = fields[..] else {
+                return Err(invalid(i + 1, "expected 7 columns"));
+            };
+            let number = |field: &str| field.parse::<f64>().map_err(|_| invalid(i + 1, field));
+            let whole = |field: &str| field.parse::<u32>().map_err(|_| invalid(i + 1, field));
+            people.push(PersonRecord {
+                person: PersonId(whole(person)?),
+                spawn_time: number(spawn_time)?,
+                wait: number(wait)?,
+                ride: number(ride)?,
+                origin: whole(origin)?,
+                destination: whole(destination)?,
+                car: match car {
+                    "" => None,
+                    car => Some(CarId(whole(car)?)),
+                },
+            });
+        }
+
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("csv");
+        let ticks_path = path.with_extension(format!("ticks.{extension}"));
+        let Ok(text) = fs::read_to_string(&ticks_path) else {
+            return Ok(Self::from_rows(people, Vec::new()));
+        };
+        let mut lines = text.lines().enumerate();
+        let header: Vec<&str> = lines
+            .next()
+            .map_or(Vec::new(), |(_, h)| h.split(',').collect());
+        let floors = header.iter().filter(|c| c.starts_with("queue_")).count();
+        // files written before the clock was added have no time of day
+        let has_clock = header.get(1) == Some(&"time_of_day");
+        let mut ticks = Vec::new();
+        for (i, line) in lines {
+            let bad = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: line {}: bad step", ticks_path.display(), i + 1),
+                )
+            };
+            let fields: Vec<&str> = line.split(',').collect();
+            let (time, mut rest) = fields.split_first().ok_or_else(bad)?;
+            let mut time_of_day = 0.;
+            if has_clock {
+                let (clock, after) = rest.split_first().ok_or_else(bad)?;
+                time_of_day = clock.parse().map_err(|_| bad())?;
+                rest = after;
+            }
+            let (queues, cars) = rest.split_at_checked(floors).ok_or_else(bad)?;
+            ticks.push(TickRecord {
+                time: time.parse().map_err(|_| bad())?,
+                time_of_day,
+                queues: queues
+                    .iter()
+                    .map(|q| q.parse().map_err(|_| bad()))
+                    .collect::<io::Result<_>>()?,
+                cars: cars
+                    .iter()
+                    .map(|c| c.parse().map_err(|_| bad()))
+                    .collect::<io::Result<_>>()?,
+            });
+        }
+        Ok(Self::from_rows(people, ticks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steady_calls_approach_true_rate() {
+        let mut est = ArrivalRateEstimator::new(3, 10.);
+
+        // one call every 2 seconds on floor 1 going up, for a long time
+        for _ in 0..500 {
+            est.record(1, Direction::Up);
+            est.tick(2.);
+        }
+
+        let rate = est.rate(1, Direction::Up);
+        assert!((rate - 0.5).abs() < 0.1);
+        assert_eq!(est.rate(1, Direction::Down), 0.);
+        assert_eq!(est.rate(0, Direction::Up), 0.);
+    }
+
+    #[test]
+    fn rates_decay_without_calls() {
+        let mut est = ArrivalRateEstimator::new(2, 5.);
+        est.record(0, Direction::Down);
+        let before = est.rate(0, Direction::Down);
+        est.tick(5.);
+
+        assert!(est.rate(0, Direction::Down) < before);
+        // out of range floors are ignored
+        est.record(9, Direction::Up);
+        assert_eq!(est.rate(9, Direction::Up), 0.);
+    }
+
+    #[test]
+    fn gini_measures_how_unevenly_people_waited() {
+        let mut summary = RunSummary::default();
+        assert_eq!(summary.wait_gini(), None);
+
+        // one person did all the waiting
+        summary.waits = vec![0., 0., 0., 12.];
+        assert!((summary.wait_gini().unwrap() - 0.75).abs() < 1e-6);
+        assert_eq!(summary.wait_ratio(), Some(4.));
+
+        summary.waits = vec![5., 5., 5.];
+        assert_eq!(summary.wait_gini(), Some(0.));
+
+        // of 20 waits, 19 are no longer than the 95th percentile
+        summary.waits = (1..=20).map(f64::from).collect();
+        assert_eq!(summary.wait_percentile(0.95), Some(19.));
+        assert_eq!(summary.wait_percentile(0.), Some(1.));
+        // someone still waiting counts too
+        summary.time = 100.;
+        summary.waiting_since.insert(PersonId(0), 10.);
+        assert_eq!(summary.wait_percentile(1.), Some(90.));
+    }
+
+    #[test]
+    fn summary_counts_deliveries_waits_and_busy_cars() {
+        use crate::control::BasicController;
+        use crate::elevator::ElevatorSim;
+        use crate::people::{PeopleConfig, PeopleSim};
+        use crate::sim::Simulation;
+        use crate::types::ScriptedArrival;
+
+        // the car starts on floor 0, so the person on floor 4 waits for it to get there
+        let arrivals = vec![ScriptedArrival {
+            time: 1.,
+            origin: 4,
+            destination: 0,
+        }];
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(5, arrivals, PeopleConfig::default()),
+            ElevatorSim::new(5, 1),
+            Box::new(BasicController),
+        );
+
+        let mut summary = RunSummary::default();
+        for _ in 0..600 {
+            sim.step(0.1);
+            summary.record(0.1, sim.people().people(), sim.building().state());
+        }
+
+        assert_eq!(summary.delivered(), 1);
+        let wait = summary.average_wait().unwrap();
+        assert!(wait > 3. && wait < 8.);
+        assert_eq!(summary.max_wait(), Some(wait));
+        // four floors at one floor per second
+        let ride = summary.average_ride().unwrap();
+        assert!(ride > 3.9 && ride < 4.5);
+        // with a single wait, nobody waited more than anyone else
+        assert_eq!(summary.wait_gini(), Some(0.));
+        assert!((summary.wait_ratio().unwrap() - 1.).abs() < 1e-6);
+        assert!(summary.utilization() > 0. && summary.utilization() < 1.);
+        assert!((summary.throughput() - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn collectors_measure_runs_their_own_way() {
+        use crate::control::BasicController;
+        use crate::elevator::ElevatorSim;
+        use crate::events::{SimEvent, TracedEvent};
+        use crate::people::{PeopleConfig, PeopleSim};
+        use crate::sim::Simulation;
+        use crate::types::ScriptedArrival;
+
+        let arrivals = vec![ScriptedArrival {
+            time: 1.,
+            origin: 4,
+            destination: 0,
+        }];
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(5, arrivals, PeopleConfig::default()),
+            ElevatorSim::new(5, 1),
+            Box::new(BasicController),
+        );
+
+        // a collector of its own measures the waits again, from the events
+        #[derive(Default)]
+        struct Waits {
+            spawned: HashMap<PersonId, f32>,
+            waits: Vec<f32>,
+        }
+        impl EventSink for Waits {
+            fn emit(&mut self, traced: &TracedEvent) {
+                match traced.event {
+                    SimEvent::PersonSpawned { person, .. } => {
+                        self.spawned.insert(person, traced.time);
+                    }
+                    SimEvent::PersonBoarded { person, .. } => {
+                        if let Some(spawned) = self.spawned.remove(&person) {
+                            self.waits.push(traced.time - spawned);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        impl MetricCollector for Waits {
+            fn report(&mut self) -> Vec<Metric> {
+                let total: f32 = self.waits.iter().sum();
+                vec![Metric {
+                    name: "wait".to_string(),
+                    value: (total / self.waits.len() as f32) as f64,
+                }]
+            }
+        }
+        sim.add_collector(Box::new(Waits::default()));
+
+        let mut summary = RunSummary::default();
+        for _ in 0..600 {
+            sim.step(0.1);
+            summary.record(0.1, sim.people().people(), sim.building().state());
+        }
+
+        let wait = summary.average_wait().unwrap();
+        let collected = sim.finish_collectors();
+        assert_eq!(collected.len(), 1);
+        assert!((collected[0].value - wait as f64).abs() < 0.2);
+        assert!(sim.finish_collectors().is_empty());
+    }
+
+    #[test]
+    fn metrics_log_exports_people_and_ticks() {
+        use crate::control::BasicController;
+        use crate::elevator::ElevatorSim;
+        use crate::people::{PeopleConfig, PeopleSim};
+        use crate::sim::Simulation;
+        use crate::types::ScriptedArrival;
+
+        let arrivals = vec![ScriptedArrival {
+            time: 1.,
+            origin: 4,
+            destination: 0,
+        }];
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(5, arrivals, PeopleConfig::default()),
+            ElevatorSim::new(5, 1),
+            Box::new(BasicController),
+        );
+        let mut log = MetricsLog::default();
+        for _ in 0..300 {
+            sim.step(0.1);
+            log.record(0.1, sim.people().people(), sim.building().state());
+        }
+
+        let [person] = log.people() else {
+            panic!("expected one person, got {:?}", log.people());
+        };
+        assert_eq!((person.origin, person.destination), (4, 0));
+        assert_eq!(person.car, Some(CarId(0)));
+        assert!((person.spawn_time - 1.).abs() < 0.15);
+        assert!(person.wait > 3. && person.ride > 3.9);
+        assert_eq!(log.ticks().len(), 300);
+        // the person queues on floor 4 while the car comes up from floor 0
+        let tick = &log.ticks()[20];
+        assert_eq!(tick.queues, vec![0, 0, 0, 0, 1]);
+        assert!(tick.cars[0] > 0. && tick.cars[0] < 4.);
+
+        let path = std::env::temp_dir().join(format!("metrics-{}.csv", std::process::id()));
+        log.save(&path).unwrap();
+        let people = std::fs::read_to_string(&path).unwrap();
+        let ticks_path = path.with_extension("ticks.csv");
+        let ticks = std::fs::read_to_string(&ticks_path).unwrap();
+        let loaded = MetricsLog::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&ticks_path).unwrap();
+        assert_eq!(people.lines().count(), 2);
+        assert!(people.lines().nth(1).unwrap().ends_with(",4,0,0"));
+        assert_eq!(
+            ticks.lines().next(),
+            Some("time,time_of_day,queue_0,queue_1,queue_2,queue_3,queue_4,car_0")
+        );
+        assert_eq!(ticks.lines().count(), 301);
+
+        // reading it back loses only the rounding
+        assert_eq!(loaded.people().len(), 1);
+        assert_eq!(loaded.people()[0].car, Some(CarId(0)));
+        assert!((loaded.people()[0].wait - person.wait).abs() < 0.01);
+        assert_eq!(loaded.ticks().len(), 300);
+        assert_eq!(loaded.ticks()[20].queues, tick.queues);
+    }
+}
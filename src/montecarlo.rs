@@ -0,0 +1,202 @@
+use crate::batch;
+use crate::config::{ConfigError, ControllerChoice, SimConfig};
+use crate::metrics::RunSummary;
+use std::fmt;
+
+/// Student's t for a two sided 95% confidence interval, by degrees of freedom from 1 to 30
+const T_95: [f32; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Student's t for a two sided 95% confidence interval with df degrees of freedom. Past 30
+/// it's close enough to the normal distribution's 1.96 that a few steps towards it will do
+fn t_95(df: usize) -> f32 {
+    match df {
+        ..=30 => T_95[df.saturating_sub(1)],
+        31..=40 => 2.021,
+        41..=60 => 2.000,
+        61..=120 => 1.980,
+        _ => 1.960,
+    }
+}
+
+/// What a metric's mean over many runs is, and how sure we can be of it
+/// runs - how many runs the metric had a value in
+/// mean, std_dev - the mean and sample standard deviation over those runs
+/// half_width - how far either side of the mean the 95% confidence interval reaches
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub runs: usize,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub half_width: f32,
+}
+
+impl Estimate {
+    /// The estimate from some values, or None if there aren't any. With only one, nothing
+    /// is known about the spread, so the interval is infinitely wide
+    pub fn of(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len();
+        let mean = values.iter().sum::<f32>() / n as f32;
+        let squares = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>();
+        if n == 1 {
+            return Some(Self {
+                runs: 1,
+                mean,
+                std_dev: 0.,
+                half_width: f32::INFINITY,
+            });
+        }
+        let std_dev = (squares / (n - 1) as f32).sqrt();
+        Some(Self {
+            runs: n,
+            mean,
+            std_dev,
+            half_width: t_95(n - 1) * std_dev / (n as f32).sqrt(),
+        })
+    }
+
+    /// The lowest and highest values the 95% confidence interval takes in
+    pub fn interval(&self) -> (f32, f32) {
+        (self.mean - self.half_width, self.mean + self.half_width)
+    }
+}
+
+/// Every controller's summaries over the seeded runs, in the order the controllers were
+/// given. Displaying it prints each controller's mean wait with its 95% confidence interval
+#[derive(Clone, Debug, Default)]
+pub struct MonteCarlo {
+    pub results: Vec<(ControllerChoice, Vec<RunSummary>)>,
+}
+
+impl MonteCarlo {
+    /// The average wait's estimate for a controller, over the runs anybody boarded in
+    pub fn wait(&self, controller: ControllerChoice) -> Option<Estimate> {
+        let (_, summaries) = self.results.iter().find(|&&(c, _)| c == controller)?;
+        let waits: Vec<f32> = summaries
+            .iter()
+            .filter_map(RunSummary::average_wait)
+            .collect();
+        Estimate::of(&waits)
+    }
+}
+
+/// Run the simulation config describes runs times with each controller, seeding run i with
+/// config's seed plus i, or i if it has none. Every controller is run on the same seeds, so
+/// they see the same people arrive. The runs are a batch, so with the parallel feature they
+/// share out over several threads, see batch::run_batch
+pub fn montecarlo(
+    config: &SimConfig,
+    controllers: &[ControllerChoice],
+    runs: u32,
+) -> Result<MonteCarlo, ConfigError> {
+    let first = config.seed.unwrap_or(0);
+    let configs: Vec<SimConfig> = controllers
+        .iter()
+        .flat_map(|&controller| {
+            (0..runs as u64).map(move |run| SimConfig {
+                controller,
+                seed: Some(first + run),
+                ..config.clone()
+            })
+        })
+        .collect();
+    let summaries = batch::run_batch(&configs);
+
+    let mut montecarlo = MonteCarlo::default();
+    for (run, summary) in configs.iter().zip(summaries) {
+        let controller = run.controller;
+        let summary = summary?;
+        match montecarlo
+            .results
+            .iter_mut()
+            .find(|(c, _)| *c == controller)
+        {
+            Some((_, summaries)) => summaries.push(summary),
+            None => montecarlo.results.push((controller, vec![summary])),
+        }
+    }
+    Ok(montecarlo)
+}
+
+impl fmt::Display for MonteCarlo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .results
+            .iter()
+            .map(|(c, _)| c.name().len())
+            .chain(["Controller".len()])
+            .max()
+            .unwrap_or(0);
+        let header = format!(
+            "{:<width$} | Runs | Avg wait |  Std dev |      95% confidence interval",
+            "Controller"
+        );
+        write!(f, "{header}\n{}", "-".repeat(header.len()))?;
+        for &(controller, ref summaries) in &self.results {
+            let name = controller.name();
+            // a controller nobody boarded with in any run is shown with a dash
+            let Some(wait) = self.wait(controller) else {
+                write!(f, "\n{name:<width$} | {:>4} | {:>8}", summaries.len(), "-")?;
+                continue;
+            };
+            let (low, high) = wait.interval();
+            write!(
+                f,
+                "\n{name:<width$} | {:>4} | {:>8.2} | {:>8.2} | {:>12.2} s to {:>8.2} s",
+                wait.runs, wait.mean, wait.std_dev, low, high
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_widen_with_spread_and_narrow_with_runs() {
+        let few = Estimate::of(&[4., 6.]).unwrap();
+        assert_eq!((few.mean, few.std_dev), (5., 2f32.sqrt()));
+        assert!((few.half_width - 12.706).abs() < 1e-3);
+
+        let many = Estimate::of(&[4., 6., 4., 6., 4., 6., 4., 6.]).unwrap();
+        assert_eq!(many.mean, 5.);
+        assert!(many.half_width < few.half_width);
+        let (low, high) = many.interval();
+        assert!(low < 5. && high > 5.);
+
+        assert!(Estimate::of(&[3.]).unwrap().half_width.is_infinite());
+        assert_eq!(Estimate::of(&[]), None);
+    }
+
+    #[test]
+    fn every_controller_runs_on_the_same_seeds() {
+        let config = SimConfig {
+            floors: 6,
+            steps: 600,
+            seed: Some(2),
+            ..SimConfig::default()
+        };
+        let controllers = [ControllerChoice::Basic, ControllerChoice::Group];
+        let results = montecarlo(&config, &controllers, 3).unwrap();
+        assert_eq!(results.results.len(), 2);
+        assert!(results.results.iter().all(|(_, runs)| runs.len() == 3));
+
+        // each run repeats what the same seed does on its own
+        let again = batch::run(&SimConfig {
+            seed: Some(3),
+            ..config.clone()
+        })
+        .unwrap();
+        assert_eq!(results.results[0].1[1].delivered(), again.delivered());
+        assert!(results.wait(ControllerChoice::Basic).is_some());
+        assert_eq!(results.to_string().lines().count(), 4);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::control::ElevatorController;
+use crate::elevator::BuildingState;
+use crate::metrics::StatsView;
+use crate::people::{Person, PersonState};
+use crate::queue::DemandInfo;
+use crate::view::BuildingView;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+/// How much the controller is shown about the people inside each car
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Observation {
+    /// only the car buttons lit inside each car, as a real controller sees them. Several
+    /// people going to the same floor light a single button
+    #[default]
+    Classic,
+    /// every rider's destination as well, in ElevatorCarState::rider_destinations
+    Rich,
+}
+
+/// What the controller is allowed to see, applied to the building's state and statistics
+/// before every tick, so a controller can't use what an experiment means to hide from it
+/// riders - how much it's shown about the people inside each car
+/// hide_loads - hide how many people are in each car, and who they are, as if the cars had
+/// no load sensors. Cars never look full
+/// hide_arrival_rates - hide the arrival rates collected so far, which all read as 0
+/// hide_queues - hide the summary of the people and the demand they make, such as how many
+/// are waiting on each floor, as a building without cameras in its halls can't count them.
+/// The demand only gives riders' destinations under a rich observation
+/// hide_future_arrivals - refuse controllers told who will arrive before they do, like
+/// OracleController
+///
+/// The default hides nothing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ObservationFilter {
+    pub riders: Observation,
+    pub hide_loads: bool,
+    pub hide_arrival_rates: bool,
+    pub hide_queues: bool,
+    pub hide_future_arrivals: bool,
+}
+
+/// The reasons a controller can't run with an ObservationFilter
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObservationError {
+    /// the controller knows who will arrive, which the filter hides
+    FutureArrivals,
+}
+
+impl fmt::Display for ObservationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObservationError::FutureArrivals => {
+                write!(f, "the controller knows who will arrive, which is hidden")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObservationError {}
+
+/// implement functions for ObservationFilter
+/// allows - check a controller can run under the filter
+/// view, state - the building as the controller sees it
+/// stats - the statistics as the controller sees them
+/// demand - the demand people make, as the controller sees it
+impl ObservationFilter {
+    /// Check that a controller doesn't know anything the filter hides, before it's run
+    pub fn allows(&self, controller: &dyn ElevatorController) -> Result<(), ObservationError> {
+        if self.hide_future_arrivals && controller.sees_future_arrivals() {
+            return Err(ObservationError::FutureArrivals);
+        }
+        Ok(())
+    }
+
+    /// The building as the controller sees it, borrowing the building's state. Only a rich
+    /// observation gathers anything, the riders' destinations
+    pub fn view<'a>(&self, state: &'a BuildingState, people: &[Person]) -> BuildingView<'a> {
+        let riders = (self.riders == Observation::Rich).then(|| {
+            let mut riders = vec![Vec::new(); state.cars.len()];
+            for person in people {
+                if let (PersonState::Riding, Some(car_id)) = (&person.state, person.in_car)
+                    && let Some(car) = riders.get_mut(car_id.0 as usize)
+                {
+                    car.push(person.target_floor);
+                }
+            }
+            riders
+        });
+        BuildingView::filtered(state, riders, self.hide_loads)
+    }
+
+    /// The building as the controller sees it, as a BuildingState. Anything beyond the
+    /// building's own state needs a copy of it, so only a filter that changes nothing
+    /// borrows it
+    pub fn state<'a>(&self, state: &'a BuildingState, people: &[Person]) -> Cow<'a, BuildingState> {
+        self.view(state, people).to_state()
+    }
+
+    /// The demand people make on the building, or None if it's hidden. Riders' destinations
+    /// are only given under a rich observation, and how many there are not if loads are hidden
+    pub fn demand(&self, state: &BuildingState, people: &[Person]) -> Option<DemandInfo> {
+        if self.hide_queues {
+            return None;
+        }
+        let reveal = self.riders == Observation::Rich;
+        Some(DemandInfo::new(state, people, reveal, self.hide_loads))
+    }
+
+    /// The statistics as the controller sees them
+    pub fn stats<'a>(&self, stats: StatsView<'a>) -> StatsView<'a> {
+        let stats = if self.hide_arrival_rates {
+            stats.without_arrival_rates()
+        } else {
+            stats
+        };
+        if self.hide_queues {
+            stats.without_people()
+        } else {
+            stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{BasicController, OracleController};
+    use crate::elevator::{ElevatorCommand, ElevatorSim};
+    use crate::people::{PeopleConfig, PeopleSim, PersonOptions};
+    use crate::types::{CarId, Direction, Floor};
+
+    #[test]
+    fn hidden_loads_and_rates_read_as_empty() {
+        let mut building = ElevatorSim::new(4, 1);
+        building
+            .apply_command(ElevatorCommand::PressOutButton {
+                floor: 2,
+                direction: Direction::Up,
+                bank: None,
+            })
+            .unwrap();
+        building.tick(0.1);
+        let mut state = building.state().clone();
+        state.cars[0].passenger_count = state.cars[0].capacity;
+
+        let open = ObservationFilter::default();
+        assert!(open.state(&state, &[]).cars[0].is_full());
+        assert!(open.stats(building.stats()).floor_arrival_rate(2) > 0.);
+
+        let blind = ObservationFilter {
+            hide_loads: true,
+            hide_arrival_rates: true,
+            ..ObservationFilter::default()
+        };
+        let seen = blind.state(&state, &[]);
+        assert!(!seen.cars[0].is_full());
+        let stats = blind.stats(building.stats());
+        assert_eq!(stats.num_floors(), 4);
+        assert!((0..4).all(|f: Floor| stats.floor_arrival_rate(f) == 0.));
+    }
+
+    #[test]
+    fn demand_counts_people_without_naming_them() {
+        let building = ElevatorSim::new(4, 2);
+        let mut people = PeopleSim::scripted(4, Vec::new(), PeopleConfig::default());
+        for (origin, destination) in [(1, 3), (1, 2), (2, 0), (0, 3)] {
+            people.add_person(origin, destination, PersonOptions::default());
+        }
+        let rider = &mut people.people_mut()[3];
+        rider.state = PersonState::Riding;
+        rider.in_car = Some(CarId(1));
+
+        let classic = ObservationFilter::default();
+        let demand = classic.demand(building.state(), people.people()).unwrap();
+        assert_eq!(demand.waiting(1, Direction::Up), 2);
+        assert_eq!(demand.waiting(2, Direction::Down), 1);
+        assert_eq!(demand.waiting(2, Direction::Up), 0);
+        assert_eq!(demand.cars[1].riders, 1);
+        assert_eq!(demand.cars[1].destinations, None);
+
+        // a rich observation reveals where riders are going, and hidden queues hide it all
+        let rich = ObservationFilter {
+            riders: Observation::Rich,
+            hide_loads: true,
+            ..ObservationFilter::default()
+        };
+        let demand = rich.demand(building.state(), people.people()).unwrap();
+        assert_eq!(demand.cars[1].riders, 0);
+        assert_eq!(demand.cars[1].destinations, Some(vec![3]));
+        let blind = ObservationFilter {
+            hide_queues: true,
+            ..ObservationFilter::default()
+        };
+        assert!(blind.demand(building.state(), people.people()).is_none());
+    }
+
+    #[test]
+    fn hiding_future_arrivals_refuses_the_oracle() {
+        let filter = ObservationFilter {
+            hide_future_arrivals: true,
+            ..ObservationFilter::default()
+        };
+        let oracle = OracleController::new(&[], &ElevatorSim::new(5, 1));
+        assert_eq!(
+            filter.allows(&oracle),
+            Err(ObservationError::FutureArrivals)
+        );
+        assert_eq!(filter.allows(&BasicController), Ok(()));
+        assert_eq!(ObservationFilter::default().allows(&oracle), Ok(()));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::control::{BasicController, ElevatorController};
+use crate::elevator::ElevatorCommand;
+use crate::features::{encode_state, feature_len};
+use crate::metrics::StatsView;
+use crate::types::Floor;
+use crate::view::BuildingView;
+use std::path::Path;
+use tract_onnx::prelude::*;
+use tract_onnx::tract_core::internal::bail;
+
+/// The type of a loaded and optimized ONNX model, ready to run
+type OnnxPlan = Arc<TypedSimplePlan>;
+
+/// A controller which runs an ONNX model every decision epoch. The model takes the feature
+/// vector from features::encode_state, with shape [1, feature_len], and returns one score
+/// for every car and floor pair, with shape [1, cars * floors]. Each idle car is sent to
+/// the highest scoring floor which has a button pressed. If the model fails to run, or its
+/// output is the wrong size, BasicController makes the decision instead
+pub struct OnnxController {
+    model: OnnxPlan,
+    num_floors: usize,
+    num_cars: usize,
+    fallback: BasicController,
+    failures: u32,
+}
+
+impl OnnxController {
+    /// Load a model from an ONNX file, for a building with num_floors floors and
+    /// num_cars cars
+    pub fn load(path: impl AsRef<Path>, num_floors: usize, num_cars: usize) -> TractResult<Self> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, feature_len(num_floors, num_cars)]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+
+        Ok(Self {
+            model,
+            num_floors,
+            num_cars,
+            fallback: BasicController,
+            failures: 0,
+        })
+    }
+
+    /// The number of decision epochs where inference failed and the fallback was used
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Run the model on the current state, returning one score per car and floor
+    fn scores(&self, view: &BuildingView, stats: &StatsView) -> TractResult<Vec<f32>> {
+        let features = encode_state(view, stats);
+        let input = Tensor::from_shape(&[1, features.len()], &features)?;
+        let outputs = self.model.run(tvec!(input.into()))?;
+        let scores: Vec<f32> = outputs[0]
+            .to_plain_array_view::<f32>()?
+            .iter()
+            .copied()
+            .collect();
+
+        if scores.len() != self.num_cars * self.num_floors {
+            bail!(
+                "expected {} scores, model returned {}",
+                self.num_cars * self.num_floors,
+                scores.len()
+            );
+        }
+        Ok(scores)
+    }
+}
+
+impl ElevatorController for OnnxController {
+    /// Send each idle car to the floor the model scores highest, among floors that
+    /// have a hall button or that car's button pressed
+    fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand> {
+        // a building of a different size than the model was loaded for can't be encoded
+        if view.floors().len() != self.num_floors || view.num_cars() != self.num_cars {
+            self.failures += 1;
+            return self.fallback.tick(view, stats);
+        }
+
+        let scores = match self.scores(view, stats) {
+            Ok(scores) => scores,
+            Err(_) => {
+                self.failures += 1;
+                return self.fallback.tick(view, stats);
+            }
+        };
+
+        let mut commands = Vec::new();
+        for (car_index, car) in view.cars().enumerate() {
+            if car.target_floor().is_some() || !car.dispatchable() {
+                continue;
+            }
+
+            let car_scores =
+                &scores[car_index * self.num_floors..(car_index + 1) * self.num_floors];
+            let mut best: Option<(usize, f32)> = None;
+
+            for (floor_index, &score) in car_scores.iter().enumerate() {
+                let floor_state = &view.floors()[floor_index];
+                let wanted = floor_state.called() || car.pressed(floor_index as Floor);
+                if !wanted || !car.serves(floor_index as Floor) {
+                    continue;
+                }
+
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some((floor_index, score));
+                }
+            }
+
+            if let Some((floor_index, _)) = best {
+                commands.push(ElevatorCommand::MoveCarTo {
+                    car_id: car.id(),
+                    floor: floor_index as Floor,
+                });
+            }
+        }
+
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_model_is_an_error() {
+        assert!(OnnxController::load("does-not-exist.onnx", 3, 1).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::control::ElevatorController;
+use crate::elevator::{DoorState, ElevatorCommand};
+use crate::forecast::DemandForecaster;
+use crate::metrics::StatsView;
+use crate::types::{CarId, Floor};
+use crate::view::{BuildingView, CarView};
+use std::collections::{HashMap, HashSet};
+
+/// Decides where cars with nothing to do wait for their next call. Controllers can ask one
+/// themselves, or be wrapped in Parked to have one decide for them
+pub trait ParkingPolicy: Send {
+    /// The floor each of the idle cars should wait on. Cars left out stay where they are
+    fn park(
+        &mut self,
+        view: &BuildingView,
+        stats: &StatsView,
+        idle: &[CarId],
+    ) -> Vec<(CarId, Floor)>;
+}
+
+/// Leave every idle car where it finished, as if there were no parking policy
+pub struct StayPut;
+
+impl ParkingPolicy for StayPut {
+    fn park(
+        &mut self,
+        _view: &BuildingView,
+        _stats: &StatsView,
+        _idle: &[CarId],
+    ) -> Vec<(CarId, Floor)> {
+        Vec::new()
+    }
+}
+
+/// Send every idle car back to the lobby, the building's first entrance, ready for the
+/// people coming in from outside. Good for up-peak, when nearly every trip starts there
+pub struct ReturnToLobby;
+
+impl ParkingPolicy for ReturnToLobby {
+    fn park(
+        &mut self,
+        view: &BuildingView,
+        _stats: &StatsView,
+        idle: &[CarId],
+    ) -> Vec<(CarId, Floor)> {
+        let lobby = view.topology().lobby();
+        idle.iter()
+            .filter(|&&car_id| view.car(car_id.0 as usize).is_some_and(|c| c.serves(lobby)))
+            .map(|&car_id| (car_id, lobby))
+            .collect()
+    }
+}
+
+/// Give every car that can be dispatched a home, spread evenly up the building with the
+/// first at the bottom, so wherever the next call comes from a car is close. Homes a car is
+/// standing at or on its way to are taken, and each other idle car, from the bottom up, is
+/// sent to the nearest home left that it stops at
+pub struct DistributeEvenly;
+
+impl ParkingPolicy for DistributeEvenly {
+    fn park(
+        &mut self,
+        view: &BuildingView,
+        _stats: &StatsView,
+        idle: &[CarId],
+    ) -> Vec<(CarId, Floor)> {
+        let floors = view.floors().len();
+        let count = view.cars().filter(|car| car.dispatchable()).count();
+        let all: Vec<Floor> = (0..count).map(|i| (i * floors / count) as Floor).collect();
+        let standing_at = |car: &CarView| {
+            let floor = car.position().round() as Floor;
+            (car.position() == floor as f32).then_some(floor)
+        };
+        let mut homes = all.clone();
+        for car in view.cars() {
+            if let Some(at) = car.target_floor().or_else(|| standing_at(&car)) {
+                homes.retain(|&home| home != at);
+            }
+        }
+
+        // cars already standing at a home stay there
+        let mut cars: Vec<_> = idle
+            .iter()
+            .filter_map(|&car_id| view.car(car_id.0 as usize))
+            .filter(|car| standing_at(car).is_none_or(|at| !all.contains(&at)))
+            .collect();
+        cars.sort_by(|a, b| a.position().total_cmp(&b.position()));
+        let mut parked = Vec::new();
+        for car in cars {
+            let nearest = homes
+                .iter()
+                .copied()
+                .filter(|&home| car.serves(home))
+                .min_by(|a, b| {
+                    let distance = |home: Floor| (home as f32 - car.position()).abs();
+                    distance(*a).total_cmp(&distance(*b))
+                });
+            if let Some(home) = nearest {
+                homes.retain(|&h| h != home);
+                parked.push((car.id(), home));
+            }
+        }
+        parked
+    }
+}
+
+/// Send idle cars to the floors a DemandForecaster expects the most hall calls from over
+/// the next horizon seconds, so a car is already waiting where the next call is likeliest.
+/// The busiest floors, one for each car that can be dispatched, are wanted, busiest first.
+/// Those a car is standing at or on its way to are taken, and each one left is given the
+/// nearest idle car that stops there. Floors nobody is expected to call from are never
+/// wanted, so with no arrivals seen yet, or arrival rates hidden, cars stay where they are
+pub struct ForecastParking {
+    forecaster: Box<dyn DemandForecaster>,
+    pub horizon: f32,
+}
+
+impl ForecastParking {
+    /// Park cars where forecaster expects calls over the next horizon seconds
+    pub fn new(forecaster: Box<dyn DemandForecaster>, horizon: f32) -> Self {
+        Self {
+            forecaster,
+            horizon,
+        }
+    }
+}
+
+impl ParkingPolicy for ForecastParking {
+    fn park(
+        &mut self,
+        view: &BuildingView,
+        stats: &StatsView,
+        idle: &[CarId],
+    ) -> Vec<(CarId, Floor)> {
+        let forecast = self.forecaster.forecast(stats, self.horizon);
+        let mut wanted: Vec<Floor> = (0..forecast.len() as Floor)
+            .filter(|&floor| forecast[floor as usize] > 0.)
+            .collect();
+        wanted.sort_by(|a, b| forecast[*b as usize].total_cmp(&forecast[*a as usize]));
+        wanted.truncate(view.cars().filter(|car| car.dispatchable()).count());
+
+        let standing_at = |car: &CarView| {
+            let floor = car.position().round() as Floor;
+            (car.position() == floor as f32).then_some(floor)
+        };
+        let mut cars: Vec<CarView> = idle
+            .iter()
+            .filter_map(|&car_id| view.car(car_id.0 as usize))
+            .collect();
+        for car in view.cars() {
+            if let Some(at) = car.target_floor().or_else(|| standing_at(&car))
+                && wanted.contains(&at)
+            {
+                wanted.retain(|&floor| floor != at);
+                cars.retain(|c| c.id() != car.id());
+            }
+        }
+
+        let mut parked = Vec::new();
+        for floor in wanted {
+            let nearest = cars
+                .iter()
+                .enumerate()
+                .filter(|(_, car)| car.serves(floor))
+                .min_by(|(_, a), (_, b)| {
+                    let distance = |car: &CarView| (floor as f32 - car.position()).abs();
+                    distance(a).total_cmp(&distance(b))
+                })
+                .map(|(i, _)| i);
+            if let Some(i) = nearest {
+                parked.push((cars.remove(i).id(), floor));
+            }
+        }
+        parked
+    }
+}
+
+/// A controller which lets another make every decision, then sends any car left idle for
+/// delay seconds to where a ParkingPolicy says. A car is idle when it can be dispatched, is
+/// standing with its door closed or left open with no one using it, and has nowhere to go,
+/// no buttons lit inside, and no command from the controller this tick
+/// idle_since - when each idle car became idle, in seconds into the run
+pub struct Parked {
+    controller: Box<dyn ElevatorController>,
+    policy: Box<dyn ParkingPolicy>,
+    pub delay: f32,
+    idle_since: HashMap<CarId, f64>,
+}
+
+impl Parked {
+    /// Wrap controller, parking the cars it leaves idle for delay seconds by policy
+    pub fn new(
+        controller: Box<dyn ElevatorController>,
+        policy: Box<dyn ParkingPolicy>,
+        delay: f32,
+    ) -> Self {
+        Self {
+            controller,
+            policy,
+            delay,
+            idle_since: HashMap::new(),
+        }
+    }
+}
+
+impl ElevatorController for Parked {
+    /// Give the wrapped controller's commands, and park whichever cars it's left idle long
+    /// enough
+    fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand> {
+        let mut commands = self.controller.tick(view, stats);
+        let now = view.clock().elapsed;
+        let commanded: HashSet<CarId> = commands.iter().filter_map(|c| c.car_id()).collect();
+        let idle: HashSet<CarId> = view
+            .cars()
+            .filter(|car| {
+                car.dispatchable()
+                    && match car.door() {
+                        DoorState::Closed => true,
+                        DoorState::Open { dwell_remaining } => dwell_remaining <= 0.,
+                        _ => false,
+                    }
+                    && car.transfer().is_none()
+                    && car.velocity() == 0.
+                    && car.target_floor().is_none()
+                    && !car.car_buttons().contains(&true)
+                    && !commanded.contains(&car.id())
+            })
+            .map(|car| car.id())
+            .collect();
+        self.idle_since.retain(|car_id, _| idle.contains(car_id));
+        let mut ready: Vec<CarId> = idle
+            .into_iter()
+            .filter(|&car_id| {
+                let since = *self.idle_since.entry(car_id).or_insert(now);
+                now - since >= self.delay as f64
+            })
+            .collect();
+        if ready.is_empty() {
+            return commands;
+        }
+        ready.sort_by_key(|car_id| car_id.0);
+
+        for (car_id, floor) in self.policy.park(view, stats, &ready) {
+            let here = view
+                .car(car_id.0 as usize)
+                .is_some_and(|car| car.position() == floor as f32);
+            if !here && ready.contains(&car_id) {
+                commands.push(ElevatorCommand::MoveCarTo { car_id, floor });
+            }
+        }
+        commands
+    }
+
+    fn sees_future_arrivals(&self) -> bool {
+        self.controller.sees_future_arrivals()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::BasicController;
+    use crate::elevator::ElevatorSim;
+    use crate::people::{PeopleConfig, PeopleSim};
+    use crate::sim::Simulation;
+    use crate::types::ScriptedArrival;
+
+    #[test]
+    fn idle_cars_go_where_the_policy_parks_them() {
+        // both cars take someone to the top floor, and are left there
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 0.,
+                origin: 0,
+                destination: 9,
+            },
+            ScriptedArrival {
+                time: 0.,
+                origin: 1,
+                destination: 9,
+            },
+        ];
+        let run = |policy: Box<dyn ParkingPolicy>| {
+            let people = PeopleSim::scripted(10, arrivals.clone(), PeopleConfig::default());
+            let controller = Parked::new(Box::new(BasicController), policy, 5.);
+            let mut sim = Simulation::new(people, ElevatorSim::new(10, 2), Box::new(controller));
+            for _ in 0..2000 {
+                sim.step(0.1);
+            }
+            let mut floors: Vec<f32> = sim
+                .building()
+                .state()
+                .cars
+                .iter()
+                .map(|car| car.current_floor)
+                .collect();
+            floors.sort_by(f32::total_cmp);
+            floors
+        };
+        assert_eq!(run(Box::new(StayPut)), vec![9., 9.]);
+        assert_eq!(run(Box::new(ReturnToLobby)), vec![0., 0.]);
+        assert_eq!(run(Box::new(DistributeEvenly)), vec![0., 5.]);
+    }
+
+    #[test]
+    fn forecast_parking_waits_where_calls_are_expected() {
+        use crate::elevator::ElevatorCommand;
+        use crate::forecast::EmaForecaster;
+        use crate::types::Direction;
+
+        let mut sim = ElevatorSim::new(10, 2);
+        for (floor, direction) in [(3, Direction::Up), (3, Direction::Down), (7, Direction::Up)] {
+            sim.apply_command(ElevatorCommand::PressOutButton {
+                floor,
+                direction,
+                bank: None,
+            })
+            .unwrap();
+        }
+        let mut policy = ForecastParking::new(Box::new(EmaForecaster), 60.);
+        let view = BuildingView::new(sim.state());
+        let idle = [CarId(0), CarId(1)];
+        assert_eq!(
+            policy.park(&view, &sim.stats(), &idle),
+            vec![(CarId(0), 3), (CarId(1), 7)]
+        );
+
+        // without arrival rates nothing is expected, so the cars stay put
+        let blind = sim.stats().without_arrival_rates();
+        assert!(policy.park(&view, &blind, &idle).is_empty());
+    }
+}
@@ -1,218 +1,3352 @@
-use crate::elevator::BuildingState;
-use crate::types::{CarId, Direction, Floor, PersonId};
-use rand::Rng;
+use crate::clock::{DAY, SimClock};
+use crate::elevator::{
+    BuildingState, CarCapabilities, CarClass, DoorState, ElevatorCarState, ElevatorEvent,
+    EmergencyMode, FloorState, ServiceState, VipStage,
+};
+use crate::queue::{HallQueues, PeopleSummary};
+use crate::rng::{self, Stream};
+use crate::snapshot::{self, SnapshotError};
+use crate::topology::BuildingTopology;
+use crate::types::{
+    BankId, CarId, Direction, Floor, FloorMismatch, GroupId, PersonId, ScriptedArrival,
+};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
 
 /// enum of actions people can take
 #[derive(Debug)]
 pub enum PersonAction {
-    CallElevator { floor: Floor, direction: Direction },
-    PressCarButton { car_id: CarId, floor: Floor },
+    CallElevator {
+        floor: Floor,
+        direction: Direction,
+        bank: Option<BankId>,
+    },
+    CallAccessible {
+        floor: Floor,
+        direction: Direction,
+        bank: Option<BankId>,
+    },
+    PressCarButton {
+        car_id: CarId,
+        floor: Floor,
+    },
+    RequestTrip {
+        floor: Floor,
+        destination: Floor,
+    },
+    EnterCar {
+        car_id: CarId,
+        person_id: PersonId,
+        weight: f32,
+    },
+    ExitCar {
+        car_id: CarId,
+        person_id: PersonId,
+        weight: f32,
+    },
+    TakeOverCar {
+        car_id: CarId,
+    },
+    OperateCar {
+        car_id: CarId,
+        floor: Floor,
+    },
+    HoldDoor {
+        car_id: CarId,
+    },
+    AuthorizeFloor {
+        car_id: CarId,
+        floor: Floor,
+    },
 }
 
-/// enum of states people can be in
-#[derive(Debug)]
+/// enum of states people can be in. Someone who waits longer than their patience gives up
+/// and takes the stairs. Someone changing banks at a sky lobby is Transferring while they
+/// walk over to the next bank's hall, then New again there
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
 pub enum PersonState {
     New,
     Waiting,
     Riding,
+    Transferring,
     Done,
+    GaveUp,
 }
 
-/// Person object, contains an id, current floor, target floor, state, and
-/// an optional elevator car id
-#[derive(Debug)]
+/// Person object, contains an id, current floor, the floor they're riding to next, the floors
+/// they go on to from there if their trip changes banks at sky lobbies, state,
+/// an optional elevator car id, the car they're walking towards after seeing its hall
+/// lantern light, if any, how many seconds they take to react to an open door, how
+/// long they've been reacting to one, how long they've been waiting, whether they found the
+/// waiting area full and waited in the back, the car they operate if they're an operator, the
+/// person they have to travel with if they're a child, the group they're travelling with, if
+/// they're in one, what they've brought along, such
+/// as a pet or bicycle, the behavior profile they were spawned with, how many kg they weigh
+/// with everything they've brought, the car they last had to get back out of for
+/// overloading it, which they don't try again until it's gone, the secured floors they
+/// have a card for, and any data a program embedding the simulation has attached to them
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Person {
     pub id: PersonId,
     pub current_floor: Floor,
     pub target_floor: Floor,
+    #[serde(default)]
+    pub onward: Vec<Floor>,
     pub state: PersonState,
     pub in_car: Option<CarId>,
+    pub approaching: Option<CarId>,
+    pub reaction_time: f32,
+    pub reaction_timer: Option<f32>,
+    pub waited: f32,
+    #[serde(default)]
+    pub overflowed: bool,
+    pub operates: Option<CarId>,
+    pub chaperone: Option<PersonId>,
+    #[serde(default)]
+    pub group: Option<GroupId>,
+    pub attributes: Vec<Attribute>,
+    pub profile: BehaviorProfile,
+    #[serde(default)]
+    pub weight: f32,
+    #[serde(default)]
+    pub stepped_out_of: Option<CarId>,
+    #[serde(default)]
+    pub permitted_floors: Vec<Floor>,
+    // attached data can be anything, so it isn't saved in snapshots
+    #[serde(skip)]
+    pub extra: Option<Box<dyn Any + Send>>,
 }
 
-/// PeopleSim object contains
-/// next_person_id - the id of the next person who will spawn
-/// num_floors - the number of floors in the building
-/// spawn_timer - a timer which increments until it reaches spawn_interval
-/// spawn_interval - a value to adjust how often new people are spawned
-/// people - a vector of people
-pub struct PeopleSim {
-    next_person_id: u32,
-    num_floors: Floor,
-    spawn_timer: f32,
-    spawn_interval: f32,
-    people: Vec<Person>,
+impl Person {
+    /// The data attached to this person, if there is some of type T
+    pub fn extra<T: Any>(&self) -> Option<&T> {
+        self.extra.as_ref()?.downcast_ref()
+    }
+
+    /// The data attached to this person, mutably, if there is some of type T
+    pub fn extra_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.extra.as_mut()?.downcast_mut()
+    }
+
+    /// Attach data to this person, replacing whatever was attached before
+    pub fn set_extra<T: Any + Send>(&mut self, data: T) {
+        self.extra = Some(Box::new(data));
+    }
+
+    /// The floor this person's whole trip ends on, past any sky lobbies they change at
+    pub fn destination(&self) -> Floor {
+        self.onward.last().copied().unwrap_or(self.target_floor)
+    }
+
+    /// Whether this person uses a wheelchair
+    pub fn accessible(&self) -> bool {
+        self.attributes.contains(&Attribute::Wheelchair)
+    }
+
+    /// How many places this person takes in a car or a waiting area, counting the extra
+    /// place a wheelchair takes
+    pub fn places(&self) -> u32 {
+        self.profile.size + u32::from(self.accessible())
+    }
+
+    /// The direction this person needs to travel in to reach their target floor
+    pub fn direction(&self) -> Direction {
+        if self.target_floor > self.current_floor {
+            Direction::Up
+        } else {
+            Direction::Down
+        }
+    }
+
+    /// Keep track of how long this person has noticed something they can act on, such as an
+    /// open door. Returns true once they've noticed it for delay seconds, and resets
+    /// whenever it goes away
+    fn react(&mut self, noticed: bool, delay: f32, dt: f32) -> bool {
+        if !noticed {
+            self.reaction_timer = None;
+            return false;
+        }
+        let timer = self.reaction_timer.map_or(0., |t| t + dt);
+        self.reaction_timer = Some(timer);
+        timer >= delay
+    }
+
+    /// How long until this person acts on something they've noticed, which takes delay
+    /// seconds
+    fn reaction_remaining(&self, delay: f32) -> f32 {
+        self.reaction_timer.map_or(0., |t| (delay - t).max(0.))
+    }
+
+    /// How long until this person next runs short of patience, by pressing the call button
+    /// again once they've waited repress_after of their patience, or by giving up. None if
+    /// they never will
+    fn patience_remaining(&self, repress_after: f32) -> Option<f32> {
+        let patience = self.profile.patience;
+        [patience * repress_after, patience]
+            .into_iter()
+            .filter(|&t| t.is_finite() && t > self.waited)
+            .map(|t| t - self.waited)
+            .reduce(f32::min)
+    }
+
+    /// How long this person takes to get to a car once its door opens: their reaction time,
+    /// plus the walk across the hall, and from the back area if they're waiting there, unless
+    /// they were already walking towards it
+    fn boarding_delay(&self, car_id: CarId, walk_distance: f32, back_area_walk: f32) -> f32 {
+        let walk = if self.approaching == Some(car_id) {
+            0.
+        } else if self.overflowed {
+            (walk_distance + back_area_walk) / self.profile.walking_speed
+        } else {
+            walk_distance / self.profile.walking_speed
+        };
+        self.reaction_time + walk
+    }
 }
 
-/// implement functions for PeopleSim
-/// new - create a new peoplesim object, with a certain number of floors
-/// people - return a slice of People
-/// tick - spawns a person, and then for each person makes decisions and generates PersonActions
-impl PeopleSim {
-    /// Create a new PeopleSim, with a particular number of floors
-    pub fn new(num_floors: Floor, spawn_interval: f32) -> Self {
-        Self {
-            next_person_id: 0,
-            num_floors,
-            spawn_timer: 0.,
-            spawn_interval,
-            people: Vec::new(),
+/// What people do when they arrive to find their floor's waiting area full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// wait in a back area, further from the cars
+    #[default]
+    Back,
+    /// leave straight away and take the stairs
+    Abandon,
+}
+
+/// Which open cars a waiting person is willing to get into
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum BoardingPolicy {
+    /// board any car at their floor with its door open
+    Opportunistic,
+    /// only board cars which aren't committed to heading the other way. A car with no
+    /// direction, such as an empty car with no buttons lit, can go either way
+    MatchingDirection,
+    /// for destination dispatch, only board the car the hall panel assigned to this
+    /// person's trip. People whose trip has no assignment board any open car
+    Assigned,
+}
+
+/// Something a person brings along which boarding rules can restrict
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Attribute {
+    Pet,
+    Bicycle,
+    /// goods being delivered, which only go in freight and service cars, see CarClass
+    Delivery,
+    /// a wheelchair, which takes an extra place in a car. Its user calls cars with the
+    /// accessible call button, so the door stays open longer for them
+    Wheelchair,
+}
+
+/// A rule about where people with an attribute can go, checked whenever they board. People
+/// who a car breaks a rule for let it leave without them, and wait for one that doesn't
+/// attribute - who the rule applies to
+/// requires - what a car must be fitted for to take them, such as a freight car for bicycles
+/// banned_floors - floors they can't travel to or from. Nobody spawns with the attribute for
+/// a trip like that, and anyone given it anyway never boards
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BoardingRule {
+    pub attribute: Attribute,
+    pub requires: CarCapabilities,
+    pub banned_floors: Vec<Floor>,
+}
+
+impl BoardingRule {
+    /// Whether this rule lets a person make their trip in a car
+    pub fn allows(&self, person: &Person, car: &ElevatorCarState) -> bool {
+        !person.attributes.contains(&self.attribute)
+            || (car.capabilities.satisfies(&self.requires)
+                && !self.bans_trip(person.current_floor, person.target_floor))
+    }
+
+    /// Whether this rule stops someone with the attribute travelling from origin to
+    /// destination at all
+    fn bans_trip(&self, origin: Floor, destination: Floor) -> bool {
+        self.banned_floors.contains(&origin) || self.banned_floors.contains(&destination)
+    }
+}
+
+/// How people call an elevator from the hall
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum HallCallMode {
+    /// press the up or down button
+    Buttons,
+    /// enter their destination at a destination dispatch kiosk
+    Kiosk,
+}
+
+/// How long people take to react when a door opens for them. Each person's reaction time
+/// is picked when they spawn
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ReactionTime {
+    /// react on the same tick
+    Instant,
+    /// everyone takes this many seconds
+    Fixed(f32),
+    /// each person takes a random time between min and max seconds
+    Uniform { min: f32, max: f32 },
+}
+
+impl ReactionTime {
+    /// Pick a reaction time for one person
+    pub fn sample(&self) -> f32 {
+        match *self {
+            ReactionTime::Instant => 0.,
+            ReactionTime::Fixed(time) => time,
+            ReactionTime::Uniform { min, max } if max > min => {
+                rng::random_range(Stream::Behavior, min..max)
+            }
+            ReactionTime::Uniform { min, .. } => min,
         }
     }
+}
 
-    /// Return a slice of all people
-    pub fn people(&self) -> &[Person] {
-        &self.people
+/// How many kg people weigh, along with anything they always bring, such as a delivery
+/// trolley. Each person's weight is picked when they spawn
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Weight {
+    /// everyone weighs this many kg
+    Fixed(f32),
+    /// each person weighs a random amount between min and max kg
+    Uniform { min: f32, max: f32 },
+}
+
+impl Weight {
+    /// Pick a weight for one person
+    pub fn sample(&self) -> f32 {
+        match *self {
+            Weight::Fixed(kg) => kg,
+            Weight::Uniform { min, max } if max > min => {
+                rng::random_range(Stream::Behavior, min..max)
+            }
+            Weight::Uniform { min, .. } => min,
+        }
     }
+}
 
-    /// Take in BuildingState, and return a vector of PersonActions, which main
-    /// can translate into ElevatorActions
-    pub fn tick(&mut self, dt: f32, building: &BuildingState) -> Vec<PersonAction> {
-        let mut actions: Vec<PersonAction> = Vec::new();
+impl Default for Weight {
+    fn default() -> Self {
+        Weight::Fixed(DEFAULT_WEIGHT)
+    }
+}
+
+/// How many kg someone weighs when their profile doesn't say
+const DEFAULT_WEIGHT: f32 = 75.;
+
+/// The kinds of people a building sees
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ProfileKind {
+    /// the single kind of person the simulation had before profiles, who reacts instantly
+    Standard,
+    /// knows the building and moves quickly
+    Commuter,
+    /// takes a moment to work out where they're going
+    Visitor,
+    /// walks and reacts slowly, but is patient
+    Elderly,
+    /// brings a trolley, so needs extra room in the car
+    Delivery,
+    /// travels with a chaperone, walking slowly but reacting quickly
+    Child,
+}
+
+/// The traits a person spawns with
+/// kind - which kind of person this is
+/// patience - how many seconds they're willing to wait for a car before taking the stairs
+/// walking_speed - how fast they walk across the hall to a car, in meters per second
+/// reaction_time - how long they take to react once a door opens for them
+/// size - how many places they need free in a car before they'll get in
+/// weight - how heavy they are, which they can't tell a car's load sensor is close to
+/// going off over. Every kind's usual weight is fixed, so no random numbers are drawn for
+/// it and seeded runs repeat as they did before people had weights
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BehaviorProfile {
+    pub kind: ProfileKind,
+    #[serde(with = "snapshot::unlimited")]
+    pub patience: f32,
+    pub walking_speed: f32,
+    pub reaction_time: ReactionTime,
+    pub size: u32,
+    #[serde(default)]
+    pub weight: Weight,
+}
+
+impl ProfileKind {
+    /// The usual profile for this kind of person
+    pub fn profile(self) -> BehaviorProfile {
+        let (patience, walking_speed, reaction_time, size) = match self {
+            ProfileKind::Standard => (f32::INFINITY, 1.3, ReactionTime::Instant, 1),
+            ProfileKind::Commuter => (60., 1.4, ReactionTime::Uniform { min: 0.3, max: 0.8 }, 1),
+            ProfileKind::Visitor => (120., 1.1, ReactionTime::Uniform { min: 0.8, max: 2. }, 1),
+            ProfileKind::Elderly => (180., 0.8, ReactionTime::Uniform { min: 1.5, max: 3. }, 1),
+            ProfileKind::Delivery => (90., 1., ReactionTime::Fixed(1.), 2),
+            ProfileKind::Child => (f32::INFINITY, 0.9, ReactionTime::Instant, 1),
+        };
+        // a loaded trolley weighs more than whoever pushes it
+        let weight = match self {
+            ProfileKind::Delivery => Weight::Fixed(275.),
+            ProfileKind::Child => Weight::Fixed(30.),
+            _ => Weight::default(),
+        };
+        BehaviorProfile {
+            kind: self,
+            patience,
+            walking_speed,
+            reaction_time,
+            size,
+            weight,
+        }
+    }
+}
 
-        self.spawn_timer += dt;
+impl Default for BehaviorProfile {
+    fn default() -> Self {
+        ProfileKind::Standard.profile()
+    }
+}
 
-        if self.spawn_timer >= self.spawn_interval {
-            self.spawn_timer = 0.0;
+/// Settings which change how people behave
+/// boarding - which cars people are willing to board
+/// hall_calls - how people call an elevator
+/// profiles - the behavior profiles people spawn with, each with a relative weight for how
+/// often it's picked
+/// hall_walk_distance - how many meters people walk from where they wait to a car door
+/// chaperoned_children - the chance each spawned person brings a child along, who makes
+/// the same trip and can't travel without them
+/// groups - the chance each arrival is a party of people making the same trip together, who
+/// wait for a car with room for them all. 0 draws no random numbers, so seeded runs repeat
+/// largest_group - how many people the biggest parties have. Each has from 2 to this many
+/// visitors - the chance someone heading for a secured floor has no card for it, so they
+/// can't ride there unless someone with a card does. Everyone else heading for one has a
+/// card for it. No random numbers are drawn in a building without secured floors
+/// attributes - the chance each spawned person has each attribute, such as bringing a pet
+/// rules - where people with each attribute are allowed to go
+/// repress_after - the share of their patience after which someone still waiting presses
+/// the call button again, in case it wasn't noticed
+/// overflow - what people do when they arrive to find their floor's waiting area full, see
+/// BuildingTopology::hall_capacities
+/// back_area_walk - how many meters further people waiting in the back area walk to a car
+/// sky_lobby_walk - how many meters people changing banks walk across a sky lobby, from one
+/// bank's cars to the other's hall, see BuildingTopology::sky_lobbies
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PeopleConfig {
+    pub boarding: BoardingPolicy,
+    pub hall_calls: HallCallMode,
+    pub profiles: Vec<(BehaviorProfile, f32)>,
+    pub hall_walk_distance: f32,
+    pub chaperoned_children: f32,
+    #[serde(default)]
+    pub groups: f32,
+    #[serde(default)]
+    pub largest_group: u32,
+    #[serde(default)]
+    pub visitors: f32,
+    pub attributes: Vec<(Attribute, f32)>,
+    pub rules: Vec<BoardingRule>,
+    pub repress_after: f32,
+    #[serde(default)]
+    pub overflow: Overflow,
+    #[serde(default)]
+    pub back_area_walk: f32,
+    #[serde(default)]
+    pub sky_lobby_walk: f32,
+}
 
-            let id = PersonId(self.next_person_id);
-            self.next_person_id += 1;
+impl Default for PeopleConfig {
+    fn default() -> Self {
+        Self {
+            boarding: BoardingPolicy::MatchingDirection,
+            hall_calls: HallCallMode::Buttons,
+            profiles: vec![(BehaviorProfile::default(), 1.)],
+            hall_walk_distance: 0.,
+            chaperoned_children: 0.,
+            groups: 0.,
+            largest_group: 6,
+            visitors: 0.,
+            attributes: Vec::new(),
+            rules: Vec::new(),
+            repress_after: 0.5,
+            overflow: Overflow::Back,
+            back_area_walk: 10.,
+            sky_lobby_walk: 20.,
+        }
+    }
+}
 
-            // create a person on a random start floor, with a random target floor
-            let start_floor = rand::rng().random_range(0..self.num_floors);
-            let mut target_floor = rand::rng().random_range(0..self.num_floors);
-            while start_floor == target_floor {
-                //ensure the target floor is not the same as the start floor
-                target_floor = rand::rng().random_range(0..self.num_floors);
+impl PeopleConfig {
+    /// Pick a behavior profile for a new person, by weight. With no profiles configured,
+    /// everyone is Standard
+    fn pick_profile(&self) -> BehaviorProfile {
+        let total: f32 = self.profiles.iter().map(|&(_, w)| w.max(0.)).sum();
+        if total <= 0. {
+            return BehaviorProfile::default();
+        }
+        let mut choice = rng::random_range(Stream::Behavior, 0. ..total);
+        for &(profile, weight) in &self.profiles {
+            if choice < weight.max(0.) {
+                return profile;
             }
+            choice -= weight.max(0.);
+        }
+        self.profiles[self.profiles.len() - 1].0
+    }
 
-            let person = Person {
-                id,
-                current_floor: start_floor,
-                target_floor,
-                state: PersonState::New,
-                in_car: None,
-            };
+    /// Pick the attributes for someone travelling from origin to destination, leaving out
+    /// any the rules ban on either floor
+    fn pick_attributes(&self, origin: Floor, destination: Floor) -> Vec<Attribute> {
+        self.attributes
+            .iter()
+            .filter(|&&(_, chance)| chance > 0. && rng::random::<f32>(Stream::Behavior) < chance)
+            .map(|&(attribute, _)| attribute)
+            .filter(|&attribute| {
+                self.rules
+                    .iter()
+                    .all(|rule| rule.attribute != attribute || !rule.bans_trip(origin, destination))
+            })
+            .collect()
+    }
+}
+
+/// Shapes where people spawn, where they're going, and how often they arrive over the
+/// course of a simulated day
+pub trait TrafficPattern {
+    /// How many people per second arrive at a given time
+    fn spawn_rate(&self, time: f32) -> f32;
 
-            self.people.push(person);
+    /// Pick the origin and destination for someone arriving at a given time. They must be
+    /// different floors
+    fn trip(&mut self, time: f32, num_floors: Floor) -> (Floor, Floor);
+
+    /// Tell the pattern what time of day the run starts, for patterns which follow the
+    /// clock. By default the time of day doesn't matter
+    fn set_clock(&mut self, _clock: SimClock) {}
+
+    /// Tell the pattern which floor people come into the building on, for patterns with trips
+    /// to and from the lobby. By default the lobby doesn't matter
+    fn set_lobby(&mut self, _lobby: Floor) {}
+}
+
+/// A working day that follows the simulated clock: an up-peak from 07:30 to 09:30, lunch
+/// traffic from 12:00 to 13:30, and a down-peak from 16:30 to 18:30, all at twice the rate,
+/// with interfloor traffic in between. From 20:00 to 06:00 only a tenth as many people come
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OfficeDay {
+    pub rate: f32,
+    pub clock: SimClock,
+    pub lobby: Floor,
+}
+
+/// People travel between random floors, at a steady rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interfloor {
+    pub rate: f32,
+}
+
+/// Morning traffic: most people arrive in the lobby and head up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UpPeak {
+    pub rate: f32,
+    pub lobby_share: f32,
+    pub lobby: Floor,
+}
+
+/// Evening traffic: most people head down to the lobby to leave
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DownPeak {
+    pub rate: f32,
+    pub lobby_share: f32,
+    pub lobby: Floor,
+}
+
+/// Lunchtime traffic: people go down to the lobby and come back up in roughly equal numbers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LunchTraffic {
+    pub rate: f32,
+    pub lobby_share: f32,
+    pub lobby: Floor,
+}
+
+/// A whole working day, day_length seconds long, which repeats. It starts with an up-peak,
+/// has lunch traffic in the middle, ends with a down-peak, and is interfloor in between.
+/// Peaks arrive at twice the base rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DailyCycle {
+    pub rate: f32,
+    pub day_length: f32,
+    pub lobby: Floor,
+}
+
+impl TrafficPattern for Interfloor {
+    fn spawn_rate(&self, _time: f32) -> f32 {
+        self.rate
+    }
+
+    fn trip(&mut self, _time: f32, num_floors: Floor) -> (Floor, Floor) {
+        random_trip(num_floors)
+    }
+}
+
+impl TrafficPattern for UpPeak {
+    fn spawn_rate(&self, _time: f32) -> f32 {
+        self.rate
+    }
+
+    fn trip(&mut self, _time: f32, num_floors: Floor) -> (Floor, Floor) {
+        if rng::random::<f32>(Stream::Arrivals) < self.lobby_share {
+            (self.lobby, other_floor(self.lobby, num_floors))
+        } else {
+            random_trip(num_floors)
         }
+    }
 
-        // for each person, make the decisions they need to make
-        for person in self.people.iter_mut() {
-            match person.state {
-                //if someone is new, they need to push the outer buttons as long as there is no
-                //elevator there already, or the buttons are already pressed
-                PersonState::New => {
-                    let mut car_here = false;
-                    //check each car in the building
-                    for car in &building.cars {
-                        //don't worry about cars whose doors aren't open
-                        if !car.door_open {
-                            continue;
-                        }
+    fn set_lobby(&mut self, lobby: Floor) {
+        self.lobby = lobby;
+    }
+}
 
-                        // if it's on the current floor, don't push the outer button
-                        let car_floor = car.current_floor.round() as Floor;
-                        if car_floor == person.current_floor {
-                            car_here = true;
-                            break;
-                        }
-                    }
-                    //if there is no car here, push the outer button
-                    if !car_here {
-                        let direction = if person.target_floor > person.current_floor {
-                            Direction::Up
-                        } else {
-                            Direction::Down
-                        };
+impl TrafficPattern for DownPeak {
+    fn spawn_rate(&self, _time: f32) -> f32 {
+        self.rate
+    }
 
-                        actions.push(PersonAction::CallElevator {
-                            floor: person.current_floor,
-                            direction,
-                        });
-                    }
+    fn trip(&mut self, _time: f32, num_floors: Floor) -> (Floor, Floor) {
+        if rng::random::<f32>(Stream::Arrivals) < self.lobby_share {
+            (other_floor(self.lobby, num_floors), self.lobby)
+        } else {
+            random_trip(num_floors)
+        }
+    }
 
-                    //now the new person can start waiting
-                    person.state = PersonState::Waiting;
-                }
-                //if a person is waiting, they need to check if there is a car on their current
-                //floor with its door open. If there is, they need to enter that car
-                PersonState::Waiting => {
-                    let mut car_to_board: Option<CarId> = None;
-                    //for each car in the building
-                    for car in &building.cars {
-                        //don't worry about cars with closed doors
-                        if !car.door_open {
-                            continue;
-                        }
+    fn set_lobby(&mut self, lobby: Floor) {
+        self.lobby = lobby;
+    }
+}
 
-                        //if it's on the current floor
-                        let car_floor = car.current_floor.round() as Floor;
-                        if car_floor == person.current_floor {
-                            car_to_board = Some(car.id); //set it as the car to board
-                            break;
-                        }
-                    }
+impl TrafficPattern for LunchTraffic {
+    fn spawn_rate(&self, _time: f32) -> f32 {
+        self.rate
+    }
 
-                    //if we got a car to board
-                    if let Some(car_id) = car_to_board {
-                        //enter the car and push the interior button
-                        actions.push(PersonAction::PressCarButton {
-                            car_id,
-                            floor: person.target_floor,
-                        });
+    fn trip(&mut self, _time: f32, num_floors: Floor) -> (Floor, Floor) {
+        if rng::random::<f32>(Stream::Arrivals) >= self.lobby_share {
+            random_trip(num_floors)
+        } else if rng::random::<bool>(Stream::Arrivals) {
+            (self.lobby, other_floor(self.lobby, num_floors))
+        } else {
+            (other_floor(self.lobby, num_floors), self.lobby)
+        }
+    }
 
-                        //the person is now riding the elevator car
-                        person.state = PersonState::Riding;
-                        person.in_car = Some(car_id);
-                    }
-                }
-                //if a person is riding an elevator car
-                PersonState::Riding => {
-                    //make sure they're in a car
-                    if let Some(car_id) = person.in_car {
-                        //make sure that car is in the building
-                        if let Some(car) = building.cars.get(car_id.0 as usize) {
-                            let car_floor = car.current_floor.round() as Floor;
-
-                            //if the car is where they want to go, and the door is open
-                            if car_floor == person.target_floor && car.door_open {
-                                //get out
-                                person.current_floor = person.target_floor;
-                                person.in_car = None;
-                                //the person is now done
-                                person.state = PersonState::Done;
-                            }
-                        }
-                    }
+    fn set_lobby(&mut self, lobby: Floor) {
+        self.lobby = lobby;
+    }
+}
+
+impl DailyCycle {
+    /// The pattern in effect at a time of day, with its rate
+    fn phase(&self, time: f32) -> Box<dyn TrafficPattern> {
+        let day = (time / self.day_length).rem_euclid(1.);
+        let peak = self.rate * 2.;
+        if day < 0.2 {
+            Box::new(UpPeak {
+                rate: peak,
+                lobby_share: 0.85,
+                lobby: self.lobby,
+            })
+        } else if (0.45..0.55).contains(&day) {
+            Box::new(LunchTraffic {
+                rate: peak,
+                lobby_share: 0.8,
+                lobby: self.lobby,
+            })
+        } else if day >= 0.8 {
+            Box::new(DownPeak {
+                rate: peak,
+                lobby_share: 0.85,
+                lobby: self.lobby,
+            })
+        } else {
+            Box::new(Interfloor { rate: self.rate })
+        }
+    }
+}
+
+impl OfficeDay {
+    /// The pattern in effect time seconds into the run, with its rate
+    fn phase(&self, time: f32) -> Box<dyn TrafficPattern> {
+        let hour = self.clock.at(time as f64).rem_euclid(DAY) / 3600.;
+        let peak = self.rate * 2.;
+        if (7.5..9.5).contains(&hour) {
+            Box::new(UpPeak {
+                rate: peak,
+                lobby_share: 0.85,
+                lobby: self.lobby,
+            })
+        } else if (12. ..13.5).contains(&hour) {
+            Box::new(LunchTraffic {
+                rate: peak,
+                lobby_share: 0.8,
+                lobby: self.lobby,
+            })
+        } else if (16.5..18.5).contains(&hour) {
+            Box::new(DownPeak {
+                rate: peak,
+                lobby_share: 0.85,
+                lobby: self.lobby,
+            })
+        } else if !(6. ..20.).contains(&hour) {
+            Box::new(Interfloor {
+                rate: self.rate / 10.,
+            })
+        } else {
+            Box::new(Interfloor { rate: self.rate })
+        }
+    }
+}
+
+impl TrafficPattern for OfficeDay {
+    fn spawn_rate(&self, time: f32) -> f32 {
+        self.phase(time).spawn_rate(time)
+    }
+
+    fn trip(&mut self, time: f32, num_floors: Floor) -> (Floor, Floor) {
+        self.phase(time).trip(time, num_floors)
+    }
+
+    fn set_clock(&mut self, clock: SimClock) {
+        self.clock = clock;
+    }
+
+    fn set_lobby(&mut self, lobby: Floor) {
+        self.lobby = lobby;
+    }
+}
+
+impl TrafficPattern for DailyCycle {
+    fn spawn_rate(&self, time: f32) -> f32 {
+        self.phase(time).spawn_rate(time)
+    }
+
+    fn trip(&mut self, time: f32, num_floors: Floor) -> (Floor, Floor) {
+        self.phase(time).trip(time, num_floors)
+    }
+
+    fn set_lobby(&mut self, lobby: Floor) {
+        self.lobby = lobby;
+    }
+}
+
+/// How many trips FloorWeights asks its pattern for before giving up on it and picking by
+/// weight alone
+const MAX_WEIGHTED_DRAWS: u32 = 1000;
+
+/// Follows another traffic pattern, but with some floors busier than others, such as a
+/// crowded lobby or an empty plant room. Each trip the pattern picks is kept with a chance
+/// proportional to the weights of both its floors, so the trips come out as the pattern's,
+/// scaled by how busy their floors are. Floors past the end of weights weigh 1, and floors
+/// weighing 0 are never visited
+pub struct FloorWeights {
+    pattern: Box<dyn TrafficPattern + Send>,
+    weights: Vec<f32>,
+}
+
+/// implement functions for FloorWeights
+/// new - weight the floors of a traffic pattern
+/// weight - how busy a floor is
+/// pick - pick a floor by weight alone
+impl FloorWeights {
+    /// Weight the floors of a pattern, from the bottom up. Negative weights count as 0
+    pub fn new(pattern: Box<dyn TrafficPattern + Send>, weights: Vec<f32>) -> Self {
+        Self { pattern, weights }
+    }
+
+    /// How busy a floor is, relative to the others
+    fn weight(&self, floor: Floor) -> f32 {
+        self.weights
+            .get(floor as usize)
+            .copied()
+            .unwrap_or(1.)
+            .max(0.)
+    }
+
+    /// Pick a floor other than skip by weight alone, or None if none of them weigh anything
+    fn pick(&self, num_floors: Floor, skip: Option<Floor>) -> Option<Floor> {
+        let floors = (0..num_floors).filter(|&f| Some(f) != skip);
+        let total: f32 = floors.clone().map(|f| self.weight(f)).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut choice = rng::random_range(Stream::Arrivals, 0. ..total);
+        for floor in floors.clone() {
+            if choice < self.weight(floor) {
+                return Some(floor);
+            }
+            choice -= self.weight(floor);
+        }
+        floors.rev().find(|&f| self.weight(f) > 0.)
+    }
+}
+
+impl TrafficPattern for FloorWeights {
+    fn spawn_rate(&self, time: f32) -> f32 {
+        self.pattern.spawn_rate(time)
+    }
+
+    fn trip(&mut self, time: f32, num_floors: Floor) -> (Floor, Floor) {
+        let heaviest = (0..num_floors).map(|f| self.weight(f)).fold(0., f32::max);
+        if heaviest > 0. {
+            for _ in 0..MAX_WEIGHTED_DRAWS {
+                let (origin, destination) = self.pattern.trip(time, num_floors);
+                let chance = self.weight(origin) * self.weight(destination) / (heaviest * heaviest);
+                if rng::random::<f32>(Stream::Arrivals) < chance {
+                    return (origin, destination);
                 }
-                PersonState::Done => {}
             }
         }
+        // the pattern hardly ever picks the busy floors, so pick them by weight alone
+        if let Some(origin) = self.pick(num_floors, None)
+            && let Some(destination) = self.pick(num_floors, Some(origin))
+        {
+            return (origin, destination);
+        }
+        random_trip(num_floors)
+    }
 
-        actions
+    fn set_clock(&mut self, clock: SimClock) {
+        self.pattern.set_clock(clock);
+    }
+
+    fn set_lobby(&mut self, lobby: Floor) {
+        self.pattern.set_lobby(lobby);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::elevator::BuildingState;
+/// Look up a traffic pattern by the name used on the command line, arriving at rate
+/// people per second. Its lobby is floor 0 until set_lobby says otherwise
+pub fn traffic_pattern(name: &str, rate: f32) -> Option<Box<dyn TrafficPattern + Send>> {
+    let pattern: Box<dyn TrafficPattern + Send> = match name {
+        "interfloor" => Box::new(Interfloor { rate }),
+        "up-peak" => Box::new(UpPeak {
+            rate,
+            lobby_share: 0.85,
+            lobby: 0,
+        }),
+        "down-peak" => Box::new(DownPeak {
+            rate,
+            lobby_share: 0.85,
+            lobby: 0,
+        }),
+        "lunch" => Box::new(LunchTraffic {
+            rate,
+            lobby_share: 0.8,
+            lobby: 0,
+        }),
+        "daily" => Box::new(DailyCycle {
+            rate,
+            day_length: 600.,
+            lobby: 0,
+        }),
+        "office-day" => Box::new(OfficeDay {
+            rate,
+            clock: SimClock::default(),
+            lobby: 0,
+        }),
+        _ => return None,
+    };
+    Some(pattern)
+}
 
-    fn empty_building() -> BuildingState {
-        BuildingState {
-            floors: Vec::new(),
-            cars: Vec::new(),
+/// A trip between two different random floors
+fn random_trip(num_floors: Floor) -> (Floor, Floor) {
+    let origin = rng::random_range(Stream::Arrivals, 0..num_floors);
+    // pick from the other floors, skipping over the origin
+    let mut destination = rng::random_range(Stream::Arrivals, 0..num_floors - 1);
+    if destination >= origin {
+        destination += 1;
+    }
+    (origin, destination)
+}
+
+/// A random floor other than the lobby
+fn other_floor(lobby: Floor, num_floors: Floor) -> Floor {
+    // pick from the other floors, skipping over the lobby
+    let floor = rng::random_range(Stream::Arrivals, 0..num_floors - 1);
+    if floor >= lobby { floor + 1 } else { floor }
+}
+
+/// Decides when people arrive in the building and where they're going
+pub trait Spawner {
+    /// The trips, as (origin, destination), of everyone who arrives in the dt seconds leading
+    /// up to time
+    fn spawn(&mut self, time: f32, dt: f32, num_floors: Floor) -> Vec<(Floor, Floor)>;
+
+    /// How long after time the next person arrives, or None if nobody ever will
+    fn time_to_next_spawn(&self, time: f32) -> Option<f32>;
+
+    /// Catch up to time without spawning anyone, when a PeopleSim is restored from a
+    /// snapshot taken then. By default nothing needs to change
+    fn skip_to(&mut self, _time: f32) {}
+
+    /// The highest floor anyone is due to arrive on or head for, if that's known before they
+    /// arrive. By default it isn't, and people go wherever the building's floors allow
+    fn highest_floor(&self) -> Option<Floor> {
+        None
+    }
+}
+
+/// Spawns people at evenly spaced times, following a traffic pattern's rate and trips
+pub struct PatternSpawner {
+    pattern: Box<dyn TrafficPattern + Send>,
+    progress: f32,
+}
+
+impl PatternSpawner {
+    /// Create a spawner which follows a traffic pattern
+    pub fn new(pattern: Box<dyn TrafficPattern + Send>) -> Self {
+        Self {
+            pattern,
+            progress: 0.,
         }
     }
+}
 
-    #[test]
-    fn person_spawned_and_calls_elevator() {
-        let mut sim = PeopleSim::new(5, 0.1);
-        let building = empty_building();
+impl Spawner for PatternSpawner {
+    fn spawn(&mut self, time: f32, dt: f32, num_floors: Floor) -> Vec<(Floor, Floor)> {
+        self.progress += dt * self.pattern.spawn_rate(time);
 
-        let actions = sim.tick(1.0, &building);
+        // at most one person spawns per tick
+        if self.progress >= 1. {
+            self.progress = 0.;
+            vec![self.pattern.trip(time, num_floors)]
+        } else {
+            Vec::new()
+        }
+    }
 
-        assert_eq!(sim.people().len(), 1);
-        assert_eq!(actions.len(), 1);
+    fn time_to_next_spawn(&self, time: f32) -> Option<f32> {
+        let rate = self.pattern.spawn_rate(time);
+        (rate > 0.).then(|| ((1. - self.progress) / rate).max(0.))
+    }
+}
+
+/// Spawns people as a Poisson process, so the gaps between arrivals are random, following a
+/// traffic pattern's rate and trips. Each gap uses the rate when the last person arrived
+pub struct PoissonSpawner {
+    pattern: Box<dyn TrafficPattern + Send>,
+    next_arrival: Option<f32>,
+}
+
+impl PoissonSpawner {
+    /// Create a spawner which follows a traffic pattern, starting at time 0
+    pub fn new(pattern: Box<dyn TrafficPattern + Send>) -> Self {
+        let mut spawner = Self {
+            pattern,
+            next_arrival: None,
+        };
+        spawner.next_arrival = spawner.arrival_after(0.);
+        spawner
+    }
+
+    /// Pick when the next person arrives after time, or None if nobody arrives at that rate
+    fn arrival_after(&self, time: f32) -> Option<f32> {
+        let rate = self.pattern.spawn_rate(time);
+        if rate <= 0. {
+            return None;
+        }
+        // 1 - u is never 0, so the log is finite
+        let u: f32 = rng::random(Stream::Arrivals);
+        Some(time - (1. - u).ln() / rate)
+    }
+}
+
+impl Spawner for PoissonSpawner {
+    fn spawn(&mut self, time: f32, _dt: f32, num_floors: Floor) -> Vec<(Floor, Floor)> {
+        let mut trips = Vec::new();
+        if self.next_arrival.is_none() {
+            self.next_arrival = self.arrival_after(time);
+        }
+        while let Some(arrival) = self.next_arrival
+            && arrival <= time
+        {
+            trips.push(self.pattern.trip(arrival, num_floors));
+            self.next_arrival = self.arrival_after(arrival);
+        }
+        trips
+    }
+
+    fn time_to_next_spawn(&self, time: f32) -> Option<f32> {
+        self.next_arrival.map(|a| (a - time).max(0.))
+    }
+
+    fn skip_to(&mut self, time: f32) {
+        self.next_arrival = self.arrival_after(time);
+    }
+}
+
+/// Spawns exactly the given arrivals, each at its scheduled time
+pub struct ScriptedSpawner {
+    arrivals: VecDeque<ScriptedArrival>,
+}
+
+impl ScriptedSpawner {
+    /// Create a spawner for a list of arrivals, in any order
+    pub fn new(mut arrivals: Vec<ScriptedArrival>) -> Self {
+        arrivals.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            arrivals: arrivals.into(),
+        }
+    }
+}
+
+impl Spawner for ScriptedSpawner {
+    fn spawn(&mut self, time: f32, _dt: f32, _num_floors: Floor) -> Vec<(Floor, Floor)> {
+        // spawn every scripted arrival whose time has come
+        let mut due = Vec::new();
+        while self.arrivals.front().is_some_and(|a| a.time <= time) {
+            due.extend(self.arrivals.pop_front().map(|a| (a.origin, a.destination)));
+        }
+        due
+    }
+
+    fn time_to_next_spawn(&self, time: f32) -> Option<f32> {
+        self.arrivals.front().map(|a| (a.time - time).max(0.))
+    }
+
+    fn skip_to(&mut self, time: f32) {
+        // everyone due by then has already arrived
+        while self.arrivals.front().is_some_and(|a| a.time <= time) {
+            self.arrivals.pop_front();
+        }
+    }
+
+    fn highest_floor(&self) -> Option<Floor> {
+        self.arrivals
+            .iter()
+            .map(|a| a.origin.max(a.destination))
+            .max()
+    }
+}
+
+/// Decides what people do each tick, moving them through their states
+pub trait PersonEngine {
+    /// Make each person's decisions, returning the actions they take. queues holds who
+    /// is waiting on each floor, in the order they arrived
+    fn tick(
+        &mut self,
+        people: &mut [Person],
+        queues: &HallQueues,
+        dt: f32,
+        building: &BuildingState,
+    ) -> Vec<PersonAction>;
+
+    /// How long until someone acts, if nothing changes in the building. None if nobody will
+    fn time_to_next_event(
+        &self,
+        people: &[Person],
+        queues: &HallQueues,
+        building: &BuildingState,
+    ) -> Option<f32>;
+
+    /// Let people react to an event from the building. By default they ignore it
+    fn observe(&mut self, _people: &mut [Person], _event: &ElevatorEvent) {}
+}
+
+/// The standard behavior: people call a car, board one their boarding policy allows once
+/// they've reacted to it, and get out at their floor. People waiting on a floor take their
+/// turn in the order they arrived, so someone still reacting keeps their place in a car
+/// from anyone who came after them. People who run out of patience
+/// take the stairs instead. Operators drive their car instead,
+/// and headings holds which way each operated car is sweeping. turned_away holds the people
+/// whose getting in set off a car's overload sensor, who step back out and wait again
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefaultEngine {
+    boarding: BoardingPolicy,
+    hall_calls: HallCallMode,
+    hall_walk_distance: f32,
+    rules: Vec<BoardingRule>,
+    repress_after: f32,
+    overflow: Overflow,
+    back_area_walk: f32,
+    sky_lobby_walk: f32,
+    headings: HashMap<CarId, Direction>,
+    turned_away: HashSet<PersonId>,
+}
+
+impl DefaultEngine {
+    /// Create an engine whose people behave according to a config
+    pub fn new(config: &PeopleConfig) -> Self {
+        Self {
+            boarding: config.boarding,
+            hall_calls: config.hall_calls,
+            hall_walk_distance: config.hall_walk_distance,
+            rules: config.rules.clone(),
+            repress_after: config.repress_after,
+            overflow: config.overflow,
+            back_area_walk: config.back_area_walk,
+            sky_lobby_walk: config.sky_lobby_walk,
+            headings: HashMap::new(),
+            turned_away: HashSet::new(),
+        }
+    }
+}
+
+impl PersonEngine for DefaultEngine {
+    fn tick(
+        &mut self,
+        people: &mut [Person],
+        queues: &HallQueues,
+        dt: f32,
+        building: &BuildingState,
+    ) -> Vec<PersonAction> {
+        let mut actions: Vec<PersonAction> = Vec::new();
+        let boarding = self.boarding;
+        let hall_calls = self.hall_calls;
+        let walk_distance = self.hall_walk_distance;
+        let back_area_walk = self.back_area_walk;
+        let rules = &self.rules;
+        // cars people have got into this tick, which the building state doesn't show yet,
+        // and the places kept in them by people ahead in the queue who are about to
+        let mut boarded: Vec<(CarId, u32)> = Vec::new();
+        let parties = Parties::new(people);
+        // the cars chaperones got into this tick, for their children to follow them into
+        let mut led: HashMap<(PersonId, Floor), CarId> = HashMap::new();
+        // chaperones who gave up, taking the children waiting with them along
+        let mut abandoned: Vec<(PersonId, Floor)> = Vec::new();
+        // operators wait for anyone about to get on or off before driving away
+        let busy = busy_cars(people, building, boarding, rules, &parties);
+        // how many places are taken in each floor's waiting area, by those not in the back
+        let mut hall = waiting_areas(people, building);
+
+        // during a recall nobody calls a car, and riders get out at the recall floor
+        let emergency = building.emergency != EmergencyMode::Normal;
+
+        // for each person, make the decisions they need to make, those waiting first in
+        // line first
+        for i in queues.turn_order(people) {
+            let person = &mut people[i];
+            if let Some(car_id) = person.operates {
+                // operators wait out an emergency, and take their car back once it's over
+                if emergency {
+                    continue;
+                }
+                // operators ride along wherever they take the car
+                let car_floor = building
+                    .cars
+                    .get(car_id.0 as usize)
+                    .map(|car| car.current_floor.round() as Floor);
+                if person.in_car.is_some()
+                    && let Some(floor) = car_floor
+                {
+                    person.current_floor = floor;
+                }
+
+                let heading = *self.headings.entry(car_id).or_insert(Direction::Up);
+                let Some(action) = operator_action(person, building, &busy, heading) else {
+                    continue;
+                };
+                match action {
+                    PersonAction::TakeOverCar { .. } => person.state = PersonState::Waiting,
+                    PersonAction::EnterCar { car_id, .. } => {
+                        boarded.push((car_id, person.places()));
+                        person.state = PersonState::Riding;
+                        person.in_car = Some(car_id);
+                    }
+                    PersonAction::OperateCar { floor, .. } if person.in_car.is_some() => {
+                        let heading = if floor > person.current_floor {
+                            Direction::Up
+                        } else {
+                            Direction::Down
+                        };
+                        self.headings.insert(car_id, heading);
+                    }
+                    _ => {}
+                }
+                actions.push(action);
+                continue;
+            }
+
+            // children waiting with their chaperone leave calling and boarding to them
+            if parties.follows(person) {
+                if person.state == PersonState::New {
+                    person.state = PersonState::Waiting;
+                }
+                continue;
+            }
+
+            let room = parties.room(person);
+            match person.state {
+                //if someone is new, they need to push the outer buttons as long as there is no
+                //elevator there already, or the buttons are already pressed
+                PersonState::New => {
+                    // in a zoned building, someone whose trip no car makes takes the stairs
+                    // straight away
+                    // in a zoned building, or one with cars not everyone can use, someone
+                    // whose trip no car they can use makes takes the stairs straight away
+                    let restricted = building
+                        .cars
+                        .iter()
+                        .any(|car| car.zoned() || car.class != CarClass::Passenger);
+                    let reachable = building.cars.iter().any(|car| {
+                        rides_in(person, car.class)
+                            && car.serves(person.current_floor)
+                            && car.serves(person.target_floor)
+                    });
+                    if restricted && !reachable {
+                        abandoned.push((person.id, person.current_floor));
+                        person.state = PersonState::GaveUp;
+                        continue;
+                    }
+
+                    // someone who finds the waiting area full waits in the back, or leaves
+                    let floor = person.current_floor;
+                    if let Some(capacity) = building.topology.hall_capacity(floor) {
+                        let in_hall = hall.entry(floor).or_insert(0);
+                        if *in_hall + room > capacity {
+                            person.overflowed = true;
+                            if self.overflow == Overflow::Abandon {
+                                abandoned.push((person.id, floor));
+                                person.state = PersonState::GaveUp;
+                                continue;
+                            }
+                        } else {
+                            *in_hall += room;
+                        }
+                    }
+
+                    // if a car they'd board is already here with its door open, don't push the
+                    // outer button
+                    let car_here =
+                        car_to_board(building, person, boarding, rules, &boarded, room).is_some();
+                    //if there is no car here, push the outer button
+                    if !car_here && !emergency {
+                        actions.push(call_action(person, hall_calls, building));
+                    }
+
+                    //now the new person can start waiting
+                    person.state = PersonState::Waiting;
+                }
+                //if a person is waiting, they need to check if there is a car on their current
+                //floor with its door open. If there is, they need to enter that car
+                PersonState::Waiting => {
+                    person.waited += dt;
+                    //once the car they had to get back out of has shut its door on them, they'd
+                    //try it again
+                    if let Some(car_id) = person.stepped_out_of
+                        && !building.cars.get(car_id.0 as usize).is_some_and(|car| {
+                            car.door_opening_or_open()
+                                && car.current_floor.round() as Floor == person.current_floor
+                        })
+                    {
+                        person.stepped_out_of = None;
+                    }
+                    //when a lantern lights over a car about to arrive going their way, or the
+                    //car they've been assigned, they walk towards it, and board it first once
+                    //its door opens
+                    if person.approaching.is_none() {
+                        person.approaching = lit_for(building, person);
+                    }
+                    let car = car_to_board(building, person, boarding, rules, &boarded, room);
+                    //if we got a car to board, and have had time to notice it and walk over
+                    let delay = car.map_or(0., |c| {
+                        person.boarding_delay(c, walk_distance, back_area_walk)
+                    });
+                    //keep their place in the car from anyone behind them
+                    if let Some(car_id) = car {
+                        boarded.push((car_id, room));
+                    }
+                    //someone already on their way to a car whose door starts closing steps into
+                    //it once, and the door's sensor opens it again. If it doesn't, they wait
+                    //for the next car
+                    let closing_on = car
+                        .is_none()
+                        .then(|| car_closing_on(building, person, boarding, rules, room))
+                        .flatten()
+                        .filter(|_| person.reaction_timer.is_some());
+                    if let Some(car_id) = closing_on {
+                        actions.push(PersonAction::HoldDoor { car_id });
+                        person.approaching = Some(car_id);
+                    }
+                    if person.react(car.is_some(), delay, dt)
+                        && let Some(car_id) = car
+                    {
+                        led.insert((person.id, person.current_floor), car_id);
+                        //enter the car, show their card if their floor is secured, and push
+                        //the interior button
+                        actions.push(PersonAction::EnterCar {
+                            car_id,
+                            person_id: person.id,
+                            weight: person.weight,
+                        });
+                        let floor = person.target_floor;
+                        if building.topology.is_secured(floor)
+                            && person.permitted_floors.contains(&floor)
+                        {
+                            actions.push(PersonAction::AuthorizeFloor { car_id, floor });
+                        }
+                        actions.push(PersonAction::PressCarButton {
+                            car_id,
+                            floor: person.target_floor,
+                        });
+
+                        //the person is now riding the elevator car
+                        person.state = PersonState::Riding;
+                        person.in_car = Some(car_id);
+                        person.approaching = None;
+                        person.reaction_timer = None;
+                    } else if person.waited >= person.profile.patience {
+                        //they've waited as long as they're willing to, and take the stairs
+                        abandoned.push((person.id, person.current_floor));
+                        person.state = PersonState::GaveUp;
+                        person.approaching = None;
+                        person.reaction_timer = None;
+                    } else if emergency {
+                        //calls are refused until the emergency is over
+                    } else if closing_on.is_some() {
+                        //they see whether the door opens for them before calling again
+                    } else if car.is_none()
+                        && call_registered(building, person, hall_calls) == Some(false)
+                    {
+                        //if no car is here, and our call isn't registered (maybe a car came
+                        //and left without us, or was too full to get into), call again
+                        actions.push(call_action(person, hall_calls, building));
+                        person.approaching = None;
+                    } else if car.is_none() {
+                        //once they've waited a while, they press the button again anyway
+                        let repress_at = person.profile.patience * self.repress_after;
+                        if person.waited >= repress_at && person.waited - dt < repress_at {
+                            actions.push(call_action(person, hall_calls, building));
+                        }
+                    }
+                }
+                //if a person is riding an elevator car
+                PersonState::Riding => {
+                    //someone whose getting in set off the car's overload sensor steps back out,
+                    //unless someone else has made room already, and waits for the next car
+                    let overloaded = |car_id: CarId| {
+                        building
+                            .cars
+                            .get(car_id.0 as usize)
+                            .is_some_and(|car| car.overloaded)
+                    };
+                    if self.turned_away.remove(&person.id)
+                        && let Some(car_id) = person.in_car
+                        && overloaded(car_id)
+                    {
+                        actions.push(PersonAction::ExitCar {
+                            car_id,
+                            person_id: person.id,
+                            weight: person.weight,
+                        });
+                        person.in_car = None;
+                        person.reaction_timer = None;
+                        person.stepped_out_of = Some(car_id);
+                        person.state = PersonState::Waiting;
+                        continue;
+                    }
+                    //if the car is where they want to go, or has been recalled, or won't take
+                    //them to their secured floor, and the door has been open long enough for
+                    //them to notice
+                    let arrived = at_destination(building, person);
+                    let recalled = recalled_to(building, person);
+                    let locked_out = locked_out_at(building, person);
+                    let noticed = arrived || recalled.is_some() || locked_out.is_some();
+                    if person.react(noticed, person.reaction_time, dt) {
+                        //get out
+                        if let Some(car_id) = person.in_car {
+                            actions.push(PersonAction::ExitCar {
+                                car_id,
+                                person_id: person.id,
+                                weight: person.weight,
+                            });
+                        }
+                        person.in_car = None;
+                        person.reaction_timer = None;
+                        match recalled {
+                            //the person gets out wherever they are, and takes the stairs
+                            None if !arrived && let Some(floor) = locked_out => {
+                                person.current_floor = floor;
+                                person.state = PersonState::GaveUp;
+                            }
+                            //the person waits out the emergency where the car left them
+                            Some(floor) if !arrived => {
+                                person.current_floor = floor;
+                                person.state = PersonState::Waiting;
+                            }
+                            //the person has reached a sky lobby, and heads for the next bank
+                            _ if !person.onward.is_empty() => {
+                                person.current_floor = person.target_floor;
+                                person.target_floor = person.onward.remove(0);
+                                person.state = PersonState::Transferring;
+                            }
+                            //the person is now done
+                            _ => {
+                                person.current_floor = person.target_floor;
+                                person.state = PersonState::Done;
+                            }
+                        }
+                    }
+                }
+                //once they've walked across the sky lobby, they call the next bank like anyone
+                //who has just arrived
+                PersonState::Transferring => {
+                    let walk = self.sky_lobby_walk / person.profile.walking_speed;
+                    if person.react(true, walk, dt) {
+                        person.reaction_timer = None;
+                        person.waited = 0.;
+                        person.overflowed = false;
+                        person.state = PersonState::New;
+                    }
+                }
+                PersonState::Done | PersonState::GaveUp => {}
+            }
+        }
+
+        // children and groups give up with whoever they were waiting with
+        if !abandoned.is_empty() {
+            for person in people.iter_mut() {
+                if person.state == PersonState::Waiting
+                    && parties
+                        .leader(person)
+                        .is_some_and(|l| abandoned.contains(&(l, person.current_floor)))
+                {
+                    person.state = PersonState::GaveUp;
+                }
+            }
+        }
+
+        // children and groups get in along with whoever they were waiting with
+        if !led.is_empty() {
+            for person in people.iter_mut() {
+                let Some(&car_id) = parties
+                    .leader(person)
+                    .and_then(|l| led.get(&(l, person.current_floor)))
+                else {
+                    continue;
+                };
+                if person.state != PersonState::Waiting {
+                    continue;
+                }
+                actions.push(PersonAction::EnterCar {
+                    car_id,
+                    person_id: person.id,
+                    weight: person.weight,
+                });
+                person.state = PersonState::Riding;
+                person.in_car = Some(car_id);
+                person.approaching = None;
+                person.reaction_timer = None;
+            }
+        }
+
+        actions
+    }
+
+    fn time_to_next_event(
+        &self,
+        people: &[Person],
+        queues: &HallQueues,
+        building: &BuildingState,
+    ) -> Option<f32> {
+        let mut next: Option<f32> = None;
+        let parties = Parties::new(people);
+        let busy = busy_cars(people, building, self.boarding, &self.rules, &parties);
+        let emergency = building.emergency != EmergencyMode::Normal;
+        // the places kept in cars by people ahead in the queue
+        let mut kept: Vec<(CarId, u32)> = Vec::new();
+        for i in queues.turn_order(people) {
+            let person = &people[i];
+            if let Some(car_id) = person.operates {
+                if emergency {
+                    continue;
+                }
+                let heading = self.headings.get(&car_id).copied().unwrap_or(Direction::Up);
+                if operator_action(person, building, &busy, heading).is_some() {
+                    next = Some(0.);
+                }
+                continue;
+            }
+            if parties.follows(person) {
+                continue;
+            }
+            let wait = match person.state {
+                PersonState::New => Some(0.),
+                PersonState::Waiting => {
+                    let room = parties.room(person);
+                    let boarding = if let Some(car) =
+                        car_to_board(building, person, self.boarding, &self.rules, &kept, room)
+                    {
+                        kept.push((car, room));
+                        let delay = person.boarding_delay(
+                            car,
+                            self.hall_walk_distance,
+                            self.back_area_walk,
+                        );
+                        Some(person.reaction_remaining(delay))
+                    } else if (person.reaction_timer.is_some()
+                        && car_closing_on(building, person, self.boarding, &self.rules, room)
+                            .is_some())
+                        || (!emergency
+                            && call_registered(building, person, self.hall_calls) == Some(false))
+                    {
+                        // they step into a door closing on them, or call again, straight away
+                        Some(0.)
+                    } else {
+                        None
+                    };
+                    [boarding, person.patience_remaining(self.repress_after)]
+                        .into_iter()
+                        .flatten()
+                        .reduce(f32::min)
+                }
+                PersonState::Riding if self.turned_away.contains(&person.id) => Some(0.),
+                PersonState::Riding => (at_destination(building, person)
+                    || recalled_to(building, person).is_some())
+                .then(|| person.reaction_remaining(person.reaction_time)),
+                PersonState::Transferring => Some(
+                    person.reaction_remaining(self.sky_lobby_walk / person.profile.walking_speed),
+                ),
+                PersonState::Done | PersonState::GaveUp => None,
+            };
+            if let Some(wait) = wait {
+                next = Some(next.map_or(wait, |n| n.min(wait)));
+            }
+        }
+        next
+    }
+
+    /// When a car's overload sensor goes off, whoever just got in gets back out
+    fn observe(&mut self, _people: &mut [Person], event: &ElevatorEvent) {
+        if let ElevatorEvent::CarOverloaded { person_id, .. } = *event {
+            self.turned_away.insert(person_id);
+        }
+    }
+}
+
+/// Optional settings for a person added with PeopleSim::add_person
+/// profile - their behavior profile, instead of one picked from PeopleConfig::profiles
+/// extra - data to attach to them, before the spawn hook sees them
+/// operates - a car for them to take into independent service and drive, answering the
+/// buttons pressed inside it and in the halls, like an old fashioned elevator operator
+/// chaperone - someone they can't travel without. While they're waiting on the same floor,
+/// neither boards a car until there's room for both, and then they get in together
+/// group - the party they're travelling with. Those waiting on the same floor board together
+/// once a car has room for them all
+/// attributes - what they bring along, such as a pet, which PeopleConfig::rules restrict
+/// permitted_floors - the secured floors they have a card for
+#[derive(Debug, Default)]
+pub struct PersonOptions {
+    pub profile: Option<BehaviorProfile>,
+    pub operates: Option<CarId>,
+    pub chaperone: Option<PersonId>,
+    pub group: Option<GroupId>,
+    pub attributes: Vec<Attribute>,
+    pub permitted_floors: Vec<Floor>,
+    pub extra: Option<Box<dyn Any + Send>>,
+}
+
+/// PeopleSim object contains
+/// next_person_id - the id of the next person who will spawn
+/// topology - the building's floors, usually shared with the building itself
+/// spawner - decides when people arrive and where they go
+/// engine - decides what people do once they're here
+/// people - a vector of people
+/// elapsed - the total simulated time so far
+/// config - settings for how people behave, of which PeopleSim uses the profiles
+/// spawn_hook - called with each person as they spawn
+/// state_hook - called with each person whose state changes, and the state they left
+/// leaving - riders who were removed, the cars they'll step out of at the next stop, and
+/// how many kg they take out with them
+/// queues - who is waiting on each floor, in the order they arrived
+///
+/// The spawner, engine and hooks can be anything, so they aren't saved in snapshots. A
+/// PeopleSim deserialized on its own uses DefaultEngine and spawns nobody new
+#[derive(Deserialize, Serialize)]
+#[serde(from = "SavedPeople")]
+pub struct PeopleSim {
+    next_person_id: u32,
+    topology: Arc<BuildingTopology>,
+    #[serde(skip)]
+    spawner: Box<dyn Spawner + Send>,
+    #[serde(skip)]
+    engine: Box<dyn PersonEngine + Send>,
+    people: Vec<Person>,
+    elapsed: f32,
+    config: PeopleConfig,
+    #[serde(skip)]
+    spawn_hook: Option<PersonHook>,
+    #[serde(skip)]
+    state_hook: Option<StateHook>,
+    leaving: Vec<(CarId, PersonId, f32)>,
+    queues: HallQueues,
+}
+
+/// The parts of a PeopleSim which are saved in snapshots
+#[derive(Deserialize)]
+struct SavedPeople {
+    next_person_id: u32,
+    topology: Arc<BuildingTopology>,
+    people: Vec<Person>,
+    elapsed: f32,
+    config: PeopleConfig,
+    leaving: Vec<SavedLeaving>,
+    #[serde(default)]
+    queues: HallQueues,
+}
+
+/// A removed rider still to step out, as saved in snapshots, which left out their weight
+/// before people had one
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedLeaving {
+    Weighed(CarId, PersonId, f32),
+    Unweighed(CarId, PersonId),
+}
+
+impl From<SavedPeople> for PeopleSim {
+    fn from(saved: SavedPeople) -> Self {
+        let engine = DefaultEngine::new(&saved.config);
+        let mut people = Self::with_parts(
+            saved.topology.floors,
+            Box::new(ScriptedSpawner::new(Vec::new())),
+            Box::new(engine),
+            saved.config,
+        );
+        people.topology = saved.topology;
+        people.next_person_id = saved.next_person_id;
+        people.people = saved.people;
+        people.elapsed = saved.elapsed;
+        people.leaving = saved
+            .leaving
+            .into_iter()
+            .map(|leaving| match leaving {
+                SavedLeaving::Weighed(car_id, person_id, weight) => (car_id, person_id, weight),
+                SavedLeaving::Unweighed(car_id, person_id) => (car_id, person_id, 0.),
+            })
+            .collect();
+        people.queues = saved.queues;
+        // snapshots from before queues were kept queue everyone waiting in id order
+        people.queues.update(&people.people);
+        people
+    }
+}
+
+/// A function called with a person, which can look at them and attach data to them
+pub type PersonHook = Box<dyn FnMut(&mut Person) + Send>;
+
+/// A function called with a person whose state just changed, and the state they left
+pub type StateHook = Box<dyn FnMut(&mut Person, PersonState) + Send>;
+
+/// implement functions for PeopleSim
+/// new - create a new peoplesim object, with a certain number of floors
+/// with_config - create a new peoplesim object whose people behave according to a config
+/// with_pattern - create a new peoplesim object whose people arrive following a traffic pattern
+/// scripted - create a new peoplesim object which spawns exactly the given arrivals
+/// with_parts - create a new peoplesim object from any spawner and engine
+/// topology, set_topology - the building's floors, and sharing the building's own topology
+/// people - return a slice of People
+/// queues, summary - who is waiting on each floor, and a summary for controllers
+/// people_mut - return a mutable slice of People, for updating attached data
+/// add_person, remove_person - put a person into the world, or take one out
+/// set_spawn_hook, set_state_hook - run a function whenever a person spawns or changes state
+/// observe - let people react to something that happened in the building
+/// save_snapshot, load_snapshot - write everyone to a file, and read them back
+/// check_floors, check_arrivals - make sure nobody goes anywhere a building doesn't have
+/// tick - spawns people, and then lets the engine make decisions and generate PersonActions
+impl PeopleSim {
+    /// Create a new PeopleSim, with a particular number of floors
+    pub fn new(num_floors: Floor, spawn_interval: f32) -> Self {
+        Self::with_config(num_floors, spawn_interval, PeopleConfig::default())
+    }
+
+    /// Create a new PeopleSim, with a particular number of floors and a config. People
+    /// travel between random floors, one every spawn_interval seconds
+    pub fn with_config(num_floors: Floor, spawn_interval: f32, config: PeopleConfig) -> Self {
+        let pattern = Interfloor {
+            rate: 1. / spawn_interval,
+        };
+        Self::with_pattern(num_floors, Box::new(pattern), config)
+    }
+
+    /// Create a new PeopleSim, with a particular number of floors, whose people arrive
+    /// following a traffic pattern and behave according to a config
+    pub fn with_pattern(
+        num_floors: Floor,
+        pattern: Box<dyn TrafficPattern + Send>,
+        config: PeopleConfig,
+    ) -> Self {
+        let spawner = PatternSpawner::new(pattern);
+        let engine = DefaultEngine::new(&config);
+        Self::with_parts(num_floors, Box::new(spawner), Box::new(engine), config)
+    }
+
+    /// Create a new PeopleSim which, instead of spawning people randomly, spawns each
+    /// arrival at its scheduled time
+    pub fn scripted(
+        num_floors: Floor,
+        arrivals: Vec<ScriptedArrival>,
+        config: PeopleConfig,
+    ) -> Self {
+        let spawner = ScriptedSpawner::new(arrivals);
+        let engine = DefaultEngine::new(&config);
+        Self::with_parts(num_floors, Box::new(spawner), Box::new(engine), config)
+    }
+
+    /// Create a new PeopleSim whose people arrive from spawner and are driven by engine.
+    /// New people get their behavior profiles from config
+    pub fn with_parts(
+        num_floors: Floor,
+        spawner: Box<dyn Spawner + Send>,
+        engine: Box<dyn PersonEngine + Send>,
+        config: PeopleConfig,
+    ) -> Self {
+        Self {
+            next_person_id: 0,
+            topology: Arc::new(BuildingTopology::new(num_floors)),
+            spawner,
+            engine,
+            people: Vec::new(),
+            elapsed: 0.,
+            config,
+            spawn_hook: None,
+            state_hook: None,
+            leaving: Vec::new(),
+            queues: HallQueues::default(),
+        }
+    }
+
+    /// The floors people move between
+    pub fn topology(&self) -> &Arc<BuildingTopology> {
+        &self.topology
+    }
+
+    /// Have people move between the floors of topology, usually the one the building was
+    /// built with, so both share it
+    pub fn set_topology(&mut self, topology: Arc<BuildingTopology>) {
+        self.topology = topology;
+    }
+
+    /// Return a slice of all people
+    pub fn people(&self) -> &[Person] {
+        &self.people
+    }
+
+    /// Who is waiting on each floor, in the order they arrived
+    pub fn queues(&self) -> &HallQueues {
+        &self.queues
+    }
+
+    /// A summary of the people, such as how many are waiting on each floor, for controllers
+    pub fn summary(&self) -> PeopleSummary<'_> {
+        PeopleSummary::new(&self.queues)
+    }
+
+    /// Return a mutable slice of all people, so programs embedding the simulation can update
+    /// the data they've attached to them
+    pub fn people_mut(&mut self) -> &mut [Person] {
+        &mut self.people
+    }
+
+    /// Call hook with every person as they spawn, before they do anything
+    pub fn set_spawn_hook(&mut self, hook: impl FnMut(&mut Person) + Send + 'static) {
+        self.spawn_hook = Some(Box::new(hook));
+    }
+
+    /// Call hook with every person whose state changes during a tick, after the change
+    pub fn set_state_hook(&mut self, hook: impl FnMut(&mut Person, PersonState) + Send + 'static) {
+        self.state_hook = Some(Box::new(hook));
+    }
+
+    /// Put a new person on origin who wants to go to destination, alongside anyone the
+    /// spawner creates. They start out like a spawned person and the spawn hook sees them
+    pub fn add_person(
+        &mut self,
+        origin: Floor,
+        destination: Floor,
+        opts: PersonOptions,
+    ) -> PersonId {
+        self.spawn(origin, destination, opts)
+    }
+
+    /// Take a person out of the world, returning them, or None if there's nobody with that
+    /// id. Someone removed while riding still steps out of their car at its next stop, so
+    /// the building doesn't keep counting them
+    pub fn remove_person(&mut self, id: PersonId) -> Option<Person> {
+        let index = self.people.iter().position(|p| p.id == id)?;
+        let person = self.people.remove(index);
+        if person.state == PersonState::Riding
+            && let Some(car_id) = person.in_car
+        {
+            self.leaving.push((car_id, id, person.weight));
+        }
+        self.queues.update(&self.people);
+        Some(person)
+    }
+
+    /// Save everyone, and the time, to a JSON file
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        snapshot::save(path, self)
+    }
+
+    /// Replace everyone, and the time, with those saved by save_snapshot. This PeopleSim keeps
+    /// its own spawner, engine and hooks, and the spawner skips ahead to the saved time
+    pub fn load_snapshot(&mut self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let saved: PeopleSim = snapshot::load(path)?;
+        saved
+            .check_floors(self.topology.floors)
+            .map_err(SnapshotError::Floors)?;
+        self.restore(saved);
+        Ok(())
+    }
+
+    /// Check everyone stays within a building with floors floors: that people don't move
+    /// between more floors than that, nobody is on or heading for a floor past the top, and
+    /// nobody is scripted to arrive on or head for one
+    pub fn check_floors(&self, floors: Floor) -> Result<(), FloorMismatch> {
+        if self.topology.floors > floors {
+            return Err(FloorMismatch::People {
+                num_floors: self.topology.floors,
+                building: floors,
+            });
+        }
+        for person in &self.people {
+            let floor = person.current_floor.max(person.target_floor);
+            if floor >= floors {
+                return Err(FloorMismatch::Person {
+                    person: person.id,
+                    floor,
+                    building: floors,
+                });
+            }
+        }
+        self.check_arrivals(floors)
+    }
+
+    /// Check nobody the spawner knows about is due to arrive on or head for a floor past the
+    /// top of a building with floors floors
+    pub fn check_arrivals(&self, floors: Floor) -> Result<(), FloorMismatch> {
+        match self.spawner.highest_floor() {
+            Some(floor) if floor >= floors => Err(FloorMismatch::Arrival {
+                floor,
+                building: floors,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Take the people and time from saved, keeping this PeopleSim's parts
+    pub(crate) fn restore(&mut self, saved: PeopleSim) {
+        self.next_person_id = saved.next_person_id;
+        self.topology = saved.topology;
+        self.people = saved.people;
+        self.elapsed = saved.elapsed;
+        self.config = saved.config;
+        self.leaving = saved.leaving;
+        self.queues = saved.queues;
+        self.spawner.skip_to(self.elapsed);
+    }
+
+    /// Let people react to an event from the building, in whatever way the engine decides
+    pub fn observe(&mut self, event: &ElevatorEvent) {
+        self.engine.observe(&mut self.people, event);
+    }
+
+    /// Take in BuildingState, and return a vector of PersonActions, which main
+    /// can translate into ElevatorActions
+    pub fn tick(&mut self, dt: f32, building: &BuildingState) -> Vec<PersonAction> {
+        self.elapsed += dt;
+
+        for (start_floor, target_floor) in
+            self.spawner.spawn(self.elapsed, dt, self.topology.floors)
+        {
+            // some arrivals are parties, named after whoever in them spawns first
+            let p = self.config.groups;
+            let party = if p > 0. && rng::random::<f32>(Stream::Arrivals) < p {
+                rng::random_range(Stream::Arrivals, 2..=self.config.largest_group.max(2))
+            } else {
+                1
+            };
+            let group = (party > 1).then_some(GroupId(self.next_person_id));
+            for _ in 0..party {
+                let adult = PersonOptions {
+                    attributes: self.config.pick_attributes(start_floor, target_floor),
+                    group,
+                    permitted_floors: self.cards_for(target_floor),
+                    ..PersonOptions::default()
+                };
+                let adult = self.spawn(start_floor, target_floor, adult);
+                let p = self.config.chaperoned_children;
+                if p > 0. && rng::random::<f32>(Stream::Arrivals) < p {
+                    let child = PersonOptions {
+                        profile: Some(ProfileKind::Child.profile()),
+                        chaperone: Some(adult),
+                        ..PersonOptions::default()
+                    };
+                    self.spawn(start_floor, target_floor, child);
+                }
+            }
+        }
+
+        // remember everyone's state, so the hook can be told who changed
+        let previous: Vec<PersonState> = match self.state_hook {
+            Some(_) => self.people.iter().map(|p| p.state).collect(),
+            None => Vec::new(),
+        };
+
+        let mut actions = self
+            .engine
+            .tick(&mut self.people, &self.queues, dt, building);
+        self.queues.update(&self.people);
+
+        // removed riders get out once their car's door opens
+        self.leaving.retain(|&(car_id, person_id, weight)| {
+            let stopped = building
+                .cars
+                .get(car_id.0 as usize)
+                .is_some_and(|car| car.door_open());
+            if stopped {
+                actions.push(PersonAction::ExitCar {
+                    car_id,
+                    person_id,
+                    weight,
+                });
+            }
+            !stopped
+        });
+
+        if let Some(hook) = &mut self.state_hook {
+            for (person, &before) in self.people.iter_mut().zip(&previous) {
+                if person.state != before {
+                    hook(person, before);
+                }
+            }
+        }
+
+        actions
+    }
+
+    /// How long until something happens to the people, if nothing changes in the building.
+    /// This is 0 if anyone would act on the next tick, otherwise it's the time until someone
+    /// acts or the next person spawns. Returns None if nothing will ever happen, such as at
+    /// the end of a script
+    pub fn time_to_next_event(&self, building: &BuildingState) -> Option<f32> {
+        let leaving = self.leaving.iter().any(|&(car_id, ..)| {
+            building
+                .cars
+                .get(car_id.0 as usize)
+                .is_some_and(|car| car.door_open())
+        });
+        if leaving {
+            return Some(0.);
+        }
+        [
+            self.spawner.time_to_next_spawn(self.elapsed),
+            self.engine
+                .time_to_next_event(&self.people, &self.queues, building),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f32::min)
+    }
+
+    /// The secured floors someone arriving for a trip to destination has a card for: their
+    /// destination if it's secured, unless they're a visitor without one
+    fn cards_for(&self, destination: Floor) -> Vec<Floor> {
+        if !self.topology.is_secured(destination) {
+            return Vec::new();
+        }
+        let p = self.config.visitors;
+        if p > 0. && rng::random::<f32>(Stream::Arrivals) < p {
+            Vec::new()
+        } else {
+            vec![destination]
+        }
+    }
+
+    /// Create a new person on start_floor who wants to go to target_floor, returning their id
+    fn spawn(&mut self, start_floor: Floor, target_floor: Floor, opts: PersonOptions) -> PersonId {
+        let id = PersonId(self.next_person_id);
+        self.next_person_id += 1;
+
+        let profile = opts.profile.unwrap_or_else(|| self.config.pick_profile());
+        let mut person = Person {
+            id,
+            current_floor: start_floor,
+            target_floor,
+            onward: Vec::new(),
+            state: PersonState::New,
+            in_car: None,
+            approaching: None,
+            reaction_time: profile.reaction_time.sample(),
+            reaction_timer: None,
+            waited: 0.,
+            overflowed: false,
+            operates: opts.operates,
+            chaperone: opts.chaperone,
+            group: opts.group,
+            permitted_floors: opts.permitted_floors,
+            attributes: opts.attributes,
+            profile,
+            weight: profile.weight.sample(),
+            stepped_out_of: None,
+            extra: opts.extra,
+        };
+
+        if let Some(hook) = &mut self.spawn_hook {
+            hook(&mut person);
+        }
+        // a trip no bank makes on its own goes by way of sky lobbies
+        if person.operates.is_none() {
+            let transfers = self
+                .topology
+                .transfers(person.current_floor, person.target_floor);
+            if let Some(&first) = transfers.first() {
+                person.onward = transfers[1..].to_vec();
+                person.onward.push(person.target_floor);
+                person.target_floor = first;
+            }
+        }
+        self.people.push(person);
+        id
+    }
+}
+
+/// Find a car on a person's floor with its door open and room places free inside, which the
+/// boarding policy and rules let them get into, preferring the car they walked towards. boarded
+/// lists the cars people have already got into this tick, and how many places they took.
+/// Nobody gets into a car during an emergency
+fn car_to_board(
+    building: &BuildingState,
+    person: &Person,
+    policy: BoardingPolicy,
+    rules: &[BoardingRule],
+    boarded: &[(CarId, u32)],
+    room: u32,
+) -> Option<CarId> {
+    pick_car(building, person, policy, rules, boarded, room, |car| {
+        car.door_open()
+    })
+}
+
+/// Find a car on a person's floor whose door is closing on them, which they'd have got into
+/// had it still been open
+fn car_closing_on(
+    building: &BuildingState,
+    person: &Person,
+    policy: BoardingPolicy,
+    rules: &[BoardingRule],
+    room: u32,
+) -> Option<CarId> {
+    pick_car(building, person, policy, rules, &[], room, |car| {
+        matches!(car.door, DoorState::Closing { .. })
+    })
+}
+
+/// Find a car on a person's floor whose door is as door says, for car_to_board and
+/// car_closing_on
+fn pick_car(
+    building: &BuildingState,
+    person: &Person,
+    policy: BoardingPolicy,
+    rules: &[BoardingRule],
+    boarded: &[(CarId, u32)],
+    room: u32,
+    door: fn(&ElevatorCarState) -> bool,
+) -> Option<CarId> {
+    if building.emergency != EmergencyMode::Normal {
+        return None;
+    }
+    building
+        .cars
+        .iter()
+        .filter(|car| door(car) && car.current_floor.round() as Floor == person.current_floor)
+        // nobody tries the car they just had to get back out of again
+        .filter(|car| person.stepped_out_of != Some(car.id))
+        // nobody gets into a car that's out of service or broken down, and a car kept for a
+        // VIP only lets in the one person who gets in first once it's come for them
+        .filter(|car| {
+            let joined = boarded.iter().any(|&(id, _)| id == car.id);
+            let service = match car.service {
+                ServiceState::OutOfService => false,
+                ServiceState::Vip { stage, .. } => {
+                    stage == VipStage::Boarding && car.passenger_count == 0 && !joined && room == 1
+                }
+                _ => true,
+            };
+            service && car.malfunction.is_none()
+        })
+        // nobody gets into a car that doesn't stop at their floor, isn't in their bank, or
+        // isn't for them
+        .filter(|car| car.serves(person.target_floor))
+        .filter(|car| class_allows(car, person))
+        .filter(|car| trip_bank(building, person).is_none_or(|bank| car.bank == Some(bank)))
+        .filter(|car| {
+            let joined: u32 = boarded
+                .iter()
+                .filter(|&&(id, _)| id == car.id)
+                .map(|&(_, places)| places)
+                .sum();
+            car.passenger_count + joined + room <= car.capacity
+        })
+        .filter(|car| rules.iter().all(|rule| rule.allows(person, car)))
+        .filter(|car| match policy {
+            BoardingPolicy::Opportunistic => true,
+            BoardingPolicy::MatchingDirection => car
+                .direction
+                .is_none_or(|direction| direction == person.direction()),
+            BoardingPolicy::Assigned => {
+                let assignment = building
+                    .floors
+                    .get(person.current_floor as usize)
+                    .and_then(|f| {
+                        f.assignments
+                            .iter()
+                            .find(|a| a.destination == person.target_floor)
+                    });
+                assignment.is_none_or(|a| a.car_id == car.id)
+            }
+        })
+        // people take a passenger car over a freight car when they can
+        .min_by_key(|car| {
+            (
+                person.approaching != Some(car.id),
+                car.class != CarClass::Passenger,
+            )
+        })
+        .map(|car| car.id)
+}
+
+/// Whether a person uses cars of a class at all: deliveries go in freight and service cars,
+/// and everyone else in passenger and freight cars
+fn rides_in(person: &Person, class: CarClass) -> bool {
+    let delivery = person.attributes.contains(&Attribute::Delivery);
+    match class {
+        CarClass::Passenger => !delivery,
+        CarClass::Freight => true,
+        CarClass::Service => delivery,
+    }
+}
+
+/// Whether a person can get into a car now, as one of a class they use, and not a freight
+/// car reserved for deliveries unless they're one
+fn class_allows(car: &ElevatorCarState, person: &Person) -> bool {
+    rides_in(person, car.class)
+        && (car.reserved.is_none() || person.attributes.contains(&Attribute::Delivery))
+}
+
+/// The first car with its lantern lit on a person's floor that suits them: the car they've
+/// been assigned if they have one, or else one which goes on their way, or hasn't said
+fn lit_for(building: &BuildingState, person: &Person) -> Option<CarId> {
+    let floor_state = building.floors.get(person.current_floor as usize)?;
+    let assigned = floor_state
+        .assignments
+        .iter()
+        .find(|a| a.destination == person.target_floor)
+        .map(|a| a.car_id);
+    floor_state
+        .lanterns
+        .iter()
+        .find(|l| match assigned {
+            Some(car_id) => l.car_id == car_id,
+            None => l.direction.is_none_or(|d| d == person.direction()),
+        })
+        .map(|l| l.car_id)
+}
+
+/// The action a person takes to call an elevator from the hall, pressing their bank's
+/// buttons in a building with banks, and the accessible call button if they use a wheelchair
+fn call_action(person: &Person, mode: HallCallMode, building: &BuildingState) -> PersonAction {
+    match mode {
+        HallCallMode::Buttons if person.accessible() => PersonAction::CallAccessible {
+            floor: person.current_floor,
+            direction: person.direction(),
+            bank: trip_bank(building, person),
+        },
+        HallCallMode::Buttons => PersonAction::CallElevator {
+            floor: person.current_floor,
+            direction: person.direction(),
+            bank: trip_bank(building, person),
+        },
+        HallCallMode::Kiosk => PersonAction::RequestTrip {
+            floor: person.current_floor,
+            destination: person.target_floor,
+        },
+    }
+}
+
+/// Whether the building knows about a person's call: the hall button they need is lit, or
+/// their destination is waiting at the kiosk or already assigned to a car. Someone in a
+/// wheelchair needs the floor's accessible call lit too. Returns None
+/// if their floor doesn't exist, or its call buttons are broken, since calling again won't
+/// help
+fn call_registered(building: &BuildingState, person: &Person, mode: HallCallMode) -> Option<bool> {
+    let floor_state = building.floors.get(person.current_floor as usize)?;
+    Some(match mode {
+        HallCallMode::Buttons if floor_state.buttons_broken => return None,
+        HallCallMode::Buttons if person.accessible() && !floor_state.access_call => false,
+        HallCallMode::Buttons => match trip_bank(building, person) {
+            Some(bank) => floor_state.bank_call(bank, person.direction()),
+            None => floor_state.hall_call(person.direction()),
+        },
+        HallCallMode::Kiosk => {
+            floor_state.trip_requests.contains(&person.target_floor)
+                || floor_state
+                    .assignments
+                    .iter()
+                    .any(|a| a.destination == person.target_floor)
+        }
+    })
+}
+
+/// The bank a person travels with, the first which makes their whole trip, if the building
+/// has one
+fn trip_bank(building: &BuildingState, person: &Person) -> Option<BankId> {
+    building
+        .topology
+        .trip_bank(person.current_floor, person.target_floor)
+}
+
+/// How many places are taken in each floor's waiting area, by everyone waiting there but
+/// not in the back area. Empty if every waiting area has room for everyone
+fn waiting_areas(people: &[Person], building: &BuildingState) -> HashMap<Floor, u32> {
+    let mut taken = HashMap::new();
+    if building.topology.hall_capacities.is_empty() {
+        return taken;
+    }
+    for person in people {
+        if person.state == PersonState::Waiting && !person.overflowed && person.operates.is_none() {
+            *taken.entry(person.current_floor).or_insert(0) += person.places();
+        }
+    }
+    taken
+}
+
+/// Who is waiting with whom. Children waiting on the same floor as their chaperone, and
+/// people waiting on the same floor as the rest of their group, get in along with whoever
+/// leads them: their chaperone, or the first of the group to spawn, or their chaperone's
+/// leader. The leader calls the car, and needs room in it for the whole party
+struct Parties {
+    /// for each leader and floor, the places needed by the people they lead there
+    followers: HashMap<(PersonId, Floor), u32>,
+    /// everyone who hasn't boarded yet, the floor they're waiting on, and their group
+    waiting: HashMap<PersonId, (Floor, Option<GroupId>)>,
+    /// the first of each group waiting on each floor
+    group_leaders: HashMap<(GroupId, Floor), PersonId>,
+}
+
+impl Parties {
+    /// Find the parties among people who haven't boarded yet
+    fn new(people: &[Person]) -> Self {
+        let mut parties = Parties {
+            followers: HashMap::new(),
+            waiting: HashMap::new(),
+            group_leaders: HashMap::new(),
+        };
+        let waiting: Vec<&Person> = people
+            .iter()
+            .filter(|p| matches!(p.state, PersonState::New | PersonState::Waiting))
+            .collect();
+        for person in &waiting {
+            parties
+                .waiting
+                .insert(person.id, (person.current_floor, person.group));
+            if let Some(group) = person.group {
+                parties
+                    .group_leaders
+                    .entry((group, person.current_floor))
+                    .or_insert(person.id);
+            }
+        }
+        for person in waiting {
+            if let Some(leader) = parties.leader(person) {
+                *parties
+                    .followers
+                    .entry((leader, person.current_floor))
+                    .or_insert(0) += person.places();
+            }
+        }
+        parties
+    }
+
+    /// Who a person boards along with, if they're following someone waiting on their floor
+    fn leader(&self, person: &Person) -> Option<PersonId> {
+        let floor = person.current_floor;
+        if let Some(chaperone) = person
+            .chaperone
+            .filter(|c| self.waiting.get(c).is_some_and(|&(f, _)| f == floor))
+        {
+            return Some(self.group_leader(chaperone, floor).unwrap_or(chaperone));
+        }
+        self.group_leader(person.id, floor)
+    }
+
+    /// The first of someone's group waiting on floor, unless it's them
+    fn group_leader(&self, id: PersonId, floor: Floor) -> Option<PersonId> {
+        let &(_, group) = self.waiting.get(&id)?;
+        let leader = *self.group_leaders.get(&(group?, floor))?;
+        (leader != id).then_some(leader)
+    }
+
+    /// Whether a person waits with someone else, who they'll board with
+    fn follows(&self, person: &Person) -> bool {
+        self.leader(person).is_some()
+    }
+
+    /// How many places a person needs free to board, counting everyone waiting with them
+    fn room(&self, person: &Person) -> u32 {
+        let followers = self.followers.get(&(person.id, person.current_floor));
+        person.places() + followers.copied().unwrap_or(0)
+    }
+}
+
+/// Cars someone is about to get on or off: people waiting next to them, and riders who
+/// have reached their floor
+fn busy_cars(
+    people: &[Person],
+    building: &BuildingState,
+    policy: BoardingPolicy,
+    rules: &[BoardingRule],
+    parties: &Parties,
+) -> Vec<CarId> {
+    people
+        .iter()
+        .filter(|p| p.operates.is_none() && !parties.follows(p))
+        .filter_map(|p| match p.state {
+            PersonState::Waiting => car_to_board(building, p, policy, rules, &[], parties.room(p)),
+            PersonState::Riding if at_destination(building, p) => p.in_car,
+            _ => None,
+        })
+        .collect()
+}
+
+/// What an operator does next, if anything. They take their car into independent service,
+/// bring it to their floor and get in. Then, once nobody is getting on or off, they drive
+/// to the nearest floor with a button lit, inside the car or in the hall, carrying on in
+/// heading while there are requests that way
+fn operator_action(
+    person: &Person,
+    building: &BuildingState,
+    busy: &[CarId],
+    heading: Direction,
+) -> Option<PersonAction> {
+    let car_id = person.operates?;
+    let car = building.cars.get(car_id.0 as usize)?;
+    // a car out of service isn't theirs to take until it's back
+    if car.service == ServiceState::OutOfService {
+        return None;
+    }
+    if car.service != ServiceState::Independent {
+        return Some(PersonAction::TakeOverCar { car_id });
+    }
+
+    let car_floor = car.current_floor.round() as Floor;
+    if person.in_car.is_none() {
+        if car.target_floor().is_some() {
+            return None;
+        }
+        let here = car_floor == person.current_floor;
+        if here && car.door_open() && !car.is_full() {
+            return Some(PersonAction::EnterCar {
+                car_id,
+                person_id: person.id,
+                weight: person.weight,
+            });
+        }
+        if !(here && car.door_opening_or_open()) {
+            return Some(PersonAction::OperateCar {
+                car_id,
+                floor: person.current_floor,
+            });
+        }
+        return None;
+    }
+
+    if car.target_floor().is_some()
+        || !car.door_open()
+        || car.transfer.is_some()
+        || busy.contains(&car_id)
+    {
+        return None;
+    }
+    next_operated_stop(car, building, heading)
+        .map(|floor| PersonAction::OperateCar { car_id, floor })
+}
+
+/// The nearest requested floor in heading, or if there isn't one, the nearest the other way
+fn next_operated_stop(
+    car: &ElevatorCarState,
+    building: &BuildingState,
+    heading: Direction,
+) -> Option<Floor> {
+    let car_floor = car.current_floor.round() as Floor;
+    let requested = |floor: &FloorState| {
+        let pressed = car
+            .car_buttons
+            .get(floor.floor as usize)
+            .copied()
+            .unwrap_or(false);
+        floor.floor != car_floor && car.serves(floor.floor) && (pressed || floor.called())
+    };
+    let nearest = |direction: Direction| {
+        building
+            .floors
+            .iter()
+            .filter(|f| requested(f))
+            .filter(|f| match direction {
+                Direction::Up => f.floor > car_floor,
+                Direction::Down => f.floor < car_floor,
+            })
+            .map(|f| f.floor)
+            .min_by_key(|f| f.abs_diff(car_floor))
+    };
+    let other = match heading {
+        Direction::Up => Direction::Down,
+        Direction::Down => Direction::Up,
+    };
+    nearest(heading).or_else(|| nearest(other))
+}
+
+/// The recall floor, if a riding person's car has been recalled there and has its door open
+fn recalled_to(building: &BuildingState, person: &Person) -> Option<Floor> {
+    let floor = building.emergency.recall_floor()?;
+    let car = building.cars.get(person.in_car?.0 as usize)?;
+    (car.current_floor.round() as Floor == floor && car.door_open()).then_some(floor)
+}
+
+/// The floor a riding person's car has its door open on, if it won't take them to their
+/// target floor: a secured floor they have no card for, which nobody else has pressed the
+/// button for either
+fn locked_out_at(building: &BuildingState, person: &Person) -> Option<Floor> {
+    let floor = person.target_floor;
+    if !building.topology.is_secured(floor) || person.permitted_floors.contains(&floor) {
+        return None;
+    }
+    let car = building.cars.get(person.in_car?.0 as usize)?;
+    let pressed = car
+        .car_buttons
+        .get(floor as usize)
+        .copied()
+        .unwrap_or(false);
+    (!pressed && car.door_open()).then_some(car.current_floor.round() as Floor)
+}
+
+/// Whether a riding person's car is at their target floor with its door open
+fn at_destination(building: &BuildingState, person: &Person) -> bool {
+    let Some(car_id) = person.in_car else {
+        return false;
+    };
+    let Some(car) = building.cars.get(car_id.0 as usize) else {
+        return false;
+    };
+    car.current_floor.round() as Floor == person.target_floor && car.door_open()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::{BuildingState, ElevatorCommand, ElevatorSim, Lantern};
+    use crate::metrics::RunSummary;
+
+    fn empty_building() -> BuildingState {
+        BuildingState {
+            topology: Arc::default(),
+            floors: Vec::new(),
+            cars: Vec::new(),
+            emergency: EmergencyMode::Normal,
+            clock: SimClock::default(),
+        }
+    }
+
+    #[test]
+    fn person_spawned_and_calls_elevator() {
+        let mut sim = PeopleSim::new(5, 0.1);
+        let building = empty_building();
+
+        let actions = sim.tick(1.0, &building);
+
+        assert_eq!(sim.people().len(), 1);
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[test]
+    fn scripted_people_spawn_on_time() {
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 2.0,
+                origin: 3,
+                destination: 0,
+            },
+            ScriptedArrival {
+                time: 0.5,
+                origin: 1,
+                destination: 4,
+            },
+        ];
+        let mut sim = PeopleSim::scripted(5, arrivals, PeopleConfig::default());
+        let building = empty_building();
+
+        sim.tick(1.0, &building);
+        assert_eq!(sim.people().len(), 1);
+        assert_eq!(sim.people()[0].current_floor, 1);
+
+        sim.tick(1.0, &building);
+        assert_eq!(sim.people().len(), 2);
+        assert_eq!(sim.people()[1].target_floor, 0);
+    }
+
+    #[test]
+    fn matching_direction_skips_car_going_the_other_way() {
+        let mut building = ElevatorSim::new(5, 1);
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 2,
+            destination: 4,
+        }];
+
+        // a car sits at floor 2 with its door open, but someone inside wants floor 0
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 2,
+            })
+            .unwrap();
+        for _ in 0..30 {
+            building.tick(0.1);
+        }
+        building
+            .apply_command(ElevatorCommand::PressCarButton {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+
+        // the button commits the car to going down, and by default people respect that
+        assert_eq!(building.state().cars[0].direction, Some(Direction::Down));
+        let mut strict = PeopleSim::scripted(5, arrivals.clone(), PeopleConfig::default());
+        strict.tick(0.1, building.state());
+        strict.tick(0.1, building.state());
+        assert!(matches!(strict.people()[0].state, PersonState::Waiting));
+
+        let config = PeopleConfig {
+            boarding: BoardingPolicy::Opportunistic,
+            ..PeopleConfig::default()
+        };
+        let mut anyone = PeopleSim::scripted(5, arrivals, config);
+        anyone.tick(0.1, building.state());
+        anyone.tick(0.1, building.state());
+        assert!(matches!(anyone.people()[0].state, PersonState::Riding));
+    }
+
+    #[test]
+    fn assigned_policy_boards_assigned_car_only() {
+        let mut building = ElevatorSim::new(5, 2);
+        // both cars are open at floor 0, but the trip to floor 3 is assigned to car 1
+        for car in 0..2 {
+            building
+                .apply_command(ElevatorCommand::MoveCarTo {
+                    car_id: CarId(car),
+                    floor: 0,
+                })
+                .unwrap();
+        }
+        building.tick(0.1);
+        building
+            .apply_command(ElevatorCommand::AssignTrip {
+                floor: 0,
+                destination: 3,
+                car_id: CarId(1),
+            })
+            .unwrap();
+
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 0,
+            destination: 3,
+        }];
+        let config = PeopleConfig {
+            boarding: BoardingPolicy::Assigned,
+            ..PeopleConfig::default()
+        };
+        let mut sim = PeopleSim::scripted(5, arrivals, config);
+        sim.tick(0.1, building.state());
+        sim.tick(0.1, building.state());
+
+        assert_eq!(sim.people()[0].in_car, Some(CarId(1)));
+    }
+
+    #[test]
+    fn full_car_leaves_people_waiting_to_call_again() {
+        let config = crate::elevator::ElevatorConfig {
+            capacities: vec![1],
+            ..Default::default()
+        };
+        let mut building = ElevatorSim::with_config(5, 1, config);
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 0.,
+                origin: 0,
+                destination: 3,
+            };
+            2
+        ];
+        let mut sim = PeopleSim::scripted(5, arrivals, PeopleConfig::default());
+        sim.tick(0.1, building.state());
+        let actions = sim.tick(0.1, building.state());
+
+        // only one fits, so the other presses the hall button again
+        assert!(matches!(sim.people()[0].state, PersonState::Riding));
+        assert!(matches!(sim.people()[1].state, PersonState::Waiting));
+        assert!(actions.iter().any(|a| matches!(
+            a,
+            PersonAction::CallElevator {
+                floor: 0,
+                direction: Direction::Up,
+                bank: None
+            }
+        )));
+    }
+
+    #[test]
+    fn wheelchair_users_press_the_accessible_button_and_need_more_room() {
+        let config = crate::elevator::ElevatorConfig {
+            capacities: vec![2],
+            ..Default::default()
+        };
+        let mut building = ElevatorSim::with_config(5, 1, config);
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+        building
+            .apply_command(ElevatorCommand::Board {
+                car_id: CarId(0),
+                person_id: PersonId(99),
+                weight: 75.,
+            })
+            .unwrap();
+
+        let mut sim = PeopleSim::scripted(5, Vec::new(), PeopleConfig::default());
+        let wheelchair = PersonOptions {
+            attributes: vec![Attribute::Wheelchair],
+            ..PersonOptions::default()
+        };
+        sim.add_person(0, 3, wheelchair);
+        let actions = sim.tick(0.1, building.state());
+
+        // the one place left isn't enough, so they call another car
+        assert_eq!(sim.people()[0].places(), 2);
+        assert_eq!(sim.people()[0].state, PersonState::Waiting);
+        assert!(matches!(
+            actions[..],
+            [PersonAction::CallAccessible {
+                floor: 0,
+                direction: Direction::Up,
+                bank: None
+            }]
+        ));
+    }
+
+    #[test]
+    fn people_step_into_a_closing_door_once() {
+        let building = ElevatorSim::new(3, 1);
+        let mut state = building.state().clone();
+        state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 0,
+            destination: 2,
+        }];
+        let config = PeopleConfig {
+            hall_walk_distance: 10.,
+            ..PeopleConfig::default()
+        };
+        let mut sim = PeopleSim::scripted(3, arrivals, config);
+        sim.tick(0.1, &state);
+        sim.tick(0.1, &state);
+
+        // the door starts closing while they walk over, so they step into it
+        state.cars[0].door = DoorState::Closing { remaining: 1. };
+        let actions = sim.tick(0.1, &state);
+        assert!(
+            actions
+                .iter()
+                .any(|a| matches!(a, PersonAction::HoldDoor { car_id: CarId(0) }))
+        );
+        assert_eq!(sim.people()[0].approaching, Some(CarId(0)));
+
+        // it didn't reopen, so they wait for it to come back
+        let actions = sim.tick(0.1, &state);
+        assert!(
+            !actions
+                .iter()
+                .any(|a| matches!(a, PersonAction::HoldDoor { .. }))
+        );
+        assert!(matches!(sim.people()[0].state, PersonState::Waiting));
+    }
+
+    #[test]
+    fn whoever_overloads_a_car_steps_back_out() {
+        let building = ElevatorSim::new(3, 1);
+        let mut state = building.state().clone();
+        state.cars[0].door = DoorState::Open {
+            dwell_remaining: 0.,
+        };
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 0,
+            destination: 2,
+        }];
+        let mut sim = PeopleSim::scripted(3, arrivals, PeopleConfig::default());
+        sim.tick(0.1, &state);
+        sim.tick(0.1, &state);
+        assert!(matches!(sim.people()[0].state, PersonState::Riding));
+
+        // the sensor goes off as they get in
+        state.cars[0].overloaded = true;
+        state.cars[0].passenger_count = state.cars[0].capacity + 1;
+        sim.observe(&ElevatorEvent::CarOverloaded {
+            car_id: CarId(0),
+            person_id: sim.people()[0].id,
+        });
+        assert_eq!(sim.time_to_next_event(&state), Some(0.));
+        let actions = sim.tick(0.1, &state);
+        assert!(actions.iter().any(|a| matches!(
+            a,
+            PersonAction::ExitCar {
+                car_id: CarId(0),
+                ..
+            }
+        )));
+        assert!(matches!(sim.people()[0].state, PersonState::Waiting));
+        assert_eq!(sim.people()[0].in_car, None);
+
+        // and the full car doesn't tempt them back in
+        state.cars[0].overloaded = false;
+        state.cars[0].passenger_count = state.cars[0].capacity;
+        sim.tick(0.1, &state);
+        assert!(matches!(sim.people()[0].state, PersonState::Waiting));
+    }
+
+    #[test]
+    fn people_board_the_car_they_walked_towards() {
+        let mut building = ElevatorSim::new(5, 2);
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 3,
+            destination: 0,
+        }];
+        let mut sim = PeopleSim::scripted(5, arrivals, PeopleConfig::default());
+        sim.tick(0.1, building.state());
+
+        // car 1's lantern lights first, then both cars turn up together
+        let mut state = building.state().clone();
+        state.floors[3].out_down = true;
+        state.floors[3].lanterns = vec![
+            Lantern {
+                car_id: CarId(1),
+                direction: None,
+            },
+            Lantern {
+                car_id: CarId(0),
+                direction: None,
+            },
+        ];
+        sim.tick(0.1, &state);
+        assert_eq!(sim.people()[0].approaching, Some(CarId(1)));
+
+        for car in 0..2 {
+            building
+                .apply_command(ElevatorCommand::MoveCarTo {
+                    car_id: CarId(car),
+                    floor: 3,
+                })
+                .unwrap();
+        }
+        for _ in 0..40 {
+            building.tick(0.1);
+        }
+        sim.tick(0.1, building.state());
+        assert_eq!(sim.people()[0].in_car, Some(CarId(1)));
+    }
+
+    #[test]
+    fn people_only_walk_towards_a_lantern_going_their_way() {
+        let building = ElevatorSim::new(5, 2);
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 3,
+            destination: 0,
+        }];
+        let mut sim = PeopleSim::scripted(5, arrivals, PeopleConfig::default());
+        let mut state = building.state().clone();
+        state.floors[3].out_down = true;
+        sim.tick(0.1, &state);
+
+        state.floors[3].lanterns = vec![Lantern {
+            car_id: CarId(0),
+            direction: Some(Direction::Up),
+        }];
+        sim.tick(0.1, &state);
+        assert_eq!(sim.people()[0].approaching, None);
+
+        state.floors[3].lanterns.push(Lantern {
+            car_id: CarId(1),
+            direction: Some(Direction::Down),
+        });
+        sim.tick(0.1, &state);
+        assert_eq!(sim.people()[0].approaching, Some(CarId(1)));
+    }
+
+    #[test]
+    fn people_take_their_reaction_time_to_board() {
+        let mut building = ElevatorSim::new(5, 1);
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 0,
+            destination: 2,
+        }];
+        let profile = BehaviorProfile {
+            reaction_time: ReactionTime::Fixed(0.5),
+            ..BehaviorProfile::default()
+        };
+        let config = PeopleConfig {
+            profiles: vec![(profile, 1.)],
+            ..PeopleConfig::default()
+        };
+        let mut sim = PeopleSim::scripted(5, arrivals, config);
+        for _ in 0..5 {
+            sim.tick(0.1, building.state());
+        }
+        assert!(matches!(sim.people()[0].state, PersonState::Waiting));
+        assert!(sim.time_to_next_event(building.state()).unwrap() > 0.);
+
+        for _ in 0..3 {
+            sim.tick(0.1, building.state());
+        }
+        assert!(matches!(sim.people()[0].state, PersonState::Riding));
+    }
+
+    #[test]
+    fn profiles_are_picked_by_weight_and_deliveries_need_room() {
+        let config = PeopleConfig {
+            profiles: vec![
+                (ProfileKind::Elderly.profile(), 0.),
+                (ProfileKind::Delivery.profile(), 1.),
+            ],
+            ..PeopleConfig::default()
+        };
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 0.,
+                origin: 0,
+                destination: 3,
+            };
+            5
+        ];
+        let mut sim = PeopleSim::scripted(5, arrivals, config);
+
+        // one place left isn't enough room for a trolley
+        let mut building = ElevatorSim::with_config(
+            5,
+            1,
+            crate::elevator::ElevatorConfig {
+                capacities: vec![2],
+                ..Default::default()
+            },
+        );
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+        building
+            .apply_command(ElevatorCommand::Board {
+                car_id: CarId(0),
+                person_id: PersonId(99),
+                weight: 75.,
+            })
+            .unwrap();
+        for _ in 0..30 {
+            sim.tick(0.1, building.state());
+        }
+
+        assert!(
+            sim.people()
+                .iter()
+                .all(|p| p.profile.kind == ProfileKind::Delivery
+                    && matches!(p.state, PersonState::Waiting))
+        );
+    }
+
+    #[test]
+    fn children_only_board_with_their_chaperone() {
+        let config = PeopleConfig {
+            chaperoned_children: 1.,
+            ..PeopleConfig::default()
+        };
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 0,
+            destination: 3,
+        }];
+        let mut sim = PeopleSim::scripted(5, arrivals, config);
+
+        // the one place left would fit the child, but not the child and their parent
+        let mut building = ElevatorSim::with_config(
+            5,
+            1,
+            crate::elevator::ElevatorConfig {
+                capacities: vec![2],
+                ..Default::default()
+            },
+        );
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+        let stranger = ElevatorCommand::Board {
+            car_id: CarId(0),
+            person_id: PersonId(99),
+            weight: 75.,
+        };
+        building.apply_command(stranger).unwrap();
+        for _ in 0..10 {
+            sim.tick(0.1, building.state());
+        }
+        let people = sim.people();
+        assert_eq!(people.len(), 2);
+        assert_eq!(people[1].chaperone, Some(people[0].id));
+        assert_eq!(people[1].profile.kind, ProfileKind::Child);
+        assert!(people.iter().all(|p| p.state == PersonState::Waiting));
+
+        // once there's room, they get in together
+        building
+            .apply_command(ElevatorCommand::Alight {
+                car_id: CarId(0),
+                person_id: PersonId(99),
+                weight: 75.,
+            })
+            .unwrap();
+        let actions = sim.tick(0.1, building.state());
+        let entered = actions
+            .iter()
+            .filter(|a| matches!(a, PersonAction::EnterCar { .. }))
+            .count();
+        assert_eq!(entered, 2);
+        assert!(sim.people().iter().all(|p| p.in_car == Some(CarId(0))));
+    }
+
+    #[test]
+    fn parties_wait_for_a_car_with_room_for_them_all() {
+        let config = PeopleConfig {
+            groups: 1.,
+            largest_group: 2,
+            ..PeopleConfig::default()
+        };
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 0,
+            destination: 3,
+        }];
+        let mut sim = PeopleSim::scripted(5, arrivals, config);
+
+        // one place is left, and the party has two
+        let mut building = ElevatorSim::with_config(
+            5,
+            1,
+            crate::elevator::ElevatorConfig {
+                capacities: vec![2],
+                ..Default::default()
+            },
+        );
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+        let stranger = ElevatorCommand::Board {
+            car_id: CarId(0),
+            person_id: PersonId(99),
+            weight: 75.,
+        };
+        building.apply_command(stranger).unwrap();
+        for _ in 0..10 {
+            sim.tick(0.1, building.state());
+        }
+        let people = sim.people();
+        assert_eq!(people.len(), 2);
+        let group = Some(GroupId(people[0].id.0));
+        assert!(
+            people
+                .iter()
+                .all(|p| p.group == group && p.target_floor == 3)
+        );
+        assert!(people.iter().all(|p| p.state == PersonState::Waiting));
+
+        // once there's room, they get in together
+        building
+            .apply_command(ElevatorCommand::Alight {
+                car_id: CarId(0),
+                person_id: PersonId(99),
+                weight: 75.,
+            })
+            .unwrap();
+        let actions = sim.tick(0.1, building.state());
+        let entered = actions
+            .iter()
+            .filter(|a| matches!(a, PersonAction::EnterCar { .. }))
+            .count();
+        assert_eq!(entered, 2);
+        assert!(sim.people().iter().all(|p| p.in_car == Some(CarId(0))));
+    }
+
+    #[test]
+    fn a_full_waiting_area_sends_people_to_the_back_or_away() {
+        let building = ElevatorSim::new(4, 1);
+        let mut state = building.state().clone();
+        state.topology = Arc::new(BuildingTopology {
+            hall_capacities: vec![0, 0, 2],
+            ..BuildingTopology::new(4)
+        });
+
+        for overflow in [Overflow::Back, Overflow::Abandon] {
+            let config = PeopleConfig {
+                overflow,
+                ..PeopleConfig::default()
+            };
+            let mut sim = PeopleSim::scripted(4, Vec::new(), config);
+            for _ in 0..3 {
+                sim.add_person(2, 0, PersonOptions::default());
+            }
+            // nobody overflows on a floor with room for everyone
+            sim.add_person(1, 0, PersonOptions::default());
+            sim.tick(0.1, &state);
+
+            let overflowed: Vec<bool> = sim.people().iter().map(|p| p.overflowed).collect();
+            assert_eq!(overflowed, vec![false, false, true, false]);
+            let last = &sim.people()[2];
+            match overflow {
+                Overflow::Back => {
+                    assert_eq!(last.state, PersonState::Waiting);
+                    // they have further to walk to a car from the back
+                    let front = sim.people()[0].boarding_delay(CarId(0), 0., 10.);
+                    assert!(last.boarding_delay(CarId(0), 0., 10.) > front);
+                }
+                Overflow::Abandon => assert_eq!(last.state, PersonState::GaveUp),
+            }
+
+            let mut summary = RunSummary::default();
+            summary.record(0.1, sim.people(), &state);
+            assert_eq!(summary.overflowed(), 1);
+        }
+    }
+
+    #[test]
+    fn impatient_people_press_again_then_take_the_stairs() {
+        let profile = BehaviorProfile {
+            patience: 10.,
+            ..ProfileKind::Standard.profile()
+        };
+        let config = PeopleConfig {
+            profiles: vec![(profile, 1.)],
+            ..PeopleConfig::default()
+        };
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 2,
+            destination: 0,
+        }];
+        let mut sim = PeopleSim::scripted(4, arrivals, config);
+
+        // the button is lit, but no car ever comes
+        let mut building = ElevatorSim::new(4, 1);
+        building
+            .apply_command(ElevatorCommand::PressOutButton {
+                floor: 2,
+                direction: Direction::Down,
+                bank: None,
+            })
+            .unwrap();
+        let mut calls = 0;
+        for _ in 0..150 {
+            let actions = sim.tick(0.1, building.state());
+            calls += actions
+                .iter()
+                .filter(|a| matches!(a, PersonAction::CallElevator { .. }))
+                .count();
+            if sim.people()[0].state == PersonState::Waiting {
+                let remaining =
+                    sim.engine
+                        .time_to_next_event(sim.people(), sim.queues(), building.state());
+                assert!(remaining.is_some_and(|t| t <= 5.));
+            }
+        }
+
+        // once on arrival, and again half way through their patience
+        assert_eq!(calls, 2);
+        assert_eq!(sim.people()[0].state, PersonState::GaveUp);
+    }
+
+    #[test]
+    fn nobody_spawns_with_a_pet_for_a_banned_floor() {
+        let config = PeopleConfig {
+            attributes: vec![(Attribute::Pet, 1.), (Attribute::Bicycle, 0.)],
+            rules: vec![BoardingRule {
+                attribute: Attribute::Pet,
+                requires: CarCapabilities::default(),
+                banned_floors: vec![3],
+            }],
+            ..PeopleConfig::default()
+        };
+        assert_eq!(config.pick_attributes(0, 2), vec![Attribute::Pet]);
+        assert!(config.pick_attributes(3, 0).is_empty());
+        assert!(config.pick_attributes(1, 3).is_empty());
+    }
+
+    #[test]
+    fn hooks_attach_and_update_extra_data() {
+        // count every state each person passes through
+        let arrivals = vec![ScriptedArrival {
+            time: 0.,
+            origin: 1,
+            destination: 3,
+        }];
+        let mut sim = PeopleSim::scripted(5, arrivals, PeopleConfig::default());
+        sim.set_spawn_hook(|person| person.set_extra(Vec::<PersonState>::new()));
+        sim.set_state_hook(|person, previous| {
+            if let Some(history) = person.extra_mut::<Vec<PersonState>>() {
+                history.push(previous);
+            }
+        });
+
+        sim.tick(0.1, &empty_building());
+        let person = &sim.people()[0];
+        assert_eq!(
+            person.extra::<Vec<PersonState>>(),
+            Some(&vec![PersonState::New])
+        );
+        assert!(person.extra::<u32>().is_none());
+    }
+
+    #[test]
+    fn traffic_patterns_shape_trips() {
+        let mut up = UpPeak {
+            rate: 1.,
+            lobby_share: 1.,
+            lobby: 0,
+        };
+        let mut down = DownPeak {
+            rate: 1.,
+            lobby_share: 1.,
+            lobby: 0,
+        };
+        let mut any = Interfloor { rate: 1. };
+        for _ in 0..100 {
+            let (origin, destination) = up.trip(0., 5);
+            assert!(origin == 0 && destination > 0);
+            let (origin, destination) = down.trip(0., 5);
+            assert!(origin > 0 && destination == 0);
+            let (origin, destination) = any.trip(0., 2);
+            assert_ne!(origin, destination);
+        }
+
+        // the daily cycle is busier at its peaks
+        let day = DailyCycle {
+            rate: 0.5,
+            day_length: 100.,
+            lobby: 0,
+        };
+        assert_eq!(day.spawn_rate(10.), 1.);
+        assert_eq!(day.spawn_rate(30.), 0.5);
+        assert!(traffic_pattern("lunch", 1.).is_some());
+
+        // the office day follows the clock, starting the run at 07:30 in the morning rush
+        let mut office = traffic_pattern("office-day", 0.5).unwrap();
+        office.set_clock(SimClock::parse("07:30").unwrap());
+        assert_eq!(office.spawn_rate(0.), 1.);
+        assert_eq!(office.spawn_rate(2. * 3600.), 0.5);
+        assert_eq!(office.spawn_rate(14. * 3600.), 0.05);
+
+        assert!(traffic_pattern("rush", 1.).is_none());
+    }
+
+    #[test]
+    fn floor_weights_skew_trips_towards_busy_floors() {
+        // a busy lobby and an empty plant room on floor 2
+        let mut weighted = FloorWeights::new(Box::new(Interfloor { rate: 1. }), vec![5., 1., 0.]);
+        let mut from_lobby = 0;
+        for _ in 0..1000 {
+            let (origin, destination) = weighted.trip(0., 5);
+            assert!(origin != 2 && destination != 2);
+            assert_ne!(origin, destination);
+            from_lobby += (origin == 0) as u32;
+        }
+        // trips weigh the product of their floors' weights, so the lobby starts 15 in 36
+        assert!((350..490).contains(&from_lobby), "{from_lobby}");
+    }
+
+    #[test]
+    fn lobby_trips_use_the_ground_floor_above_the_basements() {
+        let config = crate::config::SimConfig {
+            floors: 6,
+            basements: 2,
+            traffic: "up-peak".to_string(),
+            ..crate::config::SimConfig::default()
+        };
+        let mut up = config.traffic_pattern().unwrap();
+        let mut from_lobby = 0;
+        for _ in 0..100 {
+            let (origin, destination) = up.trip(0., 6);
+            assert_ne!(origin, destination);
+            from_lobby += (origin == 2) as u32;
+        }
+        assert!(from_lobby > 70, "{from_lobby}");
+
+        let mut down = DownPeak {
+            rate: 1.,
+            lobby_share: 1.,
+            lobby: 0,
+        };
+        down.set_lobby(2);
+        for _ in 0..100 {
+            let (origin, destination) = down.trip(0., 6);
+            assert!(origin != 2 && destination == 2);
+        }
+    }
+
+    #[test]
+    fn poisson_arrivals_with_a_custom_engine() {
+        // an engine where everyone just stands there
+        struct Idle;
+        impl PersonEngine for Idle {
+            fn tick(
+                &mut self,
+                _: &mut [Person],
+                _: &HallQueues,
+                _: f32,
+                _: &BuildingState,
+            ) -> Vec<PersonAction> {
+                Vec::new()
+            }
+
+            fn time_to_next_event(
+                &self,
+                _: &[Person],
+                _: &HallQueues,
+                _: &BuildingState,
+            ) -> Option<f32> {
+                None
+            }
+        }
+
+        let spawner = PoissonSpawner::new(Box::new(Interfloor { rate: 10. }));
+        let mut sim = PeopleSim::with_parts(
+            5,
+            Box::new(spawner),
+            Box::new(Idle),
+            PeopleConfig::default(),
+        );
+        let building = empty_building();
+        for _ in 0..100 {
+            assert!(sim.tick(0.1, &building).is_empty());
+        }
+
+        // about 100 arrivals, several of which can land in the same tick
+        let count = sim.people().len();
+        assert!((50..150).contains(&count));
+        assert!(sim.people().iter().all(|p| p.state == PersonState::New));
+        assert!(sim.time_to_next_event(&building).is_some());
+    }
+
+    #[test]
+    fn people_change_banks_at_a_sky_lobby() {
+        use crate::config::SimConfig;
+        use crate::topology::Bank;
+
+        // car 0 serves the floors up to the sky lobby on 5, and car 1 the ones from it up
+        let config = SimConfig {
+            floors: 10,
+            banks: vec![
+                Bank {
+                    name: "low".to_string(),
+                    cars: vec![CarId(0)],
+                    floors: (0..=5).collect(),
+                },
+                Bank {
+                    name: "high".to_string(),
+                    cars: vec![CarId(1)],
+                    floors: (5..=9).collect(),
+                },
+            ],
+            sky_lobbies: vec![5],
+            spawn_rate: 0.,
+            ..SimConfig::default()
+        };
+        let mut sim = config.build().unwrap();
+        assert_eq!(sim.building().topology().transfers(0, 8), vec![5]);
+        assert!(sim.building().topology().transfers(0, 4).is_empty());
+
+        let id = sim.add_person(0, 8);
+        let person = |sim: &crate::sim::Simulation| {
+            let person = sim.people().people().iter().find(|p| p.id == id).unwrap();
+            (person.state, person.current_floor, person.destination())
+        };
+        assert_eq!(person(&sim), (PersonState::New, 0, 8));
+
+        let mut summary = RunSummary::default();
+        let mut transferred_at = None;
+        for _ in 0..1000 {
+            sim.step(0.1);
+            summary.record(0.1, sim.people().people(), sim.building().state());
+            if let (PersonState::Transferring, floor, _) = person(&sim) {
+                transferred_at = Some(floor);
+            }
+        }
+        assert_eq!(transferred_at, Some(5));
+        assert_eq!(person(&sim), (PersonState::Done, 8, 8));
+        let cars = &sim.building().state().cars;
+        assert_eq!((cars[0].current_floor, cars[1].current_floor), (5., 8.));
+
+        // the journey counts the walk across the sky lobby and the second wait too
+        assert_eq!((summary.delivered(), summary.transferred()), (1, 1));
+        let journey = summary.average_journey().unwrap();
+        let walk = config.sky_lobby_walk / BehaviorProfile::default().walking_speed;
+        assert!(journey >= summary.average_wait().unwrap() + walk);
+        assert!(
+            (journey - summary.average_wait().unwrap() - summary.average_ride().unwrap()).abs()
+                < 0.2
+        );
     }
 }
@@ -0,0 +1,259 @@
+use crate::elevator::BuildingState;
+use crate::people::{Person, PersonState};
+use crate::types::{Direction, Floor, PersonId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The people waiting on each floor, in the order they started waiting there, so the first
+/// to arrive is the first to get a place in a car. Children waiting with their chaperone,
+/// and people waiting with their group, are queued too, though they get in along with them
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct HallQueues {
+    floors: Vec<VecDeque<PersonId>>,
+}
+
+/// implement functions for HallQueues
+/// queue, len, waiting - who is waiting, and how many
+/// update - catch the queues up with who is waiting now
+/// turn_order - the order people take their turns in
+impl HallQueues {
+    /// Who is waiting on a floor, first in line first
+    pub fn queue(&self, floor: Floor) -> impl Iterator<Item = PersonId> + '_ {
+        self.floors
+            .get(floor as usize)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// How many people are waiting on a floor
+    pub fn len(&self, floor: Floor) -> usize {
+        self.floors.get(floor as usize).map_or(0, VecDeque::len)
+    }
+
+    /// How many people are waiting on every floor
+    pub fn waiting(&self) -> usize {
+        self.floors.iter().map(VecDeque::len).sum()
+    }
+
+    /// Take out anyone who has stopped waiting or isn't on their floor any more, and put
+    /// anyone who has started waiting at the back of their floor's queue, in the order they
+    /// appear in people
+    pub(crate) fn update(&mut self, people: &[Person]) {
+        let waiting: HashMap<PersonId, Floor> = people
+            .iter()
+            .filter(|p| p.state == PersonState::Waiting)
+            .map(|p| (p.id, p.current_floor))
+            .collect();
+        let mut queued = HashSet::new();
+        for (floor, queue) in self.floors.iter_mut().enumerate() {
+            queue.retain(|id| waiting.get(id) == Some(&(floor as Floor)));
+            queued.extend(queue.iter().copied());
+        }
+        for person in people {
+            if person.state != PersonState::Waiting || queued.contains(&person.id) {
+                continue;
+            }
+            let floor = person.current_floor as usize;
+            if floor >= self.floors.len() {
+                self.floors.resize(floor + 1, VecDeque::new());
+            }
+            self.floors[floor].push_back(person.id);
+        }
+    }
+
+    /// The indices into people in the order they take their turns: everyone queued, floor
+    /// by floor and first in line first, then everyone else in the order they appear
+    pub fn turn_order(&self, people: &[Person]) -> Vec<usize> {
+        let index: HashMap<PersonId, usize> =
+            people.iter().enumerate().map(|(i, p)| (p.id, i)).collect();
+        let mut order: Vec<usize> = self
+            .floors
+            .iter()
+            .flatten()
+            .filter_map(|id| index.get(id).copied())
+            .collect();
+        let queued: HashSet<usize> = order.iter().copied().collect();
+        order.extend((0..people.len()).filter(|i| !queued.contains(i)));
+        order
+    }
+}
+
+/// A read-only summary of the people, which is handed to controllers alongside the
+/// building's statistics unless the observation filter hides it
+#[derive(Clone, Copy, Debug)]
+pub struct PeopleSummary<'a> {
+    queues: &'a HallQueues,
+}
+
+impl<'a> PeopleSummary<'a> {
+    /// Create a summary of the people in some queues
+    pub fn new(queues: &'a HallQueues) -> Self {
+        Self { queues }
+    }
+
+    /// How many people are waiting on a floor
+    pub fn queue_length(&self, floor: Floor) -> usize {
+        self.queues.len(floor)
+    }
+
+    /// How many people are waiting in the whole building
+    pub fn waiting(&self) -> usize {
+        self.queues.waiting()
+    }
+}
+
+/// How many people want a car where, without saying who they are, for controllers which
+/// estimate loads rather than only reading the buttons
+/// waiting_up, waiting_down - how many people are waiting on each floor to go up and to go
+/// down, from the bottom up
+/// cars - the riders in each car, in id order
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DemandInfo {
+    pub waiting_up: Vec<usize>,
+    pub waiting_down: Vec<usize>,
+    pub cars: Vec<CarDemand>,
+}
+
+/// The riders in one car
+/// riders - how many there are, or 0 when loads are hidden
+/// destinations - where each is going, only when destinations are revealed
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CarDemand {
+    pub riders: usize,
+    pub destinations: Option<Vec<Floor>>,
+}
+
+/// implement functions for DemandInfo
+/// new - gather the demand people make on a building
+/// waiting - how many are waiting on a floor to go one way
+impl DemandInfo {
+    /// The demand people make on a building. Riders' destinations are only given if
+    /// reveal_destinations is set, and rider counts are 0 if hide_loads is
+    pub fn new(
+        state: &BuildingState,
+        people: &[Person],
+        reveal_destinations: bool,
+        hide_loads: bool,
+    ) -> Self {
+        let floors = state.floors.len();
+        let mut demand = Self {
+            waiting_up: vec![0; floors],
+            waiting_down: vec![0; floors],
+            cars: vec![CarDemand::default(); state.cars.len()],
+        };
+        if reveal_destinations {
+            for car in &mut demand.cars {
+                car.destinations = Some(Vec::new());
+            }
+        }
+        for person in people {
+            match person.state {
+                PersonState::New | PersonState::Waiting => {
+                    let waiting = match person.direction() {
+                        Direction::Up => &mut demand.waiting_up,
+                        Direction::Down => &mut demand.waiting_down,
+                    };
+                    if let Some(count) = waiting.get_mut(person.current_floor as usize) {
+                        *count += 1;
+                    }
+                }
+                PersonState::Riding => {
+                    let Some(car) = person
+                        .in_car
+                        .and_then(|car_id| demand.cars.get_mut(car_id.0 as usize))
+                    else {
+                        continue;
+                    };
+                    if !hide_loads {
+                        car.riders += 1;
+                    }
+                    if let Some(destinations) = &mut car.destinations {
+                        destinations.push(person.target_floor);
+                    }
+                }
+                PersonState::Transferring | PersonState::Done | PersonState::GaveUp => {}
+            }
+        }
+        demand
+    }
+
+    /// How many people are waiting on a floor to go in a direction
+    pub fn waiting(&self, floor: Floor, direction: Direction) -> usize {
+        let waiting = match direction {
+            Direction::Up => &self.waiting_up,
+            Direction::Down => &self.waiting_down,
+        };
+        waiting.get(floor as usize).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::{ElevatorCommand, ElevatorSim};
+    use crate::observation::ObservationFilter;
+    use crate::people::{BehaviorProfile, PeopleConfig, PeopleSim, PersonOptions, ReactionTime};
+    use crate::types::CarId;
+
+    #[test]
+    fn people_board_a_nearly_full_car_in_the_order_they_arrived() {
+        let mut building = ElevatorSim::new(5, 1);
+        building
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 0,
+            })
+            .unwrap();
+        building.tick(0.1);
+        let mut state = building.state().clone();
+        state.cars[0].capacity = 1;
+
+        // the first to arrive is slow to notice the car, the second quick
+        let reacting = |seconds| PersonOptions {
+            profile: Some(BehaviorProfile {
+                reaction_time: ReactionTime::Fixed(seconds),
+                ..BehaviorProfile::default()
+            }),
+            ..PersonOptions::default()
+        };
+        let mut people = PeopleSim::scripted(5, Vec::new(), PeopleConfig::default());
+        let slow = people.add_person(0, 3, reacting(1.));
+        let quick = people.add_person(0, 2, reacting(0.2));
+        people.tick(0.1, &state);
+        assert_eq!(
+            people.queues().queue(0).collect::<Vec<_>>(),
+            vec![slow, quick]
+        );
+        assert_eq!(people.summary().queue_length(0), 2);
+
+        // the quick one doesn't take the only place while the slow one is still reacting
+        let riding = |people: &PeopleSim| -> Vec<PersonId> {
+            people
+                .people()
+                .iter()
+                .filter(|p| p.state == PersonState::Riding)
+                .map(|p| p.id)
+                .collect()
+        };
+        for _ in 0..15 {
+            if !riding(&people).is_empty() {
+                break;
+            }
+            people.tick(0.1, &state);
+        }
+        assert_eq!(riding(&people), vec![slow]);
+        assert_eq!(people.queues().queue(0).collect::<Vec<_>>(), vec![quick]);
+        assert_eq!(people.summary().waiting(), 1);
+
+        // controllers are shown the summary unless the observation filter hides it
+        let stats = building.stats().with_people(people.summary());
+        let open = ObservationFilter::default().stats(stats);
+        assert_eq!(open.people().map(|p| p.queue_length(0)), Some(1));
+        let blind = ObservationFilter {
+            hide_queues: true,
+            ..ObservationFilter::default()
+        };
+        assert!(blind.stats(stats).people().is_none());
+    }
+}
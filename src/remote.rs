@@ -0,0 +1,227 @@
+use crate::control::{BasicController, ElevatorController};
+use crate::elevator::{BuildingState, ElevatorCommand};
+use crate::metrics::StatsView;
+use crate::queue::DemandInfo;
+use crate::types::{Direction, Floor};
+use crate::view::BuildingView;
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+/// How long a scheduler reached over TCP has to answer before it's given up on
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What is sent to the scheduler every tick, as one line of JSON
+/// state - the building, as the controller is allowed to see it
+/// up_rates, down_rates - the estimated hall calls per second at each floor going up and
+/// going down
+/// queue_lengths, demand - how many people are waiting on each floor, and the demand
+/// people make, both left out when the controller isn't shown them
+#[derive(Debug, Serialize)]
+pub struct RemoteRequest<'a> {
+    pub state: &'a BuildingState,
+    pub up_rates: Vec<f32>,
+    pub down_rates: Vec<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_lengths: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub demand: Option<&'a DemandInfo>,
+}
+
+impl<'a> RemoteRequest<'a> {
+    /// The request for a building and its statistics
+    pub fn new(state: &'a BuildingState, stats: &StatsView<'a>) -> Self {
+        let rates = |direction| {
+            (0..state.floors.len())
+                .map(|floor| stats.arrival_rate(floor as Floor, direction))
+                .collect()
+        };
+        let queue_lengths = stats.people().map(|people| {
+            (0..state.floors.len())
+                .map(|floor| people.queue_length(floor as Floor))
+                .collect()
+        });
+        Self {
+            state,
+            up_rates: rates(Direction::Up),
+            down_rates: rates(Direction::Down),
+            queue_lengths,
+            demand: stats.demand(),
+        }
+    }
+}
+
+/// A controller which asks a scheduler in another process what to do, so schedulers can be
+/// written in any language against the same building. Every tick it writes a RemoteRequest
+/// as one line of JSON, and reads back one line holding a JSON list of ElevatorCommands, such
+/// as [{"MoveCarTo": {"car_id": 0, "floor": 3}}]. If the reply isn't a list of commands,
+/// BasicController makes that tick's decision instead. If the scheduler can't be reached or
+/// stops answering, BasicController makes every decision from then on
+pub struct RemoteController {
+    reader: Box<dyn BufRead + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Option<Child>,
+    connected: bool,
+    fallback: BasicController,
+    failures: u32,
+    last_error: Option<String>,
+}
+
+/// implement functions for RemoteController
+/// new - talk to a scheduler over any reader and writer
+/// connect - talk to a scheduler listening on a TCP address
+/// spawn - start a scheduler process and talk to it over its stdin and stdout
+/// failures, last_error - how often the scheduler couldn't be used, and why
+/// ask - send the scheduler a request and read its commands
+impl RemoteController {
+    /// Talk to a scheduler which reads requests from writer and answers on reader
+    pub fn new(reader: impl BufRead + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: None,
+            connected: true,
+            fallback: BasicController,
+            failures: 0,
+            last_error: None,
+        }
+    }
+
+    /// Connect to a scheduler listening on a TCP address, such as "127.0.0.1:7878"
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(BufReader::new(stream.try_clone()?), stream))
+    }
+
+    /// Start a scheduler, which reads requests on its stdin and answers on its stdout. It's
+    /// killed when the controller is dropped
+    pub fn spawn(mut command: Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("scheduler has no stdin or stdout"));
+        };
+        let mut controller = Self::new(BufReader::new(stdout), stdin);
+        controller.child = Some(child);
+        Ok(controller)
+    }
+
+    /// The number of ticks where BasicController made the decision instead of the scheduler
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Why the scheduler last couldn't be used, if it ever couldn't
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Send the scheduler a request and read back its commands. An error reading or writing
+    /// means the scheduler is gone, so it isn't asked again
+    fn ask(&mut self, request: &RemoteRequest) -> Result<Vec<ElevatorCommand>, String> {
+        let mut line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let mut reply = String::new();
+        let sent = self
+            .writer
+            .write_all(line.as_bytes())
+            .and_then(|()| self.writer.flush())
+            .and_then(|()| self.reader.read_line(&mut reply));
+        match sent {
+            Ok(0) => {
+                self.connected = false;
+                return Err("scheduler closed the connection".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.connected = false;
+                return Err(format!("couldn't reach the scheduler: {e}"));
+            }
+        }
+        serde_json::from_str(&reply).map_err(|e| format!("bad reply from the scheduler: {e}"))
+    }
+}
+
+impl ElevatorController for RemoteController {
+    /// Send the scheduler the building and use the commands it answers with
+    fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand> {
+        if self.connected {
+            match self.ask(&RemoteRequest::new(&view.to_state(), stats)) {
+                Ok(commands) => return commands,
+                Err(error) => self.last_error = Some(error),
+            }
+        }
+        self.failures += 1;
+        self.fallback.tick(view, stats)
+    }
+}
+
+impl Drop for RemoteController {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::ElevatorSim;
+    use crate::types::CarId;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn scheduler_over_tcp_moves_cars_until_it_hangs_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // a scheduler which answers two requests, sending car 0 to the top floor and then
+        // answering nonsense, and hangs up
+        let scheduler = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut floors = Vec::new();
+            for reply in [
+                "[{\"MoveCarTo\": {\"car_id\": 0, \"floor\": 4}}]",
+                "nonsense",
+            ] {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                floors.push(request["state"]["floors"].as_array().unwrap().len());
+                writeln!(writer, "{reply}").unwrap();
+            }
+            floors
+        });
+
+        let building = ElevatorSim::new(5, 2);
+        let mut controller = RemoteController::connect(address).unwrap();
+        let commands = controller.tick(&BuildingView::new(building.state()), &building.stats());
+        assert_eq!(
+            commands,
+            vec![ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 4,
+            }]
+        );
+        assert_eq!(controller.failures(), 0);
+
+        // a bad reply falls back for one tick, and so does every tick once it's gone
+        controller.tick(&BuildingView::new(building.state()), &building.stats());
+        assert_eq!(scheduler.join().unwrap(), vec![5, 5]);
+        assert!(controller.last_error().unwrap().starts_with("bad reply"));
+        controller.tick(&BuildingView::new(building.state()), &building.stats());
+        controller.tick(&BuildingView::new(building.state()), &building.stats());
+        assert_eq!(controller.failures(), 3);
+        assert!(!controller.connected);
+    }
+}
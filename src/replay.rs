@@ -0,0 +1,749 @@
+use crate::control::ElevatorController;
+use crate::elevator::{
+    BuildingState, DoorState, ElevatorCommand, ElevatorEvent, EmergencyMode, Lantern, LitButtons,
+    TripAssignment,
+};
+use crate::events::{EventSink, SimEvent, TracedEvent};
+use crate::metrics::StatsView;
+use crate::people::{BehaviorProfile, Person, PersonState};
+use crate::sim::Simulation;
+use crate::snapshot::{self, SnapshotError};
+use crate::types::{Direction, PersonId, ScriptedArrival};
+use crate::view::BuildingView;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A command the controller gave, on its tick-th tick, time seconds into the run
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RecordedCommand {
+    pub tick: u64,
+    pub time: f32,
+    pub command: ElevatorCommand,
+}
+
+/// How a run ended up: the building, and the state of everyone in it in the order they
+/// appeared. Two runs with the same outcome did exactly the same thing
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Outcome {
+    pub building: BuildingState,
+    pub people: Vec<PersonState>,
+}
+
+impl Outcome {
+    /// Where a simulation is now
+    pub fn of(sim: &Simulation) -> Self {
+        Self {
+            building: sim.building().state().clone(),
+            people: sim.people().people().iter().map(|p| p.state).collect(),
+        }
+    }
+}
+
+/// Everything a controller commanded over a run, with the seed the run used and how it
+/// ended, so it can be replayed without the controller and checked
+/// seed - the seed the random numbers were given before the run, if they were
+/// commands - every command, in the order it was given
+/// outcome - where the run ended up
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CommandLog {
+    pub seed: Option<u64>,
+    pub commands: Vec<RecordedCommand>,
+    pub outcome: Outcome,
+}
+
+/// implement functions for CommandLog
+/// save, load - write the log to a JSON file, and read one back
+/// matches - check a replay ended up where the recording did
+impl CommandLog {
+    /// Write the log to a JSON file, replacing anything already there
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        snapshot::save(path, self)
+    }
+
+    /// Read a log written by save
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        snapshot::load(path)
+    }
+
+    /// Whether a simulation has ended up exactly where the recorded run did
+    pub fn matches(&self, sim: &Simulation) -> bool {
+        Outcome::of(sim) == self.outcome
+    }
+}
+
+/// A command given by hand, time seconds into the run
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ScriptedCommand {
+    pub time: f32,
+    pub command: ElevatorCommand,
+}
+
+/// A session driven by hand, such as a teaching demo: the people added and the commands
+/// given, each at the time it happened. Simulation::start_macro records one, and
+/// Simulation::play_scenario plays one back on top of whatever the controller does. The file
+/// is plain JSON, so a recorded session can be tidied up before it's shown
+/// buttons - buttons lit before anything else happens, for setting up a situation to test
+/// a controller on. A recorded session lights none
+/// arrivals - the people added, in the order they appeared
+/// commands - the commands given, in the order they were given
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub buttons: LitButtons,
+    pub arrivals: Vec<ScriptedArrival>,
+    pub commands: Vec<ScriptedCommand>,
+}
+
+/// implement functions for Scenario
+/// save, load - write the scenario to a JSON file, and read one back
+impl Scenario {
+    /// Write the scenario to a JSON file laid out for editing, replacing anything already
+    /// there
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        snapshot::save_pretty(path, self)
+    }
+
+    /// Read a scenario written by save, or by hand
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        snapshot::load(path)
+    }
+}
+
+/// A controller which gives the recorded commands again on the same ticks, without looking
+/// at the building. Replaying against the same seeded scenario repeats the recorded run
+/// exactly, as long as the recorded controller didn't draw from rng, which would have
+/// shifted the random numbers everything else drew
+pub struct ReplayController {
+    commands: VecDeque<RecordedCommand>,
+    tick: u64,
+}
+
+impl ReplayController {
+    /// Replay the commands in a log from its first tick
+    pub fn new(log: &CommandLog) -> Self {
+        Self {
+            commands: log.commands.iter().cloned().collect(),
+            tick: 0,
+        }
+    }
+}
+
+impl ElevatorController for ReplayController {
+    /// Give every command recorded for this tick
+    fn tick(&mut self, _view: &BuildingView, _stats: &StatsView) -> Vec<ElevatorCommand> {
+        let mut commands = Vec::new();
+        while let Some(recorded) = self.commands.front()
+            && recorded.tick <= self.tick
+        {
+            commands.extend(self.commands.pop_front().map(|r| r.command));
+        }
+        self.tick += 1;
+        commands
+    }
+}
+
+/// The reasons a trace can't be played back
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+    /// the trace doesn't begin with the building, so there's nothing to play it back on
+    NotStarted,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "couldn't read trace: {e}"),
+            TraceError::Json { line, error } => write!(f, "invalid trace on line {line}: {error}"),
+            TraceError::NotStarted => write!(f, "the trace doesn't begin with the building"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+/// A trip a car made between standing still at from and at to, in the time from start to end
+#[derive(Copy, Clone, Debug)]
+struct Leg {
+    start: f32,
+    from: f32,
+    end: f32,
+    to: f32,
+}
+
+/// Plays a trace back without simulating anything, rebuilding the building and the people
+/// in it from the events, so a run can be shared and watched again later. The building is
+/// as the trace began, with every hall call, door, emergency and person since. Cars slide
+/// evenly between where they started and stopped moving, so they don't speed up and slow
+/// down as they did, and a car still moving when the trace ends stays where it set off.
+/// Hall buttons don't know whether the building has a single call button
+pub struct TracePlayer {
+    events: Vec<TracedEvent>,
+    next: usize,
+    tick: u64,
+    time: f32,
+    start: f32,
+    start_clock: f64,
+    state: BuildingState,
+    people: Vec<Person>,
+    index: HashMap<PersonId, usize>,
+    legs: Vec<Vec<Leg>>,
+    leg: Vec<usize>,
+}
+
+/// implement functions for TracePlayer
+/// new, load - play back a list of events, or a trace written by JsonlSink
+/// step - move the playback on
+/// time, state, people - where the playback is up to
+/// finished, end_time - when the trace ends
+/// verify - check a simulation traces exactly the same events
+impl TracePlayer {
+    /// Play back events, which have to begin with the building tracing started on
+    pub fn new(events: Vec<TracedEvent>) -> Result<Self, TraceError> {
+        let Some(TracedEvent {
+            time,
+            tick,
+            event: SimEvent::Started { state },
+        }) = events.first()
+        else {
+            return Err(TraceError::NotStarted);
+        };
+        let (start, tick, state) = (*time, *tick, state.clone());
+
+        // every trip each car made, from starting to move until stopping
+        let mut legs = vec![Vec::new(); state.cars.len()];
+        let mut started: Vec<Option<(f32, f32)>> = vec![None; state.cars.len()];
+        for traced in &events {
+            match traced.event {
+                SimEvent::CarStarted { car, position } => {
+                    if let Some(s) = started.get_mut(car.0 as usize) {
+                        *s = Some((traced.time, position));
+                    }
+                }
+                SimEvent::CarStopped { car, position } => {
+                    let car = car.0 as usize;
+                    if let Some((start, from)) = started.get_mut(car).and_then(Option::take) {
+                        legs[car].push(Leg {
+                            start,
+                            from,
+                            end: traced.time,
+                            to: position,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            events,
+            next: 1,
+            tick,
+            time: start,
+            start,
+            start_clock: state.clock.elapsed,
+            leg: vec![0; state.cars.len()],
+            state,
+            people: Vec::new(),
+            index: HashMap::new(),
+            legs,
+        })
+    }
+
+    /// Read a trace written by JsonlSink
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        let file = File::open(path).map_err(TraceError::Io)?;
+        let mut events = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(TraceError::Io)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&line)
+                .map_err(|error| TraceError::Json { line: i + 1, error })?;
+            events.push(event);
+        }
+        Self::new(events)
+    }
+
+    /// Play back the next tick of the trace, which lasted dt seconds, returning everything
+    /// that happened during it
+    pub fn step(&mut self, dt: f32) -> Vec<SimEvent> {
+        self.time += dt;
+        let mut happened = Vec::new();
+        while let Some(traced) = self.events.get(self.next)
+            && traced.tick <= self.tick
+        {
+            let event = traced.event.clone();
+            self.next += 1;
+            self.apply(&event);
+            happened.push(event);
+        }
+        self.tick += 1;
+        self.state.clock.elapsed = self.start_clock + (self.time - self.start) as f64;
+        self.move_cars();
+        happened
+    }
+
+    /// Change the building and people as event says
+    fn apply(&mut self, event: &SimEvent) {
+        match *event {
+            SimEvent::PersonSpawned {
+                person,
+                origin,
+                destination,
+            } => {
+                self.index.insert(person, self.people.len());
+                self.people.push(Person {
+                    id: person,
+                    current_floor: origin,
+                    target_floor: destination,
+                    onward: Vec::new(),
+                    state: PersonState::Waiting,
+                    in_car: None,
+                    approaching: None,
+                    reaction_time: 0.,
+                    reaction_timer: None,
+                    waited: 0.,
+                    overflowed: false,
+                    operates: None,
+                    chaperone: None,
+                    group: None,
+                    attributes: Vec::new(),
+                    profile: BehaviorProfile::default(),
+                    weight: 0.,
+                    stepped_out_of: None,
+                    permitted_floors: Vec::new(),
+                    extra: None,
+                });
+            }
+            SimEvent::PersonBoarded { person, car, floor } => {
+                if let Some(p) = self.person(person) {
+                    p.state = PersonState::Riding;
+                    p.in_car = Some(car);
+                    p.current_floor = floor;
+                }
+                if let Some(c) = self.state.cars.get_mut(car.0 as usize) {
+                    c.passenger_count += 1;
+                }
+            }
+            SimEvent::PersonAlighted { person, car, floor } => {
+                if let Some(p) = self.person(person) {
+                    // anyone getting out short of their floor is changing cars
+                    p.state = if floor == p.target_floor {
+                        PersonState::Done
+                    } else {
+                        PersonState::Waiting
+                    };
+                    p.in_car = None;
+                    p.current_floor = floor;
+                }
+                if let Some(c) = self.state.cars.get_mut(car.0 as usize) {
+                    c.passenger_count = c.passenger_count.saturating_sub(1);
+                }
+            }
+            SimEvent::PersonGaveUp { person, .. } => {
+                if let Some(p) = self.person(person) {
+                    p.state = PersonState::GaveUp;
+                }
+            }
+            SimEvent::DoorOpened { car, .. } => {
+                if let Some(c) = self.state.cars.get_mut(car.0 as usize) {
+                    c.door = DoorState::Open {
+                        dwell_remaining: 0.,
+                    };
+                }
+            }
+            SimEvent::DoorClosed { car, floor } => {
+                if let Some(c) = self.state.cars.get_mut(car.0 as usize) {
+                    c.door = DoorState::Closed;
+                }
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    f.assignments.retain(|a| a.car_id != car);
+                    f.lanterns.retain(|l| l.car_id != car);
+                }
+            }
+            SimEvent::CommandApplied { ref command } => self.apply_command(command),
+            // the log doesn't say which way the lantern pointed
+            SimEvent::Building(ElevatorEvent::CarArriving { car_id, floor, .. }) => {
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    f.lanterns.retain(|l| l.car_id != car_id);
+                    f.lanterns.push(Lantern {
+                        car_id,
+                        direction: None,
+                    });
+                }
+            }
+            SimEvent::Building(ElevatorEvent::CarArrived { car_id, floor, .. }) => {
+                if let Some(c) = self.state.cars.get_mut(car_id.0 as usize) {
+                    c.door = DoorState::Opening { remaining: 0. };
+                    if let Some(button) = c.car_buttons.get_mut(floor as usize) {
+                        *button = false;
+                    }
+                }
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    f.out_up = false;
+                    f.out_down = false;
+                    f.out_call = false;
+                    f.access_call = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Change the building as an accepted command did
+    fn apply_command(&mut self, command: &ElevatorCommand) {
+        match *command {
+            ElevatorCommand::PressOutButton {
+                floor, direction, ..
+            }
+            | ElevatorCommand::PressAccessButton {
+                floor, direction, ..
+            } => {
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    match direction {
+                        Direction::Up => f.out_up = true,
+                        Direction::Down => f.out_down = true,
+                    }
+                    f.access_call |= matches!(command, ElevatorCommand::PressAccessButton { .. });
+                }
+            }
+            ElevatorCommand::PressCarButton { car_id, floor } => {
+                if let Some(button) = self
+                    .state
+                    .cars
+                    .get_mut(car_id.0 as usize)
+                    .and_then(|c| c.car_buttons.get_mut(floor as usize))
+                {
+                    *button = true;
+                }
+            }
+            ElevatorCommand::AssignTrip {
+                floor,
+                destination,
+                car_id,
+            } => {
+                if let Some(f) = self.state.floors.get_mut(floor as usize) {
+                    f.assignments.push(TripAssignment {
+                        destination,
+                        car_id,
+                    });
+                }
+            }
+            ElevatorCommand::FireRecall { floor } => {
+                self.state.emergency = EmergencyMode::FireRecall { floor };
+            }
+            ElevatorCommand::ClearEmergency => self.state.emergency = EmergencyMode::Normal,
+            _ => {}
+        }
+    }
+
+    /// Someone who has spawned, by id
+    fn person(&mut self, id: PersonId) -> Option<&mut Person> {
+        let &i = self.index.get(&id)?;
+        self.people.get_mut(i)
+    }
+
+    /// Put every car where it is now along its trips
+    fn move_cars(&mut self) {
+        for (i, car) in self.state.cars.iter_mut().enumerate() {
+            let legs = &self.legs[i];
+            while let Some(leg) = legs.get(self.leg[i])
+                && leg.end <= self.time
+            {
+                car.current_floor = leg.to;
+                self.leg[i] += 1;
+            }
+            car.velocity = 0.;
+            if let Some(leg) = legs.get(self.leg[i])
+                && leg.start <= self.time
+            {
+                let length = leg.end - leg.start;
+                let share = ((self.time - leg.start) / length).clamp(0., 1.);
+                car.current_floor = leg.from + (leg.to - leg.from) * share;
+                car.velocity = (leg.to - leg.from) / length;
+            }
+        }
+    }
+
+    /// Seconds into the run the playback is up to
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// The building as it was at the playback's time
+    pub fn state(&self) -> &BuildingState {
+        &self.state
+    }
+
+    /// Everyone who had appeared by the playback's time, in the order they did
+    pub fn people(&self) -> &[Person] {
+        &self.people
+    }
+
+    /// Whether every event has been played back
+    pub fn finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+
+    /// Seconds into the run the last event happened
+    pub fn end_time(&self) -> f32 {
+        self.events.last().map_or(self.start, |e| e.time)
+    }
+
+    /// Run sim in steps of dt until the trace ends, tracing it, and check it traced exactly
+    /// the same events, which it does if it's the same seeded scenario the trace was
+    /// recorded from, traced from the start. Returns the first event where they differ, if
+    /// they do
+    pub fn verify(&self, sim: &mut Simulation, dt: f32) -> Option<TraceMismatch> {
+        let collected = Collected::default();
+        sim.start_trace(Box::new(collected.clone()));
+        let last_tick = self.events.last().map_or(0, |e| e.tick);
+        while sim.ticks() <= last_tick {
+            sim.step(dt);
+        }
+        let _ = sim.finish_trace();
+        let replayed = collected.0.lock().map(|e| e.clone()).unwrap_or_default();
+
+        let len = self.events.len().max(replayed.len());
+        (0..len).find_map(|index| {
+            let events = [
+                self.events.get(index).cloned(),
+                replayed.get(index).cloned(),
+            ];
+            (events[0] != events[1]).then_some(TraceMismatch { index, events })
+        })
+    }
+}
+
+/// Keeps every event, for comparing with a trace
+#[derive(Clone, Default)]
+struct Collected(Arc<Mutex<Vec<TracedEvent>>>);
+
+impl EventSink for Collected {
+    fn emit(&mut self, event: &TracedEvent) {
+        if let Ok(mut events) = self.0.lock() {
+            events.push(event.clone());
+        }
+    }
+}
+
+/// The first event a trace and a simulation run again disagree on: the event in the trace
+/// and the one the simulation traced, with None for whichever ran out first
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceMismatch {
+    pub index: usize,
+    pub events: [Option<TracedEvent>; 2],
+}
+
+impl fmt::Display for TraceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Traces first differ at event {}:", self.index)?;
+        for (name, event) in ["trace", "rerun"].iter().zip(&self.events) {
+            match event {
+                Some(e) => writeln!(
+                    f,
+                    "  {name}: tick {} ({:.2} s) {:?}",
+                    e.tick, e.time, e.event
+                )?,
+                None => writeln!(f, "  {name}: no more events")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::BasicController;
+    use crate::elevator::ElevatorSim;
+    use crate::events::RingBuffer;
+    use crate::people::{PeopleConfig, PeopleSim, PersonOptions, traffic_pattern};
+    use crate::rng;
+    use crate::types::CarId;
+
+    #[test]
+    fn replay_repeats_the_recorded_run_without_the_controller() {
+        let build = |controller: Box<dyn ElevatorController>| {
+            rng::seed(11);
+            let pattern = traffic_pattern("interfloor", 0.5).unwrap();
+            let people = PeopleSim::with_pattern(8, pattern, PeopleConfig::default());
+            Simulation::new(people, ElevatorSim::new(8, 2), controller)
+        };
+
+        let mut recorded = build(Box::new(BasicController));
+        recorded.start_recording();
+        for _ in 0..600 {
+            recorded.step(0.1);
+        }
+        let log = recorded.finish_recording(Some(11)).unwrap();
+        assert!(!log.commands.is_empty());
+        assert!(log.outcome.people.contains(&PersonState::Done));
+
+        let path = std::env::temp_dir().join(format!("commands-{}.json", std::process::id()));
+        log.save(&path).unwrap();
+        let log = CommandLog::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut replayed = build(Box::new(ReplayController::new(&log)));
+        for _ in 0..600 {
+            replayed.step(0.1);
+        }
+        assert!(log.matches(&replayed));
+
+        // a run with different people ends up somewhere else
+        let mut other = build(Box::new(ReplayController::new(&log)));
+        other
+            .people_mut()
+            .add_person(0, 7, PersonOptions::default());
+        for _ in 0..600 {
+            other.step(0.1);
+        }
+        assert!(!log.matches(&other));
+    }
+
+    #[test]
+    fn trace_plays_back_without_simulating() {
+        let build = || {
+            rng::seed(5);
+            let pattern = traffic_pattern("interfloor", 0.5).unwrap();
+            let people = PeopleSim::with_pattern(8, pattern, PeopleConfig::default());
+            Simulation::new(people, ElevatorSim::new(8, 2), Box::new(BasicController))
+        };
+
+        let mut sim = build();
+        let buffer = RingBuffer::new(100_000);
+        sim.start_trace(Box::new(buffer.clone()));
+        for _ in 0..600 {
+            sim.step(0.1);
+        }
+        sim.finish_trace().unwrap();
+
+        let mut player = TracePlayer::new(buffer.events()).unwrap();
+        while !player.finished() {
+            player.step(0.1);
+        }
+        let states = |people: &[Person]| -> Vec<(PersonId, PersonState)> {
+            people.iter().map(|p| (p.id, p.state)).collect()
+        };
+        assert_eq!(states(player.people()), states(sim.people().people()));
+        assert!(player.people().iter().any(|p| p.state == PersonState::Done));
+        // a car which has stopped is exactly where it stopped
+        for (played, car) in player.state().cars.iter().zip(&sim.building().state().cars) {
+            if car.velocity == 0. {
+                assert_eq!(played.current_floor, car.current_floor);
+            }
+        }
+
+        // the same scenario traces the same events again, but a different one doesn't
+        assert_eq!(player.verify(&mut build(), 0.1), None);
+        let mut other = build();
+        other
+            .people_mut()
+            .add_person(0, 7, PersonOptions::default());
+        let mismatch = player.verify(&mut other, 0.1).unwrap();
+        assert!(mismatch.index > 0);
+
+        assert!(matches!(
+            TracePlayer::new(Vec::new()),
+            Err(TraceError::NotStarted)
+        ));
+    }
+
+    #[test]
+    fn macro_records_what_was_done_by_hand_and_plays_it_back() {
+        let build = || {
+            let people = PeopleSim::scripted(6, Vec::new(), PeopleConfig::default());
+            Simulation::new(people, ElevatorSim::new(6, 2), Box::new(BasicController))
+        };
+        let mut sim = build();
+        sim.start_macro();
+        for tick in 0..300 {
+            match tick {
+                10 => {
+                    sim.add_person(4, 0);
+                }
+                50 => sim
+                    .inject(ElevatorCommand::MoveCarTo {
+                        car_id: CarId(1),
+                        floor: 5,
+                    })
+                    .unwrap(),
+                120 => {
+                    sim.add_person(0, 3);
+                }
+                _ => {}
+            }
+            sim.step(0.1);
+        }
+        let scenario = sim.finish_macro().unwrap();
+        assert_eq!(scenario.arrivals.len(), 2);
+        assert_eq!(scenario.commands.len(), 1);
+        assert!((scenario.commands[0].time - 5.).abs() < 1e-3);
+
+        // the file reads back the same, and playing it repeats the session
+        let path = std::env::temp_dir().join("elevator_macro_test.json");
+        scenario.save(&path).unwrap();
+        let loaded = Scenario::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, scenario);
+        let mut played = build();
+        played.play_scenario(&loaded).unwrap();
+        for _ in 0..300 {
+            played.step(0.1);
+        }
+        assert_eq!(Outcome::of(&played), Outcome::of(&sim));
+    }
+
+    #[test]
+    fn scenarios_light_buttons_before_the_first_tick() {
+        let people = PeopleSim::scripted(6, Vec::new(), PeopleConfig::default());
+        let mut sim = Simulation::new(people, ElevatorSim::new(6, 2), Box::new(BasicController));
+        // calls on every floor, and a stale call in an empty car
+        let scenario = Scenario {
+            buttons: LitButtons {
+                hall: (0..6).map(|floor| (floor, Direction::Down)).collect(),
+                car: vec![(CarId(1), 4)],
+            },
+            ..Scenario::default()
+        };
+        sim.play_scenario(&scenario).unwrap();
+        let state = sim.building().state();
+        assert!(state.floors.iter().all(|f| f.out_down && !f.out_up));
+        assert!(state.cars[1].car_buttons[4]);
+        assert_eq!(state.cars[1].passenger_count, 0);
+        // nobody arrived to press them
+        assert_eq!(sim.building().stats().floor_arrival_rate(3), 0.);
+
+        sim.step(0.1);
+        let state = sim.building().state();
+        assert!(state.cars.iter().all(|car| car.target_floor().is_some()));
+
+        // a button the building doesn't have is refused, and nothing is lit
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(6, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::new(6, 2),
+            Box::new(BasicController),
+        );
+        let outside = Scenario {
+            buttons: LitButtons {
+                hall: vec![(2, Direction::Up)],
+                car: vec![(CarId(0), 9)],
+            },
+            ..Scenario::default()
+        };
+        assert!(sim.play_scenario(&outside).is_err());
+        assert!(!sim.building().state().floors[2].called());
+    }
+}
@@ -0,0 +1,99 @@
+use rand::distr::uniform::{SampleRange, SampleUniform};
+use rand::distr::{Distribution, StandardUniform};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
+/// One of the separate streams of random numbers the simulation draws from. Each part of the
+/// simulation draws from its own, so turning on something that draws random numbers, such
+/// as door jitter, doesn't change what's drawn for anything else, such as who arrives
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// who arrives when, where from and where to
+    Arrivals,
+    /// how people behave once they've arrived, such as how quickly they react
+    Behavior,
+    /// what breaks down and when
+    Faults,
+    /// how much door times and acceleration vary
+    Jitter,
+}
+
+thread_local! {
+    /// The random number generator for each stream on this thread. They start from OS
+    /// entropy, so runs differ unless they are seeded
+    static RNGS: RefCell<[StdRng; 4]> =
+        RefCell::new(std::array::from_fn(|_| StdRng::from_os_rng()));
+}
+
+/// Reseed the random numbers on this thread, so a run with the same seed repeats exactly.
+/// Each stream gets its own seed, drawn from this one in the order the streams are listed
+pub fn seed(seed: u64) {
+    let mut master = StdRng::seed_from_u64(seed);
+    RNGS.with(|rngs| {
+        for rng in rngs.borrow_mut().iter_mut() {
+            *rng = StdRng::seed_from_u64(master.random());
+        }
+    });
+}
+
+/// A seed nobody chose, from OS entropy, for a run that has to be repeatable later
+pub fn new_seed() -> u64 {
+    StdRng::from_os_rng().random()
+}
+
+/// Draw from a stream's random number generator on this thread
+fn with_stream<T>(stream: Stream, draw: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNGS.with(|rngs| draw(&mut rngs.borrow_mut()[stream as usize]))
+}
+
+/// A random value from a stream, such as an f32 between 0 and 1 or a bool
+pub fn random<T>(stream: Stream) -> T
+where
+    StandardUniform: Distribution<T>,
+{
+    with_stream(stream, |rng| rng.random())
+}
+
+/// A random value in a range, from a stream
+pub fn random_range<T, R>(stream: Stream, range: R) -> T
+where
+    T: SampleUniform,
+    R: SampleRange<T>,
+{
+    with_stream(stream, |rng| rng.random_range(range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_repeats() {
+        seed(7);
+        let first: Vec<u32> = (0..5)
+            .map(|_| random_range(Stream::Arrivals, 0..100))
+            .collect();
+        seed(7);
+        let second: Vec<u32> = (0..5)
+            .map(|_| random_range(Stream::Arrivals, 0..100))
+            .collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn drawing_from_one_stream_leaves_the_others_alone() {
+        let arrivals = |jitter: usize| {
+            seed(7);
+            (0..5)
+                .map(|_| {
+                    for _ in 0..jitter {
+                        random::<f32>(Stream::Jitter);
+                    }
+                    random_range(Stream::Arrivals, 0..100)
+                })
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(arrivals(0), arrivals(3));
+    }
+}
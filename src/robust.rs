@@ -0,0 +1,304 @@
+use crate::bench::{self, BenchConfig, CarFault, Contender};
+use crate::metrics::RunSummary;
+use crate::observation::ObservationError;
+use crate::people::{PatternSpawner, TrafficPattern};
+use crate::rng::{self, Stream};
+use crate::types::{CarId, Floor, ScriptedArrival};
+use std::fmt;
+
+/// Settings for checking how well controllers cope with scenarios a little different to
+/// the one they were built for
+/// bench - the base scenario's building and run length. Each run is seeded with its seed
+/// plus the run's number
+/// runs - how many perturbed scenarios to run every controller on
+/// rate_spread - how far the arrival rate is scaled up or down, as a share of the base rate
+/// max_shift - the most seconds the traffic is moved earlier or later, which moves its peaks
+/// fault_duration - how many seconds one random car spends out of service in each run,
+/// starting at a random time. 0 leaves every car working
+#[derive(Clone, Debug, PartialEq)]
+pub struct RobustnessConfig {
+    pub bench: BenchConfig,
+    pub runs: u32,
+    pub rate_spread: f32,
+    pub max_shift: f32,
+    pub fault_duration: f32,
+}
+
+impl Default for RobustnessConfig {
+    fn default() -> Self {
+        Self {
+            bench: BenchConfig::default(),
+            runs: 20,
+            rate_spread: 0.1,
+            max_shift: 60.,
+            fault_duration: 120.,
+        }
+    }
+}
+
+/// How one run's scenario differs from the base scenario
+/// rate_scale - what the arrival rate is multiplied by
+/// shift - how many seconds later the traffic happens, or earlier if negative
+/// fault - the car which breaks down, if any
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Perturbation {
+    pub rate_scale: f32,
+    pub shift: f32,
+    pub fault: Option<CarFault>,
+}
+
+impl Perturbation {
+    /// Draw a perturbation within the config's limits
+    pub fn random(config: &RobustnessConfig) -> Self {
+        let spread = config.rate_spread.abs();
+        let shift = config.max_shift.abs();
+        let bench = &config.bench;
+        let fault = (config.fault_duration > 0. && bench.cars > 0).then(|| {
+            let start = rng::random_range(Stream::Faults, 0. ..bench.duration.max(f32::EPSILON));
+            CarFault {
+                car_id: CarId(rng::random_range(Stream::Faults, 0..bench.cars) as u32),
+                start,
+                end: start + config.fault_duration,
+            }
+        });
+        Self {
+            rate_scale: rng::random_range(Stream::Arrivals, 1. - spread..=1. + spread),
+            shift: rng::random_range(Stream::Arrivals, -shift..=shift),
+            fault,
+        }
+    }
+}
+
+/// A traffic pattern with its rate scaled, and moved shift seconds later
+pub struct Perturbed {
+    pub pattern: Box<dyn TrafficPattern + Send>,
+    pub rate_scale: f32,
+    pub shift: f32,
+}
+
+impl TrafficPattern for Perturbed {
+    fn spawn_rate(&self, time: f32) -> f32 {
+        self.pattern.spawn_rate(time - self.shift) * self.rate_scale
+    }
+
+    fn trip(&mut self, time: f32, num_floors: Floor) -> (Floor, Floor) {
+        self.pattern.trip(time - self.shift, num_floors)
+    }
+}
+
+/// The lowest, highest and mean value of a metric over several runs, and its standard
+/// deviation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spread {
+    pub mean: f32,
+    pub std_dev: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Spread {
+    /// The spread of some values, or None if there aren't any
+    pub fn of(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f32;
+        let mean = values.iter().sum::<f32>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        Some(Self {
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f32::INFINITY, f32::min),
+            max: values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        })
+    }
+}
+
+/// Every controller's summaries over the perturbed runs, in the order the controllers were
+/// given, and the controllers which weren't run because they know something the bench hides.
+/// Displaying it prints the spread of each metric
+#[derive(Clone, Debug, Default)]
+pub struct Robustness {
+    pub results: Vec<(String, Vec<RunSummary>)>,
+    pub refused: Vec<(String, ObservationError)>,
+}
+
+/// How to read a metric from a summary
+type Metric = fn(&RunSummary) -> Option<f32>;
+
+/// The metrics the spread is shown for, by name. Runs where a metric has no value, such as
+/// the average wait when nobody boarded, are left out of it
+const METRICS: [(&str, Metric); 6] = [
+    ("Delivered", |s| Some(s.delivered() as f32)),
+    ("Gave up", |s| Some(s.gave_up() as f32)),
+    ("Avg wait", RunSummary::average_wait),
+    ("95th pct wait", |s| s.wait_percentile(0.95)),
+    ("Avg ride", RunSummary::average_ride),
+    ("Utilization %", |s| Some(s.utilization() * 100.)),
+];
+
+impl Robustness {
+    /// The spread of a metric over one controller's runs, by the names used in the table
+    pub fn spread(&self, controller: &str, metric: &str) -> Option<Spread> {
+        let (_, summaries) = self.results.iter().find(|(name, _)| name == controller)?;
+        let (_, read) = METRICS.iter().find(|(name, _)| *name == metric)?;
+        let values: Vec<f32> = summaries.iter().filter_map(read).collect();
+        Spread::of(&values)
+    }
+}
+
+/// Run every controller on config.runs perturbations of a base scenario, and collect how
+/// each did. pattern builds the base scenario's traffic, and contenders the controllers for
+/// a run's arrivals, since each run needs fresh ones. Within a run every controller sees the
+/// same people and the same fault, so the spread comes from the scenarios alone
+pub fn robustness(
+    config: &RobustnessConfig,
+    pattern: &dyn Fn() -> Box<dyn TrafficPattern + Send>,
+    contenders: &dyn Fn(&[ScriptedArrival]) -> Vec<Contender>,
+) -> Robustness {
+    let mut robustness = Robustness::default();
+    for run in 0..config.runs as u64 {
+        let seed = config.bench.seed + run;
+        rng::seed(seed);
+        let perturbation = Perturbation::random(config);
+        let mut spawner = PatternSpawner::new(Box::new(Perturbed {
+            pattern: pattern(),
+            rate_scale: perturbation.rate_scale,
+            shift: perturbation.shift,
+        }));
+        let bench = BenchConfig {
+            seed,
+            faults: perturbation.fault.into_iter().collect(),
+            ..config.bench.clone()
+        };
+        let arrivals =
+            bench::record_arrivals(&mut spawner, bench.floors, bench.duration, bench.timestep);
+
+        let comparison = bench::compare(&bench, &arrivals, contenders(&arrivals));
+        for (name, summary) in comparison.results {
+            match robustness.results.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, summaries)) => summaries.push(summary),
+                None => robustness.results.push((name, vec![summary])),
+            }
+        }
+        for (name, error) in comparison.refused {
+            if !robustness.refused.iter().any(|(n, _)| *n == name) {
+                robustness.refused.push((name, error));
+            }
+        }
+    }
+    robustness
+}
+
+impl fmt::Display for Robustness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .results
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["Controller".len()])
+            .max()
+            .unwrap_or(0);
+        let metric_width = METRICS
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+
+        let header = format!(
+            "{:<width$} | {:<metric_width$} |     Mean |  Std dev |      Min |      Max",
+            "Controller", "Metric"
+        );
+        write!(f, "{header}\n{}", "-".repeat(header.len()))?;
+        for (name, summaries) in &self.results {
+            write!(f, "\n{name:<width$} | {} runs", summaries.len())?;
+            for (metric, _) in METRICS {
+                // a metric with no value in any run is shown as a dash
+                let Some(spread) = self.spread(name, metric) else {
+                    write!(f, "\n{:<width$} | {metric:<metric_width$} | {:>8}", "", "-")?;
+                    continue;
+                };
+                write!(
+                    f,
+                    "\n{:<width$} | {metric:<metric_width$} | {:>8.1} | {:>8.1} | {:>8.1} | {:>8.1}",
+                    "", spread.mean, spread.std_dev, spread.min, spread.max
+                )?;
+            }
+        }
+        for (name, error) in &self.refused {
+            write!(f, "\n{name} wasn't run: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{BasicController, DestinationController};
+    use crate::people::{DailyCycle, HallCallMode, Interfloor, PeopleConfig};
+
+    #[test]
+    fn perturbed_patterns_are_scaled_and_shifted() {
+        let day = DailyCycle {
+            rate: 1.,
+            day_length: 100.,
+            lobby: 0,
+        };
+        let perturbed = Perturbed {
+            pattern: Box::new(day),
+            rate_scale: 0.5,
+            shift: 30.,
+        };
+        // the morning peak now runs from 30 to 50 seconds, at half its rate
+        assert_eq!(perturbed.spawn_rate(10.), day.spawn_rate(80.) * 0.5);
+        assert_eq!(perturbed.spawn_rate(40.), 1.);
+
+        let spread = Spread::of(&[1., 3.]).unwrap();
+        assert_eq!((spread.mean, spread.std_dev), (2., 1.));
+        assert_eq!((spread.min, spread.max), (1., 3.));
+        assert_eq!(Spread::of(&[]), None);
+    }
+
+    #[test]
+    fn every_controller_runs_on_every_perturbed_scenario() {
+        let config = RobustnessConfig {
+            bench: BenchConfig {
+                floors: 6,
+                duration: 120.,
+                ..BenchConfig::default()
+            },
+            runs: 3,
+            fault_duration: 60.,
+            ..RobustnessConfig::default()
+        };
+        let pattern = || -> Box<dyn TrafficPattern + Send> { Box::new(Interfloor { rate: 0.1 }) };
+        let contenders = |_: &[ScriptedArrival]| {
+            vec![
+                Contender {
+                    name: "basic".to_string(),
+                    controller: Box::new(BasicController),
+                    people: PeopleConfig::default(),
+                },
+                Contender {
+                    name: "destination".to_string(),
+                    controller: Box::new(DestinationController::default()),
+                    people: PeopleConfig {
+                        hall_calls: HallCallMode::Kiosk,
+                        ..PeopleConfig::default()
+                    },
+                },
+            ]
+        };
+
+        let robustness = robustness(&config, &pattern, &contenders);
+        assert_eq!(robustness.results.len(), 2);
+        assert!(robustness.results.iter().all(|(_, runs)| runs.len() == 3));
+        let delivered = robustness.spread("basic", "Delivered").unwrap();
+        assert!(delivered.min > 0. && delivered.min <= delivered.max);
+
+        // a controller name line and a line per metric for each controller
+        let table = robustness.to_string();
+        assert_eq!(table.lines().count(), 2 + 2 * (1 + METRICS.len()));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::config::{ConfigError, SimConfig};
+use crate::elevator::{CommandError, ElevatorCommand};
+use crate::replay::{Scenario, ScriptedCommand};
+use crate::sim::Simulation;
+use crate::topology::BuildingTopology;
+use crate::types::{CarId, Direction, Floor, FloorId, ScriptedArrival};
+use std::path::Path;
+use std::{fmt, fs, io};
+
+/// The reasons a scenario script can't be read or played
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    /// a line which isn't an event, with its line number, counting from 1
+    Line(usize, String),
+    Config(ConfigError),
+    Command(CommandError),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "couldn't read scenario: {e}"),
+            ScenarioError::Line(line, reason) => write!(f, "line {line}: {reason}"),
+            ScenarioError::Config(e) => write!(f, "{e}"),
+            ScenarioError::Command(e) => write!(f, "couldn't set up the scenario: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// Read a scenario script for a building from a file, see parse
+pub fn load(
+    path: impl AsRef<Path>,
+    topology: &BuildingTopology,
+) -> Result<Scenario, ScenarioError> {
+    let text = fs::read_to_string(path).map_err(ScenarioError::Io)?;
+    parse(&text, topology)
+}
+
+/// Read a scenario script, which lists exactly what happens and when, one event a line:
+/// ```text
+/// # the morning's first arrivals
+/// t=0: person from 0 to 7
+/// t=12.5: person from 3 to 9
+/// t=30: call 5 down
+/// t=60: car 0 to 4
+/// t=200: car 1 out of service
+/// t=260: car 1 in service
+/// t=280: vip at 6 in car 0
+/// t=300: fire recall to 0
+/// t=400: clear emergency
+/// t=410: command {"ReserveCar": {"car_id": 1, "duration": 60}}
+/// ```
+/// Floors go by what topology calls them, such as B1 in a building with basements, or by
+/// their level counted from the ground floor, see BuildingTopology::named. Anything after a
+/// # is a comment. command gives any ElevatorCommand, written as JSON, with its floors
+/// named the same way, as a FloorId such as "B1" or 4
+pub fn parse(text: &str, topology: &BuildingTopology) -> Result<Scenario, ScenarioError> {
+    let mut scenario = Scenario::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |reason: String| ScenarioError::Line(i + 1, reason);
+        let (time, event) = line
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or_else(|| error(format!("expected t=seconds: event, found {line}")))?;
+        let time: f32 = time
+            .trim()
+            .parse()
+            .ok()
+            .filter(|t: &f32| t.is_finite() && *t >= 0.)
+            .ok_or_else(|| error(format!("invalid time {time}")))?;
+        match Event::parse(event.trim(), topology).map_err(error)? {
+            Event::Person(origin, destination) => scenario.arrivals.push(ScriptedArrival {
+                time,
+                origin,
+                destination,
+            }),
+            Event::Command(command) => scenario.commands.push(ScriptedCommand { time, command }),
+        }
+    }
+    Ok(scenario)
+}
+
+/// Build the simulation config describes, with nobody arriving but the scenario's people, and
+/// play scenario on it, so a run repeats exactly. With a seed in config, anything else left
+/// to chance, such as how long people take to react, repeats too
+pub fn build(config: &SimConfig, scenario: &Scenario) -> Result<Simulation, ScenarioError> {
+    let scripted = SimConfig {
+        spawn_rate: 0.,
+        delivery_rate: 0.,
+        ..config.clone()
+    };
+    let mut sim = scripted.build().map_err(ScenarioError::Config)?;
+    sim.play_scenario(scenario)
+        .map_err(ScenarioError::Command)?;
+    Ok(sim)
+}
+
+/// One line of a script, after its time
+enum Event {
+    Person(Floor, Floor),
+    Command(ElevatorCommand),
+}
+
+impl Event {
+    /// Read an event, with its floors named the way topology names them
+    fn parse(event: &str, topology: &BuildingTopology) -> Result<Self, String> {
+        if let Some(json) = event.strip_prefix("command ") {
+            let command: ElevatorCommand<FloorId> =
+                serde_json::from_str(json).map_err(|e| format!("invalid command {json}: {e}"))?;
+            return command
+                .resolve(topology)
+                .map(Event::Command)
+                .map_err(|e| format!("invalid command {json}: {e}"));
+        }
+        let words: Vec<&str> = event.split_whitespace().collect();
+        let floor = |word: &str| {
+            topology
+                .named(word)
+                .ok_or_else(|| format!("invalid floor {word}"))
+        };
+        let car = |word: &str| {
+            word.parse::<u32>()
+                .map(CarId)
+                .map_err(|_| format!("invalid car {word}"))
+        };
+        Ok(match words[..] {
+            ["person", "from", origin, "to", destination] => {
+                Event::Person(floor(origin)?, floor(destination)?)
+            }
+            ["call", at, direction] => Event::Command(ElevatorCommand::PressOutButton {
+                floor: floor(at)?,
+                direction: match direction {
+                    "up" => Direction::Up,
+                    "down" => Direction::Down,
+                    _ => return Err(format!("invalid direction {direction}")),
+                },
+                bank: None,
+            }),
+            ["car", id, "to", to] => Event::Command(ElevatorCommand::MoveCarTo {
+                car_id: car(id)?,
+                floor: floor(to)?,
+            }),
+            ["car", id, "out", "of", "service"] => Event::Command(ElevatorCommand::SetCarService {
+                car_id: car(id)?,
+                in_service: false,
+            }),
+            ["car", id, "in", "service"] => Event::Command(ElevatorCommand::SetCarService {
+                car_id: car(id)?,
+                in_service: true,
+            }),
+            ["vip", "at", at, "in", "car", id] => Event::Command(ElevatorCommand::VipCall {
+                car_id: car(id)?,
+                floor: floor(at)?,
+            }),
+            ["fire", "recall", "to", to] => {
+                Event::Command(ElevatorCommand::FireRecall { floor: floor(to)? })
+            }
+            ["clear", "emergency"] => Event::Command(ElevatorCommand::ClearEmergency),
+            _ => return Err(format!("unknown event {event}")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::{ServiceState, VipStage};
+    use crate::people::PersonState;
+
+    #[test]
+    fn scripts_play_exactly_what_they_list() {
+        let script = "
+            # two people, then a car is taken away
+            t=0: person from 0 to 4
+            t=12.5: person from 3 to 1  # on their way down
+            t=20: car 1 out of service
+            t=21: command {\"ReserveCar\": {\"car_id\": 0, \"duration\": 0}}
+        ";
+        let config = SimConfig {
+            floors: 5,
+            seed: Some(3),
+            ..SimConfig::default()
+        };
+        let scenario = parse(script, &config.topology()).unwrap();
+        assert_eq!(scenario.arrivals.len(), 2);
+        assert_eq!(scenario.arrivals[1].time, 12.5);
+        assert_eq!(
+            scenario.commands[0],
+            ScriptedCommand {
+                time: 20.,
+                command: ElevatorCommand::SetCarService {
+                    car_id: CarId(1),
+                    in_service: false,
+                },
+            }
+        );
+
+        let mut sim = build(&config, &scenario).unwrap();
+        for _ in 0..600 {
+            sim.step(0.1);
+        }
+        let people = sim.people().people();
+        assert_eq!(people.len(), 2);
+        assert!(people.iter().all(|p| matches!(p.state, PersonState::Done)));
+    }
+
+    #[test]
+    fn vips_are_fetched_alone_and_carried_nonstop() {
+        let config = SimConfig {
+            floors: 6,
+            cars: 1,
+            seed: Some(5),
+            ..SimConfig::default()
+        };
+        let script = "
+            t=0: vip at 4 in car 0
+            t=0: person from 4 to 0
+            t=0: person from 4 to 2
+            t=0: person from 2 to 5
+        ";
+        let scenario = parse(script, &config.topology()).unwrap();
+        let mut sim = build(&config, &scenario).unwrap();
+
+        let mut vip = None;
+        for _ in 0..1200 {
+            sim.step(0.1);
+            let car = &sim.building().state().cars[0];
+            let riding: Vec<_> = sim
+                .people()
+                .people()
+                .iter()
+                .filter(|p| matches!(p.state, PersonState::Riding))
+                .map(|p| p.id)
+                .collect();
+            match car.service {
+                // only the VIP gets in, and the car takes them nowhere but their floor
+                ServiceState::Vip {
+                    stage: VipStage::Delivering,
+                    ..
+                } => {
+                    assert_eq!(riding.len(), 1);
+                    assert!(car.stops.len() <= 1);
+                    vip = Some(riding[0]);
+                }
+                ServiceState::Vip { .. } => assert!(riding.is_empty()),
+                _ => {}
+            }
+        }
+
+        // someone was delivered, and once the car was back everyone else was too
+        assert!(vip.is_some());
+        let people = sim.people().people();
+        assert!(people.iter().all(|p| matches!(p.state, PersonState::Done)));
+        assert_eq!(sim.building().state().cars[0].service, ServiceState::Normal);
+    }
+
+    #[test]
+    fn floors_go_by_their_labels() {
+        let config = SimConfig {
+            floors: 6,
+            basements: 2,
+            ..SimConfig::default()
+        };
+        let script = "
+            t=0: person from B2 to 3
+            t=1: call g up
+            t=2: fire recall to -1
+            t=3: command {\"MoveCarTo\": {\"car_id\": 0, \"floor\": \"b1\"}}
+            t=4: command {\"AddStop\": {\"car_id\": 0, \"floor\": 2}}
+        ";
+        let scenario = parse(script, &config.topology()).unwrap();
+        let arrival = scenario.arrivals[0];
+        assert_eq!((arrival.origin, arrival.destination), (0, 5));
+        assert_eq!(
+            scenario.commands[1].command,
+            ElevatorCommand::FireRecall { floor: 1 }
+        );
+        assert_eq!(
+            scenario.commands[2].command,
+            ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 1,
+            }
+        );
+        assert_eq!(
+            scenario.commands[3].command,
+            ElevatorCommand::AddStop {
+                car_id: CarId(0),
+                floor: 4,
+            }
+        );
+        assert!(matches!(
+            parse("t=0: car 0 to B3", &config.topology()),
+            Err(ScenarioError::Line(1, _))
+        ));
+        assert!(matches!(
+            parse(
+                "t=0: command {\"FireRecall\": {\"floor\": \"B3\"}}",
+                &config.topology()
+            ),
+            Err(ScenarioError::Line(1, _))
+        ));
+    }
+
+    #[test]
+    fn bad_lines_say_where_they_are() {
+        let topology = BuildingTopology::new(5);
+        let error = parse("t=0: person from 0 to 4\nt=5 car 1 to 3", &topology).unwrap_err();
+        assert!(matches!(error, ScenarioError::Line(2, _)));
+        assert!(matches!(
+            parse("t=-1: car 0 to 2", &topology),
+            Err(ScenarioError::Line(1, _))
+        ));
+        assert!(matches!(
+            parse("t=1: call 2 sideways", &topology),
+            Err(ScenarioError::Line(1, _))
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::control::ElevatorController;
+use crate::elevator::ElevatorCommand;
+use crate::metrics::StatsView;
+use crate::replay::RecordedCommand;
+use crate::types::CarId;
+use crate::view::BuildingView;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How many of the ticks the controllers disagreed on are kept for the report, so a shadow
+/// which disagrees all the time doesn't fill memory on a long run
+const KEPT_DIVERGENCES: usize = 1000;
+
+/// How many disagreements the report prints
+const SHOWN_DIVERGENCES: usize = 5;
+
+/// A tick the two controllers gave different commands on
+/// tick, time - when it was, in ticks and seconds into the run
+/// primary - the commands the controller in charge gave, which were applied
+/// shadow - the commands the shadow gave instead, which weren't
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShadowDivergence {
+    pub tick: u64,
+    pub time: f32,
+    pub primary: Vec<ElevatorCommand>,
+    pub shadow: Vec<ElevatorCommand>,
+}
+
+/// Where a shadow controller would have done something different from the one in charge
+/// ticks - how many ticks the shadow was shown
+/// diverged - how many of them it gave different commands on
+/// cars - how many ticks it gave a car different commands on, by car
+/// commands - every command the shadow gave, and when
+/// divergences - the first ticks it gave different commands on, up to a thousand
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShadowReport {
+    pub ticks: u64,
+    pub diverged: u64,
+    pub cars: BTreeMap<CarId, u64>,
+    pub commands: Vec<RecordedCommand>,
+    pub divergences: Vec<ShadowDivergence>,
+}
+
+/// A controller run alongside the one in charge, shown the same building and statistics
+/// every tick. Its commands are written down and compared, but never given, so it can be
+/// tried out on real traffic without changing the run. It sees the building the primary's
+/// commands made, not the one its own would have
+pub(crate) struct Shadow {
+    controller: Box<dyn ElevatorController>,
+    report: ShadowReport,
+}
+
+impl Shadow {
+    /// Shadow the controller in charge with controller
+    pub(crate) fn new(controller: Box<dyn ElevatorController>) -> Self {
+        Self {
+            controller,
+            report: ShadowReport::default(),
+        }
+    }
+
+    /// The controller being tried out
+    pub(crate) fn controller(&self) -> &dyn ElevatorController {
+        self.controller.as_ref()
+    }
+
+    /// Show the shadow what the controller in charge was shown on a tick, and compare its
+    /// commands with the ones primary gave
+    pub(crate) fn tick(
+        &mut self,
+        view: &BuildingView,
+        stats: &StatsView,
+        primary: &[ElevatorCommand],
+        tick: u64,
+        time: f32,
+    ) {
+        let shadow = self.controller.tick(view, stats);
+        let report = &mut self.report;
+        report.ticks += 1;
+        report
+            .commands
+            .extend(shadow.iter().map(|command| RecordedCommand {
+                tick,
+                time,
+                command: command.clone(),
+            }));
+        if same_commands(primary, &shadow) {
+            return;
+        }
+
+        report.diverged += 1;
+        let mut cars: Vec<CarId> = primary
+            .iter()
+            .chain(&shadow)
+            .filter_map(ElevatorCommand::car_id)
+            .collect();
+        cars.sort();
+        cars.dedup();
+        for car_id in cars {
+            let of = |commands: &[ElevatorCommand]| -> Vec<ElevatorCommand> {
+                commands
+                    .iter()
+                    .filter(|c| c.car_id() == Some(car_id))
+                    .cloned()
+                    .collect()
+            };
+            if !same_commands(&of(primary), &of(&shadow)) {
+                *report.cars.entry(car_id).or_default() += 1;
+            }
+        }
+        if report.divergences.len() < KEPT_DIVERGENCES {
+            report.divergences.push(ShadowDivergence {
+                tick,
+                time,
+                primary: primary.to_vec(),
+                shadow,
+            });
+        }
+    }
+
+    /// Stop shadowing, and return the report
+    pub(crate) fn finish(self) -> ShadowReport {
+        self.report
+    }
+}
+
+/// Whether two lists hold the same commands, in any order
+fn same_commands(a: &[ElevatorCommand], b: &[ElevatorCommand]) -> bool {
+    let count =
+        |commands: &[ElevatorCommand], command| commands.iter().filter(|&c| c == command).count();
+    a.len() == b.len() && a.iter().all(|c| count(a, c) == count(b, c))
+}
+
+impl fmt::Display for ShadowReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "The shadow controller gave different commands on {} of {} ticks",
+            self.diverged, self.ticks
+        )?;
+        for (car_id, ticks) in &self.cars {
+            writeln!(f, "  car {}: {ticks} ticks", car_id.0)?;
+        }
+        for divergence in self.divergences.iter().take(SHOWN_DIVERGENCES) {
+            writeln!(
+                f,
+                "At tick {} ({:.2} s): gave {:?}, shadow would have given {:?}",
+                divergence.tick, divergence.time, divergence.primary, divergence.shadow
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ControllerChoice, SimConfig};
+
+    #[test]
+    fn a_shadow_is_compared_but_never_obeyed() {
+        let config = SimConfig {
+            seed: Some(4),
+            spawn_rate: 0.5,
+            ..SimConfig::default()
+        };
+        let run = |shadow| {
+            let mut sim = SimConfig {
+                shadow,
+                ..config.clone()
+            }
+            .build()
+            .unwrap();
+            for _ in 0..600 {
+                sim.step(0.1);
+            }
+            sim
+        };
+        let alone = run(None);
+        let mut shadowed = run(Some(ControllerChoice::Group));
+        assert_eq!(
+            alone.building().state().cars,
+            shadowed.building().state().cars
+        );
+
+        let report = shadowed.finish_shadow().unwrap();
+        assert_eq!(report.ticks, 600);
+        assert!(report.diverged > 0);
+        assert!(report.cars.values().all(|&ticks| ticks <= report.diverged));
+        let first = &report.divergences[0];
+        assert_ne!(first.primary, first.shadow);
+        assert!(shadowed.finish_shadow().is_none());
+
+        // a shadow of the same controller always agrees
+        let report = run(Some(ControllerChoice::Basic)).finish_shadow().unwrap();
+        assert_eq!((report.diverged, report.divergences.len()), (0, 0));
+    }
+}
@@ -0,0 +1,1721 @@
+use crate::control::ElevatorController;
+use crate::deliveries::DeliveryConfig;
+use crate::elevator::{
+    BuildingState, CommandError, ElevatorCommand, ElevatorEvent, ElevatorSim, EmergencyMode,
+    MAX_EVENTS,
+};
+use crate::events::{EventSink, SimEvent, Tracer};
+use crate::faults::FaultConfig;
+use crate::features::FeatureExporter;
+use crate::metrics::{Metric, MetricCollector};
+use crate::observation::{ObservationError, ObservationFilter};
+use crate::people::{PeopleSim, Person, PersonAction, PersonOptions, PersonState};
+use crate::replay::{CommandLog, Outcome, RecordedCommand, Scenario, ScriptedCommand};
+use crate::shadow::{Shadow, ShadowReport};
+use crate::snapshot::{self, SnapshotError};
+use crate::types::{CarId, Floor, FloorMismatch, PersonId, ScriptedArrival};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The most ticks in a row step_to_next_event will take without advancing time, before it
+/// forces time forward by FORCED_STEP. This stops a chain of instant reactions from looping
+/// forever
+const MAX_INSTANT_STEPS: u32 = 100;
+const FORCED_STEP: f32 = 0.1;
+
+/// How close to the end step_to_next_event_before treats as the end, since the last sliver
+/// of a run can be too short to add to its time
+const END_SLACK: f32 = 1e-3;
+
+/// How long each of advance_to's steps lasts, unless set_step_length chooses otherwise
+const DEFAULT_STEP_LENGTH: f32 = 0.1;
+
+/// A disagreement between PeopleSim and ElevatorSim about who is in which car
+#[derive(Clone, Debug, PartialEq)]
+pub enum OccupancyMismatch {
+    /// the person thinks they're riding the car, but the car doesn't list them
+    MissingFromCar { person_id: PersonId, car_id: CarId },
+    /// the car lists the person, but they don't think they're riding it
+    UnexpectedOccupant { person_id: PersonId, car_id: CarId },
+}
+
+/// A Simulation ties together PeopleSim, ElevatorSim, and an ElevatorController, translating
+/// between them the same way every tick. It can either be stepped by a fixed timestep, be
+/// asked to jump straight to the next moment anything happens, or be told what time it is by
+/// a host which owns the clock
+pub struct Simulation {
+    people: PeopleSim,
+    building: ElevatorSim,
+    controller: Box<dyn ElevatorController>,
+    shadow: Option<Shadow>,
+    features: Option<FeatureExporter<Box<dyn Write + Send>>>,
+    observation: ObservationFilter,
+    faults: FaultConfig,
+    deliveries: DeliveryConfig,
+    ticks: u64,
+    recording: Option<Vec<RecordedCommand>>,
+    macro_recording: Option<Scenario>,
+    scripted_arrivals: VecDeque<ScriptedArrival>,
+    scripted_commands: VecDeque<ScriptedCommand>,
+    tracer: Option<Tracer>,
+    collectors: Vec<Tracer<dyn MetricCollector>>,
+    time: f32,
+    instant_steps: u32,
+    step_length: f32,
+    previous_positions: Vec<f32>,
+    check_occupancy: bool,
+    mismatches: Vec<(f32, OccupancyMismatch)>,
+    events: VecDeque<ElevatorEvent>,
+}
+
+/// What save_snapshot writes, borrowing from the simulation
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    time: f32,
+    people: &'a PeopleSim,
+    building: &'a ElevatorSim,
+    scripted_arrivals: &'a VecDeque<ScriptedArrival>,
+    scripted_commands: &'a VecDeque<ScriptedCommand>,
+}
+
+/// What load_snapshot reads back
+#[derive(Deserialize)]
+struct Snapshot {
+    time: f32,
+    people: PeopleSim,
+    building: ElevatorSim,
+    #[serde(default)]
+    scripted_arrivals: VecDeque<ScriptedArrival>,
+    #[serde(default)]
+    scripted_commands: VecDeque<ScriptedCommand>,
+}
+
+/// implement functions for Simulation
+/// new, try_new - create a simulation from its parts
+/// step - advance the simulation by a fixed timestep
+/// step_to_next_event - advance the simulation straight to the next event
+/// advance_to, set_step_length - catch the simulation up to a time the host chooses
+/// interpolated_state, alpha_at - where the cars are between the last two ticks, for drawing
+/// smoothly
+/// take_events - drain the building's events, after people have seen them
+/// set_check_occupancy - turn the per tick occupancy consistency check on or off
+/// set_observation - choose what the controller is allowed to see
+/// set_controller - swap in a different controller
+/// start_shadow, finish_shadow - show a second controller everything the one in charge is
+/// shown, and report where its commands would have differed
+/// set_faults - choose how often cars and call buttons break down
+/// set_deliveries - choose how often deliveries arrive for the freight cars
+/// start_recording, finish_recording - write down every command the controller gives, to
+/// replay later with ReplayController
+/// inject, add_person - give the building a command, or put a person in it, by hand
+/// start_macro, finish_macro - write down the commands given and people added by hand, as a
+/// Scenario to play back later
+/// play_scenario - light a Scenario's buttons, then give its commands and add its people when
+/// their times come
+/// start_trace, finish_trace - send everything that happens to an EventSink
+/// add_collector, finish_collectors - measure the run with a MetricCollector of its own
+/// save_snapshot, load_snapshot - checkpoint the people, building, time and scenario being
+/// played to a file, and resume from one
+/// people, building, time - look at the simulation's state
+/// people_mut, building_mut - change the simulation's people or building
+impl Simulation {
+    /// Create a new simulation from people, a building, and a controller for the building
+    pub fn new(
+        people: PeopleSim,
+        building: ElevatorSim,
+        controller: Box<dyn ElevatorController>,
+    ) -> Self {
+        let previous_positions = positions(&building);
+        Self {
+            people,
+            building,
+            controller,
+            shadow: None,
+            features: None,
+            observation: ObservationFilter::default(),
+            faults: FaultConfig::default(),
+            deliveries: DeliveryConfig::default(),
+            ticks: 0,
+            recording: None,
+            macro_recording: None,
+            scripted_arrivals: VecDeque::new(),
+            scripted_commands: VecDeque::new(),
+            tracer: None,
+            collectors: Vec::new(),
+            time: 0.,
+            instant_steps: 0,
+            step_length: DEFAULT_STEP_LENGTH,
+            previous_positions,
+            check_occupancy: false,
+            mismatches: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Create a new simulation like new, but first check with check_floors that the people
+    /// and the building agree on the floors, so a mismatch is found now rather than mid-run
+    pub fn try_new(
+        people: PeopleSim,
+        building: ElevatorSim,
+        controller: Box<dyn ElevatorController>,
+    ) -> Result<Self, FloorMismatch> {
+        check_floors(&people, &building)?;
+        Ok(Self::new(people, building, controller))
+    }
+
+    /// Advance the simulation by dt seconds. People act first, then the controller, then
+    /// anything due to break does, then the building moves for dt seconds
+    pub fn step(&mut self, dt: f32) {
+        self.react(dt);
+        self.faults.inject(&mut self.building, dt);
+        self.deliveries
+            .deliver(&mut self.building, &mut self.people, dt);
+        self.previous_positions = positions(&self.building);
+        self.building.tick(dt);
+        self.time += dt;
+        self.trace_building();
+    }
+
+    /// Advance the simulation straight to the next event, such as a car arriving, a door
+    /// timer running out, or a person spawning, but never by more than max_dt seconds.
+    /// The building moves first, then people and the controller react to where it ended
+    /// up. Returns how much time passed, which is 0 when people or the controller have
+    /// something to react to right away
+    pub fn step_to_next_event(&mut self, max_dt: f32) -> f32 {
+        // people hear about anything that happened since they last acted before saying when
+        // they'll next act, such as having set off an overload sensor
+        self.collect_events();
+        let state = self.building.state();
+        let next = [
+            self.people.time_to_next_event(state),
+            self.building.time_to_next_event(),
+            // a scenario being played adds people and gives commands at set times
+            self.scripted_arrivals
+                .front()
+                .map(|a| (a.time - self.time).max(0.)),
+            self.scripted_commands
+                .front()
+                .map(|c| (c.time - self.time).max(0.)),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(max_dt, f32::min);
+
+        // a long run of instant steps means something is reacting without changing anything
+        let dt = if next > 0. {
+            self.instant_steps = 0;
+            next
+        } else if self.instant_steps >= MAX_INSTANT_STEPS {
+            self.instant_steps = 0;
+            FORCED_STEP.min(max_dt)
+        } else {
+            self.instant_steps += 1;
+            0.
+        };
+
+        self.faults.inject(&mut self.building, dt);
+        self.deliveries
+            .deliver(&mut self.building, &mut self.people, dt);
+        self.previous_positions = positions(&self.building);
+        self.building.tick(dt);
+        self.react(dt);
+        self.time += dt;
+        self.trace_building();
+        dt
+    }
+
+    /// Advance straight to the next event as step_to_next_event does, without going past end
+    /// seconds into the run. Returns how much time passed, or None once end has been reached,
+    /// so a run can be played out event by event
+    pub fn step_to_next_event_before(&mut self, end: f32) -> Option<f32> {
+        (self.time + END_SLACK < end).then(|| self.step_to_next_event(end - self.time))
+    }
+
+    /// Catch the simulation up to t seconds into the run, for hosts such as game engines
+    /// which own the clock and move it on by a different amount every frame. The simulation
+    /// takes as many whole steps of step_length seconds as fit, and carries whatever is left
+    /// over into the next call, so a run comes out the same however its frames are sliced.
+    /// A time already passed does nothing. Returns how many steps were taken
+    pub fn advance_to(&mut self, t: f32) -> u32 {
+        // allow for the rounding of adding up many steps
+        let slack = self.step_length * 1e-3;
+        let mut steps = 0;
+        while self.time + self.step_length <= t + slack {
+            self.step(self.step_length);
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Choose how long each of advance_to's steps lasts. Shorter steps follow the host's
+    /// clock more closely, but take longer to simulate. Steps must be longer than 0
+    pub fn set_step_length(&mut self, dt: f32) {
+        if dt > 0. {
+            self.step_length = dt;
+        }
+    }
+
+    /// The building as it is now, but with every car alpha of the way from where it was
+    /// before the last tick to where it is now, so a renderer drawing more often than the
+    /// simulation ticks can move the cars smoothly. alpha is clamped to between 0 and 1
+    pub fn interpolated_state(&self, alpha: f32) -> BuildingState {
+        let alpha = alpha.clamp(0., 1.);
+        let topology = self.building.topology();
+        let mut state = self.building.state().clone();
+        for (car, &previous) in state.cars.iter_mut().zip(&self.previous_positions) {
+            car.elevation = previous + (car.elevation - previous) * alpha;
+            car.current_floor = topology.floor_at(car.elevation);
+        }
+        state
+    }
+
+    /// How far the host's clock at t is into the next of advance_to's steps, as the alpha
+    /// for interpolated_state. Drawing a step behind the clock, rather than guessing ahead,
+    /// keeps the cars from overshooting where they stop
+    pub fn alpha_at(&self, t: f32) -> f32 {
+        ((t - self.time) / self.step_length).clamp(0., 1.)
+    }
+
+    /// Let people act, turning their actions into commands, then let the controller act
+    /// unless there's an emergency
+    fn react(&mut self, dt: f32) {
+        self.collect_events();
+        self.play_due();
+
+        // step PeopleSim, and get the vector of PersonActions
+        let person_actions = self.people.tick(dt, self.building.state());
+        //trace who appeared or moved before the buttons they pressed
+        self.observe(self.ticks);
+        for act in person_actions {
+            //translate those PersonActions into ElevatorCommands
+            if let Some(cmd) = person_action_to_cmd(act) {
+                self.apply(cmd);
+            }
+        }
+
+        //the fire service has the cars during an emergency, so the controller isn't asked
+        //for anything until it's cleared
+        if self.building.state().emergency == EmergencyMode::Normal {
+            self.control();
+        }
+        self.ticks += 1;
+
+        if self.check_occupancy && self.building.config().track_occupants {
+            let mismatches = check_occupancy(self.people.people(), self.building.state());
+            self.mismatches
+                .extend(mismatches.into_iter().map(|m| (self.time, m)));
+        }
+    }
+
+    /// Show the controller the building, and give the building the commands it asks for
+    fn control(&mut self) {
+        //show the controller the building, without copying it, to get ElevatorCommands
+        let view = self
+            .observation
+            .view(self.building.state(), self.people.people());
+        let demand = self
+            .observation
+            .demand(self.building.state(), self.people.people());
+        let mut stats = self
+            .observation
+            .stats(self.building.stats().with_people(self.people.summary()));
+        if let Some(demand) = &demand {
+            stats = stats.with_demand(demand);
+        }
+        let control_cmds = self.controller.tick(&view, &stats);
+        if let Some(shadow) = &mut self.shadow {
+            shadow.tick(&view, &stats, &control_cmds, self.ticks, self.time);
+        }
+        if let Some(features) = &mut self.features {
+            features.record(&view, &stats, &control_cmds);
+        }
+        for cmd in control_cmds {
+            if let Some(recording) = &mut self.recording {
+                recording.push(RecordedCommand {
+                    tick: self.ticks,
+                    time: self.time,
+                    command: cmd.clone(),
+                });
+            }
+            self.apply(cmd);
+        }
+    }
+
+    /// Apply a command to the building, tracing it if it's accepted. Rejected commands are
+    /// recorded as events by ElevatorSim
+    fn apply(&mut self, cmd: ElevatorCommand) {
+        let traced = self.traced().then(|| cmd.clone());
+        if self.building.apply_command(cmd).is_ok()
+            && let Some(command) = traced
+        {
+            self.emit(self.ticks, SimEvent::CommandApplied { command });
+        }
+    }
+
+    /// Add the people and give the commands of the scenario being played whose time has come
+    fn play_due(&mut self) {
+        while let Some(arrival) = self.scripted_arrivals.front().copied()
+            && arrival.time <= self.time
+        {
+            self.scripted_arrivals.pop_front();
+            self.add_person(arrival.origin, arrival.destination);
+        }
+        while let Some(scripted) = self.scripted_commands.front()
+            && scripted.time <= self.time
+        {
+            let command = scripted.command.clone();
+            self.scripted_commands.pop_front();
+            // the building records a refused command as an event, as it does the controller's
+            let _ = self.inject(command);
+        }
+    }
+
+    /// Trace what the building did during the last tick, and how people and doors ended up
+    fn trace_building(&mut self) {
+        if !self.traced() {
+            return;
+        }
+        // the events would otherwise wait for the next tick to be collected
+        self.collect_events();
+        self.observe(self.ticks.saturating_sub(1));
+    }
+
+    /// Whether anything is taking in events: a trace, or a metric collector
+    fn traced(&self) -> bool {
+        self.tracer.is_some() || !self.collectors.is_empty()
+    }
+
+    /// Send an event to the trace and every metric collector
+    fn emit(&mut self, tick: u64, event: SimEvent) {
+        for collector in &mut self.collectors {
+            collector.emit(self.time, tick, event.clone());
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.emit(self.time, tick, event);
+        }
+    }
+
+    /// Show the trace and every metric collector how the people and building are now
+    fn observe(&mut self, tick: u64) {
+        let (people, state) = (self.people.people(), self.building.state());
+        for collector in &mut self.collectors {
+            collector.observe(self.time, tick, people, state);
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.observe(self.time, tick, people, state);
+        }
+    }
+
+    /// Move the building's new events into this simulation's buffer, showing each one to
+    /// the people on the way, and tracing them
+    fn collect_events(&mut self) {
+        for event in self.building.take_events() {
+            self.people.observe(&event);
+            if self.traced() {
+                self.emit(
+                    self.ticks.saturating_sub(1),
+                    SimEvent::Building(event.clone()),
+                );
+            }
+            if self.events.len() >= MAX_EVENTS {
+                self.events.pop_front();
+            }
+            self.events.push_back(event);
+        }
+    }
+
+    /// Remove and return every event the building has recorded since the last call
+    pub fn take_events(&mut self) -> Vec<ElevatorEvent> {
+        self.collect_events();
+        self.events.drain(..).collect()
+    }
+
+    /// Turn on or off checking, every tick, that PeopleSim and ElevatorSim agree on who is in
+    /// which car. This only does anything if the building tracks occupants
+    pub fn set_check_occupancy(&mut self, on: bool) {
+        self.check_occupancy = on;
+    }
+
+    /// Choose what the controller is allowed to see. Nothing is hidden by default. Fails,
+    /// keeping the old filter, if the controller already knows something the new one hides
+    pub fn set_observation(&mut self, filter: ObservationFilter) -> Result<(), ObservationError> {
+        filter.allows(self.controller.as_ref())?;
+        if let Some(shadow) = &self.shadow {
+            filter.allows(shadow.controller())?;
+        }
+        self.observation = filter;
+        Ok(())
+    }
+
+    /// Replace the controller, which carries on from the building as it is
+    pub fn set_controller(&mut self, controller: Box<dyn ElevatorController>) {
+        self.controller = controller;
+    }
+
+    /// Show controller everything the controller in charge is shown from now on, and write
+    /// down its commands without giving them. Fails if the observation filter hides
+    /// something it knows, and replaces any shadow already running
+    pub fn start_shadow(
+        &mut self,
+        controller: Box<dyn ElevatorController>,
+    ) -> Result<(), ObservationError> {
+        self.observation.allows(controller.as_ref())?;
+        self.shadow = Some(Shadow::new(controller));
+        Ok(())
+    }
+
+    /// Stop shadowing, and report where the shadow's commands differed from the ones given.
+    /// Returns None if there was no shadow
+    pub fn finish_shadow(&mut self) -> Option<ShadowReport> {
+        self.shadow.take().map(Shadow::finish)
+    }
+
+    /// Write a row of features for every decision the controller makes from now on, with the
+    /// building as it was shown and the floors the cars were sent to, so the controller can
+    /// be imitated by a trained model. Any export already running is finished first,
+    /// ignoring its errors
+    pub fn start_feature_export(&mut self, exporter: FeatureExporter<Box<dyn Write + Send>>) {
+        let _ = self.finish_feature_export();
+        self.features = Some(exporter);
+    }
+
+    /// Stop exporting features, returning anything that went wrong while writing them. Does
+    /// nothing if nothing is being exported
+    pub fn finish_feature_export(&mut self) -> io::Result<()> {
+        match self.features.take() {
+            Some(mut features) => features.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Choose how often cars and call buttons break down from now on. Nothing breaks by
+    /// default
+    pub fn set_faults(&mut self, faults: FaultConfig) {
+        self.faults = faults;
+    }
+
+    /// Choose how often deliveries arrive from now on, and how long they keep freight cars
+    /// for. None arrive by default
+    pub fn set_deliveries(&mut self, deliveries: DeliveryConfig) {
+        self.deliveries = deliveries;
+    }
+
+    /// Start sending every event, from people spawning to doors closing, to sink, beginning
+    /// with the building as it is now. Any sink already tracing is finished first, ignoring
+    /// its errors
+    pub fn start_trace(&mut self, sink: Box<dyn EventSink>) {
+        let _ = self.finish_trace();
+        let mut tracer = Tracer::new(sink);
+        let state = self.building.state().clone();
+        tracer.emit(self.time, self.ticks, SimEvent::Started { state });
+        tracer.observe(
+            self.time,
+            self.ticks,
+            self.people.people(),
+            self.building.state(),
+        );
+        self.tracer = Some(tracer);
+    }
+
+    /// Stop tracing and finish the sink, such as flushing a file, returning anything that
+    /// went wrong while writing. Does nothing if nothing is being traced
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        match self.tracer.take() {
+            Some(mut tracer) => tracer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Measure the run from now on with collector as well as the built in metrics. It takes
+    /// in every event, beginning with the building as it is now, like a trace's sink
+    pub fn add_collector(&mut self, collector: Box<dyn MetricCollector>) {
+        let mut tracer = Tracer::new(collector);
+        let state = self.building.state().clone();
+        tracer.emit(self.time, self.ticks, SimEvent::Started { state });
+        tracer.observe(
+            self.time,
+            self.ticks,
+            self.people.people(),
+            self.building.state(),
+        );
+        self.collectors.push(tracer);
+    }
+
+    /// Stop every metric collector, and return what each measured, in the order they were
+    /// added
+    pub fn finish_collectors(&mut self) -> Vec<Metric> {
+        self.collectors
+            .drain(..)
+            .flat_map(|mut tracer| tracer.sink_mut().report())
+            .collect()
+    }
+
+    /// Give the building a command by hand, on top of whatever the controller does, such as
+    /// pressing a button during a demo. An accepted command is traced, and written into the
+    /// macro if one is being recorded
+    pub fn inject(&mut self, command: ElevatorCommand) -> Result<(), CommandError> {
+        self.building.apply_command(command.clone())?;
+        if self.traced() {
+            let command = command.clone();
+            self.emit(self.ticks, SimEvent::CommandApplied { command });
+        }
+        if let Some(recording) = &mut self.macro_recording {
+            recording.commands.push(ScriptedCommand {
+                time: self.time,
+                command,
+            });
+        }
+        Ok(())
+    }
+
+    /// Put a person on origin who wants to go to destination by hand, returning their id.
+    /// They're written into the macro if one is being recorded
+    pub fn add_person(&mut self, origin: Floor, destination: Floor) -> PersonId {
+        if let Some(recording) = &mut self.macro_recording {
+            recording.arrivals.push(ScriptedArrival {
+                time: self.time,
+                origin,
+                destination,
+            });
+        }
+        self.people
+            .add_person(origin, destination, PersonOptions::default())
+    }
+
+    /// Start writing down every command given with inject and every person added with
+    /// add_person, and when, for a scenario that can be edited and played back
+    pub fn start_macro(&mut self) {
+        self.macro_recording = Some(Scenario::default());
+    }
+
+    /// Stop recording a macro, and return what was done by hand as a scenario. Returns None if
+    /// nothing was recording
+    pub fn finish_macro(&mut self) -> Option<Scenario> {
+        self.macro_recording.take()
+    }
+
+    /// Light scenario's buttons straight away, then add its people and give its commands once
+    /// the run reaches their times, as well as whatever the people and controller do
+    /// themselves. Times already passed happen on the next step. If a button is on a floor or
+    /// in a car the building doesn't have, the error is returned and nothing is played
+    pub fn play_scenario(&mut self, scenario: &Scenario) -> Result<(), CommandError> {
+        self.building.light_buttons(&scenario.buttons)?;
+        self.scripted_arrivals
+            .extend(scenario.arrivals.iter().copied());
+        self.scripted_arrivals
+            .make_contiguous()
+            .sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.scripted_commands
+            .extend(scenario.commands.iter().cloned());
+        self.scripted_commands
+            .make_contiguous()
+            .sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(())
+    }
+
+    /// Start writing down every command the controller gives, and when. Recording from the
+    /// very start lets ReplayController repeat the run
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stop recording, and return the commands with where the run ended up. seed is the
+    /// seed the run was started with, for replaying it. Returns None if nothing was recording
+    pub fn finish_recording(&mut self, seed: Option<u64>) -> Option<CommandLog> {
+        let commands = self.recording.take()?;
+        Some(CommandLog {
+            seed,
+            commands,
+            outcome: Outcome::of(self),
+        })
+    }
+
+    /// Every occupancy mismatch found so far, with the time it was found at
+    pub fn occupancy_mismatches(&self) -> &[(f32, OccupancyMismatch)] {
+        &self.mismatches
+    }
+
+    /// Save the people, the building, the time and what's left of the scenario being played
+    /// to a JSON file. Events from earlier steps which haven't been taken aren't saved
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        snapshot::save(
+            path,
+            &SnapshotRef {
+                time: self.time,
+                people: &self.people,
+                building: &self.building,
+                scripted_arrivals: &self.scripted_arrivals,
+                scripted_commands: &self.scripted_commands,
+            },
+        )
+    }
+
+    /// Resume from a snapshot saved by save_snapshot. The controller, and the people's spawner,
+    /// engine and hooks, stay as they are, so the simulation should be built the same way as
+    /// the one that was saved. The scenario being played goes on from where it was saved,
+    /// in place of anything played here
+    pub fn load_snapshot(&mut self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let saved: Snapshot = snapshot::load(path)?;
+        // the people arriving from now on are this simulation's, not the snapshot's
+        check_floors(&saved.people, &saved.building)
+            .and_then(|()| self.people.check_arrivals(floors(&saved.building)))
+            .map_err(SnapshotError::Floors)?;
+        self.time = saved.time;
+        self.people.restore(saved.people);
+        self.building = saved.building;
+        self.scripted_arrivals = saved.scripted_arrivals;
+        self.scripted_commands = saved.scripted_commands;
+        self.previous_positions = positions(&self.building);
+        self.instant_steps = 0;
+        self.events.clear();
+        Ok(())
+    }
+
+    /// The people in the simulation
+    pub fn people(&self) -> &PeopleSim {
+        &self.people
+    }
+
+    /// The people, mutably, so people can be added or removed between steps
+    pub fn people_mut(&mut self) -> &mut PeopleSim {
+        &mut self.people
+    }
+
+    /// The building in the simulation
+    pub fn building(&self) -> &ElevatorSim {
+        &self.building
+    }
+
+    /// The building, mutably
+    pub fn building_mut(&mut self) -> &mut ElevatorSim {
+        &mut self.building
+    }
+
+    /// How many seconds have been simulated
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// How many steps have been taken
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+}
+
+/// Check people fit in building: that they don't move between more floors than it has, and
+/// that nobody is on, heading for or scripted to arrive on a floor past its top. The
+/// building's own settings are checked too. Anything built from parts which might not agree,
+/// such as a snapshot or a config, should check them here before running
+pub fn check_floors(people: &PeopleSim, building: &ElevatorSim) -> Result<(), FloorMismatch> {
+    building.check_floors()?;
+    people.check_floors(floors(building))
+}
+
+/// How many floors the building has
+fn floors(building: &ElevatorSim) -> Floor {
+    building.state().floors.len() as Floor
+}
+
+/// How high up every car in the building is
+fn positions(building: &ElevatorSim) -> Vec<f32> {
+    building
+        .state()
+        .cars
+        .iter()
+        .map(|car| car.elevation)
+        .collect()
+}
+
+/// Compare where people think they are with who each car thinks is inside it, returning
+/// every disagreement
+pub fn check_occupancy(people: &[Person], state: &BuildingState) -> Vec<OccupancyMismatch> {
+    let mut mismatches = Vec::new();
+
+    // everyone riding should be listed by their car
+    for person in people {
+        if let (PersonState::Riding, Some(car_id)) = (&person.state, person.in_car) {
+            let listed = state
+                .cars
+                .get(car_id.0 as usize)
+                .is_some_and(|car| car.occupants.contains(&person.id));
+            if !listed {
+                mismatches.push(OccupancyMismatch::MissingFromCar {
+                    person_id: person.id,
+                    car_id,
+                });
+            }
+        }
+    }
+
+    // everyone listed by a car should be riding it
+    for car in &state.cars {
+        for &person_id in &car.occupants {
+            let riding = people.iter().any(|p| {
+                p.id == person_id
+                    && matches!(p.state, PersonState::Riding)
+                    && p.in_car == Some(car.id)
+            });
+            if !riding {
+                mismatches.push(OccupancyMismatch::UnexpectedOccupant {
+                    person_id,
+                    car_id: car.id,
+                });
+            }
+        }
+    }
+
+    mismatches
+}
+
+/// Translate PersonActions to ElevatorCommands
+pub fn person_action_to_cmd(action: PersonAction) -> Option<ElevatorCommand> {
+    match action {
+        //If a person tries to call an elevator, press the outer button
+        PersonAction::CallElevator {
+            floor,
+            direction,
+            bank,
+        } => Some(ElevatorCommand::PressOutButton {
+            floor,
+            direction,
+            bank,
+        }),
+        //If a person in a wheelchair calls an elevator, press the accessible call button
+        PersonAction::CallAccessible {
+            floor,
+            direction,
+            bank,
+        } => Some(ElevatorCommand::PressAccessButton {
+            floor,
+            direction,
+            bank,
+        }),
+        //If a person tries to press an interior car button, press the interior car button
+        PersonAction::PressCarButton { car_id, floor } => {
+            Some(ElevatorCommand::PressCarButton { car_id, floor })
+        }
+        //If a person enters a destination at a kiosk, request that trip
+        PersonAction::RequestTrip { floor, destination } => {
+            Some(ElevatorCommand::RequestTrip { floor, destination })
+        }
+        //If a person steps into or out of a car, tell the car
+        PersonAction::EnterCar {
+            car_id,
+            person_id,
+            weight,
+        } => Some(ElevatorCommand::Board {
+            car_id,
+            person_id,
+            weight,
+        }),
+        PersonAction::ExitCar {
+            car_id,
+            person_id,
+            weight,
+        } => Some(ElevatorCommand::Alight {
+            car_id,
+            person_id,
+            weight,
+        }),
+        //If an operator takes over a car, or drives it somewhere, tell the car
+        PersonAction::TakeOverCar { car_id } => {
+            Some(ElevatorCommand::SetIndependentService { car_id, on: true })
+        }
+        PersonAction::OperateCar { car_id, floor } => {
+            Some(ElevatorCommand::OperateCar { car_id, floor })
+        }
+        //If someone steps into a closing door, its sensor sees them
+        PersonAction::HoldDoor { car_id } => Some(ElevatorCommand::ReopenDoor { car_id }),
+        //If someone shows their card, the car's reader sees it
+        PersonAction::AuthorizeFloor { car_id, floor } => {
+            Some(ElevatorCommand::AuthorizeFloor { car_id, floor })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::BasicController;
+    use crate::elevator::{
+        CarCapabilities, CommandError, DoorState, ElevatorConfig, EmergencyMode, HallButtons,
+        LitButtons, ServiceState,
+    };
+    use crate::metrics::StatsView;
+    use crate::observation::Observation;
+    use crate::people::{Attribute, BehaviorProfile, BoardingRule, PeopleConfig, PersonOptions};
+    use crate::topology::BuildingTopology;
+    use crate::types::{Direction, Floor, ScriptedArrival};
+    use crate::view::BuildingView;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn call_elevator_to_press_out_button() {
+        let cmd = person_action_to_cmd(PersonAction::CallElevator {
+            floor: 3,
+            direction: Direction::Up,
+            bank: None,
+        });
+        match cmd {
+            Some(ElevatorCommand::PressOutButton { floor, .. }) => {
+                assert_eq!(floor, 3)
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn press_car_button_to_press_car_button() {
+        let cmd = person_action_to_cmd(PersonAction::PressCarButton {
+            car_id: CarId(0),
+            floor: 3,
+        });
+        match cmd {
+            Some(ElevatorCommand::PressCarButton { car_id, floor }) => {
+                assert_eq!(car_id, CarId(0));
+                assert_eq!(floor, 3)
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn event_mode_delivers_everyone_in_few_steps() {
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 5.,
+                origin: 0,
+                destination: 8,
+            },
+            ScriptedArrival {
+                time: 40.,
+                origin: 6,
+                destination: 1,
+            },
+        ];
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, arrivals, PeopleConfig::default()),
+            ElevatorSim::new(10, 2),
+            Box::new(BasicController),
+        );
+
+        let mut steps = 0;
+        while sim.time() < 100. {
+            sim.step_to_next_event(100.);
+            steps += 1;
+        }
+
+        // stepping every 0.1s would take 1000 steps
+        assert!(steps < 100);
+        assert!(
+            sim.people()
+                .people()
+                .iter()
+                .all(|p| matches!(p.state, PersonState::Done))
+        );
+    }
+
+    #[test]
+    fn single_call_buttons_still_get_everyone_there() {
+        // two people on the same floor going opposite ways share one button
+        let arrivals = [(5., 3, 8), (6., 3, 0), (30., 8, 2)]
+            .map(|(time, origin, destination)| ScriptedArrival {
+                time,
+                origin,
+                destination,
+            })
+            .to_vec();
+        let config = ElevatorConfig {
+            hall_buttons: HallButtons::Single,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, arrivals, PeopleConfig::default()),
+            ElevatorSim::with_config(10, 1, config),
+            Box::new(BasicController),
+        );
+
+        for _ in 0..2000 {
+            sim.step(0.1);
+        }
+        assert!(
+            sim.people()
+                .people()
+                .iter()
+                .all(|p| matches!(p.state, PersonState::Done))
+        );
+    }
+
+    #[test]
+    fn whoever_tips_a_car_over_its_weight_limit_waits_for_the_next_trip() {
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 0.,
+                origin: 0,
+                destination: 5,
+            };
+            3
+        ];
+        let config = ElevatorConfig {
+            weight_limits: vec![200.],
+            ..ElevatorConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(6, arrivals, PeopleConfig::default()),
+            ElevatorSim::with_config(6, 1, config),
+            Box::new(BasicController),
+        );
+
+        // two 75 kg people fit, and the third sets off the sensor
+        let mut overloads = 0;
+        let mut heaviest: f32 = 0.;
+        for _ in 0..1000 {
+            sim.step(0.1);
+            let car = &sim.building().state().cars[0];
+            if car.velocity != 0. {
+                heaviest = heaviest.max(car.load);
+            }
+            overloads += sim
+                .take_events()
+                .iter()
+                .filter(|e| matches!(e, ElevatorEvent::CarOverloaded { .. }))
+                .count();
+        }
+        assert_eq!(overloads, 1);
+        assert_eq!(heaviest, 150.);
+        assert!(
+            sim.people()
+                .people()
+                .iter()
+                .all(|p| matches!(p.state, PersonState::Done))
+        );
+    }
+
+    #[test]
+    fn only_people_with_a_card_ride_to_a_secured_floor() {
+        let topology = Arc::new(BuildingTopology {
+            secured_floors: vec![4],
+            ..BuildingTopology::new(6)
+        });
+        let mut people = PeopleSim::scripted(6, Vec::new(), PeopleConfig::default());
+        people.set_topology(Arc::clone(&topology));
+        let building = ElevatorSim::with_topology(topology, 1, ElevatorConfig::default());
+        let mut sim = Simulation::new(people, building, Box::new(BasicController));
+
+        // a visitor without a card can't press the button, so takes the stairs
+        let visitor = sim.people_mut().add_person(0, 4, PersonOptions::default());
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+        let refused = sim.take_events().into_iter().any(|e| {
+            e == ElevatorEvent::CommandRejected {
+                error: CommandError::Unauthorized {
+                    car_id: CarId(0),
+                    floor: 4,
+                },
+            }
+        });
+        assert!(refused);
+        let person = &sim.people().people()[visitor.0 as usize];
+        assert_eq!(person.state, PersonState::GaveUp);
+        assert_eq!(person.current_floor, 0);
+
+        // someone with a card shows it first
+        let holder = PersonOptions {
+            permitted_floors: vec![4],
+            ..PersonOptions::default()
+        };
+        let holder = sim.people_mut().add_person(0, 4, holder);
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+        let person = &sim.people().people()[holder.0 as usize];
+        assert_eq!(person.state, PersonState::Done);
+        assert_eq!(person.current_floor, 4);
+    }
+
+    #[test]
+    fn snapshot_resumes_exactly_where_it_left_off() {
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 2.,
+                origin: 0,
+                destination: 6,
+            },
+            ScriptedArrival {
+                time: 12.,
+                origin: 5,
+                destination: 1,
+            },
+            ScriptedArrival {
+                time: 30.,
+                origin: 3,
+                destination: 7,
+            },
+        ];
+        let build = || {
+            Simulation::new(
+                PeopleSim::scripted(8, arrivals.clone(), PeopleConfig::default()),
+                ElevatorSim::new(8, 2),
+                Box::new(BasicController),
+            )
+        };
+
+        let mut original = build();
+        for _ in 0..150 {
+            original.step(0.1);
+        }
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+        original.save_snapshot(&path).unwrap();
+
+        let mut resumed = build();
+        resumed.load_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.time(), original.time());
+
+        for _ in 0..400 {
+            original.step(0.1);
+            resumed.step(0.1);
+        }
+        // the person who arrived before the snapshot wasn't spawned a second time
+        assert_eq!(resumed.people().people().len(), 3);
+        assert_eq!(resumed.building().state(), original.building().state());
+        assert_eq!(
+            format!("{:?}", resumed.people().people()),
+            format!("{:?}", original.people().people())
+        );
+    }
+
+    #[test]
+    fn snapshot_carries_on_the_scenario_being_played() {
+        let scenario = Scenario {
+            buttons: LitButtons::default(),
+            arrivals: vec![
+                ScriptedArrival {
+                    time: 2.,
+                    origin: 0,
+                    destination: 6,
+                },
+                ScriptedArrival {
+                    time: 25.,
+                    origin: 5,
+                    destination: 1,
+                },
+            ],
+            commands: vec![ScriptedCommand {
+                time: 20.,
+                command: ElevatorCommand::SetCarService {
+                    car_id: CarId(1),
+                    in_service: false,
+                },
+            }],
+        };
+        let build = || {
+            let mut sim = Simulation::new(
+                PeopleSim::scripted(8, Vec::new(), PeopleConfig::default()),
+                ElevatorSim::new(8, 2),
+                Box::new(BasicController),
+            );
+            sim.play_scenario(&scenario).unwrap();
+            sim
+        };
+
+        let mut original = build();
+        for _ in 0..150 {
+            original.step(0.1);
+        }
+        let path = std::env::temp_dir().join(format!("scenario-{}.json", std::process::id()));
+        original.save_snapshot(&path).unwrap();
+
+        let mut resumed = build();
+        resumed.load_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for _ in 0..400 {
+            original.step(0.1);
+            resumed.step(0.1);
+        }
+        // the first person wasn't added again, and the rest of the scenario still happened
+        assert_eq!(resumed.people().people().len(), 2);
+        assert_eq!(
+            resumed.building().state().cars[1].service,
+            ServiceState::OutOfService
+        );
+        assert_eq!(resumed.building().state(), original.building().state());
+    }
+
+    #[test]
+    fn occupancy_stays_consistent() {
+        let arrivals = vec![ScriptedArrival {
+            time: 1.,
+            origin: 2,
+            destination: 0,
+        }];
+        let config = ElevatorConfig {
+            track_occupants: true,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(3, arrivals, PeopleConfig::default()),
+            ElevatorSim::with_config(3, 1, config),
+            Box::new(BasicController),
+        );
+        sim.set_check_occupancy(true);
+
+        for _ in 0..100 {
+            sim.step(0.1);
+        }
+
+        assert!(matches!(sim.people().people()[0].state, PersonState::Done));
+        assert!(sim.occupancy_mismatches().is_empty());
+    }
+
+    #[test]
+    fn operator_drives_the_only_car_for_everyone() {
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(8, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::new(8, 1),
+            Box::new(BasicController),
+        );
+        let operator = PersonOptions {
+            operates: Some(CarId(0)),
+            ..PersonOptions::default()
+        };
+        sim.people_mut().add_person(2, 2, operator);
+        sim.people_mut().add_person(5, 1, PersonOptions::default());
+        sim.people_mut().add_person(3, 7, PersonOptions::default());
+
+        for _ in 0..400 {
+            sim.step(0.1);
+        }
+
+        // the controller never tried to take the car back
+        assert!(
+            !sim.take_events()
+                .iter()
+                .any(|e| matches!(e, ElevatorEvent::CommandRejected { .. }))
+        );
+        let car = &sim.building().state().cars[0];
+        assert_eq!(car.service, ServiceState::Independent);
+        let people = sim.people().people();
+        assert_eq!(people[0].state, PersonState::Riding);
+        assert!(people[1..].iter().all(|p| p.state == PersonState::Done));
+    }
+
+    #[test]
+    fn added_people_ride_and_removed_riders_leave_their_car() {
+        let config = ElevatorConfig {
+            track_occupants: true,
+            ..ElevatorConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(6, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::with_config(6, 1, config),
+            Box::new(BasicController),
+        );
+
+        let stays = sim.people_mut().add_person(0, 5, PersonOptions::default());
+        let leaves = sim.people_mut().add_person(
+            0,
+            5,
+            PersonOptions {
+                extra: Some(Box::new("leaving")),
+                ..PersonOptions::default()
+            },
+        );
+        assert_ne!(stays, leaves);
+
+        while sim.people().people()[1].state != PersonState::Riding {
+            sim.step(0.1);
+        }
+        let removed = sim.people_mut().remove_person(leaves).unwrap();
+        assert_eq!(removed.extra::<&str>(), Some(&"leaving"));
+        assert!(sim.people_mut().remove_person(leaves).is_none());
+
+        for _ in 0..100 {
+            sim.step(0.1);
+        }
+        assert_eq!(sim.people().people().len(), 1);
+        assert_eq!(sim.people().people()[0].state, PersonState::Done);
+        // the removed rider got out with everyone else at floor 5
+        assert_eq!(sim.building().state().cars[0].passenger_count, 0);
+        assert!(sim.building().state().cars[0].occupants.is_empty());
+    }
+
+    #[test]
+    fn bicycles_wait_for_the_freight_car() {
+        let people = PeopleConfig {
+            rules: vec![BoardingRule {
+                attribute: Attribute::Bicycle,
+                requires: CarCapabilities { freight: true },
+                banned_floors: Vec::new(),
+            }],
+            ..PeopleConfig::default()
+        };
+        let building = ElevatorConfig {
+            capabilities: vec![
+                CarCapabilities::default(),
+                CarCapabilities { freight: true },
+            ],
+            ..ElevatorConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(6, Vec::new(), people),
+            ElevatorSim::with_config(6, 2, building),
+            Box::new(BasicController),
+        );
+
+        let cyclist = PersonOptions {
+            attributes: vec![Attribute::Bicycle],
+            ..PersonOptions::default()
+        };
+        sim.people_mut().add_person(0, 4, cyclist);
+        sim.people_mut().add_person(0, 4, PersonOptions::default());
+
+        let mut cars = Vec::new();
+        for _ in 0..600 {
+            sim.step(0.1);
+            let person = &sim.people().people()[0];
+            if let Some(car_id) = person.in_car {
+                cars.push(car_id);
+            }
+        }
+        assert!(
+            sim.people()
+                .people()
+                .iter()
+                .all(|p| p.state == PersonState::Done)
+        );
+        assert!(!cars.is_empty() && cars.iter().all(|&car_id| car_id == CarId(1)));
+    }
+
+    #[test]
+    fn zoned_cars_only_carry_people_to_floors_they_serve() {
+        // car 0 serves the low floors, and car 1 is an express to the top
+        let config = ElevatorConfig {
+            served_floors: vec![(0..=5).collect(), vec![0, 7, 8, 9]],
+            ..ElevatorConfig::default()
+        };
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::with_config(10, 2, config),
+            Box::new(BasicController),
+        );
+        let state = sim.building().state();
+        assert_eq!(state.cars_serving(3).count(), 1);
+        assert_eq!(state.cars_serving(0).count(), 2);
+
+        // the low rise car answers the call from the lobby first, but doesn't go to the top
+        sim.people_mut().add_person(0, 8, PersonOptions::default());
+        for _ in 0..1200 {
+            sim.step(0.1);
+            let person = &sim.people().people()[0];
+            assert!(person.in_car.is_none_or(|c| c == CarId(1)));
+        }
+        assert_eq!(sim.people().people()[0].state, PersonState::Done);
+
+        // no car goes from floor 3 to floor 8, so nobody waits for one
+        sim.people_mut().add_person(3, 8, PersonOptions::default());
+        sim.step(0.1);
+        assert_eq!(sim.people().people()[1].state, PersonState::GaveUp);
+    }
+
+    /// BasicController, keeping every rider destination it was shown
+    struct Recording(Arc<Mutex<Vec<Floor>>>);
+
+    impl ElevatorController for Recording {
+        fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand> {
+            for car in view.cars() {
+                self.0.lock().unwrap().extend(car.rider_destinations());
+            }
+            BasicController.tick(view, stats)
+        }
+    }
+
+    #[test]
+    fn only_rich_observation_shows_where_riders_are_going() {
+        for riders in [Observation::Classic, Observation::Rich] {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let mut sim = Simulation::new(
+                PeopleSim::scripted(6, Vec::new(), PeopleConfig::default()),
+                ElevatorSim::new(6, 1),
+                Box::new(Recording(seen.clone())),
+            );
+            let filter = ObservationFilter {
+                riders,
+                ..ObservationFilter::default()
+            };
+            sim.set_observation(filter).unwrap();
+            sim.people_mut().add_person(0, 4, PersonOptions::default());
+            for _ in 0..100 {
+                sim.step(0.1);
+            }
+
+            assert_eq!(sim.people().people()[0].state, PersonState::Done);
+            // the building itself never knows
+            assert!(sim.building().state().cars[0].rider_destinations.is_empty());
+            let seen = seen.lock().unwrap();
+            match riders {
+                Observation::Classic => assert!(seen.is_empty()),
+                Observation::Rich => assert!(!seen.is_empty() && seen.iter().all(|&f| f == 4)),
+            }
+        }
+    }
+
+    #[test]
+    fn fire_recall_empties_the_cars_at_the_recall_floor() {
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::new(10, 2),
+            Box::new(BasicController),
+        );
+        sim.people_mut().add_person(3, 9, PersonOptions::default());
+        while sim.people().people()[0].state != PersonState::Riding {
+            sim.step(0.1);
+        }
+        sim.people_mut().add_person(6, 1, PersonOptions::default());
+        sim.step(0.1);
+
+        sim.building_mut()
+            .apply_command(ElevatorCommand::FireRecall { floor: 0 })
+            .unwrap();
+        for _ in 0..200 {
+            sim.step(0.1);
+        }
+
+        // every car waits open at the recall floor, and the rider got out there
+        let state = sim.building().state();
+        assert_eq!(state.emergency, EmergencyMode::FireRecall { floor: 0 });
+        assert!(
+            state
+                .cars
+                .iter()
+                .all(|car| car.current_floor == 0. && car.door_open())
+        );
+        assert!(state.floors.iter().all(|f| !f.out_up && !f.out_down));
+        let people = sim.people().people();
+        assert_eq!(people[0].state, PersonState::Waiting);
+        assert_eq!(people[0].current_floor, 0);
+        assert_eq!(people[1].state, PersonState::Waiting);
+        assert_eq!(
+            sim.building_mut()
+                .apply_command(ElevatorCommand::MoveCarTo {
+                    car_id: CarId(0),
+                    floor: 4,
+                }),
+            Err(CommandError::Emergency)
+        );
+
+        // once it's over, everyone carries on to where they were going
+        sim.building_mut()
+            .apply_command(ElevatorCommand::ClearEmergency)
+            .unwrap();
+        for _ in 0..600 {
+            sim.step(0.1);
+        }
+        assert!(
+            sim.people()
+                .people()
+                .iter()
+                .all(|p| p.state == PersonState::Done)
+        );
+    }
+
+    /// BasicController, counting how many times it was asked what to do
+    struct Counting(Arc<Mutex<u32>>);
+
+    impl ElevatorController for Counting {
+        fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand> {
+            *self.0.lock().unwrap() += 1;
+            BasicController.tick(view, stats)
+        }
+    }
+
+    #[test]
+    fn controllers_sit_out_emergencies() {
+        let asked = Arc::new(Mutex::new(0));
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::new(10, 2),
+            Box::new(Counting(asked.clone())),
+        );
+        sim.step(0.1);
+        assert_eq!(*asked.lock().unwrap(), 1);
+
+        sim.building_mut()
+            .apply_command(ElevatorCommand::FireRecall { floor: 0 })
+            .unwrap();
+        sim.people_mut().add_person(4, 8, PersonOptions::default());
+        for _ in 0..100 {
+            sim.step(0.1);
+        }
+        assert_eq!(*asked.lock().unwrap(), 1);
+
+        sim.building_mut()
+            .apply_command(ElevatorCommand::ClearEmergency)
+            .unwrap();
+        sim.step(0.1);
+        assert_eq!(*asked.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn recall_gives_cars_back_their_service_once_cleared() {
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::new(10, 3),
+            Box::new(BasicController),
+        );
+        let building = sim.building_mut();
+        building
+            .apply_command(ElevatorCommand::SetCarService {
+                car_id: CarId(1),
+                in_service: false,
+            })
+            .unwrap();
+        building
+            .apply_command(ElevatorCommand::SetIndependentService {
+                car_id: CarId(2),
+                on: true,
+            })
+            .unwrap();
+        building
+            .apply_command(ElevatorCommand::FireRecall { floor: 0 })
+            .unwrap();
+        for _ in 0..100 {
+            sim.step(0.1);
+        }
+
+        sim.building_mut()
+            .apply_command(ElevatorCommand::ClearEmergency)
+            .unwrap();
+        sim.people_mut().add_person(4, 8, PersonOptions::default());
+        for _ in 0..400 {
+            sim.step(0.1);
+        }
+
+        // only the car still in normal service was sent to pick them up
+        let services: Vec<_> = sim
+            .building()
+            .state()
+            .cars
+            .iter()
+            .map(|car| car.service)
+            .collect();
+        assert_eq!(
+            services,
+            [
+                ServiceState::Normal,
+                ServiceState::OutOfService,
+                ServiceState::Independent
+            ]
+        );
+        assert_eq!(sim.people().people()[0].state, PersonState::Done);
+        assert!(
+            sim.building().state().cars[1..]
+                .iter()
+                .all(|car| car.current_floor == 0.)
+        );
+    }
+
+    #[test]
+    fn car_out_of_service_finishes_its_trip_and_parks() {
+        let mut sim = Simulation::new(
+            PeopleSim::scripted(10, Vec::new(), PeopleConfig::default()),
+            ElevatorSim::new(10, 1),
+            Box::new(BasicController),
+        );
+        sim.people_mut().add_person(3, 9, PersonOptions::default());
+        while sim.people().people()[0].state != PersonState::Riding {
+            sim.step(0.1);
+        }
+        sim.building_mut()
+            .apply_command(ElevatorCommand::SetCarService {
+                car_id: CarId(0),
+                in_service: false,
+            })
+            .unwrap();
+        sim.people_mut().add_person(5, 1, PersonOptions::default());
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+
+        // the rider got out, but the car went past the new caller and shut itself away
+        let people = sim.people().people();
+        assert_eq!(people[0].state, PersonState::Done);
+        assert_eq!(people[1].state, PersonState::Waiting);
+        let car = &sim.building().state().cars[0];
+        assert_eq!(car.service, ServiceState::OutOfService);
+        assert_eq!(car.current_floor, 9.);
+        assert_eq!(car.door, DoorState::Closed);
+        assert_eq!(
+            sim.building_mut()
+                .apply_command(ElevatorCommand::MoveCarTo {
+                    car_id: CarId(0),
+                    floor: 5,
+                }),
+            Err(CommandError::OutOfService { car_id: CarId(0) })
+        );
+
+        // back in service, the controller fetches the caller
+        sim.building_mut()
+            .apply_command(ElevatorCommand::SetCarService {
+                car_id: CarId(0),
+                in_service: true,
+            })
+            .unwrap();
+        for _ in 0..300 {
+            sim.step(0.1);
+        }
+        assert_eq!(sim.people().people()[1].state, PersonState::Done);
+    }
+
+    #[test]
+    fn check_occupancy_finds_both_kinds_of_mismatch() {
+        let building = ElevatorSim::new(3, 1);
+        let mut state = building.state().clone();
+        state.cars[0].occupants.push(PersonId(4));
+        let people = vec![Person {
+            id: PersonId(9),
+            current_floor: 0,
+            target_floor: 2,
+            onward: Vec::new(),
+            state: PersonState::Riding,
+            in_car: Some(CarId(0)),
+            approaching: None,
+            reaction_time: 0.,
+            reaction_timer: None,
+            waited: 0.,
+            overflowed: false,
+            operates: None,
+            chaperone: None,
+            group: None,
+            attributes: Vec::new(),
+            profile: BehaviorProfile::default(),
+            weight: 75.,
+            stepped_out_of: None,
+            permitted_floors: Vec::new(),
+            extra: None,
+        }];
+
+        let mismatches = check_occupancy(&people, &state);
+        assert_eq!(
+            mismatches,
+            vec![
+                OccupancyMismatch::MissingFromCar {
+                    person_id: PersonId(9),
+                    car_id: CarId(0)
+                },
+                OccupancyMismatch::UnexpectedOccupant {
+                    person_id: PersonId(4),
+                    car_id: CarId(0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn advancing_by_uneven_frames_matches_fixed_steps() {
+        let config = crate::config::SimConfig {
+            floors: 8,
+            cars: 2,
+            spawn_rate: 1.,
+            seed: Some(11),
+            ..crate::config::SimConfig::default()
+        };
+        let mut fixed = config.build().unwrap();
+        for _ in 0..600 {
+            fixed.step(config.timestep);
+        }
+
+        // a game whose frames take a different time each, including some long hitches
+        let mut hosted = config.build().unwrap();
+        let frames = [0.016, 0.033, 0.007, 0.25, 0.016, 0.5];
+        let mut now = 0.;
+        let mut steps = 0;
+        for frame in frames.iter().cycle() {
+            now += frame;
+            if now > 60. {
+                break;
+            }
+            steps += hosted.advance_to(now);
+        }
+        steps += hosted.advance_to(60.);
+        assert_eq!(steps, 600);
+        assert_eq!(hosted.building().state(), fixed.building().state());
+        assert_eq!(
+            format!("{:?}", hosted.people().people()),
+            format!("{:?}", fixed.people().people())
+        );
+
+        // going back in time does nothing
+        assert_eq!(hosted.advance_to(30.), 0);
+    }
+
+    #[test]
+    fn interpolated_cars_move_smoothly_between_ticks() {
+        let people = PeopleSim::scripted(4, Vec::new(), PeopleConfig::default());
+        let building = ElevatorSim::new(4, 1);
+        let mut sim = Simulation::new(people, building, Box::new(BasicController));
+        sim.building_mut()
+            .apply_command(ElevatorCommand::MoveCarTo {
+                car_id: CarId(0),
+                floor: 3,
+            })
+            .unwrap();
+        for _ in 0..20 {
+            sim.step(0.1);
+        }
+
+        let current = sim.building().state().cars[0].current_floor;
+        let before = sim.interpolated_state(0.).cars[0].current_floor;
+        assert!(before < current);
+        assert_eq!(sim.interpolated_state(1.).cars[0].current_floor, current);
+        let halfway = sim.interpolated_state(0.5).cars[0].current_floor;
+        assert!((halfway - (before + current) / 2.).abs() < 1e-6);
+
+        sim.set_step_length(0.1);
+        assert!((sim.alpha_at(sim.time() + 0.025) - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn floors_past_the_top_are_refused_before_running() {
+        let people = PeopleSim::new(15, 1.);
+        let result =
+            Simulation::try_new(people, ElevatorSim::new(10, 1), Box::new(BasicController));
+        assert_eq!(
+            result.err(),
+            Some(FloorMismatch::People {
+                num_floors: 15,
+                building: 10,
+            })
+        );
+
+        let arrivals = vec![ScriptedArrival {
+            time: 5.,
+            origin: 0,
+            destination: 12,
+        }];
+        let people = PeopleSim::scripted(10, arrivals, PeopleConfig::default());
+        let result =
+            Simulation::try_new(people, ElevatorSim::new(10, 1), Box::new(BasicController));
+        assert_eq!(
+            result.err(),
+            Some(FloorMismatch::Arrival {
+                floor: 12,
+                building: 10,
+            })
+        );
+
+        let config = crate::config::SimConfig {
+            floors: 10,
+            served_floors: vec![vec![0, 4, 11]],
+            ..Default::default()
+        };
+        let error = config.build().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid floors: served_floors mentions floor 11, but the building has 10 floors"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use crate::types::FloorMismatch;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// The reasons a snapshot can't be saved or loaded
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Floors(FloorMismatch),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "couldn't access snapshot: {e}"),
+            SnapshotError::Json(e) => write!(f, "invalid snapshot: {e}"),
+            SnapshotError::Floors(e) => write!(f, "snapshot doesn't fit the building: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Write value to a file at path as JSON, replacing anything already there
+pub fn save<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<(), SnapshotError> {
+    let file = File::create(path).map_err(SnapshotError::Io)?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, value).map_err(SnapshotError::Json)?;
+    writer.flush().map_err(SnapshotError::Io)
+}
+
+/// Write value like save, but spread over lines and indented, for files people edit by hand
+pub fn save_pretty<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<(), SnapshotError> {
+    let file = File::create(path).map_err(SnapshotError::Io)?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, value).map_err(SnapshotError::Json)?;
+    writer.flush().map_err(SnapshotError::Io)
+}
+
+/// Read a value back from a JSON file written by save
+pub fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, SnapshotError> {
+    let file = File::open(path).map_err(SnapshotError::Io)?;
+    serde_json::from_reader(BufReader::new(file)).map_err(SnapshotError::Json)
+}
+
+/// Serde helpers for a number where infinity means there's no limit. JSON has no infinity,
+/// so it's saved as null instead
+pub(crate) mod unlimited {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f32(*value)
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        Ok(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::INFINITY))
+    }
+}
@@ -0,0 +1,316 @@
+use crate::sim::Simulation;
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// How many steps are run between looks at the clock and the cars
+const BATCH_STEPS: u64 = 100;
+
+/// How much more than growth_limit allows the people vector and memory have to grow by to
+/// fail, so a handful of extra people or a few pages don't count
+const PEOPLE_SLACK: f64 = 100.;
+const MEMORY_SLACK: f64 = 16. * 1024. * 1024.;
+
+/// Reads one of the things that shouldn't keep growing from a sample
+type Reading = fn(&SoakSample) -> Option<f64>;
+
+/// Settings for a soak test, which runs a simulation as fast as it goes for a long time,
+/// watching for anything that keeps growing or drifting
+/// duration - how long to run for, in wall clock time
+/// interval - how often to take a sample, in wall clock time
+/// growth_limit - how much higher memory or the number of people can peak in the second
+/// half of the samples than in the first, as a share, before they count as growing without
+/// bound. Steps per second can fall by as much
+/// time_drift_limit - how many seconds the simulation's clock can drift from the time its
+/// steps add up to
+/// level_drift_limit - how far from a floor a car can stand with its door open, in floors
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoakConfig {
+    pub duration: Duration,
+    pub interval: Duration,
+    pub growth_limit: f64,
+    pub time_drift_limit: f64,
+    pub level_drift_limit: f32,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(3600),
+            interval: Duration::from_secs(60),
+            growth_limit: 0.25,
+            time_drift_limit: 1.,
+            level_drift_limit: 0.01,
+        }
+    }
+}
+
+/// How the simulation was doing at one sample
+/// elapsed - wall clock seconds since the soak started
+/// ticks, time - how many steps have been taken, and how many seconds they've simulated
+/// memory - the process's resident memory in bytes, where the platform says
+/// people - how long the simulation's people vector is
+/// time_drift - how far the simulation's clock is from the time its steps add up to
+/// level_drift - the furthest from a floor any car stood with its door open since the last
+/// sample
+/// steps_per_second - how fast the simulation ran since the last sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoakSample {
+    pub elapsed: f64,
+    pub ticks: u64,
+    pub time: f64,
+    pub memory: Option<u64>,
+    pub people: usize,
+    pub time_drift: f64,
+    pub level_drift: f32,
+    pub steps_per_second: f64,
+}
+
+/// Something a soak test found wrong
+#[derive(Clone, Debug, PartialEq)]
+pub enum SoakFailure {
+    /// memory or the people vector peaked higher in the second half of the run than the
+    /// first allows
+    Growth {
+        what: &'static str,
+        early: f64,
+        late: f64,
+    },
+    /// the simulation ran slower in the second half of the run, in steps per second
+    Slowdown { early: f64, late: f64 },
+    /// the clock or a car's level drifted further than allowed, by the time of the sample
+    /// at elapsed wall clock seconds
+    Drift {
+        what: &'static str,
+        drift: f64,
+        elapsed: f64,
+    },
+}
+
+/// Everything a soak test sampled, and anything it found wrong
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SoakReport {
+    pub samples: Vec<SoakSample>,
+    pub failures: Vec<SoakFailure>,
+}
+
+impl SoakReport {
+    /// Whether nothing was found wrong
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Step sim by dt seconds at a time for the config's duration, handing each sample to log
+/// as it's taken, then check the samples for anything growing or drifting
+pub fn soak(
+    sim: &mut Simulation,
+    dt: f32,
+    config: &SoakConfig,
+    mut log: impl FnMut(&SoakSample),
+) -> SoakReport {
+    let start = Instant::now();
+    let start_time = sim.time() as f64;
+    let mut steps: u64 = 0;
+    let mut samples = Vec::new();
+    let mut last_sample = (start, 0);
+    let mut level_drift: f32 = 0.;
+
+    loop {
+        for _ in 0..BATCH_STEPS {
+            sim.step(dt);
+            sim.take_events();
+        }
+        steps += BATCH_STEPS;
+        level_drift = level_drift.max(worst_level(sim));
+
+        let now = Instant::now();
+        let done = now.duration_since(start) >= config.duration;
+        if !done && now.duration_since(last_sample.0) < config.interval {
+            continue;
+        }
+        let wall = now.duration_since(last_sample.0).as_secs_f64();
+        let expected = start_time + steps as f64 * dt as f64;
+        let sample = SoakSample {
+            elapsed: now.duration_since(start).as_secs_f64(),
+            ticks: sim.ticks(),
+            time: sim.time() as f64,
+            memory: resident_memory(),
+            people: sim.people().people().len(),
+            time_drift: (sim.time() as f64 - expected).abs(),
+            level_drift,
+            steps_per_second: (steps - last_sample.1) as f64 / wall.max(f64::EPSILON),
+        };
+        log(&sample);
+        samples.push(sample);
+        last_sample = (now, steps);
+        level_drift = 0.;
+        if done {
+            break;
+        }
+    }
+
+    let failures = check(&samples, config);
+    SoakReport { samples, failures }
+}
+
+/// Find anything in samples that grew, slowed or drifted further than config allows
+pub fn check(samples: &[SoakSample], config: &SoakConfig) -> Vec<SoakFailure> {
+    let mut failures = Vec::new();
+    for sample in samples {
+        if sample.time_drift > config.time_drift_limit {
+            failures.push(SoakFailure::Drift {
+                what: "simulated time",
+                drift: sample.time_drift,
+                elapsed: sample.elapsed,
+            });
+            break;
+        }
+    }
+    for sample in samples {
+        if sample.level_drift > config.level_drift_limit {
+            failures.push(SoakFailure::Drift {
+                what: "car level",
+                drift: sample.level_drift as f64,
+                elapsed: sample.elapsed,
+            });
+            break;
+        }
+    }
+
+    // growth is judged by comparing the two halves of the run, so there have to be a few
+    // samples in each
+    if samples.len() < 4 {
+        return failures;
+    }
+    let (early, late) = samples.split_at(samples.len() / 2);
+    let peak = |half: &[SoakSample], value: Reading| half.iter().filter_map(value).reduce(f64::max);
+    let growing: [(&str, Reading, f64); 2] = [
+        ("people", |s| Some(s.people as f64), PEOPLE_SLACK),
+        ("memory", |s| s.memory.map(|m| m as f64), MEMORY_SLACK),
+    ];
+    for (what, value, slack) in growing {
+        if let (Some(early), Some(late)) = (peak(early, value), peak(late, value))
+            && late > early * (1. + config.growth_limit) + slack
+        {
+            failures.push(SoakFailure::Growth { what, early, late });
+        }
+    }
+    let mean_rate = |half: &[SoakSample]| {
+        half.iter().map(|s| s.steps_per_second).sum::<f64>() / half.len() as f64
+    };
+    let (early, late) = (mean_rate(early), mean_rate(late));
+    if late * (1. + config.growth_limit) < early {
+        failures.push(SoakFailure::Slowdown { early, late });
+    }
+    failures
+}
+
+/// The furthest from a floor any car is standing with its door open
+fn worst_level(sim: &Simulation) -> f32 {
+    sim.building()
+        .state()
+        .cars
+        .iter()
+        .filter(|car| car.door_opening_or_open() && car.velocity == 0.)
+        .map(|car| (car.current_floor - car.current_floor.round()).abs())
+        .fold(0., f32::max)
+}
+
+/// How much memory the process has resident, in bytes, read from /proc on Linux. None on
+/// other platforms
+fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+impl fmt::Display for SoakSample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let memory = self.memory.map_or("-".to_string(), |m| {
+            format!("{:.1} MiB", m as f64 / (1024. * 1024.))
+        });
+        write!(
+            f,
+            "{:>8.0} s: {} steps, {:.0} s simulated, {:.0} steps/s, memory {memory}, {} people, \
+time drift {:.4} s, level drift {:.4} floors",
+            self.elapsed,
+            self.ticks,
+            self.time,
+            self.steps_per_second,
+            self.people,
+            self.time_drift,
+            self.level_drift
+        )
+    }
+}
+
+impl fmt::Display for SoakFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoakFailure::Growth { what, early, late } => write!(
+                f,
+                "{what} kept growing: peaked at {early:.0} in the first half, {late:.0} in the \
+second"
+            ),
+            SoakFailure::Slowdown { early, late } => write!(
+                f,
+                "slowed down from {early:.0} steps/s in the first half to {late:.0} in the second"
+            ),
+            SoakFailure::Drift {
+                what,
+                drift,
+                elapsed,
+            } => write!(f, "{what} drifted by {drift:.4} after {elapsed:.0} s"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+
+    #[test]
+    fn soak_samples_a_run_and_catches_growth() {
+        let config = SimConfig {
+            seed: Some(3),
+            ..SimConfig::default()
+        };
+        let mut sim = config.build().unwrap();
+        let soak_config = SoakConfig {
+            duration: Duration::from_millis(200),
+            interval: Duration::from_millis(20),
+            ..SoakConfig::default()
+        };
+        let mut logged = 0;
+        let report = soak(&mut sim, config.timestep, &soak_config, |_| logged += 1);
+        assert_eq!(logged, report.samples.len());
+        assert!(report.samples.len() >= 2);
+        let last = report.samples.last().unwrap();
+        assert_eq!(last.ticks, sim.ticks());
+        // the f32 clock is compared with the steps added up exactly
+        let exact = last.ticks as f64 * config.timestep as f64;
+        assert!((last.time_drift - (last.time - exact).abs()).abs() < 1e-9);
+        assert!(last.level_drift <= soak_config.level_drift_limit);
+
+        // a people vector that keeps growing fails, and one that levels off doesn't
+        let sample = |people| SoakSample {
+            people,
+            memory: None,
+            ..*last
+        };
+        let growing: Vec<SoakSample> = (0..8).map(|i| sample(1000 * i)).collect();
+        assert_eq!(
+            check(&growing, &soak_config),
+            vec![SoakFailure::Growth {
+                what: "people",
+                early: 3000.,
+                late: 7000.,
+            }]
+        );
+        let level: Vec<SoakSample> = (0..8).map(|i| sample(1000 + i % 2)).collect();
+        assert!(check(&level, &soak_config).is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+use crate::batch;
+use crate::config::{ConfigError, SimConfig};
+use crate::metrics::RunSummary;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// The most runs one sweep makes, so a typo in a range doesn't start a run that never ends
+pub const MAX_SWEEP_RUNS: usize = 10_000;
+
+/// A setting a sweep can vary, by the name it has in a config file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Floors,
+    Cars,
+    SpawnRate,
+    /// the seconds between people arriving, the inverse of spawn_rate
+    SpawnInterval,
+    MaxSpeed,
+    DoorDwellTime,
+}
+
+impl Setting {
+    /// Every setting a sweep can vary
+    pub const ALL: [Setting; 6] = [
+        Setting::Floors,
+        Setting::Cars,
+        Setting::SpawnRate,
+        Setting::SpawnInterval,
+        Setting::MaxSpeed,
+        Setting::DoorDwellTime,
+    ];
+
+    /// What the setting is called on the command line and in the CSV header
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::Floors => "floors",
+            Setting::Cars => "cars",
+            Setting::SpawnRate => "spawn_rate",
+            Setting::SpawnInterval => "spawn_interval",
+            Setting::MaxSpeed => "max_speed",
+            Setting::DoorDwellTime => "door_dwell_time",
+        }
+    }
+
+    /// Whether the setting only takes whole numbers
+    fn whole(self) -> bool {
+        matches!(self, Setting::Floors | Setting::Cars)
+    }
+
+    /// Change config's setting to value
+    fn apply(self, config: &mut SimConfig, value: f32) {
+        match self {
+            Setting::Floors => config.floors = value as _,
+            Setting::Cars => config.cars = value as _,
+            Setting::SpawnRate => config.spawn_rate = value,
+            Setting::SpawnInterval => config.spawn_rate = 1. / value,
+            Setting::MaxSpeed => config.max_speed = value,
+            Setting::DoorDwellTime => config.door_dwell_time = value,
+        }
+    }
+}
+
+/// The reasons a sweep can't be run
+#[derive(Debug)]
+pub enum SweepError {
+    /// a range that couldn't be read, and why
+    Range(String),
+    /// more runs than MAX_SWEEP_RUNS
+    TooManyRuns(usize),
+    Config(ConfigError),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Range(reason) => write!(f, "invalid range: {reason}"),
+            SweepError::TooManyRuns(runs) => write!(
+                f,
+                "the sweep would make {runs} runs, more than the {MAX_SWEEP_RUNS} allowed"
+            ),
+            SweepError::Config(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
+
+/// A setting and the values a sweep gives it, read from name=from..to, which counts up in
+/// ones, name=from..to:step, or name=a,b,c. Both ends of a range are included
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axis {
+    pub setting: Setting,
+    pub values: Vec<f32>,
+}
+
+impl FromStr for Axis {
+    type Err = SweepError;
+
+    fn from_str(text: &str) -> Result<Self, SweepError> {
+        let error = |reason: String| SweepError::Range(format!("{text}: {reason}"));
+        let number = |word: &str| {
+            word.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| error(format!("{word} isn't a number")))
+        };
+        let (name, range) = text
+            .split_once('=')
+            .ok_or_else(|| error("expected setting=range".to_string()))?;
+        let setting = Setting::ALL
+            .into_iter()
+            .find(|s| s.name() == name.trim())
+            .ok_or_else(|| {
+                let names: Vec<&str> = Setting::ALL.iter().map(|s| s.name()).collect();
+                error(format!("the setting must be one of {}", names.join(", ")))
+            })?;
+
+        let values: Vec<f32> = match range.split_once("..") {
+            Some((from, rest)) => {
+                let (to, step) = match rest.split_once(':') {
+                    Some((to, step)) => (number(to)?, number(step)?),
+                    None => (number(rest)?, 1.),
+                };
+                let from = number(from)?;
+                if step <= 0. || to < from {
+                    return Err(error(
+                        "a range must count up from its start to its end".to_string(),
+                    ));
+                }
+                // counting in whole steps from the start keeps rounding from creeping in
+                let count = ((to - from) / step + 1e-4).floor() as usize + 1;
+                if count > MAX_SWEEP_RUNS {
+                    return Err(SweepError::TooManyRuns(count));
+                }
+                (0..count).map(|i| from + i as f32 * step).collect()
+            }
+            None => range.split(',').map(number).collect::<Result<_, _>>()?,
+        };
+        let bad = values.iter().find(|&&v| {
+            v <= 0. && setting != Setting::DoorDwellTime || setting.whole() && v.fract() != 0.
+        });
+        if let Some(v) = bad {
+            return Err(error(format!("{} can't be {v}", setting.name())));
+        }
+        Ok(Axis { setting, values })
+    }
+}
+
+/// Every run of a sweep, with the values its settings had in the order of the axes, and how
+/// it went
+#[derive(Clone, Debug, Default)]
+pub struct Sweep {
+    pub settings: Vec<Setting>,
+    pub runs: Vec<(Vec<f32>, RunSummary)>,
+}
+
+impl Sweep {
+    /// Write the sweep as CSV: a column for each setting, then the average wait in seconds,
+    /// left empty if nobody boarded, the people delivered per minute, how many were
+    /// delivered, and how many gave up
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        let header: Vec<&str> = self
+            .settings
+            .iter()
+            .map(|s| s.name())
+            .chain(["avg_wait", "throughput", "delivered", "gave_up"])
+            .collect();
+        writeln!(out, "{}", header.join(","))?;
+        for (values, summary) in &self.runs {
+            let row: Vec<String> = values
+                .iter()
+                .map(|v| v.to_string())
+                .chain([
+                    summary
+                        .average_wait()
+                        .map_or(String::new(), |w| format!("{w:.2}")),
+                    format!("{:.2}", summary.throughput()),
+                    summary.delivered().to_string(),
+                    summary.gave_up().to_string(),
+                ])
+                .collect();
+            writeln!(out, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Run base with every combination of the axes' values, as a batch, so with the parallel
+/// feature the runs share out over several threads. The runs go through the first axis's
+/// values slowest and the last's fastest, and every run has base's seed
+pub fn sweep(base: &SimConfig, axes: &[Axis]) -> Result<Sweep, SweepError> {
+    let runs = axes
+        .iter()
+        .try_fold(1usize, |runs, axis| runs.checked_mul(axis.values.len()))
+        .unwrap_or(usize::MAX);
+    if runs > MAX_SWEEP_RUNS {
+        return Err(SweepError::TooManyRuns(runs));
+    }
+
+    let mut grid: Vec<Vec<f32>> = vec![Vec::new()];
+    for axis in axes {
+        grid = grid
+            .into_iter()
+            .flat_map(|point| {
+                axis.values.iter().map(move |&v| {
+                    let mut point = point.clone();
+                    point.push(v);
+                    point
+                })
+            })
+            .collect();
+    }
+    let configs: Vec<SimConfig> = grid
+        .iter()
+        .map(|point| {
+            let mut config = base.clone();
+            for (axis, &value) in axes.iter().zip(point) {
+                axis.setting.apply(&mut config, value);
+            }
+            config
+        })
+        .collect();
+
+    let mut sweep = Sweep {
+        settings: axes.iter().map(|axis| axis.setting).collect(),
+        runs: Vec::new(),
+    };
+    for (point, result) in grid.into_iter().zip(batch::run_batch(&configs)) {
+        sweep
+            .runs
+            .push((point, result.map_err(SweepError::Config)?));
+    }
+    Ok(sweep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_read_inclusively() {
+        let cars: Axis = "cars=1..4".parse().unwrap();
+        assert_eq!(cars.setting, Setting::Cars);
+        assert_eq!(cars.values, vec![1., 2., 3., 4.]);
+        let rate: Axis = "spawn_rate=0.1..0.3:0.1".parse().unwrap();
+        assert_eq!(rate.values.len(), 3);
+        let dwell: Axis = "door_dwell_time=0,2.5".parse().unwrap();
+        assert_eq!(dwell.values, vec![0., 2.5]);
+
+        assert!("cars=4..1".parse::<Axis>().is_err());
+        assert!("cars=1.5,2".parse::<Axis>().is_err());
+        assert!("spawn_interval=0..2".parse::<Axis>().is_err());
+        assert!("lifts=1..3".parse::<Axis>().is_err());
+        assert!(matches!(
+            "floors=2..1000000".parse::<Axis>(),
+            Err(SweepError::TooManyRuns(_))
+        ));
+    }
+
+    #[test]
+    fn every_combination_is_run_and_written() {
+        let base = SimConfig {
+            floors: 6,
+            steps: 300,
+            seed: Some(4),
+            ..SimConfig::default()
+        };
+        let axes = [
+            "cars=1..2".parse().unwrap(),
+            "spawn_interval=2,4,8".parse().unwrap(),
+        ];
+        let sweep = sweep(&base, &axes).unwrap();
+        assert_eq!(sweep.runs.len(), 6);
+        assert_eq!(sweep.runs[1].0, vec![1., 4.]);
+        assert_eq!(sweep.runs[5].0, vec![2., 8.]);
+
+        let mut csv = Vec::new();
+        sweep.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("cars,spawn_interval,avg_wait,throughput,delivered,gave_up")
+        );
+        assert!(lines.next().unwrap().starts_with("1,2,"));
+        assert_eq!(lines.count(), 5);
+    }
+}
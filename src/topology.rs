@@ -0,0 +1,397 @@
+use crate::types::{BankId, CarId, Floor, FloorId, FloorMismatch};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// How tall a floor is when the topology doesn't say, in metres
+pub const DEFAULT_FLOOR_HEIGHT: f32 = 3.5;
+
+/// The shape of a building, which is built once and shared by the building, its people, its
+/// controller and whatever draws it, so they all agree on which floors there are and what
+/// they're like
+/// floors - how many floors there are, numbered from 0 at the bottom
+/// basements - how many of them are below ground. The floor above them is the ground floor,
+/// and every floor has a level counted from it, negative for basements
+/// labels - what each floor is called, from the bottom up, such as "G" or "Sky lobby".
+/// Floors past the end of the list, or with an empty label, go by their level, with
+/// basements called B1, B2 and so on down and the ground floor G if there are any, or by
+/// their number if there aren't
+/// heights - how tall each floor is, from the bottom up, in metres, such as a tall lobby and
+/// a low mezzanine above it. Floors past the end of the list, or no taller than 0, are
+/// DEFAULT_FLOOR_HEIGHT tall
+/// banks - groups of cars which serve the same floors, with hall buttons of their own on
+/// each floor, see Bank. Empty when the building doesn't group its cars
+/// shafts - which cars run in which shaft, see Shaft. Empty for every car having a shaft of
+/// its own from the bottom to the top
+/// sky_lobbies - floors where people change from one bank to another, for trips no single
+/// bank makes. Empty for nobody changing
+/// entrances - the floors people come into the building on from outside
+/// hall_capacities - how many people fit in the waiting area of each floor, from the bottom
+/// up. Floors past the end of the list, or with a capacity of 0, have room for everyone
+/// secured_floors - floors only people with a card for them can ride to. A car's button for
+/// one is refused unless a card was shown in the car first, see AuthorizeFloor
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BuildingTopology {
+    pub floors: Floor,
+    pub basements: Floor,
+    pub labels: Vec<String>,
+    pub heights: Vec<f32>,
+    pub banks: Vec<Bank>,
+    pub shafts: Vec<Shaft>,
+    pub sky_lobbies: Vec<Floor>,
+    pub entrances: Vec<Floor>,
+    pub hall_capacities: Vec<u32>,
+    pub secured_floors: Vec<Floor>,
+}
+
+impl Default for BuildingTopology {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// A group of cars which serve the same floors, such as the low rise cars of a tall building.
+/// Each floor the bank stops at has hall buttons for it alone, and people press the ones of
+/// the first bank which makes their whole trip
+/// name - what the bank is called, such as "low rise"
+/// cars - the cars in the bank
+/// floors - the floors its cars stop at, from the bottom up, unless the car's own served
+/// floors say otherwise. Empty for every floor
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Bank {
+    pub name: String,
+    pub cars: Vec<CarId>,
+    pub floors: Vec<Floor>,
+}
+
+impl Bank {
+    /// Whether the bank's cars stop at a floor
+    pub fn serves(&self, floor: Floor) -> bool {
+        self.floors.is_empty() || self.floors.contains(&floor)
+    }
+}
+
+/// A shaft, and the cars running in it
+/// cars - the cars in the shaft, from the bottom up
+/// bottom, top - the lowest and highest floors the shaft reaches
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Shaft {
+    pub cars: Vec<CarId>,
+    pub bottom: Floor,
+    pub top: Floor,
+}
+
+/// implement functions for BuildingTopology
+/// new - a building with a number of floors and nothing else said about them
+/// top, contains - the floors there are
+/// level, at_level - the floors' levels counted from the ground floor
+/// floor_id, named, floor - what a floor is called, and the floor going by a name
+/// floor_height, elevation, floor_at - how tall floors are, and converting between floors and
+/// metres up the shaft
+/// bank, bank_of, bank_id_of, trip_bank, shaft_of, is_entrance, lobby - the building's banks,
+/// shafts and entrances
+/// transfers - the sky lobbies a trip changes banks at
+/// hall_capacity - how many people fit in a floor's waiting area
+/// is_secured - whether riding to a floor takes a card
+/// check_floors - make sure nothing names a floor the building doesn't have
+impl BuildingTopology {
+    /// A building with floors floors, all of the default height, entered from floor 0 if it
+    /// has any
+    pub fn new(floors: Floor) -> Self {
+        Self {
+            floors,
+            basements: 0,
+            labels: Vec::new(),
+            heights: Vec::new(),
+            banks: Vec::new(),
+            shafts: Vec::new(),
+            sky_lobbies: Vec::new(),
+            entrances: if floors > 0 { vec![0] } else { Vec::new() },
+            hall_capacities: Vec::new(),
+            secured_floors: Vec::new(),
+        }
+    }
+
+    /// The top floor, or 0 if there are no floors
+    pub fn top(&self) -> Floor {
+        self.floors.saturating_sub(1)
+    }
+
+    /// Whether the building has a floor
+    pub fn contains(&self, floor: Floor) -> bool {
+        floor < self.floors
+    }
+
+    /// How many floors above the ground floor a floor is, negative for basements
+    pub fn level(&self, floor: Floor) -> i32 {
+        floor as i32 - self.basements as i32
+    }
+
+    /// The floor at a level counted from the ground floor, if the building has one there
+    pub fn at_level(&self, level: i32) -> Option<Floor> {
+        let floor = level.checked_add(self.basements as i32)?;
+        Floor::try_from(floor).ok().filter(|&f| self.contains(f))
+    }
+
+    /// What a floor is called, which is its level unless it's been given a label, such as
+    /// B2 two floors below ground
+    pub fn floor_id(&self, floor: Floor) -> FloorId {
+        FloorId(match self.labels.get(floor as usize) {
+            Some(label) if !label.is_empty() => label.clone(),
+            _ if self.basements == 0 => floor.to_string(),
+            _ => match self.level(floor) {
+                0 => "G".to_string(),
+                level if level < 0 => format!("B{}", -level),
+                level => level.to_string(),
+            },
+        })
+    }
+
+    /// The floor going by a name, which is its label, ignoring case, or its level such as
+    /// "-1". None if no floor does
+    pub fn named(&self, name: &str) -> Option<Floor> {
+        let name = name.trim();
+        (0..self.floors)
+            .find(|&floor| self.floor_id(floor).0.eq_ignore_ascii_case(name))
+            .or_else(|| self.at_level(name.parse().ok()?))
+    }
+
+    /// The floor a FloorId names, see named
+    pub fn floor(&self, id: &FloorId) -> Option<Floor> {
+        self.named(&id.0)
+    }
+
+    /// How far it is from floor up to the one above, in metres
+    pub fn floor_height(&self, floor: Floor) -> f32 {
+        match self.heights.get(floor as usize) {
+            Some(&height) if height > 0. => height,
+            _ => DEFAULT_FLOOR_HEIGHT,
+        }
+    }
+
+    /// How many metres above floor 0 a position is, given as a fractional floor such as 2.5
+    /// for halfway between floors 2 and 3
+    pub fn elevation(&self, position: f32) -> f32 {
+        let floor = position.floor().max(0.);
+        let listed = (floor as usize).min(self.heights.len());
+        let below: f32 = (0..listed).map(|f| self.floor_height(f as Floor)).sum();
+        below
+            + (floor - listed as f32) * DEFAULT_FLOOR_HEIGHT
+            + (position - floor) * self.floor_height(floor as Floor)
+    }
+
+    /// The fractional floor a position elevation metres above floor 0 is at, the other way
+    /// round from elevation
+    pub fn floor_at(&self, elevation: f32) -> f32 {
+        let mut base = 0.;
+        for floor in 0..self.heights.len() {
+            let height = self.floor_height(floor as Floor);
+            if elevation < base + height {
+                return floor as f32 + (elevation - base) / height;
+            }
+            base += height;
+        }
+        self.heights.len() as f32 + (elevation - base) / DEFAULT_FLOOR_HEIGHT
+    }
+
+    /// The bank with an id, if there is one
+    pub fn bank(&self, bank: BankId) -> Option<&Bank> {
+        self.banks.get(bank.0 as usize)
+    }
+
+    /// The bank car belongs to, if it's in one
+    pub fn bank_of(&self, car: CarId) -> Option<&Bank> {
+        self.banks.iter().find(|bank| bank.cars.contains(&car))
+    }
+
+    /// The id of the bank car belongs to, if it's in one
+    pub fn bank_id_of(&self, car: CarId) -> Option<BankId> {
+        let index = self
+            .banks
+            .iter()
+            .position(|bank| bank.cars.contains(&car))?;
+        Some(BankId(index as u32))
+    }
+
+    /// The first bank which stops at both origin and destination, which someone making that
+    /// trip calls, or None if no bank does
+    pub fn trip_bank(&self, origin: Floor, destination: Floor) -> Option<BankId> {
+        let index = self
+            .banks
+            .iter()
+            .position(|bank| bank.serves(origin) && bank.serves(destination))?;
+        Some(BankId(index as u32))
+    }
+
+    /// The sky lobbies someone going from origin to destination changes banks at, in the
+    /// order they reach them, with as few changes as there can be. Empty when one bank makes
+    /// the whole trip, or when no banks do, even by changing at sky lobbies
+    pub fn transfers(&self, origin: Floor, destination: Floor) -> Vec<Floor> {
+        if self.sky_lobbies.is_empty() || self.trip_bank(origin, destination).is_some() {
+            return Vec::new();
+        }
+        // search outwards from the origin, one bank's ride at a time
+        let mut came_from: HashMap<Floor, Floor> = HashMap::new();
+        let mut next = VecDeque::from([origin]);
+        while let Some(floor) = next.pop_front() {
+            if self.trip_bank(floor, destination).is_some() {
+                let mut transfers = vec![floor];
+                while let Some(&before) = came_from.get(transfers.last().unwrap()) {
+                    transfers.push(before);
+                }
+                transfers.pop();
+                transfers.reverse();
+                return transfers;
+            }
+            for &lobby in &self.sky_lobbies {
+                if lobby != origin
+                    && !came_from.contains_key(&lobby)
+                    && self.trip_bank(floor, lobby).is_some()
+                {
+                    came_from.insert(lobby, floor);
+                    next.push_back(lobby);
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    /// The shaft car runs in, if the topology lists it
+    pub fn shaft_of(&self, car: CarId) -> Option<&Shaft> {
+        self.shafts.iter().find(|shaft| shaft.cars.contains(&car))
+    }
+
+    /// Whether people come into the building from outside on a floor
+    pub fn is_entrance(&self, floor: Floor) -> bool {
+        self.entrances.contains(&floor)
+    }
+
+    /// The floor most people come into the building on, which is its first entrance, or
+    /// floor 0 if it has none
+    pub fn lobby(&self) -> Floor {
+        self.entrances.first().copied().unwrap_or(0)
+    }
+
+    /// Whether only people with a card for a floor can ride to it
+    pub fn is_secured(&self, floor: Floor) -> bool {
+        self.secured_floors.contains(&floor)
+    }
+
+    /// How many people fit in a floor's waiting area, or None if there's room for everyone
+    pub fn hall_capacity(&self, floor: Floor) -> Option<u32> {
+        self.hall_capacities
+            .get(floor as usize)
+            .copied()
+            .filter(|&capacity| capacity > 0)
+    }
+
+    /// Check every floor the banks, shafts, sky lobbies, entrances and secured floors name is
+    /// one the building has, and that it has a floor above its basements
+    pub fn check_floors(&self) -> Result<(), FloorMismatch> {
+        let settings = [
+            ("basements", (self.basements > 0).then_some(&self.basements)),
+            ("banks", self.banks.iter().flat_map(|b| &b.floors).max()),
+            ("shafts", self.shafts.iter().map(|s| &s.top).max()),
+            ("sky_lobbies", self.sky_lobbies.iter().max()),
+            ("entrances", self.entrances.iter().max()),
+            ("secured_floors", self.secured_floors.iter().max()),
+        ];
+        for (setting, floor) in settings {
+            if let Some(&floor) = floor
+                && floor >= self.floors
+            {
+                return Err(FloorMismatch::Setting {
+                    setting,
+                    floor,
+                    building: self.floors,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigError, SimConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn floors_have_labels_heights_and_elevations() {
+        let topology = BuildingTopology {
+            labels: vec!["G".to_string(), String::new(), "Sky".to_string()],
+            heights: vec![5., 2.5],
+            ..BuildingTopology::new(4)
+        };
+        assert_eq!(topology.floor_id(0), "G");
+        assert_eq!(topology.floor_id(1), "1");
+        assert_eq!(topology.floor_id(3), "3");
+        assert_eq!(topology.elevation(1.), 5.);
+        assert_eq!(topology.elevation(1.5), 6.25);
+        assert_eq!(topology.elevation(3.), 5. + 2.5 + DEFAULT_FLOOR_HEIGHT);
+        for position in [0., 0.3, 1., 1.7, 2.2, 3.] {
+            assert!((topology.floor_at(topology.elevation(position)) - position).abs() < 1e-5);
+        }
+
+        let lobbies = BuildingTopology {
+            entrances: vec![0, 4],
+            ..topology
+        };
+        assert_eq!(
+            lobbies.check_floors(),
+            Err(FloorMismatch::Setting {
+                setting: "entrances",
+                floor: 4,
+                building: 4,
+            })
+        );
+
+        // a simulation built from a config shares one topology between people and building
+        let config = SimConfig {
+            floors: 3,
+            floor_labels: vec!["G".to_string()],
+            ..SimConfig::default()
+        };
+        let sim = config.build().unwrap();
+        let topology = sim.building().topology();
+        assert!(Arc::ptr_eq(topology, sim.people().topology()));
+        assert_eq!(topology.floor_id(0), "G");
+    }
+
+    #[test]
+    fn basements_are_counted_down_from_the_ground_floor() {
+        let config = SimConfig {
+            floors: 6,
+            basements: 2,
+            floor_labels: vec![String::new(), String::new(), String::new(), "M".to_string()],
+            ..SimConfig::default()
+        };
+        let topology = config.topology();
+        let labels: Vec<_> = (0..6).map(|floor| topology.floor_id(floor)).collect();
+        assert_eq!(labels, ["B2", "B1", "G", "M", "2", "3"]);
+        assert_eq!((topology.level(0), topology.level(5)), (-2, 3));
+        assert_eq!(topology.at_level(-3), None);
+        assert_eq!(topology.entrances, vec![2]);
+        assert_eq!(topology.named("b1"), Some(1));
+        assert_eq!(topology.named("M"), Some(3));
+        assert_eq!(topology.named("-2"), Some(0));
+        assert_eq!(topology.named("7"), None);
+        // a FloorId read from a number is the floor of that name
+        let id: FloorId = serde_json::from_str("2").unwrap();
+        assert_eq!(topology.floor(&id), Some(4));
+        assert_eq!(topology.floor(&FloorId::from("g")), Some(2));
+
+        let underground = SimConfig {
+            floors: 2,
+            ..config
+        };
+        assert!(matches!(
+            underground.build(),
+            Err(ConfigError::Floors(FloorMismatch::Setting {
+                setting: "basements",
+                ..
+            }))
+        ));
+    }
+}
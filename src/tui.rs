@@ -0,0 +1,404 @@
+use crate::clock::SimClock;
+use crate::elevator::{BuildingState, DoorState};
+use crate::faults;
+use crate::people::{Person, PersonState};
+use crate::types::{CarId, Direction, Floor};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How long a step is shown for at normal speed
+const FRAME: Duration = Duration::from_millis(25);
+
+/// The most messages kept at the bottom of the screen
+const MAX_MESSAGES: usize = 3;
+
+/// Playback settings the user changes from the keyboard
+/// paused - whether the simulation is waiting for the user
+/// speed - how many times faster than normal steps are shown
+/// step_once - advance a single step, even while paused
+/// quit - stop the run
+#[derive(Clone, Debug, PartialEq)]
+pub struct Controls {
+    pub paused: bool,
+    pub speed: f32,
+    pub step_once: bool,
+    pub quit: bool,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.,
+            step_once: false,
+            quit: false,
+        }
+    }
+}
+
+/// implement functions for Controls
+/// handle - change the settings for a key press
+/// delay - how long to show each step for
+impl Controls {
+    /// Space pauses and resumes, n or . steps once, + and - change the speed, and q or Esc
+    /// quits. Other keys are ignored
+    pub fn handle(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('n') | KeyCode::Char('.') => self.step_once = true,
+            KeyCode::Char('+') | KeyCode::Char('=') => self.speed = (self.speed * 2.).min(64.),
+            KeyCode::Char('-') => self.speed = (self.speed / 2.).max(1. / 16.),
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            _ => {}
+        }
+    }
+
+    /// How long each step stays on screen at the current speed
+    pub fn delay(&self) -> Duration {
+        FRAME.div_f32(self.speed)
+    }
+}
+
+/// A full screen terminal renderer, drawing each floor of the shaft with its hall calls and
+/// queue, and every car with its riders and door. The terminal is restored when it's dropped
+pub struct Tui {
+    terminal: DefaultTerminal,
+    controls: Controls,
+    time: f32,
+    clock: SimClock,
+    rows: Vec<Row<'static>>,
+    num_cars: usize,
+    recall: Option<String>,
+    faults: Option<String>,
+    messages: VecDeque<String>,
+    finished: bool,
+}
+
+/// implement functions for Tui
+/// new - take over the terminal
+/// draw - show the building after a step
+/// message - show a line of text below the building, such as a rejected command
+/// wait - handle key presses until the next step should run
+/// finish - show the last step until the user quits
+impl Tui {
+    /// Switch the terminal to a full screen of its own
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            terminal: ratatui::try_init()?,
+            controls: Controls::default(),
+            time: 0.,
+            clock: SimClock::default(),
+            rows: Vec::new(),
+            num_cars: 0,
+            recall: None,
+            faults: None,
+            messages: VecDeque::new(),
+            finished: false,
+        })
+    }
+
+    /// Show the building and people as they are time seconds into the run
+    pub fn draw(
+        &mut self,
+        time: f32,
+        state: &BuildingState,
+        people: &[Person],
+        arriving: &[(CarId, Floor, Direction)],
+    ) -> io::Result<()> {
+        self.time = time;
+        self.clock = state.clock;
+        self.rows = floor_rows(state, people, arriving);
+        self.num_cars = state.cars.len();
+        self.recall = state
+            .emergency
+            .recall_floor()
+            .map(|floor| state.topology.floor_id(floor).0);
+        self.faults = faults::describe(state);
+        self.redraw()
+    }
+
+    /// Show a line of text below the building, keeping only the latest few
+    pub fn message(&mut self, text: String) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(text);
+    }
+
+    /// Handle key presses until it's time for the next step. Returns false if the user quit
+    pub fn wait(&mut self) -> io::Result<bool> {
+        let start = Instant::now();
+        loop {
+            if self.controls.quit {
+                return Ok(false);
+            }
+            if self.controls.step_once {
+                self.controls.step_once = false;
+                return Ok(true);
+            }
+
+            // while paused, only a key press can move things along
+            let timeout = if self.controls.paused {
+                Duration::from_millis(250)
+            } else {
+                let remaining = self.controls.delay().saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    return Ok(true);
+                }
+                remaining
+            };
+
+            if event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.controls.handle(key.code);
+                self.redraw()?;
+            }
+        }
+    }
+
+    /// Keep the last step on screen until the user quits
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.finished = true;
+        self.controls.paused = true;
+        self.redraw()?;
+        while self.wait()? {}
+        Ok(())
+    }
+
+    /// Draw the screen again from the last rows, for when only the status has changed
+    fn redraw(&mut self) -> io::Result<()> {
+        let status = if self.finished {
+            "finished".to_string()
+        } else if self.controls.paused {
+            "paused".to_string()
+        } else {
+            format!("running at {}x", self.controls.speed)
+        };
+        let mut status = format!("Time: {} ({:.1} s) | {status}", self.clock, self.time);
+        if let Some(floor) = &self.recall {
+            status.push_str(&format!(" | FIRE RECALL: all cars to floor {floor}"));
+        }
+        if let Some(faults) = &self.faults {
+            status.push_str(&format!(" | {faults}"));
+        }
+        let table = building_table(self.rows.clone(), self.num_cars);
+        let messages: Vec<Line> = self.messages.iter().map(|m| Line::raw(m.clone())).collect();
+
+        self.terminal.draw(|frame| {
+            draw_frame(frame, status, table, messages);
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// Lay out the status line, the building, any messages, and the keys along the bottom
+fn draw_frame(frame: &mut Frame, status: String, table: Table<'static>, messages: Vec<Line>) {
+    let [top, middle, bottom, keys] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(messages.len() as u16),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(Paragraph::new(status).bold(), top);
+    frame.render_widget(table, middle);
+    frame.render_widget(Paragraph::new(messages).red(), bottom);
+    frame.render_widget(
+        Paragraph::new(
+            "space pause/resume | n step | +/- speed | q quit | doors: white shut, yellow moving, green open",
+        )
+        .dim(),
+        keys,
+    );
+}
+
+/// The building as a table, with a column for each car's shaft
+fn building_table(rows: Vec<Row<'static>>, num_cars: usize) -> Table<'static> {
+    let mut header = vec![
+        Cell::from("Floor"),
+        Cell::from("Calls"),
+        Cell::from("Waiting"),
+    ];
+    let mut widths = vec![
+        Constraint::Length(5),
+        Constraint::Length(5),
+        Constraint::Length(16),
+    ];
+    for car in 0..num_cars {
+        header.push(Cell::from(format!("Car {car}")));
+        widths.push(Constraint::Length(6));
+    }
+    header.push(Cell::from("Arriving"));
+    widths.push(Constraint::Min(0));
+
+    Table::new(rows, widths)
+        .header(Row::new(header).bold())
+        .block(Block::bordered().title("Building"))
+}
+
+/// One row for each floor, top floor first. Each row shows the hall buttons pressed, how many
+/// people are queued, the car on this floor in each shaft, and which cars are about to arrive
+fn floor_rows(
+    state: &BuildingState,
+    people: &[Person],
+    arriving: &[(CarId, Floor, Direction)],
+) -> Vec<Row<'static>> {
+    let mut waiting_counts = vec![0usize; state.floors.len()];
+    let mut riding_counts = vec![0usize; state.cars.len()];
+    for person in people {
+        match person.state {
+            PersonState::Waiting => {
+                if let Some(count) = waiting_counts.get_mut(person.current_floor as usize) {
+                    *count += 1;
+                }
+            }
+            PersonState::Riding => {
+                if let Some(count) = person
+                    .in_car
+                    .and_then(|car_id| riding_counts.get_mut(car_id.0 as usize))
+                {
+                    *count += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    state
+        .floors
+        .iter()
+        .rev()
+        .map(|floor_state| {
+            let floor = floor_state.floor;
+            // a single call button shows as a star
+            let up = if floor_state.out_call {
+                '*'
+            } else if floor_state.out_up {
+                '^'
+            } else {
+                '.'
+            };
+            let down = if floor_state.out_down { 'v' } else { '.' };
+            // a bar showing the queue, which stops growing at 10
+            let waiting = waiting_counts[floor as usize];
+            let queue = format!("{waiting:>3} {}", "#".repeat(waiting.min(10)));
+
+            let mut cells = vec![
+                Cell::from(state.topology.floor_id(floor).0),
+                Cell::from(format!("[{up}{down}]")),
+                Cell::from(queue).yellow(),
+            ];
+
+            for car in &state.cars {
+                if car.current_floor.round() as Floor != floor {
+                    cells.push(Cell::from("  |").dark_gray());
+                    continue;
+                }
+                let arrow = if car.velocity > 0. {
+                    '^'
+                } else if car.velocity < 0. {
+                    'v'
+                } else {
+                    ' '
+                };
+                let riders = riding_counts[car.id.0 as usize];
+                let color = match car.door {
+                    DoorState::Closed => Color::White,
+                    DoorState::Opening { .. } | DoorState::Closing { .. } => Color::Yellow,
+                    DoorState::Open { .. } => Color::Green,
+                };
+                cells.push(
+                    Cell::from(format!(" [{riders}]{arrow}")).style(Style::new().fg(color).bold()),
+                );
+            }
+
+            let incoming: Vec<String> = arriving
+                .iter()
+                .filter(|&&(_, f, _)| f == floor)
+                .map(|&(car_id, _, direction)| {
+                    let arrow = match direction {
+                        Direction::Up => '^',
+                        Direction::Down => 'v',
+                    };
+                    format!("car {}{arrow}", car_id.0)
+                })
+                .collect();
+            cells.push(Cell::from(incoming.join(", ")));
+
+            Row::new(cells)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::ElevatorSim;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn keys_pause_step_and_change_speed() {
+        let mut controls = Controls::default();
+        controls.handle(KeyCode::Char(' '));
+        assert!(controls.paused);
+        controls.handle(KeyCode::Char('n'));
+        assert!(controls.step_once);
+
+        controls.handle(KeyCode::Char('+'));
+        assert_eq!(controls.delay(), FRAME / 2);
+        for _ in 0..20 {
+            controls.handle(KeyCode::Char('-'));
+        }
+        assert_eq!(controls.speed, 1. / 16.);
+
+        controls.handle(KeyCode::Char('x'));
+        assert!(!controls.quit);
+        controls.handle(KeyCode::Esc);
+        assert!(controls.quit);
+    }
+
+    #[test]
+    fn shaft_shows_cars_on_their_floor() {
+        let building = ElevatorSim::new(3, 2);
+        let rows = floor_rows(building.state(), &[], &[]);
+        assert_eq!(rows.len(), 3);
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 8)).unwrap();
+        terminal
+            .draw(|frame| {
+                let table = building_table(rows, 2);
+                frame.render_widget(table, frame.area());
+            })
+            .unwrap();
+        let screen: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(60)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+
+        // both cars start on the ground floor, the last floor row
+        assert!(screen[1].contains("Car 0") && screen[1].contains("Car 1"));
+        assert!(screen[2].starts_with("│2"));
+        assert_eq!(screen[4].matches("[0]").count(), 2);
+        assert_eq!(screen[3].matches('|').count(), 2);
+    }
+}
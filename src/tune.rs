@@ -0,0 +1,224 @@
+use crate::bench::{self, BenchConfig, Contender};
+use crate::control::ElevatorController;
+use crate::metrics::RunSummary;
+use crate::people::PeopleConfig;
+use crate::types::ScriptedArrival;
+
+/// What the tuner tries to make as small as possible
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective {
+    /// the average wait of the people who boarded. A run where nobody boarded scores infinity
+    MeanWait,
+    /// the wait at a percentile from 0 to 1, such as 0.95 for the 95th percentile. People
+    /// left waiting count too, see RunSummary::wait_percentile
+    WaitPercentile(f32),
+}
+
+impl Objective {
+    /// The score of a single run, where lower is better
+    pub fn score(&self, summary: &RunSummary) -> f32 {
+        match *self {
+            Objective::MeanWait => summary.average_wait().unwrap_or(f32::INFINITY),
+            Objective::WaitPercentile(p) => summary.wait_percentile(p).unwrap_or(0.),
+        }
+    }
+}
+
+/// Settings for tuning a controller
+/// bench - the building and run length every evaluation uses
+/// bounds - the lowest and highest value of each parameter
+/// objective - what to minimize
+/// max_evaluations - the most parameter vectors to try, each run on every scenario
+/// min_step - stop once the search step, as a share of each parameter's range, is smaller
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuneConfig {
+    pub bench: BenchConfig,
+    pub bounds: Vec<(f32, f32)>,
+    pub objective: Objective,
+    pub max_evaluations: u32,
+    pub min_step: f32,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        Self {
+            bench: BenchConfig::default(),
+            bounds: Vec::new(),
+            objective: Objective::WaitPercentile(0.95),
+            max_evaluations: 50,
+            min_step: 0.02,
+        }
+    }
+}
+
+/// The best parameters the tuner found, their score, and how many it tried to find them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuned {
+    pub params: Vec<f32>,
+    pub score: f32,
+    pub evaluations: u32,
+}
+
+/// Score a parameter vector by running the controller build makes from it on every
+/// scenario, and averaging the objective over the runs. A controller the bench's
+/// observation filter refuses scores infinity
+pub fn evaluate(
+    config: &TuneConfig,
+    scenarios: &[Vec<ScriptedArrival>],
+    people: &PeopleConfig,
+    params: &[f32],
+    build: &dyn Fn(&[f32]) -> Box<dyn ElevatorController>,
+) -> f32 {
+    if scenarios.is_empty() {
+        return 0.;
+    }
+    let total: f32 = scenarios
+        .iter()
+        .map(|arrivals| {
+            let contender = Contender {
+                name: String::new(),
+                controller: build(params),
+                people: people.clone(),
+            };
+            let comparison = bench::compare(&config.bench, arrivals, vec![contender]);
+            comparison
+                .results
+                .first()
+                .map_or(f32::INFINITY, |(_, summary)| {
+                    config.objective.score(summary)
+                })
+        })
+        .sum();
+    total / scenarios.len() as f32
+}
+
+/// Search for the parameters which minimize the objective, starting from start. Each step
+/// tries moving one parameter up or down by a share of its range, keeping any move that
+/// lowers the score, and halves the share once no move does. This is a local search, so a
+/// good start matters. The same scenarios are used for every evaluation, so differences
+/// come from the parameters alone
+pub fn tune(
+    config: &TuneConfig,
+    scenarios: &[Vec<ScriptedArrival>],
+    people: &PeopleConfig,
+    start: &[f32],
+    build: &dyn Fn(&[f32]) -> Box<dyn ElevatorController>,
+) -> Tuned {
+    let mut best: Vec<f32> = config
+        .bounds
+        .iter()
+        .enumerate()
+        .map(|(i, &(low, high))| start.get(i).copied().unwrap_or(low).clamp(low, high))
+        .collect();
+    let mut best_score = evaluate(config, scenarios, people, &best, build);
+    let mut evaluations = 1;
+    let mut step = 0.25;
+
+    while step >= config.min_step && evaluations < config.max_evaluations {
+        let mut improved = false;
+        for (i, &(low, high)) in config.bounds.iter().enumerate() {
+            for sign in [1., -1.] {
+                if evaluations >= config.max_evaluations {
+                    break;
+                }
+                let mut candidate = best.clone();
+                candidate[i] = (best[i] + sign * step * (high - low)).clamp(low, high);
+                if candidate[i] == best[i] {
+                    continue;
+                }
+
+                let score = evaluate(config, scenarios, people, &candidate, build);
+                evaluations += 1;
+                if score < best_score {
+                    best = candidate;
+                    best_score = score;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        if !improved {
+            step /= 2.;
+        }
+    }
+
+    Tuned {
+        params: best,
+        score: best_score,
+        evaluations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::BasicController;
+    use crate::elevator::ElevatorCommand;
+    use crate::metrics::StatsView;
+    use crate::view::BuildingView;
+
+    /// a controller which runs like BasicController when switched on, and leaves every car
+    /// where it is when switched off
+    struct Switch(bool);
+
+    impl ElevatorController for Switch {
+        fn tick(&mut self, view: &BuildingView, stats: &StatsView) -> Vec<ElevatorCommand> {
+            if self.0 {
+                BasicController.tick(view, stats)
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    #[test]
+    fn tuner_finds_the_parameters_that_serve_people() {
+        let arrivals = vec![
+            ScriptedArrival {
+                time: 1.,
+                origin: 3,
+                destination: 0,
+            },
+            ScriptedArrival {
+                time: 2.,
+                origin: 1,
+                destination: 4,
+            },
+        ];
+        let config = TuneConfig {
+            bench: BenchConfig {
+                floors: 5,
+                cars: 1,
+                duration: 60.,
+                ..BenchConfig::default()
+            },
+            bounds: vec![(0., 1.)],
+            ..TuneConfig::default()
+        };
+        // only a low parameter switches the controller on
+        let build =
+            |params: &[f32]| -> Box<dyn ElevatorController> { Box::new(Switch(params[0] < 0.5)) };
+
+        // people who are never picked up wait the whole run
+        let stranded = evaluate(
+            &config,
+            std::slice::from_ref(&arrivals),
+            &PeopleConfig::default(),
+            &[1.],
+            &build,
+        );
+        assert!(stranded > 50.);
+
+        // a quarter of the range down from the start switches it on
+        let tuned = tune(
+            &config,
+            &[arrivals],
+            &PeopleConfig::default(),
+            &[0.6],
+            &build,
+        );
+        assert!(tuned.params[0] < 0.5);
+        assert!(tuned.score < 20.);
+        assert!(tuned.evaluations <= config.max_evaluations);
+    }
+}
@@ -1,17 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
 /// PersonId newtype, should be unique for each person
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct PersonId(pub u32);
 
 /// CarId newtype, should be unique for each car
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct CarId(pub u32);
 
+/// GroupId newtype, shared by people travelling together. It's the id of whoever in the
+/// group spawned first
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct GroupId(pub u32);
+
+/// BankId newtype, the index of a bank in the building's topology
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct BankId(pub u32);
+
 /// Direction enum used for exterior buttons
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Direction {
     Up,
     Down,
 }
 
-/// It's less important that floor is type safe, so I made it a type alias
+/// It's less important that floor is type safe, so I made it a type alias. Floors are
+/// numbered from 0 at the bottom, basements included, which is how the simulation indexes
+/// them. Commands given from outside and anything drawn go by FloorId instead
 pub type Floor = u32;
+
+/// A floor as the building names it, such as B2, G, M or 12, see BuildingTopology::floor_id.
+/// Commands and button presses from outside the simulation name their floors this way, and
+/// BuildingTopology::floor turns one back into the Floor the simulation works with. Read
+/// from a number, it's the floor of that name, so 3 is the floor called 3
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct FloorId(pub String);
+
+impl fmt::Display for FloorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl PartialEq<&str> for FloorId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<&str> for FloorId {
+    fn from(name: &str) -> Self {
+        FloorId(name.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for FloorId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Name {
+            Label(String),
+            Number(i64),
+        }
+        Ok(match Name::deserialize(deserializer)? {
+            Name::Label(label) => FloorId(label),
+            Name::Number(number) => FloorId(number.to_string()),
+        })
+    }
+}
+
+/// A person who will appear at a known time, travelling from origin to destination.
+/// Used by scripted scenarios instead of random spawning
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ScriptedArrival {
+    pub time: f32,
+    pub origin: Floor,
+    pub destination: Floor,
+}
+
+/// Something which refers to a floor the building doesn't have, found when the parts of a
+/// simulation are put together rather than partway through a run. building is how many
+/// floors the building has
+#[derive(Clone, Debug, PartialEq)]
+pub enum FloorMismatch {
+    /// people move between num_floors floors, more than the building has
+    People { num_floors: Floor, building: Floor },
+    /// someone is on, or heading for, floor
+    Person {
+        person: PersonId,
+        floor: Floor,
+        building: Floor,
+    },
+    /// someone is scripted to arrive on, or head for, floor
+    Arrival { floor: Floor, building: Floor },
+    /// one of the building's settings, named by setting, mentions floor
+    Setting {
+        setting: &'static str,
+        floor: Floor,
+        building: Floor,
+    },
+}
+
+impl fmt::Display for FloorMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FloorMismatch::People {
+                num_floors,
+                building,
+            } => write!(
+                f,
+                "people move between {num_floors} floors, but the building has {building}"
+            ),
+            FloorMismatch::Person {
+                person,
+                floor,
+                building,
+            } => write!(
+                f,
+                "person {} is on or heading for floor {floor}, but the building has {building} \
+floors",
+                person.0
+            ),
+            FloorMismatch::Arrival { floor, building } => write!(
+                f,
+                "someone is scripted to arrive on or head for floor {floor}, but the building \
+has {building} floors"
+            ),
+            FloorMismatch::Setting {
+                setting,
+                floor,
+                building,
+            } => write!(
+                f,
+                "{setting} mentions floor {floor}, but the building has {building} floors"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FloorMismatch {}
@@ -0,0 +1,204 @@
+use crate::elevator::{BuildingState, DoorState};
+use crate::types::{CarId, Floor, PersonId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// How far a car can be past the top or bottom floor, or be moved when it levels itself
+/// with a floor on arriving, in floors. Cars arrive once they're this close to their floor
+const TOLERANCE: f32 = 0.01;
+
+/// Something that should never happen in the building, found by InvariantChecker
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// a car moved without its door closed, from where it was to where it is now
+    MovedWithDoorOpen { car_id: CarId, from: f32, to: f32 },
+    /// a car is above the top floor or below the bottom one
+    OutOfBounds { car_id: CarId, floor: f32 },
+    /// more than one car lists the person as inside
+    InTwoCars {
+        person_id: PersonId,
+        cars: Vec<CarId>,
+    },
+    /// a car opened its door at a floor whose button inside it is still lit
+    ButtonNotCleared { car_id: CarId, floor: Floor },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MovedWithDoorOpen { car_id, from, to } => write!(
+                f,
+                "car {} moved from floor {from:.2} to {to:.2} without its door closed",
+                car_id.0
+            ),
+            Violation::OutOfBounds { car_id, floor } => {
+                write!(
+                    f,
+                    "car {} is outside the building, at floor {floor:.2}",
+                    car_id.0
+                )
+            }
+            Violation::InTwoCars { person_id, cars } => {
+                let cars: Vec<String> = cars.iter().map(|c| c.0.to_string()).collect();
+                write!(
+                    f,
+                    "person {} is inside cars {} at once",
+                    person_id.0,
+                    cars.join(", ")
+                )
+            }
+            Violation::ButtonNotCleared { car_id, floor } => write!(
+                f,
+                "car {} opened its door at floor {floor} with the button for it still lit",
+                car_id.0
+            ),
+        }
+    }
+}
+
+/// Checks the building after each tick for things that should never happen: a car leaving
+/// with its door open, a car outside the building, someone inside two cars, or a car
+/// stopping at a floor without putting out the button for it. Run it after every step of a
+/// simulation, as --check does, or from a fuzzing or proptest harness
+#[derive(Debug, Default)]
+pub struct InvariantChecker {
+    /// where each car was and what its door was doing when last checked
+    previous: Vec<(f32, DoorState)>,
+}
+
+impl InvariantChecker {
+    /// A checker which hasn't seen the building yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check state, which is the building one tick after it was last checked, returning
+    /// everything wrong with it
+    pub fn check(&mut self, state: &BuildingState) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let top = state.floors.len().saturating_sub(1) as f32;
+        let mut inside: HashMap<PersonId, Vec<CarId>> = HashMap::new();
+
+        for (i, car) in state.cars.iter().enumerate() {
+            let floor = car.current_floor;
+            if floor < -TOLERANCE || floor > top + TOLERANCE {
+                violations.push(Violation::OutOfBounds {
+                    car_id: car.id,
+                    floor,
+                });
+            }
+            for &person_id in &car.occupants {
+                inside.entry(person_id).or_default().push(car.id);
+            }
+
+            let Some(&(from, previous_door)) = self.previous.get(i) else {
+                continue;
+            };
+            let moved = (floor - from).abs();
+            // a car arriving levels itself with the floor and opens its door in one tick
+            let arrived = previous_door == DoorState::Closed
+                && car.door_opening_or_open()
+                && moved < TOLERANCE;
+            if car.door != DoorState::Closed && moved > 0. && !arrived {
+                violations.push(Violation::MovedWithDoorOpen {
+                    car_id: car.id,
+                    from,
+                    to: floor,
+                });
+            }
+            let here = floor.round();
+            if arrived
+                && (floor - here).abs() < TOLERANCE
+                && car.car_buttons.get(here as usize) == Some(&true)
+            {
+                violations.push(Violation::ButtonNotCleared {
+                    car_id: car.id,
+                    floor: here as Floor,
+                });
+            }
+        }
+
+        let mut twice: Vec<(PersonId, Vec<CarId>)> = inside
+            .into_iter()
+            .filter(|(_, cars)| cars.len() > 1)
+            .collect();
+        twice.sort_by_key(|&(person_id, _)| person_id.0);
+        violations.extend(
+            twice
+                .into_iter()
+                .map(|(person_id, cars)| Violation::InTwoCars { person_id, cars }),
+        );
+
+        self.previous = state
+            .cars
+            .iter()
+            .map(|car| (car.current_floor, car.door))
+            .collect();
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::elevator::{ElevatorCommand, ElevatorSim};
+
+    #[test]
+    fn a_busy_run_keeps_every_invariant() {
+        let config = SimConfig {
+            cars: 3,
+            spawn_rate: 1.,
+            seed: Some(11),
+            ..SimConfig::default()
+        };
+        let mut sim = config.build().unwrap();
+        let mut checker = InvariantChecker::new();
+        for _ in 0..3000 {
+            sim.step(0.1);
+            let violations = checker.check(sim.building().state());
+            assert!(violations.is_empty(), "{violations:?}");
+        }
+    }
+
+    #[test]
+    fn broken_states_are_caught() {
+        let mut sim = ElevatorSim::new(5, 2);
+        sim.apply_command(ElevatorCommand::MoveCarTo {
+            car_id: CarId(0),
+            floor: 3,
+        })
+        .unwrap();
+        let mut checker = InvariantChecker::new();
+        assert!(checker.check(sim.state()).is_empty());
+        for _ in 0..10 {
+            sim.tick(0.1);
+        }
+
+        let mut state = sim.state().clone();
+        state.cars[0].door = DoorState::Open {
+            dwell_remaining: 1.,
+        };
+        state.cars[1].current_floor = 5.5;
+        state.cars[0].occupants.push(PersonId(4));
+        state.cars[1].occupants.push(PersonId(4));
+        assert_eq!(
+            checker.check(&state),
+            vec![
+                Violation::MovedWithDoorOpen {
+                    car_id: CarId(0),
+                    from: 0.,
+                    to: state.cars[0].current_floor,
+                },
+                Violation::OutOfBounds {
+                    car_id: CarId(1),
+                    floor: 5.5,
+                },
+                Violation::InTwoCars {
+                    person_id: PersonId(4),
+                    cars: vec![CarId(0), CarId(1)],
+                },
+            ]
+        );
+    }
+}
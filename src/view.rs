@@ -0,0 +1,367 @@
+use crate::clock::SimClock;
+use crate::elevator::{
+    BuildingState, CarCapabilities, CarClass, DoorState, ElevatorCarState, EmergencyMode,
+    FloorState, Malfunction, PassengerTransfer, ServiceState,
+};
+use crate::topology::BuildingTopology;
+use crate::types::{BankId, CarId, Direction, Floor, PersonId};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+/// A hall call: the floor, and which way the caller is going, if the floor's buttons say
+pub type HallCall = (Floor, Option<Direction>);
+
+/// The building as a controller sees it. It borrows the building's state instead of copying
+/// it, and only hands out shared references, so a controller can't change anything behind
+/// the building's back. What an ObservationFilter hides or adds is applied as each value
+/// is read, so filtering doesn't copy the state either
+#[derive(Clone, Debug)]
+pub struct BuildingView<'a> {
+    state: &'a BuildingState,
+    riders: Option<Vec<Vec<Floor>>>,
+    hide_loads: bool,
+}
+
+/// implement functions for BuildingView
+/// new - see everything the building's state holds
+/// topology - the shape of the building
+/// floors, floor, calls - the floors, and the hall calls lit on them
+/// cars, car, cars_serving - the cars, as CarViews
+/// emergency, clock - the rest of the building
+/// to_state - the building as a BuildingState
+impl<'a> BuildingView<'a> {
+    /// A view of everything the building's state holds
+    pub fn new(state: &'a BuildingState) -> Self {
+        Self {
+            state,
+            riders: None,
+            hide_loads: false,
+        }
+    }
+
+    /// A view which shows riders as every car's rider destinations, if given, and hides
+    /// how full the cars are if hide_loads is set
+    pub(crate) fn filtered(
+        state: &'a BuildingState,
+        riders: Option<Vec<Vec<Floor>>>,
+        hide_loads: bool,
+    ) -> Self {
+        Self {
+            state,
+            riders,
+            hide_loads,
+        }
+    }
+
+    /// The building's floors, banks, shafts and entrances
+    pub fn topology(&self) -> &'a BuildingTopology {
+        &self.state.topology
+    }
+
+    /// Every floor, from the bottom up
+    pub fn floors(&self) -> &'a [FloorState] {
+        &self.state.floors
+    }
+
+    /// One floor, if it's in the building
+    pub fn floor(&self, floor: Floor) -> Option<&'a FloorState> {
+        self.state.floors.get(floor as usize)
+    }
+
+    /// Every hall call lit, from the bottom up. A single call button gives a call with no
+    /// direction, and a pair of buttons a call for each one lit
+    pub fn calls(&self) -> impl Iterator<Item = HallCall> + 'a {
+        self.state.floors.iter().flat_map(|f| {
+            let single = f.out_call.then_some((f.floor, None));
+            let up = (!f.out_call && f.out_up).then_some((f.floor, Some(Direction::Up)));
+            let down = (!f.out_call && f.out_down).then_some((f.floor, Some(Direction::Down)));
+            [single, up, down].into_iter().flatten()
+        })
+    }
+
+    /// How many cars there are
+    pub fn num_cars(&self) -> usize {
+        self.state.cars.len()
+    }
+
+    /// Every car, in id order
+    pub fn cars(&self) -> impl Iterator<Item = CarView<'_>> {
+        (0..self.state.cars.len()).filter_map(|i| self.car(i))
+    }
+
+    /// The i-th car, if there is one
+    pub fn car(&self, i: usize) -> Option<CarView<'_>> {
+        let car = self.state.cars.get(i)?;
+        let riders = match &self.riders {
+            Some(riders) => riders.get(i).map_or(&[][..], Vec::as_slice),
+            None => &car.rider_destinations,
+        };
+        Some(CarView {
+            car,
+            riders,
+            hide_loads: self.hide_loads,
+        })
+    }
+
+    /// The cars which stop at a floor, so controllers only send cars that can get there
+    pub fn cars_serving(&self, floor: Floor) -> impl Iterator<Item = CarView<'_>> {
+        self.cars().filter(move |car| car.serves(floor))
+    }
+
+    /// Whether the building is running normally, or has been taken over in an emergency
+    pub fn emergency(&self) -> EmergencyMode {
+        self.state.emergency
+    }
+
+    /// The simulated time of day
+    pub fn clock(&self) -> SimClock {
+        self.state.clock
+    }
+
+    /// The building as a BuildingState, for handing to something which wants one, such as
+    /// a scheduler in another process. Only a view which hides or adds something is copied
+    pub fn to_state(&self) -> Cow<'a, BuildingState> {
+        if self.riders.is_none() && !self.hide_loads {
+            return Cow::Borrowed(self.state);
+        }
+        let mut state = self.state.clone();
+        for (i, car) in state.cars.iter_mut().enumerate() {
+            if let Some(view) = self.car(i) {
+                car.rider_destinations = view.rider_destinations().to_vec();
+                car.passenger_count = view.passenger_count();
+                car.occupants = view.occupants().to_vec();
+                car.load = view.load();
+            }
+        }
+        Cow::Owned(state)
+    }
+}
+
+/// One car, as a controller sees it. See ElevatorCarState for what each part means
+#[derive(Copy, Clone, Debug)]
+pub struct CarView<'a> {
+    car: &'a ElevatorCarState,
+    riders: &'a [Floor],
+    hide_loads: bool,
+}
+
+/// implement functions for CarView
+/// id, position, elevation, velocity, direction, stops, target_floor - where the car is and is going
+/// door, door_open, door_opening_or_open, transfer - its door and anyone using it
+/// car_buttons, pressed - the buttons lit inside it
+/// serves, zoned, nearest_floor_ahead - the floors it stops at
+/// capacity, passenger_count, is_full, occupants, rider_destinations - who is inside
+/// load, weight_limit - how heavy they are, and how heavy they're allowed to be
+/// service, dispatchable, malfunction, capabilities, bank, class, reserved - what the car
+/// can do, and who for
+/// overloaded - whether its overload sensor is holding it
+impl<'a> CarView<'a> {
+    /// The car's id
+    pub fn id(&self) -> CarId {
+        self.car.id
+    }
+
+    /// Where the car is, in floors up the shaft
+    pub fn position(&self) -> f32 {
+        self.car.current_floor
+    }
+
+    /// Where the car is, in metres above floor 0
+    pub fn elevation(&self) -> f32 {
+        self.car.elevation
+    }
+
+    /// How fast the car is moving, in metres per second with up being positive
+    pub fn velocity(&self) -> f32 {
+        self.car.velocity
+    }
+
+    /// Which way the car is committed to travel, if it is
+    pub fn direction(&self) -> Option<Direction> {
+        self.car.direction
+    }
+
+    /// The floors the car will stop at, in order
+    pub fn stops(&self) -> &'a VecDeque<Floor> {
+        &self.car.stops
+    }
+
+    /// The floor the car is on its way to, the first of its stops
+    pub fn target_floor(&self) -> Option<Floor> {
+        self.car.target_floor()
+    }
+
+    /// What the car's door is doing
+    pub fn door(&self) -> DoorState {
+        self.car.door
+    }
+
+    /// Whether the door is fully open
+    pub fn door_open(&self) -> bool {
+        self.car.door_open()
+    }
+
+    /// Whether the door is open or on its way there, so the car is stopped for people
+    pub fn door_opening_or_open(&self) -> bool {
+        self.car.door_opening_or_open()
+    }
+
+    /// Someone getting on or off, if anyone is
+    pub fn transfer(&self) -> Option<PassengerTransfer> {
+        self.car.transfer
+    }
+
+    /// The button for each floor inside the car, and whether it's lit
+    pub fn car_buttons(&self) -> &'a [bool] {
+        &self.car.car_buttons
+    }
+
+    /// Whether the button inside the car for floor is lit
+    pub fn pressed(&self, floor: Floor) -> bool {
+        self.car
+            .car_buttons
+            .get(floor as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Whether the car can be sent to a floor
+    pub fn serves(&self, floor: Floor) -> bool {
+        self.car.serves(floor)
+    }
+
+    /// Whether the car only serves some floors, such as an express car
+    pub fn zoned(&self) -> bool {
+        self.car.zoned()
+    }
+
+    /// The nearest floor the car serves that it can stop at without turning around
+    pub fn nearest_floor_ahead(&self) -> Floor {
+        self.car.nearest_floor_ahead()
+    }
+
+    /// How many people the car holds
+    pub fn capacity(&self) -> u32 {
+        self.car.capacity
+    }
+
+    /// How many people are inside, or 0 if loads are hidden
+    pub fn passenger_count(&self) -> u32 {
+        if self.hide_loads {
+            0
+        } else {
+            self.car.passenger_count
+        }
+    }
+
+    /// Whether the car has no room for anyone else. Cars never look full if loads are hidden
+    pub fn is_full(&self) -> bool {
+        self.passenger_count() >= self.capacity()
+    }
+
+    /// How many kg the car is carrying, or 0 if loads are hidden
+    pub fn load(&self) -> f32 {
+        if self.hide_loads { 0. } else { self.car.load }
+    }
+
+    /// How many kg the car carries before its overload sensor goes off, or 0 for no limit
+    pub fn weight_limit(&self) -> f32 {
+        self.car.weight_limit
+    }
+
+    /// Who is inside, when occupants are tracked and loads aren't hidden
+    pub fn occupants(&self) -> &'a [PersonId] {
+        if self.hide_loads {
+            &[]
+        } else {
+            &self.car.occupants
+        }
+    }
+
+    /// Where each rider is going, which only a rich observation shows
+    pub fn rider_destinations(&self) -> &'a [Floor] {
+        self.riders
+    }
+
+    /// Who the car takes its orders from
+    pub fn service(&self) -> ServiceState {
+        self.car.service
+    }
+
+    /// Whether the controller can send the car anywhere
+    pub fn dispatchable(&self) -> bool {
+        self.car.dispatchable()
+    }
+
+    /// What's wrong with the car, if it has broken down
+    pub fn malfunction(&self) -> Option<Malfunction> {
+        self.car.malfunction
+    }
+
+    /// What the car is fitted for
+    pub fn capabilities(&self) -> CarCapabilities {
+        self.car.capabilities
+    }
+
+    /// The bank the car belongs to, if it's in one
+    pub fn bank(&self) -> Option<BankId> {
+        self.car.bank
+    }
+
+    /// What class of car it is
+    pub fn class(&self) -> CarClass {
+        self.car.class
+    }
+
+    /// How many more seconds the car is kept for deliveries, if it's reserved
+    pub fn reserved(&self) -> Option<f32> {
+        self.car.reserved
+    }
+
+    /// Whether the car's overload sensor has gone off, so it won't leave until someone
+    /// gets out. Everyone in the hall hears it, so loads being hidden doesn't hide it
+    pub fn overloaded(&self) -> bool {
+        self.car.overloaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::{ElevatorCommand, ElevatorSim};
+
+    #[test]
+    fn views_borrow_the_state_and_filter_as_they_read() {
+        let mut building = ElevatorSim::new(4, 2);
+        for (floor, direction) in [(1, Direction::Up), (2, Direction::Down)] {
+            building
+                .apply_command(ElevatorCommand::PressOutButton {
+                    floor,
+                    direction,
+                    bank: None,
+                })
+                .unwrap();
+        }
+        let mut state = building.state().clone();
+        state.cars[1].passenger_count = state.cars[1].capacity;
+
+        let open = BuildingView::new(&state);
+        assert!(matches!(open.to_state(), Cow::Borrowed(_)));
+        let calls: Vec<HallCall> = open.calls().collect();
+        assert_eq!(
+            calls,
+            vec![(1, Some(Direction::Up)), (2, Some(Direction::Down))]
+        );
+        assert!(open.car(1).unwrap().is_full());
+
+        // riders and hidden loads are read through the view, and only copied on request
+        let filtered = BuildingView::filtered(&state, Some(vec![vec![3], vec![]]), true);
+        let car = filtered.car(1).unwrap();
+        assert!(!car.is_full());
+        assert_eq!(car.passenger_count(), 0);
+        assert_eq!(filtered.car(0).unwrap().rider_destinations(), &[3]);
+        let copied = filtered.to_state();
+        assert_eq!(copied.cars[0].rider_destinations, vec![3]);
+        assert_eq!(copied.cars[1].passenger_count, 0);
+    }
+}
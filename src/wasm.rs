@@ -0,0 +1,242 @@
+use crate::config::SimConfig;
+use crate::elevator::{ElevatorCommand, ElevatorSim};
+use crate::replay::Scenario;
+use crate::sim::Simulation;
+use crate::types::{Floor, FloorId};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_wasm_bindgen::Serializer;
+use wasm_bindgen::prelude::*;
+
+/// Turn anything serializable into a plain JS object. It goes through JSON on the way, like
+/// from_js, so maps become objects rather than JS Maps, and 64 bit numbers become ordinary
+/// numbers, and the result can go straight into JSON.stringify
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    to_json(value)
+        .map_err(|e| JsError::new(&e.to_string()))?
+        .serialize(&Serializer::json_compatible())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// The JSON to_js hands to JavaScript. This part doesn't need a browser, so it can be
+/// tested anywhere
+fn to_json(value: &impl Serialize) -> serde_json::Result<serde_json::Value> {
+    serde_json::to_value(value)
+}
+
+/// Read a plain JS object. It goes through JSON on the way, so it's read exactly like a
+/// JSON file would be, and a misspelled config field is an error rather than ignored
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsError> {
+    let json: serde_json::Value =
+        serde_wasm_bindgen::from_value(value).map_err(|e| JsError::new(&e.to_string()))?;
+    serde_json::from_value(json).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// A whole simulation, people, building and controller, for JavaScript. It's built from a
+/// config object with the same fields as a TOML config file, and any field left out keeps
+/// its default
+#[wasm_bindgen(js_name = Simulation)]
+pub struct WasmSimulation {
+    sim: Simulation,
+}
+
+/// implement functions for WasmSimulation
+/// new - build the simulation a config object describes
+/// step, stepToNextEvent, advanceTo - advance the simulation
+/// state, people, time - look at the simulation, as plain JS objects
+/// interpolatedState, alphaAt - the building with its cars between ticks, for smooth drawing
+/// takeEvents - drain the building's events
+/// addPerson, applyCommand - change the simulation from JavaScript
+/// startMacro, finishMacro, playScenario - record what's done from JavaScript as a scenario,
+/// and play one back
+#[wasm_bindgen(js_class = Simulation)]
+impl WasmSimulation {
+    /// Build the simulation a config object describes, or the default one for undefined
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: JsValue) -> Result<WasmSimulation, JsError> {
+        let config: SimConfig = if config.is_undefined() || config.is_null() {
+            SimConfig::default()
+        } else {
+            from_js(config)?
+        };
+        let sim = config.build().map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Self { sim })
+    }
+
+    /// Advance the simulation by dt seconds
+    pub fn step(&mut self, dt: f32) {
+        self.sim.step(dt);
+    }
+
+    /// Advance the simulation to the next event, but never by more than maxDt seconds,
+    /// returning how much time passed
+    #[wasm_bindgen(js_name = stepToNextEvent)]
+    pub fn step_to_next_event(&mut self, max_dt: f32) -> f32 {
+        self.sim.step_to_next_event(max_dt)
+    }
+
+    /// Catch the simulation up to t seconds into the run, in steps of the config's
+    /// timestep, returning how many steps were taken
+    #[wasm_bindgen(js_name = advanceTo)]
+    pub fn advance_to(&mut self, t: f32) -> u32 {
+        self.sim.advance_to(t)
+    }
+
+    /// The building as it is now: every floor with its calls, every car with its position,
+    /// door and riders, and any emergency
+    pub fn state(&self) -> Result<JsValue, JsError> {
+        to_js(self.sim.building().state())
+    }
+
+    /// The building with every car alpha of the way from where it was a tick ago to where it
+    /// is now
+    #[wasm_bindgen(js_name = interpolatedState)]
+    pub fn interpolated_state(&self, alpha: f32) -> Result<JsValue, JsError> {
+        to_js(&self.sim.interpolated_state(alpha))
+    }
+
+    /// How far t is into the next of advanceTo's steps, as the alpha for interpolatedState
+    #[wasm_bindgen(js_name = alphaAt)]
+    pub fn alpha_at(&self, t: f32) -> f32 {
+        self.sim.alpha_at(t)
+    }
+
+    /// Everyone in the simulation, with where they are and where they're going
+    pub fn people(&self) -> Result<JsValue, JsError> {
+        to_js(&self.sim.people().people())
+    }
+
+    /// How many seconds the simulation has run for
+    pub fn time(&self) -> f32 {
+        self.sim.time()
+    }
+
+    /// Every event since the last call, such as cars arriving and commands being refused
+    #[wasm_bindgen(js_name = takeEvents)]
+    pub fn take_events(&mut self) -> Result<JsValue, JsError> {
+        to_js(&self.sim.take_events())
+    }
+
+    /// Put a person on origin who wants to go to destination, returning their id
+    #[wasm_bindgen(js_name = addPerson)]
+    pub fn add_person(&mut self, origin: Floor, destination: Floor) -> u32 {
+        self.sim.add_person(origin, destination).0
+    }
+
+    /// Give the building a command object, such as { MoveCarTo: { car_id: 0, floor: "B1" } },
+    /// throwing if it's refused. Floors go by what the building calls them, see FloorId
+    #[wasm_bindgen(js_name = applyCommand)]
+    pub fn apply_command(&mut self, command: JsValue) -> Result<(), JsError> {
+        let command = resolve(self.sim.building(), command)?;
+        self.sim
+            .inject(command)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Start recording every person added and command applied from here on
+    #[wasm_bindgen(js_name = startMacro)]
+    pub fn start_macro(&mut self) {
+        self.sim.start_macro();
+    }
+
+    /// Stop recording, returning the scenario object, or undefined if nothing was recording
+    #[wasm_bindgen(js_name = finishMacro)]
+    pub fn finish_macro(&mut self) -> Result<JsValue, JsError> {
+        match self.sim.finish_macro() {
+            Some(scenario) => to_js(&scenario),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    /// Play back a scenario object from finishMacro, or one written by hand
+    #[wasm_bindgen(js_name = playScenario)]
+    pub fn play_scenario(&mut self, scenario: JsValue) -> Result<(), JsError> {
+        let scenario: Scenario = from_js(scenario)?;
+        self.sim
+            .play_scenario(&scenario)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+}
+
+/// Just the building, for JavaScript that moves people around and controls the cars itself
+#[wasm_bindgen(js_name = Building)]
+pub struct WasmBuilding {
+    building: ElevatorSim,
+}
+
+/// implement functions for WasmBuilding
+/// new - create a building with a number of floors and cars
+/// tick - move the cars and doors
+/// state - the building as a plain JS object
+/// applyCommand - give the building a command
+#[wasm_bindgen(js_class = Building)]
+impl WasmBuilding {
+    /// Create a building with the default settings
+    #[wasm_bindgen(constructor)]
+    pub fn new(floors: usize, cars: usize) -> WasmBuilding {
+        Self {
+            building: ElevatorSim::new(floors, cars),
+        }
+    }
+
+    /// Move the cars and doors on by dt seconds
+    pub fn tick(&mut self, dt: f32) {
+        self.building.tick(dt);
+    }
+
+    /// The building as it is now
+    pub fn state(&self) -> Result<JsValue, JsError> {
+        to_js(self.building.state())
+    }
+
+    /// Give the building a command object, throwing if it's refused. Floors go by what the
+    /// building calls them, see FloorId
+    #[wasm_bindgen(js_name = applyCommand)]
+    pub fn apply_command(&mut self, command: JsValue) -> Result<(), JsError> {
+        let command = resolve(&self.building, command)?;
+        self.building
+            .apply_command(command)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+}
+
+/// Read a command from a JS object, with its floors named the way building names them
+fn resolve(building: &ElevatorSim, command: JsValue) -> Result<ElevatorCommand, JsError> {
+    let command: ElevatorCommand<FloorId> = from_js(command)?;
+    command
+        .resolve(building.topology())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevator::BuildingState;
+    use crate::people::Person;
+
+    #[test]
+    fn state_reaches_javascript_every_tick() {
+        let mut wasm = WasmSimulation {
+            sim: SimConfig {
+                seed: Some(2),
+                ..SimConfig::default()
+            }
+            .build()
+            .unwrap(),
+        };
+        for _ in 0..200 {
+            wasm.step(0.1);
+            // what state and people hand over reads back as the building and people it came
+            // from
+            let state = to_json(wasm.sim.building().state()).unwrap();
+            let read: BuildingState = serde_json::from_value(state).unwrap();
+            assert_eq!(&read, wasm.sim.building().state());
+            let people = to_json(&wasm.sim.people().people()).unwrap();
+            let read: Vec<Person> = serde_json::from_value(people).unwrap();
+            assert_eq!(read.len(), wasm.sim.people().people().len());
+            to_json(&wasm.sim.interpolated_state(0.5)).unwrap();
+            to_json(&wasm.sim.take_events()).unwrap();
+        }
+        assert!(!wasm.sim.people().people().is_empty());
+    }
+}